@@ -6,7 +6,7 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{info, warn};
@@ -18,6 +18,8 @@ use tracing::{info, warn};
 /// - Revocation list management (token IDs)
 /// - Kill switch endpoint to stop heartbeats
 /// - Session key rotation triggers
+/// - Published session key set for cross-instance receipt verification (known sidecars only)
+/// - Going-away notices from sidecars that are shutting down
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct HeartbeatRequest {
@@ -26,11 +28,18 @@ struct HeartbeatRequest {
     timestamp: u64,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct GoingAwayRequest {
+    sidecar_id: String,
+}
+
 #[derive(Debug, Serialize)]
 struct HeartbeatResponse {
     healthy: bool,
     #[serde(default)]
     revoked_token_ids: Option<Vec<[u8; 32]>>,
+    #[serde(default)]
+    unrevoked_token_ids: Option<Vec<[u8; 32]>>,
 }
 
 /// Sidecar state tracking
@@ -41,21 +50,51 @@ struct SidecarInfo {
     session_key_pub: String,
 }
 
+/// How long a session key stays published after the last heartbeat that carried it.
+///
+/// Matches the sidecar's default `session_key_rotation_interval_secs`: receipts minted
+/// just before a rotation (or before a sidecar stops heartbeating) stay verifiable by
+/// peers for one rotation period, and no longer.
+const SESSION_KEY_TTL_SECS: u64 = 300;
+
+/// A session public key seen in a heartbeat
+#[derive(Debug, Clone)]
+struct SessionKeyInfo {
+    sidecar_id: String,
+    last_seen: SystemTime,
+}
+
+/// Sidecar IDs whose session keys may be published, comma-separated.
+///
+/// This only keeps keys of unconfigured sidecars out of /session-keys. It is not
+/// authentication: heartbeats are unauthenticated and the ID is self-asserted, so the
+/// published set is only as trustworthy as the network path to the control plane.
+const KNOWN_SIDECARS_ENV: &str = "VAC_KNOWN_SIDECAR_IDS";
+
 /// Control Plane state
 struct ControlPlaneState {
+    /// Sidecar IDs whose session keys are published at /session-keys
+    known_sidecars: HashSet<String>,
     /// Registered sidecars
     sidecars: Arc<RwLock<HashMap<String, SidecarInfo>>>,
+    /// Session public keys (base64) seen in heartbeats, including rotated-out ones
+    session_keys: Arc<RwLock<HashMap<String, SessionKeyInfo>>>,
     /// Revoked token IDs (32-byte arrays)
     revoked_tokens: Arc<RwLock<Vec<[u8; 32]>>>,
+    /// Revocations rescinded via /unrevoke, so sidecars that already applied them remove them
+    unrevoked_tokens: Arc<RwLock<Vec<[u8; 32]>>>,
     /// Kill switch: if true, all heartbeats return unhealthy
     kill_switch_active: Arc<RwLock<bool>>,
 }
 
 impl ControlPlaneState {
-    fn new() -> Self {
+    fn new(known_sidecars: HashSet<String>) -> Self {
         Self {
+            known_sidecars,
             sidecars: Arc::new(RwLock::new(HashMap::new())),
+            session_keys: Arc::new(RwLock::new(HashMap::new())),
             revoked_tokens: Arc::new(RwLock::new(Vec::new())),
+            unrevoked_tokens: Arc::new(RwLock::new(Vec::new())),
             kill_switch_active: Arc::new(RwLock::new(false)),
         }
     }
@@ -79,11 +118,28 @@ async fn handle_heartbeat(
         return Ok(ResponseJson(HeartbeatResponse {
             healthy: false,
             revoked_token_ids: None,
+            unrevoked_token_ids: None,
         }));
     }
     
-    // Update sidecar info
+    // Update sidecar info; only known sidecars get their session key published
     {
+        if state.known_sidecars.contains(&request.sidecar_id) {
+            let mut session_keys = state.session_keys.write()
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            session_keys.insert(
+                request.session_key_pub.clone(),
+                SessionKeyInfo {
+                    sidecar_id: request.sidecar_id.clone(),
+                    last_seen: SystemTime::now(),
+                },
+            );
+        } else {
+            warn!(
+                "Session key of unknown sidecar {} not published (not in {})",
+                request.sidecar_id, KNOWN_SIDECARS_ENV
+            );
+        }
         let mut sidecars = state.sidecars.write()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         sidecars.insert(
@@ -107,9 +163,20 @@ async fn handle_heartbeat(
         }
     };
     
+    let unrevoked_tokens = {
+        let unrevoked = state.unrevoked_tokens.read()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if unrevoked.is_empty() {
+            None
+        } else {
+            Some(unrevoked.clone())
+        }
+    };
+    
     Ok(ResponseJson(HeartbeatResponse {
         healthy: true,
         revoked_token_ids: revoked_tokens,
+        unrevoked_token_ids: unrevoked_tokens,
     }))
 }
 
@@ -122,25 +189,26 @@ struct RevokeRequest {
     token_id: String, // Hex-encoded 32-byte token ID
 }
 
+/// Parse a hex-encoded 32-byte token ID
+fn parse_token_id(token_id: &str) -> Result<[u8; 32], StatusCode> {
+    let token_id_bytes = hex::decode(token_id)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    token_id_bytes.try_into().map_err(|_| StatusCode::BAD_REQUEST)
+}
+
 async fn handle_revoke(
     state: axum::extract::State<Arc<ControlPlaneState>>,
     Json(request): Json<RevokeRequest>,
 ) -> Result<StatusCode, StatusCode> {
-    // Parse hex-encoded token ID
-    let token_id_bytes = hex::decode(&request.token_id)
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    
-    if token_id_bytes.len() != 32 {
-        return Err(StatusCode::BAD_REQUEST);
-    }
+    let token_id = parse_token_id(&request.token_id)?;
     
-    let mut token_id = [0u8; 32];
-    token_id.copy_from_slice(&token_id_bytes);
-    
-    // Add to revocation list
+    // Add to revocation list (re-revoking a token that was un-revoked)
     {
         let mut revoked = state.revoked_tokens.write()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut unrevoked = state.unrevoked_tokens.write()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        unrevoked.retain(|id| *id != token_id);
         if !revoked.contains(&token_id) {
             revoked.push(token_id);
             info!("🚫 Token revoked: {}", request.token_id);
@@ -150,6 +218,53 @@ async fn handle_revoke(
     Ok(StatusCode::OK)
 }
 
+/// Rescind a revocation (e.g. one issued in error)
+/// 
+/// POST /unrevoke
+/// Body: { "token_id": "hex-encoded-32-byte-token-id" }
+/// The token leaves the revocation list and is sent to sidecars as `unrevoked_token_ids`.
+async fn handle_unrevoke(
+    state: axum::extract::State<Arc<ControlPlaneState>>,
+    Json(request): Json<RevokeRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let token_id = parse_token_id(&request.token_id)?;
+    
+    {
+        let mut revoked = state.revoked_tokens.write()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut unrevoked = state.unrevoked_tokens.write()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(pos) = revoked.iter().position(|id| *id == token_id) {
+            revoked.remove(pos);
+            if !unrevoked.contains(&token_id) {
+                unrevoked.push(token_id);
+            }
+            info!("✅ Token unrevoked: {}", request.token_id);
+        }
+    }
+    
+    Ok(StatusCode::OK)
+}
+
+/// Going-away notice from a sidecar that is shutting down
+/// 
+/// POST /going-away
+/// Deregisters the sidecar. Its session keys stay published until they expire, so
+/// receipts it minted can still be redeemed on peers.
+async fn handle_going_away(
+    state: axum::extract::State<Arc<ControlPlaneState>>,
+    Json(request): Json<GoingAwayRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let removed = state.sidecars.write()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .remove(&request.sidecar_id)
+        .is_some();
+    if removed {
+        info!("👋 Sidecar going away: {}", request.sidecar_id);
+    }
+    Ok(StatusCode::OK)
+}
+
 /// Activate kill switch (stops all sidecars)
 /// 
 /// POST /kill
@@ -208,28 +323,81 @@ async fn list_sidecars(
     })))
 }
 
+/// Published session key set
+/// 
+/// GET /session-keys
+/// Returns every session public key seen in a heartbeat from a known sidecar
+/// (`VAC_KNOWN_SIDECAR_IDS`) within the last `SESSION_KEY_TTL_SECS`, with the time it
+/// expires. Sidecars running with `accept_peer_receipts` accept receipts signed by any of them.
+async fn list_session_keys(
+    state: axum::extract::State<Arc<ControlPlaneState>>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut session_keys = state.session_keys.write()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let expires_at = |info: &SessionKeyInfo| {
+        info.last_seen
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            + SESSION_KEY_TTL_SECS
+    };
+    // Forget keys of sidecars that rotated away or stopped heartbeating.
+    session_keys.retain(|_, info| expires_at(info) > now);
+    let keys: Vec<_> = session_keys
+        .iter()
+        .map(|(session_key_pub, info)| {
+            serde_json::json!({
+                "sidecar_id": info.sidecar_id,
+                "session_key_pub": session_key_pub,
+                "expires_at": expires_at(info),
+            })
+        })
+        .collect();
+    
+    Ok(ResponseJson(serde_json::json!({ "keys": keys })))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
     
-    let state = Arc::new(ControlPlaneState::new());
+    let known_sidecars: HashSet<String> = std::env::var(KNOWN_SIDECARS_ENV)
+        .unwrap_or_default()
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    if known_sidecars.is_empty() {
+        info!("{} not set: no session keys are published at /session-keys", KNOWN_SIDECARS_ENV);
+    }
+    let state = Arc::new(ControlPlaneState::new(known_sidecars));
     
     let app = Router::new()
         .route("/heartbeat", post(handle_heartbeat))
+        .route("/going-away", post(handle_going_away))
         .route("/revoke", post(handle_revoke))
+        .route("/unrevoke", post(handle_unrevoke))
         .route("/kill", post(handle_kill))
         .route("/revive", post(handle_revive))
         .route("/sidecars", axum::routing::get(list_sidecars))
+        .route("/session-keys", axum::routing::get(list_session_keys))
         .with_state(state);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8081").await?;
     info!("🎛️ V-A-C Control Plane Mock Server listening on 0.0.0.0:8081");
     info!("Endpoints:");
     info!("  POST /heartbeat - Receive heartbeat from sidecar");
+    info!("  POST /going-away - Deregister a sidecar that is shutting down");
     info!("  POST /revoke - Revoke a token ID");
+    info!("  POST /unrevoke - Rescind a token ID's revocation");
     info!("  POST /kill - Activate kill switch");
     info!("  POST /revive - Deactivate kill switch");
     info!("  GET /sidecars - List registered sidecars");
+    info!("  GET /session-keys - List active session public keys of known sidecars");
     
     axum::serve(listener, app).await?;
     
@@ -3,21 +3,21 @@
 
 use axum::{
     extract::State,
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, Method, StatusCode, Uri},
     response::Json,
-    routing::{get, post},
+    routing::{any, get, post},
     Router,
 };
 use clap::Parser;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tracing::{info, warn};
 
 #[derive(Clone)]
 struct AppState {
     api_key: String,
-    port: u16,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -34,6 +34,7 @@ struct ChargeRequest {
     description: Option<String>,
 }
 
+#[allow(dead_code)] // Documents the `data` shape of charge responses
 #[derive(Serialize, Deserialize)]
 struct ChargeResponse {
     id: String,
@@ -47,6 +48,7 @@ struct SearchRequest {
     query: String,
 }
 
+#[allow(dead_code)] // Documents the `data` shape of search responses
 #[derive(Serialize, Deserialize)]
 struct SearchResponse {
     results: Vec<serde_json::Value>,
@@ -133,6 +135,53 @@ async fn charge(
     }))
 }
 
+/// Echo endpoint: returns the method, path, query and headers exactly as received
+///
+/// Lets tests and operators see what the sidecar actually forwarded (injected API key,
+/// stripped `x-vac-*` headers). Repeated headers are joined with ", ".
+async fn echo(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    verify_api_key(&headers, &state.api_key)?;
+
+    let mut received: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in &headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        received
+            .entry(name.as_str().to_string())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(&value);
+            })
+            .or_insert(value);
+    }
+
+    info!("Echo request: {} {}", method, uri.path());
+
+    Ok(Json(json!({
+        "method": method.as_str(),
+        "path": uri.path(),
+        "query": uri.query(),
+        "headers": received,
+    })))
+}
+
+/// Demo API router; every endpoint except `/health` requires `api_key`
+fn app(api_key: impl Into<String>) -> Router {
+    let state = Arc::new(AppState {
+        api_key: api_key.into(),
+    });
+    Router::new()
+        .route("/health", get(health))
+        .route("/search", post(search))
+        .route("/charge", post(charge))
+        .route("/echo", any(echo))
+        .route("/echo/*path", any(echo))
+        .with_state(state)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -151,22 +200,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .or_else(|| std::env::var("DEMO_API_PORT").ok().and_then(|v| v.parse().ok()))
         .unwrap_or(8080u16);
 
-    let state = Arc::new(AppState { api_key, port });
-
     // Build router
-    let app = Router::new()
-        .route("/health", get(health))
-        .route("/search", post(search))
-        .route("/charge", post(charge))
-        .with_state(state.clone());
+    let app = app(api_key.clone());
 
-    let addr = format!("0.0.0.0:{}", state.port);
+    let addr = format!("0.0.0.0:{}", port);
     info!("🚀 V-A-C Demo API starting on {}", addr);
-    info!("📝 API Key: {}****", &state.api_key[..state.api_key.len().min(4)]);
+    info!("📝 API Key: {}****", &api_key[..api_key.len().min(4)]);
     info!("📚 Endpoints:");
     info!("   GET  /health - Health check (no auth)");
     info!("   POST /search - Search endpoint (requires API key)");
     info!("   POST /charge - Charge endpoint (requires API key)");
+    info!("   ANY  /echo   - Echo received method, path and headers (requires API key)");
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     axum::serve(listener, app).await?;
@@ -186,3 +230,54 @@ struct Args {
     #[arg(long)]
     port: Option<u16>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn send(req: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let resp = app("demo-api-key").oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn echo_returns_the_request_as_received() {
+        let req = Request::builder()
+            .method("PATCH")
+            .uri("/echo/orders?limit=5")
+            .header("Authorization", "Bearer demo-api-key")
+            .header("X-Request-Source", "agent")
+            .header("Accept", "text/plain")
+            .header("Accept", "application/json")
+            .body(Body::empty())
+            .unwrap();
+
+        let (status, echo) = send(req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echo["method"], "PATCH");
+        assert_eq!(echo["path"], "/echo/orders");
+        assert_eq!(echo["query"], "limit=5");
+        assert_eq!(echo["headers"]["authorization"], "Bearer demo-api-key");
+        assert_eq!(echo["headers"]["x-request-source"], "agent");
+        // Repeated headers are joined in the order received.
+        assert_eq!(echo["headers"]["accept"], "text/plain, application/json");
+    }
+
+    #[tokio::test]
+    async fn echo_requires_api_key() {
+        let req = Request::builder().uri("/echo").body(Body::empty()).unwrap();
+        assert_eq!(send(req).await.0, StatusCode::UNAUTHORIZED);
+
+        let req = Request::builder()
+            .uri("/echo")
+            .header("Authorization", "Bearer agent-root-biscuit")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(req).await.0, StatusCode::UNAUTHORIZED);
+    }
+}
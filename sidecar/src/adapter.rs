@@ -1,40 +1,81 @@
-use wasmtime::{Engine, Module, Store};
+use wasmtime::{Engine, InstancePre, Module, ResourceLimiter, Store, StoreLimits, StoreLimitsBuilder};
 use wasmtime_wasi::WasiCtxBuilder;
 use wasmtime_wasi::WasiP1Ctx;
 use crate::error::VacError;
 use sha2::{Sha256, Digest};
 use std::collections::HashMap;
+use std::io;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, RwLock};
-use serde::Deserialize;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::metrics::RequestMetrics;
 
 /// Maximum size for WASM adapter modules (10MB)
 const MAX_MODULE_SIZE: usize = 10 * 1024 * 1024;
 
-/// Maximum execution time for adapter (5 seconds)
+/// Maximum execution time for adapter (5 seconds); a guest still running then is
+/// interrupted (epoch interruption) and the call fails.
 const MAX_EXECUTION_TIME_MS: u64 = 5000;
 
+/// Interval at which each adapter engine's epoch advances; execution limits are counted
+/// in these ticks.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Default threshold above which an adapter run is logged as slow (`adapter_slow_threshold_ms`)
+pub const DEFAULT_ADAPTER_SLOW_THRESHOLD_MS: u64 = 1000;
+
+/// Default ceiling on an adapter instance's linear memory (`adapter_max_memory_bytes`, 64 MiB)
+pub const DEFAULT_ADAPTER_MAX_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Headers never passed to adapters, whatever `adapter_context_headers` says: they carry
+/// the caller's credentials.
+pub const ADAPTER_CONTEXT_FORBIDDEN_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
 /// Maximum bytes we'll read from adapter output.
 ///
 /// This is a safety cap to prevent scanning unbounded memory if the adapter
 /// returns an invalid pointer or forgets to NUL-terminate its output.
 const MAX_ADAPTER_OUTPUT_BYTES: usize = 256 * 1024;
 
+/// A compiled adapter, the engine it runs on and that engine's [`EpochTicker`]
+type LoadedAdapter = (Module, Engine, Arc<EpochTicker>);
+
 /// WASM Adapter Registry
 /// 
 /// Manages loaded WASM adapters with hash verification and caching.
 /// Adapters are pinned by SHA-256 hash for security.
 #[derive(Clone)]
 pub struct AdapterRegistry {
-    /// Loaded adapters (hash -> (module, engine))
-    adapters: Arc<RwLock<HashMap<String, (Module, Engine)>>>,
+    /// Loaded adapters (hash -> (module, engine, the engine's epoch ticker))
+    adapters: Arc<RwLock<HashMap<String, LoadedAdapter>>>,
+    /// Linked, ready-to-instantiate adapters (hash -> instance-pre), filled on first use or by `prewarm`
+    instance_pres: Arc<RwLock<HashMap<String, InstancePre<AdapterCtx>>>>,
+    /// Where execution time per adapter hash (`vac_adapter_duration_seconds`) is recorded
+    metrics: RequestMetrics,
+    /// Runs at least this long are logged as slow (milliseconds, 0 = never)
+    slow_threshold_ms: Arc<AtomicU64>,
+    /// Ceiling on each instance's linear memory (bytes)
+    max_memory_bytes: Arc<AtomicU64>,
 }
 
 impl AdapterRegistry {
     /// Create a new adapter registry
     pub fn new() -> Self {
+        Self::with_metrics(RequestMetrics::new())
+    }
+
+    /// Create a new adapter registry that records adapter run times into `metrics`
+    pub fn with_metrics(metrics: RequestMetrics) -> Self {
         Self {
             adapters: Arc::new(RwLock::new(HashMap::new())),
+            instance_pres: Arc::new(RwLock::new(HashMap::new())),
+            metrics,
+            slow_threshold_ms: Arc::new(AtomicU64::new(DEFAULT_ADAPTER_SLOW_THRESHOLD_MS)),
+            max_memory_bytes: Arc::new(AtomicU64::new(DEFAULT_ADAPTER_MAX_MEMORY_BYTES)),
         }
     }
     
@@ -52,13 +93,22 @@ impl AdapterRegistry {
         wasm_bytes: &[u8],
         expected_hash: &str,
     ) -> Result<(), VacError> {
-        // Verify hash
         let computed_hash = {
             let mut hasher = Sha256::new();
             hasher.update(wasm_bytes);
             hex::encode(hasher.finalize())
         };
-        
+        self.load_adapter_with_hash(wasm_bytes, &computed_hash, expected_hash)
+    }
+    
+    /// Like [`Self::load_adapter`], for bytes whose hash was computed while they were read.
+    fn load_adapter_with_hash(
+        &self,
+        wasm_bytes: &[u8],
+        computed_hash: &str,
+        expected_hash: &str,
+    ) -> Result<(), VacError> {
+        // Verify hash
         if computed_hash != expected_hash {
             return Err(VacError::ConfigError(format!(
                 "Adapter hash mismatch: expected {}, got {}",
@@ -74,19 +124,38 @@ impl AdapterRegistry {
             )));
         }
         
-        // Create engine with limited resources
-        let engine = Engine::default();
+        // Create engine with limited resources. Epoch interruption, driven by the
+        // engine's ticker, stops a guest stuck in a loop at its store's deadline.
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.epoch_interruption(true);
+        let engine = Engine::new(&engine_config)
+            .map_err(|e| VacError::InternalError(format!("Failed to create WASM engine: {}", e)))?;
+        let ticker = Arc::new(EpochTicker::start(&engine));
         
         // Compile module
         let module = Module::new(&engine, wasm_bytes)
             .map_err(|e| VacError::InternalError(format!("Failed to compile WASM module: {}", e)))?;
+
+        // Link and instantiate once in a throwaway store, so a module that cannot be
+        // instantiated or lacks the required exports is rejected now rather than
+        // surfacing as a 500 on the first request that uses it.
+        let pre = link_adapter(&module, &engine)
+            .map_err(|e| VacError::ConfigError(format!("Adapter {} failed to link: {}", expected_hash, e)))?;
+        let limit = Duration::from_millis(MAX_EXECUTION_TIME_MS);
+        let max_memory = self.max_memory_bytes();
+        validate_adapter_exports(&pre, &engine, max_memory, limit)
+            .map_err(|e| VacError::ConfigError(format!("Adapter {} rejected: {}", expected_hash, e)))?;
         
         // Cache adapter
         {
             let mut adapters = self.adapters.write().map_err(|_| {
                 VacError::InternalError("Failed to acquire adapter registry lock".to_string())
             })?;
-            adapters.insert(expected_hash.to_string(), (module, engine));
+            adapters.insert(expected_hash.to_string(), (module, engine, ticker));
+        }
+        // A reloaded adapter replaces the instance-pre linked against the old module.
+        if let Ok(mut pres) = self.instance_pres.write() {
+            pres.insert(expected_hash.to_string(), pre);
         }
         
         Ok(())
@@ -95,7 +164,94 @@ impl AdapterRegistry {
     /// Get a cached adapter by hash
     fn get_adapter(&self, hash: &str) -> Option<(Module, Engine)> {
         let adapters = self.adapters.read().ok()?;
-        adapters.get(hash).cloned()
+        adapters.get(hash).map(|(module, engine, _)| (module.clone(), engine.clone()))
+    }
+
+    /// Get (or link and cache) the instance-pre for an adapter.
+    fn instance_pre(&self, hash: &str, module: &Module, engine: &Engine) -> Result<InstancePre<AdapterCtx>, VacError> {
+        if let Some(pre) = self.instance_pres.read().ok().and_then(|p| p.get(hash).cloned()) {
+            return Ok(pre);
+        }
+
+        let pre = link_adapter(module, engine).map_err(VacError::InternalError)?;
+
+        if let Ok(mut pres) = self.instance_pres.write() {
+            pres.insert(hash.to_string(), pre.clone());
+        }
+        Ok(pre)
+    }
+
+    /// Instantiate every loaded adapter once so the first real request does not pay
+    /// the instantiate cost.
+    ///
+    /// Fails if any adapter cannot be instantiated or lacks the required exports
+    /// (normally already rejected when it was loaded).
+    /// A trial call with an empty body is also made; its failure is only logged,
+    /// since adapters are free to reject an empty body.
+    ///
+    /// Returns the number of adapters warmed.
+    pub fn prewarm(&self) -> Result<usize, VacError> {
+        let hashes: Vec<String> = {
+            let adapters = self.adapters.read().map_err(|_| {
+                VacError::InternalError("Failed to acquire adapter registry lock".to_string())
+            })?;
+            adapters.keys().cloned().collect()
+        };
+
+        for hash in &hashes {
+            instantiate_adapter(hash, self, Duration::from_millis(MAX_EXECUTION_TIME_MS)).map_err(|e| {
+                VacError::ConfigError(format!("Adapter {} failed to prewarm: {}", hash, e))
+            })?;
+            if let Err(e) = extract_facts_from_body_sync(hash, &[], &AdapterContext::default().to_json(), self) {
+                tracing::warn!(adapter_hash = %hash, error = %e, "Adapter prewarm call with empty body failed");
+            }
+        }
+
+        Ok(hashes.len())
+    }
+
+    /// Whether the adapter with this hash is loaded
+    pub fn is_loaded(&self, hash: &str) -> bool {
+        self.adapters.read().map(|adapters| adapters.contains_key(hash)).unwrap_or(false)
+    }
+
+    /// Number of loaded adapters (for monitoring)
+    pub fn adapter_count(&self) -> usize {
+        self.adapters.read().map(|adapters| adapters.len()).unwrap_or(0)
+    }
+
+    /// Log adapter runs taking at least `threshold` as slow (`Duration::ZERO` disables).
+    pub fn set_slow_threshold(&self, threshold: Duration) {
+        self.slow_threshold_ms
+            .store(threshold.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Cap each adapter instance's linear memory at `bytes`; growing past it traps.
+    /// Applies to instances created from now on.
+    pub fn set_max_memory(&self, bytes: u64) {
+        self.max_memory_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    fn max_memory_bytes(&self) -> usize {
+        usize::try_from(self.max_memory_bytes.load(Ordering::Relaxed)).unwrap_or(usize::MAX)
+    }
+
+    /// Record one run of a loaded adapter (unknown hashes are ignored, keeping the
+    /// metric's label set bounded by the loaded adapters).
+    fn record_duration(&self, hash: &str, elapsed: Duration) {
+        if self.get_adapter(hash).is_none() {
+            return;
+        }
+        self.metrics.observe_adapter_duration(hash, elapsed);
+        let threshold_ms = self.slow_threshold_ms.load(Ordering::Relaxed);
+        if threshold_ms > 0 && elapsed >= Duration::from_millis(threshold_ms) {
+            tracing::warn!(
+                adapter_hash = %hash,
+                adapter_duration_ms = elapsed.as_millis() as u64,
+                adapter_slow_threshold_ms = threshold_ms,
+                "Slow WASM adapter execution"
+            );
+        }
     }
 }
 
@@ -105,11 +261,64 @@ impl Default for AdapterRegistry {
     }
 }
 
+/// Request context for adapters exporting `extract_facts_with_context`, passed to the
+/// guest as JSON: `{"method": "POST", "path": "/charge", "headers": {"x-tenant-id": "t1"}}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AdapterContext {
+    pub method: String,
+    /// Normalized request path, as in the `operation` fact
+    pub path: String,
+    /// The request's headers among `adapter_context_headers`, by lowercase name; repeated
+    /// headers are joined with ", "
+    pub headers: BTreeMap<String, String>,
+}
+
+impl AdapterContext {
+    /// Context for a request, with only the `selected` (lowercase) headers that are present
+    /// and not in [`ADAPTER_CONTEXT_FORBIDDEN_HEADERS`].
+    pub fn from_request(method: &str, path: &str, headers: &axum::http::HeaderMap, selected: &[String]) -> Self {
+        let headers = selected
+            .iter()
+            .filter(|name| !ADAPTER_CONTEXT_FORBIDDEN_HEADERS.contains(&name.as_str()))
+            .filter_map(|name| {
+                let values: Vec<&str> = headers
+                    .get_all(name.as_str())
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .collect();
+                (!values.is_empty()).then(|| (name.clone(), values.join(", ")))
+            })
+            .collect();
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers,
+        }
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_else(|_| b"{}".to_vec())
+    }
+}
+
 /// Extract facts from HTTP request body using a WASM adapter
+///
+/// Same as [`extract_facts_from_request`] with an empty [`AdapterContext`].
+pub async fn extract_facts_from_body(
+    adapter_hash: &str,
+    request_body: &[u8],
+    registry: &AdapterRegistry,
+) -> Result<Vec<AdapterFact>, VacError> {
+    extract_facts_from_request(adapter_hash, request_body, &AdapterContext::default(), registry).await
+}
+
+/// Extract facts from a request (body, and context for adapters that take it) using a
+/// WASM adapter
 /// 
 /// # Arguments
 /// - `adapter_hash`: SHA-256 hash of the adapter (must be loaded first)
 /// - `request_body`: Raw HTTP request body bytes
+/// - `context`: Method, path and selected headers of the request
 /// - `registry`: Adapter registry with loaded adapters
 /// 
 /// # Returns
@@ -129,32 +338,47 @@ impl Default for AdapterRegistry {
 /// Returns:
 /// - `i32`: Pointer to JSON-encoded facts array
 /// 
+/// An adapter may export this instead (it is preferred when both are exported), to also
+/// receive the [`AdapterContext`] as JSON, written to guest memory right after the body:
+/// ```wat
+/// (func $extract_facts_with_context (param i32 i32 i32 i32) (result i32))
+/// ```
+/// with the body pointer and length, then the context pointer and length.
+/// 
 /// JSON Format:
 /// ```json
 /// [
 ///   {"fact": "amount", "args": ["350"]},
-///   {"fact": "currency", "args": ["USD"]}
+///   {"fact": "currency", "args": ["USD"]},
+///   {"fact": "kyc_verified", "args": [{"type": "bool", "value": "true"}]}
 /// ]
 /// ```
-pub async fn extract_facts_from_body(
+/// 
+/// A bare string argument is an integer if it parses as one, otherwise a string; the
+/// typed form (`int`, `string`, `bool`, `date`) is converted as declared.
+pub async fn extract_facts_from_request(
     adapter_hash: &str,
     request_body: &[u8],
+    context: &AdapterContext,
     registry: &AdapterRegistry,
 ) -> Result<Vec<AdapterFact>, VacError> {
-    // Enforce a time limit on adapter execution.
-    //
-    // NOTE: This is a pragmatic Phase 4.1 timeout. It will cancel awaiting the result,
-    // but does not preempt the underlying thread if it is stuck in guest code.
-    // A stricter implementation can use epoch interruption in a future hardening step.
-    let adapter_hash = adapter_hash.to_string();
-    let request_body = request_body.to_vec();
-    let registry = registry.clone();
-
-    let handle = tokio::task::spawn_blocking(move || {
-        extract_facts_from_body_sync(&adapter_hash, &request_body, &registry)
-    });
-
-    match tokio::time::timeout(Duration::from_millis(MAX_EXECUTION_TIME_MS), handle).await {
+    // Enforce a time limit on adapter execution. The guest itself is interrupted at its
+    // store's epoch deadline (see `EpochTicker`), which frees the blocking thread; this
+    // timeout only bounds the wait should the thread be slow to return.
+    let started = Instant::now();
+    let handle = {
+        let adapter_hash = adapter_hash.to_string();
+        let request_body = request_body.to_vec();
+        let context = context.to_json();
+        let registry = registry.clone();
+        tokio::task::spawn_blocking(move || {
+            extract_facts_from_body_sync(&adapter_hash, &request_body, &context, &registry)
+        })
+    };
+
+    let result = tokio::time::timeout(Duration::from_millis(MAX_EXECUTION_TIME_MS), handle).await;
+    registry.record_duration(adapter_hash, started.elapsed());
+    match result {
         Ok(join_res) => join_res.map_err(|e| {
             VacError::InternalError(format!("WASM adapter task join failed: {}", e))
         })?,
@@ -165,48 +389,228 @@ pub async fn extract_facts_from_body(
     }
 }
 
-fn extract_facts_from_body_sync(
+/// Advances an adapter engine's epoch every [`EPOCH_TICK`] on one long-lived thread,
+/// for as long as the adapter is loaded.
+///
+/// Each store gets its own deadline in ticks from its creation ([`epoch_deadline_ticks`]),
+/// so a guest still running after its limit traps with `Trap::Interrupt` while other runs
+/// of the same adapter carry on.
+struct EpochTicker {
+    /// Dropping it disconnects the channel, which stops the thread.
+    _stop: mpsc::Sender<()>,
+}
+
+impl EpochTicker {
+    fn start(engine: &Engine) -> Self {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let engine = engine.clone();
+        std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(EPOCH_TICK) {
+                engine.increment_epoch();
+            }
+        });
+        Self { _stop: stop_tx }
+    }
+}
+
+/// Epoch ticks a store may run for before it is interrupted: `limit` rounded up to whole
+/// ticks, plus one for the partial tick already under way when the store is created.
+fn epoch_deadline_ticks(limit: Duration) -> u64 {
+    let tick = EPOCH_TICK.as_nanos();
+    let ticks = limit.as_nanos().div_ceil(tick) + 1;
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+/// Map a guest error, reporting an epoch interruption as the execution limit.
+fn guest_error(context: &str, e: wasmtime::Error, limit: Duration) -> VacError {
+    if e.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::Interrupt) {
+        return VacError::InternalError(format!(
+            "WASM adapter exceeded {}ms execution limit",
+            limit.as_millis()
+        ));
+    }
+    VacError::InternalError(format!("{}: {}", context, e))
+}
+
+/// Store data of an adapter instance: its sandboxed WASI context and memory limits.
+struct AdapterCtx {
+    wasi: WasiP1Ctx,
+    limiter: MemoryLimiter,
+}
+
+/// [`StoreLimits`] capping linear memory (trapping when a grow would exceed it), noting
+/// whether the cap was hit so the trap can be reported as such.
+struct MemoryLimiter {
+    limits: StoreLimits,
+    max_memory_bytes: usize,
+    exceeded: bool,
+}
+
+impl MemoryLimiter {
+    fn new(max_memory_bytes: usize) -> Self {
+        Self {
+            limits: StoreLimitsBuilder::new()
+                .memory_size(max_memory_bytes)
+                .trap_on_grow_failure(true)
+                .build(),
+            max_memory_bytes,
+            exceeded: false,
+        }
+    }
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> wasmtime::Result<bool> {
+        if desired > self.max_memory_bytes {
+            self.exceeded = true;
+        }
+        self.limits.memory_growing(current, desired, maximum)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> wasmtime::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+}
+
+/// Report a failure in `store` as the memory limit if the limiter refused a grow.
+fn memory_limit_error(store: &Store<AdapterCtx>, e: VacError) -> VacError {
+    let limiter = &store.data().limiter;
+    if limiter.exceeded {
+        return VacError::InternalError(format!(
+            "WASM adapter exceeded {} byte memory limit",
+            limiter.max_memory_bytes
+        ));
+    }
+    e
+}
+
+/// `extract_facts(ptr, len) -> ptr` export signature.
+type ExtractFactsFn = wasmtime::TypedFunc<(i32, i32), i32>;
+
+/// `extract_facts_with_context(body_ptr, body_len, context_ptr, context_len) -> ptr` export signature.
+type ExtractFactsWithContextFn = wasmtime::TypedFunc<(i32, i32, i32, i32), i32>;
+
+/// The adapter's entry point: the context-taking export if it has one.
+enum AdapterEntry {
+    Body(ExtractFactsFn),
+    WithContext(ExtractFactsWithContextFn),
+}
+
+/// Instantiate an adapter in a fresh sandboxed store and resolve its required exports.
+fn instantiate_adapter(
     adapter_hash: &str,
-    request_body: &[u8],
     registry: &AdapterRegistry,
-) -> Result<Vec<AdapterFact>, VacError> {
+    limit: Duration,
+) -> Result<(Store<AdapterCtx>, wasmtime::Memory, AdapterEntry), VacError> {
     // Get adapter from registry
     let (module, engine) = registry
         .get_adapter(adapter_hash)
         .ok_or_else(|| VacError::ConfigError(format!("Adapter not found: {}", adapter_hash)))?;
 
+    // Create instance with WASI (linking is cached per adapter)
+    let pre = registry.instance_pre(adapter_hash, &module, &engine)?;
+    let (mut store, instance) =
+        instantiate_sandboxed(&pre, &engine, registry.max_memory_bytes(), limit).map_err(VacError::InternalError)?;
+    let (memory, entry) = adapter_exports(&instance, &mut store).map_err(VacError::InternalError)?;
+
+    Ok((store, memory, entry))
+}
+
+/// Link a compiled adapter against WASI preview 1.
+fn link_adapter(module: &Module, engine: &Engine) -> Result<InstancePre<AdapterCtx>, String> {
+    let mut linker = wasmtime::Linker::new(engine);
+    wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |s: &mut AdapterCtx| &mut s.wasi)
+        .map_err(|e| format!("Failed to create WASI linker: {}", e))?;
+    linker
+        .instantiate_pre(module)
+        .map_err(|e| format!("Failed to link WASM module: {}", e))
+}
+
+/// Instantiate a linked adapter in a fresh store whose linear memory is capped at
+/// `max_memory_bytes` and whose guest code is interrupted after `limit`.
+fn instantiate_sandboxed(
+    pre: &InstancePre<AdapterCtx>,
+    engine: &Engine,
+    max_memory_bytes: usize,
+    limit: Duration,
+) -> Result<(Store<AdapterCtx>, wasmtime::Instance), String> {
     // Create WASI context (sandboxed):
     // - no preopened dirs
     // - no inherited env/args
     let wasi_ctx: WasiP1Ctx = WasiCtxBuilder::new().build_p1();
+    let mut store = Store::new(
+        engine,
+        AdapterCtx {
+            wasi: wasi_ctx,
+            limiter: MemoryLimiter::new(max_memory_bytes),
+        },
+    );
+    store.limiter(|ctx| &mut ctx.limiter);
+    // Trap once `limit` has passed (see `EpochTicker`); a start function counts too.
+    store.set_epoch_deadline(epoch_deadline_ticks(limit));
+    let instance = pre
+        .instantiate(&mut store)
+        .map_err(|e| format!("Failed to instantiate WASM module: {}", e))?;
+    Ok((store, instance))
+}
 
-    let mut store = Store::new(&engine, wasi_ctx);
-
-    // Create instance with WASI
-    let mut linker = wasmtime::Linker::new(&engine);
-    wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |s: &mut WasiP1Ctx| s)
-        .map_err(|e| VacError::InternalError(format!("Failed to create WASI linker: {}", e)))?;
+/// Resolve the exports every adapter must provide: `memory` and either
+/// `extract_facts(i32, i32) -> i32` or `extract_facts_with_context(i32, i32, i32, i32) -> i32`.
+fn adapter_exports(
+    instance: &wasmtime::Instance,
+    store: &mut Store<AdapterCtx>,
+) -> Result<(wasmtime::Memory, AdapterEntry), String> {
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .ok_or_else(|| "WASM module must export 'memory'".to_string())?;
+    if instance.get_export(&mut *store, "extract_facts_with_context").is_some() {
+        let extract_facts = instance
+            .get_typed_func::<(i32, i32, i32, i32), i32>(&mut *store, "extract_facts_with_context")
+            .map_err(|e| format!("WASM module exports 'extract_facts_with_context' with the wrong signature: {}", e))?;
+        return Ok((memory, AdapterEntry::WithContext(extract_facts)));
+    }
+    let extract_facts = instance
+        .get_typed_func::<(i32, i32), i32>(&mut *store, "extract_facts")
+        .map_err(|e| format!("WASM module must export 'extract_facts' function: {}", e))?;
+    Ok((memory, AdapterEntry::Body(extract_facts)))
+}
 
-    let instance = linker
-        .instantiate(&mut store, &module)
-        .map_err(|e| VacError::InternalError(format!("Failed to instantiate WASM module: {}", e)))?;
+/// Load-time check: instantiate once in a throwaway store and resolve the required exports.
+fn validate_adapter_exports(
+    pre: &InstancePre<AdapterCtx>,
+    engine: &Engine,
+    max_memory_bytes: usize,
+    limit: Duration,
+) -> Result<(), String> {
+    let (mut store, instance) = instantiate_sandboxed(pre, engine, max_memory_bytes, limit)?;
+    adapter_exports(&instance, &mut store).map(|_| ())
+}
 
-    // Get memory
-    let memory = instance
-        .get_memory(&mut store, "memory")
-        .ok_or_else(|| VacError::InternalError("WASM module must export 'memory'".to_string()))?;
+fn extract_facts_from_body_sync(
+    adapter_hash: &str,
+    request_body: &[u8],
+    context: &[u8],
+    registry: &AdapterRegistry,
+) -> Result<Vec<AdapterFact>, VacError> {
+    run_extract_facts(adapter_hash, request_body, context, registry, Duration::from_millis(MAX_EXECUTION_TIME_MS))
+}
 
-    // Get extract_facts function
-    let extract_facts = instance
-        .get_typed_func::<(i32, i32), i32>(&mut store, "extract_facts")
-        .map_err(|e| {
-            VacError::InternalError(format!(
-                "WASM module must export 'extract_facts' function: {}",
-                e
-            ))
-        })?;
+/// One adapter run (instantiation and `extract_facts`), interrupted after `limit`.
+fn run_extract_facts(
+    adapter_hash: &str,
+    request_body: &[u8],
+    context: &[u8],
+    registry: &AdapterRegistry,
+    limit: Duration,
+) -> Result<Vec<AdapterFact>, VacError> {
+    let (mut store, memory, entry) = instantiate_adapter(adapter_hash, registry, limit)?;
+    // Only adapters that take the context get it written to memory.
+    let context = match entry {
+        AdapterEntry::WithContext(_) => context,
+        AdapterEntry::Body(_) => &[],
+    };
 
-    // Write request body to memory
+    // Write request body (and context, right after it) to memory
     let body_ptr = {
         let ptr_u64 = memory.data_size(&store);
         let ptr: usize = usize::try_from(ptr_u64).map_err(|_| {
@@ -216,6 +620,7 @@ fn extract_facts_from_body_sync(
         // Grow memory if needed
         let new_size: usize = ptr
             .checked_add(request_body.len())
+            .and_then(|v: usize| v.checked_add(context.len()))
             .and_then(|v: usize| v.checked_add(1024)) // extra space for output
             .ok_or_else(|| VacError::InternalError("WASM memory size overflow".to_string()))?;
 
@@ -224,29 +629,39 @@ fn extract_facts_from_body_sync(
         let current_pages: u64 = memory.size(&store);
         if required_pages > current_pages {
             let additional_pages = required_pages - current_pages;
-            memory
-                .grow(&mut store, additional_pages)
-                .map_err(|e| VacError::InternalError(format!("Failed to grow memory: {}", e)))?;
+            memory.grow(&mut store, additional_pages).map_err(|e| {
+                memory_limit_error(&store, VacError::InternalError(format!("Failed to grow memory: {}", e)))
+            })?;
         }
 
         let memory_view = memory.data_mut(&mut store);
         let start = ptr;
         let end = start
             .checked_add(request_body.len())
+            .and_then(|v: usize| v.checked_add(context.len()))
             .ok_or_else(|| VacError::InternalError("WASM body pointer overflow".to_string()))?;
         if end > memory_view.len() {
             return Err(VacError::InternalError(
                 "WASM memory bounds check failed when writing request body".to_string(),
             ));
         }
-        memory_view[start..end].copy_from_slice(request_body);
+        let context_start = start + request_body.len();
+        memory_view[start..context_start].copy_from_slice(request_body);
+        memory_view[context_start..end].copy_from_slice(context);
         ptr as i32
     };
+    let context_ptr = body_ptr + request_body.len() as i32;
 
-    // Call extract_facts function
-    let result_ptr = extract_facts
-        .call(&mut store, (body_ptr, request_body.len() as i32))
-        .map_err(|e| VacError::InternalError(format!("WASM adapter execution failed: {}", e)))?;
+    // Call the adapter's entry point
+    let result = match &entry {
+        AdapterEntry::Body(extract_facts) => extract_facts.call(&mut store, (body_ptr, request_body.len() as i32)),
+        AdapterEntry::WithContext(extract_facts) => extract_facts.call(
+            &mut store,
+            (body_ptr, request_body.len() as i32, context_ptr, context.len() as i32),
+        ),
+    };
+    let result_ptr =
+        result.map_err(|e| memory_limit_error(&store, guest_error("WASM adapter execution failed", e, limit)))?;
 
     // Read result from memory.
     // ABI (Phase 4.1): NUL-terminated UTF-8 JSON string pointer.
@@ -260,7 +675,7 @@ fn extract_facts_from_body_sync(
         .into_iter()
         .map(|w| AdapterFact {
             fact_name: w.fact,
-            args: w.args,
+            args: w.args.into_iter().map(AdapterArg::from).collect(),
         })
         .collect())
 }
@@ -268,12 +683,33 @@ fn extract_facts_from_body_sync(
 #[derive(Debug, Deserialize)]
 struct AdapterFactWire {
     fact: String,
-    args: Vec<String>,
+    args: Vec<AdapterArgWire>,
+}
+
+/// One argument on the wire: a bare string (untyped) or `{"type": ..., "value": ...}`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AdapterArgWire {
+    Untyped(String),
+    Typed {
+        #[serde(rename = "type")]
+        ty: AdapterArgType,
+        value: String,
+    },
+}
+
+impl From<AdapterArgWire> for AdapterArg {
+    fn from(wire: AdapterArgWire) -> Self {
+        match wire {
+            AdapterArgWire::Untyped(value) => AdapterArg { value, ty: None },
+            AdapterArgWire::Typed { ty, value } => AdapterArg { value, ty: Some(ty) },
+        }
+    }
 }
 
 fn read_nul_terminated_utf8(
     memory: &wasmtime::Memory,
-    store: &Store<WasiP1Ctx>,
+    store: &Store<AdapterCtx>,
     start: usize,
     max_bytes: usize,
 ) -> Result<String, VacError> {
@@ -305,7 +741,71 @@ fn read_nul_terminated_utf8(
 #[derive(Debug, Clone)]
 pub struct AdapterFact {
     pub fact_name: String,
-    pub args: Vec<String>,
+    pub args: Vec<AdapterArg>,
+}
+
+/// Datalog type an adapter declares for a fact argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdapterArgType {
+    Int,
+    String,
+    Bool,
+    /// RFC 3339, e.g. `2024-01-01T00:00:00Z`
+    Date,
+}
+
+/// Argument of an adapter fact, as returned: its value and, in the typed wire form
+/// (`{"type": "bool", "value": "true"}`), its declared type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterArg {
+    pub value: String,
+    /// None for the untyped form (a bare string): an integer if it parses as one,
+    /// otherwise a string
+    pub ty: Option<AdapterArgType>,
+}
+
+impl AdapterArg {
+    fn to_term(&self, fact_name: &str) -> Result<biscuit_auth::builder::Term, VacError> {
+        use biscuit_auth::builder::{self, Term};
+
+        let invalid = |ty: &str| {
+            VacError::InternalError(format!(
+                "WASM adapter fact '{}': '{}' is not a valid {}",
+                fact_name, self.value, ty
+            ))
+        };
+        Ok(match self.ty {
+            None => match self.value.parse::<i64>() {
+                Ok(i) => builder::int(i),
+                Err(_) => builder::string(&self.value),
+            },
+            Some(AdapterArgType::String) => builder::string(&self.value),
+            Some(AdapterArgType::Int) => builder::int(self.value.parse().map_err(|_| invalid("int"))?),
+            Some(AdapterArgType::Bool) => match self.value.as_str() {
+                "true" => builder::boolean(true),
+                "false" => builder::boolean(false),
+                _ => return Err(invalid("bool")),
+            },
+            // Parsed as a Datalog date literal; anything but a single date term is refused,
+            // so the value cannot smuggle in other terms.
+            Some(AdapterArgType::Date) => {
+                let fact = builder::Fact::try_from(format!("date({})", self.value).as_str())
+                    .map_err(|_| invalid("date"))?;
+                match fact.predicate.terms.as_slice() {
+                    [date @ Term::Date(_)] => date.clone(),
+                    _ => return Err(invalid("date")),
+                }
+            }
+        })
+    }
+}
+
+/// Compares the value only, so untyped arguments read like plain strings.
+impl PartialEq<String> for AdapterArg {
+    fn eq(&self, other: &String) -> bool {
+        self.value == *other
+    }
 }
 
 /// Simplified adapter interface for Phase 4.1
@@ -315,19 +815,14 @@ pub struct AdapterFact {
 /// Full implementation will require WASM memory management.
 impl AdapterFact {
     /// Convert to Datalog Fact format
+    ///
+    /// Typed arguments become the declared term (`int`, `string`, `boolean`, `date`) and
+    /// fail if the value does not fit it; untyped ones are integers when they parse as one
+    /// and strings otherwise.
     pub fn to_biscuit_fact(&self) -> Result<biscuit_auth::builder::Fact, VacError> {
-        use biscuit_auth::builder;
-        
-        let args: Vec<_> = self.args.iter()
-            .map(|arg| {
-                // Try to parse as integer, otherwise treat as string
-                if let Ok(i) = arg.parse::<i64>() {
-                    builder::int(i)
-                } else {
-                    builder::string(arg)
-                }
-            })
-            .collect();
+        let args = self.args.iter()
+            .map(|arg| arg.to_term(&self.fact_name))
+            .collect::<Result<Vec<_>, _>>()?;
         
         Ok(biscuit_auth::builder::Fact::new(
             self.fact_name.clone(),
@@ -336,6 +831,82 @@ impl AdapterFact {
     }
 }
 
+/// Predicates the sidecar itself derives from verified tokens, receipts and the request.
+///
+/// An adapter fact with one of these names could forge, say, a `prior_event` the policy
+/// would trust as much as a verified receipt.
+pub const RESERVED_FACT_NAMES: &[&str] = &[
+    "operation",
+    "correlation_id",
+    "time",
+    "prior_event",
+    "receipt_count",
+    "idempotency_key_present",
+    "delegation_chain",
+    "depth",
+    "minted_by_sidecar",
+    "adapter_hash",
+];
+
+/// Prefix given to reserved adapter facts under `AdapterReservedFacts::Namespace`
+pub const ADAPTER_FACT_NAMESPACE_PREFIX: &str = "adapter_";
+
+/// What to do with adapter facts named like a trusted predicate (`RESERVED_FACT_NAMES`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdapterReservedFacts {
+    /// Inject them as returned (historical behavior).
+    #[default]
+    Allow,
+    /// Deny the request with 403.
+    Reject,
+    /// Inject them renamed with the `adapter_` prefix (`prior_event` → `adapter_prior_event`).
+    Namespace,
+}
+
+impl std::str::FromStr for AdapterReservedFacts {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "allow" => Ok(AdapterReservedFacts::Allow),
+            "reject" => Ok(AdapterReservedFacts::Reject),
+            "namespace" => Ok(AdapterReservedFacts::Namespace),
+            other => Err(VacError::ConfigError(format!(
+                "adapter_reserved_facts must be one of allow, reject, namespace (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Apply the reserved-predicate rule to facts returned by an adapter.
+pub fn screen_adapter_facts(
+    facts: Vec<AdapterFact>,
+    mode: AdapterReservedFacts,
+) -> Result<Vec<AdapterFact>, VacError> {
+    if mode == AdapterReservedFacts::Allow {
+        return Ok(facts);
+    }
+    facts
+        .into_iter()
+        .map(|mut fact| {
+            if !RESERVED_FACT_NAMES.contains(&fact.fact_name.as_str()) {
+                return Ok(fact);
+            }
+            match mode {
+                AdapterReservedFacts::Reject => Err(VacError::PolicyViolation(format!(
+                    "Adapter returned reserved fact '{}'",
+                    fact.fact_name
+                ))),
+                _ => {
+                    fact.fact_name = format!("{}{}", ADAPTER_FACT_NAMESPACE_PREFIX, fact.fact_name);
+                    Ok(fact)
+                }
+            }
+        })
+        .collect()
+}
+
 /// Load adapter from local file path
 pub fn load_adapter_from_file(
     registry: &AdapterRegistry,
@@ -344,10 +915,11 @@ pub fn load_adapter_from_file(
 ) -> Result<(), VacError> {
     use std::fs;
     
-    let wasm_bytes = fs::read(file_path)
+    let (wasm_bytes, computed_hash) = fs::File::open(file_path)
+        .and_then(read_adapter_hashed)
         .map_err(|e| VacError::ConfigError(format!("Failed to read adapter file: {}", e)))?;
     
-    registry.load_adapter(&wasm_bytes, expected_hash)
+    registry.load_adapter_with_hash(&wasm_bytes, &computed_hash, expected_hash)
 }
 
 /// Load all `.wasm` files from a directory into the registry, keyed by their SHA-256 hash.
@@ -368,9 +940,11 @@ pub fn load_adapters_from_dir(registry: &AdapterRegistry, dir_path: &str) -> Res
         if path.extension().and_then(|e| e.to_str()) != Some("wasm") {
             continue;
         }
-        let wasm_bytes = std::fs::read(&path).map_err(|e| {
-            VacError::ConfigError(format!("Failed to read adapter file '{}': {}", path.display(), e))
-        })?;
+        let (wasm_bytes, hash) = std::fs::File::open(&path)
+            .and_then(read_adapter_hashed)
+            .map_err(|e| {
+                VacError::ConfigError(format!("Failed to read adapter file '{}': {}", path.display(), e))
+            })?;
         if wasm_bytes.len() > MAX_MODULE_SIZE {
             return Err(VacError::ConfigError(format!(
                 "Adapter module too large: '{}' ({} bytes, max {})",
@@ -379,12 +953,7 @@ pub fn load_adapters_from_dir(registry: &AdapterRegistry, dir_path: &str) -> Res
                 MAX_MODULE_SIZE
             )));
         }
-        let hash = {
-            let mut hasher = Sha256::new();
-            hasher.update(&wasm_bytes);
-            hex::encode(hasher.finalize())
-        };
-        registry.load_adapter(&wasm_bytes, &hash)?;
+        registry.load_adapter_with_hash(&wasm_bytes, &hash, &hash)?;
         loaded += 1;
     }
 
@@ -402,7 +971,7 @@ pub async fn load_adapter_from_url(
     url: &str,
     expected_hash: &str,
 ) -> Result<(), VacError> {
-    let resp = reqwest::get(url)
+    let mut resp = reqwest::get(url)
         .await
         .map_err(|e| VacError::ConfigError(format!("Failed to download adapter: {}", e)))?;
 
@@ -413,18 +982,62 @@ pub async fn load_adapter_from_url(
         )));
     }
 
-    let bytes = resp
-        .bytes()
+    // Hash chunks as they arrive and stop as soon as the size limit is exceeded.
+    let mut sink = HashingBuffer::default();
+    while let Some(chunk) = resp
+        .chunk()
         .await
-        .map_err(|e| VacError::ConfigError(format!("Failed to read adapter bytes: {}", e)))?;
+        .map_err(|e| VacError::ConfigError(format!("Failed to read adapter bytes: {}", e)))?
+    {
+        sink.update(&chunk);
+        if sink.bytes.len() > MAX_MODULE_SIZE {
+            return Err(VacError::ConfigError(format!(
+                "Adapter module too large: {} bytes (max {})",
+                sink.bytes.len(),
+                MAX_MODULE_SIZE
+            )));
+        }
+    }
 
-    if bytes.len() > MAX_MODULE_SIZE {
-        return Err(VacError::ConfigError(format!(
-            "Adapter module too large: {} bytes (max {})",
-            bytes.len(),
-            MAX_MODULE_SIZE
-        )));
+    let (bytes, computed_hash) = sink.finish();
+    registry.load_adapter_with_hash(&bytes, &computed_hash, expected_hash)
+}
+
+/// Read an adapter module, computing its SHA-256 (hex) in the same pass.
+///
+/// Reads at most `MAX_MODULE_SIZE + 1` bytes, so an oversized module is detected
+/// (by the caller's length check) without buffering all of it.
+pub fn read_adapter_hashed<R: io::Read>(reader: R) -> io::Result<(Vec<u8>, String)> {
+    let mut sink = HashingBuffer::default();
+    io::copy(&mut reader.take(MAX_MODULE_SIZE as u64 + 1), &mut sink)?;
+    Ok(sink.finish())
+}
+
+/// Write sink that hashes module bytes while buffering them for compilation.
+#[derive(Default)]
+struct HashingBuffer {
+    hasher: Sha256,
+    bytes: Vec<u8>,
+}
+
+impl HashingBuffer {
+    fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+        self.bytes.extend_from_slice(chunk);
     }
 
-    registry.load_adapter(&bytes, expected_hash)
+    fn finish(self) -> (Vec<u8>, String) {
+        (self.bytes, hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl io::Write for HashingBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
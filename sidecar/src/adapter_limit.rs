@@ -0,0 +1,105 @@
+//! Per-correlation-ID adapter concurrency limit (`max_concurrent_adapters_per_correlation`)
+//!
+//! Adapter runs are the most expensive step of a request, and many concurrent requests
+//! sharing one correlation ID could each start one. This caps how many adapter runs may
+//! be in flight per correlation ID; excess requests are shed with 429 instead of queued.
+
+use dashmap::DashMap;
+use std::sync::Arc;
+
+use crate::error::VacError;
+
+/// In-flight adapter runs per correlation ID.
+#[derive(Clone, Default)]
+pub struct AdapterConcurrencyLimiter {
+    inflight: Arc<DashMap<String, usize>>,
+    /// `None` disables the limit
+    max_concurrent: Option<usize>,
+}
+
+impl AdapterConcurrencyLimiter {
+    pub fn new(max_concurrent: Option<usize>) -> Self {
+        Self {
+            inflight: Arc::new(DashMap::new()),
+            max_concurrent,
+        }
+    }
+
+    /// Change the cap; runs already in flight keep their permits.
+    pub fn set_max_concurrent(&mut self, max_concurrent: Option<usize>) {
+        self.max_concurrent = max_concurrent;
+    }
+
+    /// Take a permit to run an adapter for `correlation_id`, held until dropped.
+    ///
+    /// Returns `Ok(None)` when the limit is disabled and `Err(AdapterBusy)` when the
+    /// correlation ID already has the maximum number of adapter runs in flight.
+    pub fn try_acquire(&self, correlation_id: &str) -> Result<Option<AdapterPermit>, VacError> {
+        let Some(max_concurrent) = self.max_concurrent else {
+            return Ok(None);
+        };
+        let mut running = self.inflight.entry(correlation_id.to_string()).or_insert(0);
+        if *running >= max_concurrent {
+            return Err(VacError::AdapterBusy);
+        }
+        *running += 1;
+        Ok(Some(AdapterPermit {
+            limiter: self.clone(),
+            correlation_id: correlation_id.to_string(),
+        }))
+    }
+
+    /// Adapter runs in flight for `correlation_id`.
+    pub fn running(&self, correlation_id: &str) -> usize {
+        self.inflight.get(correlation_id).map(|n| *n).unwrap_or(0)
+    }
+
+    fn release(&self, correlation_id: &str) {
+        // Remove the entry with the last permit so idle correlation IDs take no memory.
+        self.inflight.remove_if_mut(correlation_id, |_, running| {
+            *running = running.saturating_sub(1);
+            *running == 0
+        });
+    }
+}
+
+/// Permission to run one adapter; released on drop.
+pub struct AdapterPermit {
+    limiter: AdapterConcurrencyLimiter,
+    correlation_id: String,
+}
+
+impl Drop for AdapterPermit {
+    fn drop(&mut self) {
+        self.limiter.release(&self.correlation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excess_runs_are_shed_until_a_permit_is_released() {
+        let limiter = AdapterConcurrencyLimiter::new(Some(2));
+        let first = limiter.try_acquire("flow-1").unwrap().unwrap();
+        let _second = limiter.try_acquire("flow-1").unwrap().unwrap();
+        assert!(matches!(limiter.try_acquire("flow-1"), Err(VacError::AdapterBusy)));
+        // Other correlation IDs are limited separately.
+        assert!(limiter.try_acquire("flow-2").unwrap().is_some());
+
+        drop(first);
+        assert_eq!(limiter.running("flow-1"), 1);
+        assert!(limiter.try_acquire("flow-1").unwrap().is_some());
+    }
+
+    #[test]
+    fn idle_correlation_ids_are_forgotten() {
+        let limiter = AdapterConcurrencyLimiter::new(Some(1));
+        drop(limiter.try_acquire("flow-1").unwrap());
+        assert!(limiter.inflight.is_empty());
+
+        let disabled = AdapterConcurrencyLimiter::new(None);
+        assert!(disabled.try_acquire("flow-1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,236 @@
+//! Production wiring: sidecar state, router and background tasks from a [`Config`]
+//!
+//! `main` is a thin shell around these functions, so an embedder (or an integration
+//! test) gets exactly the routes, guard and tasks the binary runs instead of a copy
+//! that drifts from them.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    routing::{any, get},
+    Router,
+};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use crate::adapter::load_adapters_from_dir;
+use crate::cache_stats::start_cache_size_log_task;
+use crate::config::Config;
+use crate::error::VacError;
+use crate::guard::VacGuardLayer;
+use crate::health::{healthz_handler, readyz_handler, HEALTHZ_PATH, READYZ_PATH};
+use crate::heartbeat::supervise_heartbeat_task;
+#[cfg(feature = "metrics")]
+use crate::metrics::{metrics_handler, start_metrics_upkeep_task, METRICS_PATH, METRICS_UPKEEP_INTERVAL};
+use crate::proxy::upstream_handler;
+use crate::rate_limit::{start_rate_limit_cleanup_task, RateLimitBackend, RateLimitBackendKind, RateLimiter};
+use crate::reload::upstream_client_settings;
+use crate::replay_cache::{start_replay_cleanup_task, start_replay_persist_task, REPLAY_CLEANUP_INTERVAL};
+use crate::state::{SharedState, SidecarState};
+
+/// Interval for expiring per-correlation-ID step counts and token bindings
+const CORRELATION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Build the sidecar state for `config`: sidecar ID, upstream protocol and client, operator policy,
+/// the replay cache saved at `replay_cache_path` and the adapters preloaded from
+/// `adapters_dir` (prewarmed with `adapter_prewarm`).
+pub fn build_state(config: &Config) -> Result<SharedState, VacError> {
+    let root_public_key = biscuit_auth::PublicKey::from_bytes(&config.root_public_key)
+        .map_err(|e| VacError::ConfigError(format!("Invalid public key format: {}", e)))?;
+
+    let mut sidecar_state = SidecarState::new(
+        root_public_key,
+        config.api_key.clone(),
+        config.upstream_url.clone(),
+        config.rate_limit_max_requests,
+        config.rate_limit_window_secs,
+        config.replay_cache_enabled,
+        config.replay_cache_ttl_secs,
+    );
+    sidecar_state.apply_config(config)?;
+    // Fixed for the life of the process: not part of apply_config, so a reload cannot
+    // make the control plane see a different sidecar.
+    if let Some(sidecar_id) = &config.sidecar_id {
+        sidecar_state.sidecar_id = sidecar_id.clone();
+    }
+    // Also startup-only: gRPC clients need the HTTP/2 listener, which a reload cannot enable.
+    sidecar_state.protocol = config.protocol;
+    // Startup-only too, like the limits themselves: the rate limiter is not rebuilt on reload.
+    sidecar_state.rate_limiter = RateLimiter::with_algorithm(
+        config.rate_limit_max_requests,
+        Duration::from_secs(config.rate_limit_window_secs),
+        config.rate_limit_algorithm,
+    );
+    sidecar_state.rate_limit_backend = rate_limit_backend(config, &sidecar_state.rate_limiter)?;
+    sidecar_state.set_upstream_client_settings(upstream_client_settings(config));
+
+    // A snapshot that cannot be read only loses replay protection for IDs seen before
+    // the restart, so it is not worth refusing to start over.
+    if let (true, Some(path)) = (config.replay_cache_enabled, &config.replay_cache_path) {
+        match sidecar_state.replay_cache.load_from(path) {
+            Ok(loaded) => tracing::info!("🔁 Loaded {} replay cache entries from {}", loaded, path.display()),
+            Err(e) => tracing::warn!(error = %e, "Replay cache snapshot not loaded; starting empty"),
+        }
+    }
+
+    if let Some(dir) = &config.adapters_dir {
+        let loaded = load_adapters_from_dir(&sidecar_state.adapter_registry, dir)?;
+        tracing::info!("🧩 Loaded {} WASM adapter(s) from {}", loaded, dir);
+        if config.adapter_prewarm && loaded > 0 {
+            let warmed = sidecar_state.adapter_registry.prewarm()?;
+            tracing::info!("🔥 Prewarmed {} WASM adapter(s)", warmed);
+        }
+    }
+
+    Ok(Arc::new(tokio::sync::RwLock::new(sidecar_state)))
+}
+
+/// The configured rate limit backend; `local` is the in-memory limiter, which the Redis
+/// backend falls back to while Redis is unreachable.
+fn rate_limit_backend(config: &Config, local: &RateLimiter) -> Result<Arc<dyn RateLimitBackend>, VacError> {
+    match config.rate_limit_backend {
+        RateLimitBackendKind::Memory => Ok(Arc::new(local.clone())),
+        #[cfg(feature = "redis")]
+        RateLimitBackendKind::Redis => {
+            let redis_url = config.redis_url.as_deref().unwrap_or_default();
+            let backend = crate::rate_limit_redis::RedisRateLimiter::new(
+                redis_url,
+                config.rate_limit_max_requests,
+                Duration::from_secs(config.rate_limit_window_secs),
+                Duration::from_secs(config.rate_limit_bucket_max_age_secs),
+                local.clone(),
+            )?;
+            tracing::info!("🚦 Rate limit buckets shared through Redis");
+            Ok(Arc::new(backend))
+        }
+        #[cfg(not(feature = "redis"))]
+        RateLimitBackendKind::Redis => Err(VacError::ConfigError(
+            "rate_limit_backend = redis needs the sidecar built with the `redis` feature".to_string(),
+        )),
+    }
+}
+
+/// `GET /metrics` on its own, for a separate admin listener (`metrics_addr`).
+#[cfg(feature = "metrics")]
+pub fn metrics_router(state: SharedState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
+
+/// The proxy router: health probes and every other path through `VacGuardLayer` to the
+/// upstream. `/_vac/metrics` is included unless `metrics_addr` moves the metrics to their
+/// own listener, or the sidecar is built without the `metrics` feature.
+pub fn build_router(state: SharedState, config: &Config) -> Router {
+    let app = Router::new()
+        .route(HEALTHZ_PATH, get(healthz_handler))
+        .route(READYZ_PATH, get(readyz_handler))
+        .route("/*path", any(upstream_handler).layer(VacGuardLayer::new(state.clone())))
+        .with_state(state.clone());
+    #[cfg(feature = "metrics")]
+    if config.metrics_addr.is_none() {
+        return app.merge(Router::new().route(METRICS_PATH, get(metrics_handler)).with_state(state));
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = config;
+    app
+}
+
+/// Spawn the background tasks onto `tasks`: replay cache cleanup and snapshots (if
+/// enabled), step
+/// count and correlation binding expiry, rate limit bucket cleanup, the cache-size log,
+/// metrics upkeep and the supervised heartbeat. All of them stop when `shutdown` is cancelled.
+pub async fn spawn_background_tasks(
+    state: &SharedState,
+    config: &Config,
+    tasks: &TaskTracker,
+    shutdown: &CancellationToken,
+) {
+    let (replay_cache, step_limiter, correlation_bindings, rate_limiter) = {
+        let s = state.read().await;
+        (
+            s.replay_cache.clone(),
+            s.step_limiter.clone(),
+            s.correlation_bindings.clone(),
+            s.rate_limiter.clone(),
+        )
+    };
+
+    if config.replay_cache_enabled {
+        if let Some(path) = &config.replay_cache_path {
+            tasks.spawn(start_replay_persist_task(
+                replay_cache.clone(),
+                path.clone(),
+                Duration::from_secs(config.replay_cache_persist_interval_secs),
+                shutdown.clone(),
+            ));
+        }
+        tasks.spawn(start_replay_cleanup_task(replay_cache, REPLAY_CLEANUP_INTERVAL, shutdown.clone()));
+    }
+
+    // Always running, since a reload can turn max_steps_per_correlation /
+    // bind_correlation_to_token on; while they are off the maps stay empty.
+    {
+        let shutdown = shutdown.clone();
+        tasks.spawn(async move {
+            let mut interval = tokio::time::interval(CORRELATION_CLEANUP_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {
+                        step_limiter.cleanup_expired();
+                        correlation_bindings.cleanup_expired();
+                    }
+                }
+            }
+        });
+    }
+
+    // Idle rate limit buckets, so one bucket per sidecar ID ever seen is not kept forever
+    tasks.spawn(start_rate_limit_cleanup_task(
+        rate_limiter,
+        Duration::from_secs(config.rate_limit_bucket_max_age_secs),
+        shutdown.clone(),
+    ));
+
+    // Periodic cache-size log line for trending memory growth from logs
+    if config.cache_size_log_interval_secs > 0 {
+        tasks.spawn(start_cache_size_log_task(
+            state.clone(),
+            config.cache_size_log_interval_secs,
+            shutdown.clone(),
+        ));
+    }
+
+    // Histogram samples are only folded into buckets on a scrape or upkeep pass
+    #[cfg(feature = "metrics")]
+    tasks.spawn(start_metrics_upkeep_task(
+        state.read().await.metrics.clone(),
+        METRICS_UPKEEP_INTERVAL,
+        shutdown.clone(),
+    ));
+
+    // Heartbeat, supervised so it cannot die silently
+    tasks.spawn(supervise_heartbeat_task(
+        state.clone(),
+        config.control_plane_urls.clone(),
+        config.heartbeat_interval_secs,
+        config.session_key_rotation_interval_secs,
+        config.heartbeat_exit_action,
+        shutdown.clone(),
+    ));
+}
+
+/// Save the replay cache to `replay_cache_path` (if set), once in-flight requests have
+/// drained at shutdown.
+pub async fn save_replay_cache(state: &SharedState, config: &Config) {
+    let (true, Some(path)) = (config.replay_cache_enabled, &config.replay_cache_path) else {
+        return;
+    };
+    let replay_cache = state.read().await.replay_cache.clone();
+    match replay_cache.save_to(path) {
+        Ok(saved) => tracing::info!("🔁 Saved {} replay cache entries to {}", saved, path.display()),
+        Err(e) => tracing::warn!(error = %e, "Failed to persist replay cache"),
+    }
+}
@@ -1,8 +1,75 @@
 use biscuit_auth::{Biscuit, PublicKey};
 use crate::error::VacError;
 use crate::revocation::{extract_token_id, RevocationFilter};
+use crate::session_keys::{unix_now, SessionKeySet};
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Default limit on the base64 length of a token (`max_token_bytes`), matching the
+/// header value cap in `security::validate_header_value`.
+pub const DEFAULT_MAX_TOKEN_BYTES: usize = 8192;
+
+/// Reject a base64 token longer than `max_bytes` before it is decoded or parsed.
+pub fn check_token_size(token_str: &str, max_bytes: usize) -> Result<(), VacError> {
+    if token_str.len() > max_bytes {
+        return Err(VacError::InvalidTokenFormat);
+    }
+    Ok(())
+}
+
+/// Token from an `Authorization` header value using the Bearer scheme.
+///
+/// The scheme is matched case-insensitively and any run of spaces or tabs may separate
+/// it from the token (RFC 7235). Another scheme (e.g. `Basic`) carries no bearer token
+/// (`MissingToken`); `Bearer` followed by nothing, or by more than one word, is an
+/// `InvalidTokenFormat`.
+pub fn parse_bearer_token(header: &str) -> Result<&str, VacError> {
+    let header = header.trim_matches([' ', '\t']);
+    let (scheme, credentials) = header
+        .split_once([' ', '\t'])
+        .unwrap_or((header, ""));
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return Err(VacError::MissingToken);
+    }
+    let token = credentials.trim_start_matches([' ', '\t']);
+    if token.is_empty() || token.contains([' ', '\t']) {
+        return Err(VacError::InvalidTokenFormat);
+    }
+    Ok(token)
+}
+
+/// Fact (and rule head) predicates a token may carry under `strict_token_shape`: the ones
+/// [`crate::issuer::build_root_biscuit`] and [`crate::delegation::delegate`] write.
+pub const STRICT_TOKEN_PREDICATES: &[&str] = &["depth", "adapter_hash", "not_before"];
+
+/// Describe why a verified token falls outside the shape the sidecar issues, if it does
+/// (`strict_token_shape`).
+///
+/// A token may have at most one block per delegation hop plus the authority block, and
+/// its facts and rules may only produce [`STRICT_TOKEN_PREDICATES`]. Checks are not
+/// restricted: they can only narrow what a token is allowed to do.
+pub fn token_shape_violation(biscuit: &Biscuit) -> Option<String> {
+    let max_blocks = 1 + crate::delegation::DEFAULT_MAX_DELEGATION_DEPTH as usize;
+    if biscuit.block_count() > max_blocks {
+        return Some(format!("{} blocks (max {})", biscuit.block_count(), max_blocks));
+    }
+
+    let authorizer = match biscuit.authorizer() {
+        Ok(authorizer) => authorizer,
+        Err(e) => return Some(format!("unreadable token: {:?}", e)),
+    };
+    let (facts, rules, _checks, _policies) = authorizer.dump();
+    let unexpected = facts
+        .iter()
+        .map(|fact| &fact.predicate.name)
+        .chain(rules.iter().map(|rule| &rule.head.name))
+        .find(|name| !STRICT_TOKEN_PREDICATES.contains(&name.as_str()));
+    unexpected.map(|name| format!("unexpected predicate '{}'", name))
+}
 
 /// Verify a Root Biscuit signature using the user's root public key
 /// 
@@ -13,19 +80,7 @@ pub fn verify_root_biscuit(
     revocation_filter: Option<&Arc<RwLock<RevocationFilter>>>,
 ) -> Result<Biscuit, VacError> {
     // Check revocation filter first (before expensive signature verification)
-    if let Some(filter) = revocation_filter {
-        let token_id = extract_token_id(token_str)?;
-        let is_revoked = {
-            let f = filter.read().map_err(|_| {
-                VacError::InternalError("Failed to acquire revocation filter lock".to_string())
-            })?;
-            f.is_revoked(&token_id)
-        };
-        
-        if is_revoked {
-            return Err(VacError::InvalidSignature); // Token is revoked
-        }
-    }
+    check_not_revoked(token_str, revocation_filter)?;
     
     // Parse and verify Biscuit signature
     // The callback receives a key ID (for multi-key scenarios) and returns the public key
@@ -40,6 +95,114 @@ pub fn verify_root_biscuit(
     Ok(biscuit)
 }
 
+/// Reject a verified Root Biscuit used before its activation time.
+///
+/// Reads the `not_before($t)` facts of the authority block (what the issuer signed;
+/// a later `not_before` wins) and fails with [`VacError::TokenNotYetValid`] if `now`
+/// is earlier. Tokens without the fact are valid immediately. Expiry is enforced
+/// separately, by the token's own `check if time($time), ...`.
+pub fn check_not_before(biscuit: &Biscuit, now: SystemTime) -> Result<(), VacError> {
+    let mut authorizer = biscuit
+        .authorizer()
+        .map_err(|e| VacError::InternalError(format!("Failed to read token facts: {:?}", e)))?;
+    let not_before: Vec<(SystemTime,)> = authorizer
+        .query("not_before_value($t) <- not_before($t)")
+        // A `not_before` that is not a date is malformed, not merely early.
+        .map_err(|_| VacError::InvalidTokenFormat)?;
+    match not_before.iter().map(|(t,)| *t).max() {
+        Some(activates_at) if now < activates_at => Err(VacError::TokenNotYetValid),
+        _ => Ok(()),
+    }
+}
+
+/// Verify a Root Biscuit against several root public keys (`root_public_keys`, during a
+/// root key rotation), indexed by biscuit root key ID.
+///
+/// A token that names a root key ID is verified only with that key; one without is
+/// tried with each key in order. Returns the token and the key that verified it.
+pub fn verify_root_biscuit_with_keys(
+    token_str: &str,
+    root_public_keys: &[PublicKey],
+    revocation_filter: Option<&Arc<RwLock<RevocationFilter>>>,
+) -> Result<(Biscuit, PublicKey), VacError> {
+    check_not_revoked(token_str, revocation_filter)?;
+
+    let selected = Cell::new(None);
+    let by_key_id = Biscuit::from_base64(token_str, |key_id: Option<u32>| {
+        let key = match key_id {
+            Some(id) => root_public_keys.get(id as usize),
+            None => root_public_keys.first(),
+        };
+        selected.set(Some((key_id, key.copied())));
+        key.copied().ok_or(biscuit_auth::error::Format::UnknownPublicKey)
+    });
+    match (by_key_id, selected.get()) {
+        (Ok(biscuit), Some((_, Some(key)))) => Ok((biscuit, key)),
+        // Unknown key ID, or a bad signature under the key the token named
+        (_, Some((Some(_), _))) => Err(VacError::InvalidSignature),
+        // No key ID: the first key did not verify it, try the others
+        _ => root_public_keys
+            .iter()
+            .skip(1)
+            .find_map(|key| verify_root_biscuit(token_str, key, None).ok().map(|b| (b, *key)))
+            .ok_or(VacError::InvalidSignature),
+    }
+}
+
+fn check_not_revoked(
+    token_str: &str,
+    revocation_filter: Option<&Arc<RwLock<RevocationFilter>>>,
+) -> Result<(), VacError> {
+    let Some(filter) = revocation_filter else {
+        return Ok(());
+    };
+    let token_id = extract_token_id(token_str)?;
+    let is_revoked = {
+        let f = filter.read().map_err(|_| {
+            VacError::InternalError("Failed to acquire revocation filter lock".to_string())
+        })?;
+        f.is_revoked(&token_id)
+    };
+    if is_revoked {
+        return Err(VacError::TokenRevoked);
+    }
+    Ok(())
+}
+
+/// Future returned by [`RootTokenVerifier::verify`]
+pub type RootVerifyFuture<'a> = Pin<Box<dyn Future<Output = Result<PublicKey, VacError>> + Send + 'a>>;
+
+/// External check of a Root Biscuit's signature, e.g. by an HSM, a KMS or a signing service.
+///
+/// biscuit-auth cannot load a token without a root public key, so a verifier that accepts
+/// the token returns the key it verified it against; the sidecar then loads the token,
+/// and checks any delegation chain, with that key. An `Err` denies the request (return
+/// `VacError::InvalidSignature` for a bad signature).
+pub trait RootTokenVerifier: Send + Sync {
+    /// Verify the base64 root token as presented in the `Authorization` header.
+    fn verify<'a>(&'a self, token: &'a str) -> RootVerifyFuture<'a>;
+}
+
+/// [`verify_root_biscuit_with_keys`], with the signature check delegated to `verifier`
+/// when one is configured (otherwise the in-process `root_public_keys` are used).
+///
+/// Revoked tokens are rejected before the verifier is called. Returns the token and the
+/// root public key it was verified against.
+pub async fn verify_root_biscuit_with_verifier(
+    token_str: &str,
+    root_public_keys: &[PublicKey],
+    revocation_filter: Option<&Arc<RwLock<RevocationFilter>>>,
+    verifier: Option<&dyn RootTokenVerifier>,
+) -> Result<(Biscuit, PublicKey), VacError> {
+    let Some(verifier) = verifier else {
+        return verify_root_biscuit_with_keys(token_str, root_public_keys, revocation_filter);
+    };
+    check_not_revoked(token_str, revocation_filter)?;
+    let verified_key = verifier.verify(token_str).await?;
+    let biscuit = verify_root_biscuit(token_str, &verified_key, None)?;
+    Ok((biscuit, verified_key))
+}
+
 /// Verify a Receipt Biscuit signature using the sidecar's session public key
 pub fn verify_receipt_biscuit(
     receipt_str: &str,
@@ -54,6 +217,21 @@ pub fn verify_receipt_biscuit(
     Ok(receipt)
 }
 
+/// Verify a Receipt Biscuit signed by this sidecar or by any unexpired key in the
+/// published session key set (`accept_peer_receipts`).
+pub fn verify_receipt_biscuit_with_keys(
+    receipt_str: &str,
+    session_public_key: &PublicKey,
+    peer_keys: &SessionKeySet,
+) -> Result<Biscuit, VacError> {
+    verify_receipt_biscuit(receipt_str, session_public_key).or_else(|err| {
+        peer_keys
+            .active_keys(unix_now())
+            .find_map(|key| verify_receipt_biscuit(receipt_str, key).ok())
+            .ok_or(err)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,6 +242,83 @@ mod tests {
         KeyPair::new()
     }
 
+    #[test]
+    fn bearer_scheme_is_case_insensitive_and_whitespace_tolerant() {
+        assert_eq!(parse_bearer_token("Bearer abc").unwrap(), "abc");
+        assert_eq!(parse_bearer_token("bearer abc").unwrap(), "abc");
+        assert_eq!(parse_bearer_token("BEARER \t abc ").unwrap(), "abc");
+        assert!(matches!(parse_bearer_token("Basic dXNlcjpwdw=="), Err(VacError::MissingToken)));
+        assert!(matches!(parse_bearer_token("Bearerabc"), Err(VacError::MissingToken)));
+        assert!(matches!(parse_bearer_token("Bearer"), Err(VacError::InvalidTokenFormat)));
+        assert!(matches!(parse_bearer_token("Bearer   "), Err(VacError::InvalidTokenFormat)));
+        assert!(matches!(parse_bearer_token("Bearer abc def"), Err(VacError::InvalidTokenFormat)));
+    }
+
+    #[test]
+    fn check_token_size_rejects_over_limit() {
+        assert!(check_token_size(&"A".repeat(64), 64).is_ok());
+        assert!(matches!(
+            check_token_size(&"A".repeat(65), 64),
+            Err(VacError::InvalidTokenFormat)
+        ));
+    }
+
+    #[test]
+    fn token_shape_allows_issued_claims_only() {
+        let kp = test_keypair();
+        let issued = crate::issuer::build_root_biscuit(
+            &kp,
+            crate::issuer::RootClaims {
+                depth: Some(0),
+                valid_until: Some(std::time::SystemTime::now() + std::time::Duration::from_secs(60)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(token_shape_violation(&issued), None);
+
+        let delegated = crate::delegation::delegate(&issued, Default::default()).unwrap();
+        assert_eq!(token_shape_violation(&delegated), None);
+
+        let mut builder = Biscuit::builder();
+        builder.add_code("depth(0); role(\"admin\");").unwrap();
+        let extended = builder.build(&kp).unwrap();
+        assert_eq!(
+            token_shape_violation(&extended).as_deref(),
+            Some("unexpected predicate 'role'")
+        );
+
+        let mut builder = Biscuit::builder();
+        builder.add_code("adapter_hash($h) <- wanted($h);").unwrap();
+        assert!(token_shape_violation(&builder.build(&kp).unwrap()).is_some());
+    }
+
+    #[test]
+    fn not_before_in_the_future_is_rejected() {
+        let kp = test_keypair();
+        let now = std::time::SystemTime::now();
+        let not_before = |t| {
+            crate::issuer::build_root_biscuit(
+                &kp,
+                crate::issuer::RootClaims {
+                    not_before: Some(t),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let hour = std::time::Duration::from_secs(3600);
+
+        assert!(matches!(
+            check_not_before(&not_before(now + hour), now),
+            Err(VacError::TokenNotYetValid)
+        ));
+        assert!(check_not_before(&not_before(now - hour), now).is_ok());
+        // The same token becomes usable once its activation time has passed.
+        assert!(check_not_before(&not_before(now + hour), now + 2 * hour).is_ok());
+        assert!(check_not_before(&Biscuit::builder().build(&kp).unwrap(), now).is_ok());
+    }
+
     #[test]
     fn verify_root_biscuit_valid_token_correct_key() {
         let kp = test_keypair();
@@ -86,6 +341,33 @@ mod tests {
         assert!(matches!(result, Err(crate::error::VacError::InvalidSignature)));
     }
 
+    #[test]
+    fn verify_root_biscuit_with_keys_selects_by_key_id() {
+        let old_kp = test_keypair();
+        let new_kp = KeyPair::new();
+        let keys = [old_kp.public(), new_kp.public()];
+        let signed_with = |kp: &KeyPair, key_id: Option<u32>| {
+            let mut builder = Biscuit::builder();
+            if let Some(id) = key_id {
+                builder.set_root_key_id(id);
+            }
+            builder.build(kp).unwrap().to_base64().unwrap()
+        };
+
+        let (_, key) = verify_root_biscuit_with_keys(&signed_with(&new_kp, Some(1)), &keys, None).unwrap();
+        assert_eq!(key, new_kp.public());
+        // Without a key ID, every key is tried.
+        let (_, key) = verify_root_biscuit_with_keys(&signed_with(&new_kp, None), &keys, None).unwrap();
+        assert_eq!(key, new_kp.public());
+        // A key ID naming the wrong key, or no configured key, is a bad signature.
+        for token in [signed_with(&new_kp, Some(0)), signed_with(&new_kp, Some(7))] {
+            assert!(matches!(
+                verify_root_biscuit_with_keys(&token, &keys, None),
+                Err(crate::error::VacError::InvalidSignature)
+            ));
+        }
+    }
+
     #[test]
     fn verify_root_biscuit_invalid_base64() {
         let kp = test_keypair();
@@ -103,11 +385,11 @@ mod tests {
         let pk = kp.public();
         let token_id = crate::revocation::extract_token_id(&token).unwrap();
         let mut filter = RevocationFilter::new();
-        filter.revoke(&token_id).unwrap();
+        filter.revoke(&token_id, crate::revocation::RevocationSource::Admin).unwrap();
         let filter = Arc::new(RwLock::new(filter));
         let result = verify_root_biscuit(&token, &pk, Some(&filter));
         assert!(result.is_err());
-        assert!(matches!(result, Err(crate::error::VacError::InvalidSignature)));
+        assert!(matches!(result, Err(crate::error::VacError::TokenRevoked)));
     }
 
     #[test]
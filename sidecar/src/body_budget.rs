@@ -0,0 +1,100 @@
+//! Global byte budget for buffered request bodies (`max_total_body_bytes`)
+//!
+//! Each request body is buffered (up to [`MAX_REQUEST_BODY_SIZE`]) so adapters can read
+//! it, which makes sidecar memory grow with the number of concurrent requests. This caps
+//! the bytes all in-flight requests may reserve together: a request reserves its body
+//! size before buffering and releases it once the upstream has answered. Requests that
+//! do not fit are shed with 503 instead of queued.
+
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::error::VacError;
+use crate::security::MAX_REQUEST_BODY_SIZE;
+
+/// Shared byte budget for buffered request bodies.
+#[derive(Clone, Default)]
+pub struct BodyBudget {
+    /// Unreserved bytes; `None` disables the budget
+    bytes: Option<Arc<Semaphore>>,
+    max_total_bytes: Option<usize>,
+}
+
+impl BodyBudget {
+    pub fn new(max_total_bytes: Option<usize>) -> Self {
+        Self {
+            bytes: max_total_bytes
+                .map(|max| Arc::new(Semaphore::new(max.min(Semaphore::MAX_PERMITS)))),
+            max_total_bytes,
+        }
+    }
+
+    /// Change the budget. An unchanged budget keeps its reservations; a new one starts
+    /// empty, and bodies already buffered release into the old one.
+    pub fn set_max_total_bytes(&mut self, max_total_bytes: Option<usize>) {
+        if max_total_bytes != self.max_total_bytes {
+            *self = Self::new(max_total_bytes);
+        }
+    }
+
+    /// Bytes a body will take once buffered, from the upper bound of its size hint (the
+    /// declared `Content-Length`, 0 for no body); the per-request limit when unknown (chunked).
+    pub fn reservation_for(size_upper_bound: Option<u64>) -> usize {
+        size_upper_bound.map_or(MAX_REQUEST_BODY_SIZE, |upper| {
+            usize::try_from(upper).unwrap_or(usize::MAX).min(MAX_REQUEST_BODY_SIZE)
+        })
+    }
+
+    /// Reserve `bytes` of the budget, held until the permit is dropped.
+    ///
+    /// Returns `Ok(None)` when the budget is disabled and `Err(BodyBudgetExhausted)` when
+    /// the bytes are not available right now.
+    pub fn try_acquire(&self, bytes: usize) -> Result<Option<BodyBudgetPermit>, VacError> {
+        let Some(semaphore) = &self.bytes else {
+            return Ok(None);
+        };
+        let bytes = u32::try_from(bytes).map_err(|_| VacError::BodyBudgetExhausted)?;
+        match semaphore.clone().try_acquire_many_owned(bytes) {
+            Ok(permit) => Ok(Some(BodyBudgetPermit { _permit: permit })),
+            Err(TryAcquireError::NoPermits) | Err(TryAcquireError::Closed) => {
+                Err(VacError::BodyBudgetExhausted)
+            }
+        }
+    }
+
+    /// Unreserved bytes, or `None` when the budget is disabled.
+    pub fn available(&self) -> Option<usize> {
+        self.bytes.as_ref().map(|s| s.available_permits())
+    }
+}
+
+/// Reserved body bytes; returned to the budget on drop.
+pub struct BodyBudgetPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations_are_shed_until_bytes_are_released() {
+        let budget = BodyBudget::new(Some(1000));
+        let first = budget.try_acquire(600).unwrap().unwrap();
+        assert!(matches!(budget.try_acquire(600), Err(VacError::BodyBudgetExhausted)));
+        assert!(budget.try_acquire(400).unwrap().is_some());
+        assert_eq!(budget.available(), Some(400));
+
+        drop(first);
+        assert_eq!(budget.available(), Some(1000));
+        assert!(BodyBudget::new(None).try_acquire(usize::MAX).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_body_size_reserves_the_per_request_limit() {
+        assert_eq!(BodyBudget::reservation_for(Some(0)), 0);
+        assert_eq!(BodyBudget::reservation_for(Some(512)), 512);
+        assert_eq!(BodyBudget::reservation_for(Some(u64::MAX)), MAX_REQUEST_BODY_SIZE);
+        assert_eq!(BodyBudget::reservation_for(None), MAX_REQUEST_BODY_SIZE);
+    }
+}
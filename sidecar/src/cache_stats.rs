@@ -0,0 +1,129 @@
+//! Periodic cache-size log (`cache_size_log_interval_secs`)
+//!
+//! Logs one structured line with the sizes of the sidecar's in-memory caches, so slow
+//! growth in a long-running sidecar can be trended from logs without a metrics stack.
+
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::state::SharedState;
+
+/// Default interval between cache-size log lines (5 minutes).
+pub const DEFAULT_CACHE_SIZE_LOG_INTERVAL_SECS: u64 = 300;
+
+/// Snapshot of the in-memory cache sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheSizes {
+    pub replay_cache_size: usize,
+    pub rate_limit_buckets: usize,
+    pub revoked_count: usize,
+    pub adapter_count: usize,
+}
+
+/// Read the current cache sizes.
+pub async fn cache_sizes(state: &SharedState) -> CacheSizes {
+    let s = state.read().await;
+    CacheSizes {
+        replay_cache_size: s.replay_cache.size(),
+        rate_limit_buckets: s.rate_limiter.bucket_count(),
+        revoked_count: s.revocation_filter.read().map(|f| f.revoked_count()).unwrap_or(0),
+        adapter_count: s.adapter_registry.adapter_count(),
+    }
+}
+
+/// One tick of the cache-size task: log the current sizes and return them.
+pub async fn log_cache_sizes(state: &SharedState) -> CacheSizes {
+    let sizes = cache_sizes(state).await;
+    tracing::info!(
+        replay_cache_size = sizes.replay_cache_size,
+        rate_limit_buckets = sizes.rate_limit_buckets,
+        revoked_count = sizes.revoked_count,
+        adapter_count = sizes.adapter_count,
+        "Cache sizes"
+    );
+    sizes
+}
+
+/// Log cache sizes every `interval_secs` seconds until `shutdown` is cancelled.
+pub async fn start_cache_size_log_task(state: SharedState, interval_secs: u64, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {
+                log_cache_sizes(&state).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::SidecarState;
+    use biscuit_auth::KeyPair;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn tick_logs_all_cache_sizes() {
+        let state = SidecarState::new(
+            KeyPair::new().public(),
+            "k".to_string(),
+            "http://upstream.invalid".to_string(),
+            10,
+            60,
+            true,
+            60,
+        );
+        state.replay_cache.check_and_insert("cid-1").unwrap();
+        state.replay_cache.check_and_insert("cid-2").unwrap();
+        state.rate_limiter.check("sidecar-1");
+        state.revocation_filter.write().unwrap().revoke(&[7u8; 32], crate::revocation::RevocationSource::Admin).unwrap();
+        let state = Arc::new(tokio::sync::RwLock::new(state));
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let sizes = log_cache_sizes(&state).await;
+        assert_eq!(
+            sizes,
+            CacheSizes {
+                replay_cache_size: 2,
+                rate_limit_buckets: 1,
+                revoked_count: 1,
+                adapter_count: 0,
+            }
+        );
+
+        let out = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(out.lines().count(), 1, "{}", out);
+        for field in [
+            "replay_cache_size=2",
+            "rate_limit_buckets=1",
+            "revoked_count=1",
+            "adapter_count=0",
+        ] {
+            assert!(out.contains(field), "missing {} in {}", field, out);
+        }
+    }
+}
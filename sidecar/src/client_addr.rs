@@ -0,0 +1,219 @@
+//! Client address resolution behind reverse proxies (`trusted_proxies`)
+//!
+//! `X-Forwarded-For` and `X-Forwarded-Proto` are plain request headers, so any client can
+//! send them. They are only honored when the immediate peer (the socket address) is in
+//! the configured `trusted_proxies` CIDRs; otherwise the sidecar uses the socket address
+//! and treats the headers as spoofed. Anything derived from the client address or
+//! scheme is only as trustworthy as this boundary.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+
+use crate::error::VacError;
+
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+pub const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// One CIDR block, e.g. `10.0.0.0/8` or `fd00::/8` (a bare address is a single host).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(u32::from(net) as u128, u32::from(ip) as u128, self.prefix_len, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), self.prefix_len, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || VacError::ConfigError(format!("trusted_proxies: invalid CIDR '{}'", s));
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, len)) => (addr, Some(len.parse::<u8>().map_err(|_| invalid())?)),
+            None => (s.trim(), None),
+        };
+        let network = canonical(addr.parse::<IpAddr>().map_err(|_| invalid())?);
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_len);
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Cidr { network, prefix_len })
+    }
+}
+
+/// IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`, as seen on dual-stack sockets) compare as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+fn prefix_matches(net: u128, ip: u128, prefix_len: u8, bits: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = (bits - prefix_len) as u32;
+    (net >> shift) == (ip >> shift)
+}
+
+/// The set of peers whose `X-Forwarded-*` headers are believed; empty trusts nobody.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies {
+    cidrs: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Parse a list of CIDRs (or bare addresses).
+    pub fn parse<S: AsRef<str>>(cidrs: &[S]) -> Result<Self, VacError> {
+        let cidrs = cidrs
+            .iter()
+            .map(|c| c.as_ref())
+            .filter(|c| !c.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { cidrs })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cidrs.is_empty()
+    }
+}
+
+/// Who sent the request, after applying the `trusted_proxies` boundary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAddr {
+    /// Client address: the socket peer, or from `X-Forwarded-For` behind a trusted proxy.
+    /// `None` if the listener did not record the peer address.
+    pub ip: Option<IpAddr>,
+    /// `https` only if a trusted proxy said so; the sidecar listener itself is plain HTTP.
+    pub proto: String,
+    /// Whether the `X-Forwarded-*` headers were honored
+    pub forwarded_trusted: bool,
+}
+
+impl ClientAddr {
+    /// Resolve the client address from the socket `peer` and the request headers.
+    ///
+    /// Behind trusted proxies, the client is the right-most `X-Forwarded-For` entry that
+    /// is not itself a trusted proxy: entries to its left were written by the client and
+    /// cannot be believed.
+    pub fn resolve(peer: Option<SocketAddr>, headers: &HeaderMap, trusted: &TrustedProxies) -> Self {
+        let peer_ip = peer.map(|p| canonical(p.ip()));
+        let untrusted = ClientAddr {
+            ip: peer_ip,
+            proto: "http".to_string(),
+            forwarded_trusted: false,
+        };
+        let Some(peer_ip) = peer_ip.filter(|ip| trusted.contains(*ip)) else {
+            return untrusted;
+        };
+
+        let hops: Vec<IpAddr> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .map(canonical)
+            .collect();
+        let ip = hops
+            .iter()
+            .rev()
+            .find(|ip| !trusted.contains(**ip))
+            .or_else(|| hops.first())
+            .copied()
+            .unwrap_or(peer_ip);
+        let proto = headers
+            .get(X_FORWARDED_PROTO)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .filter(|v| v == "http" || v == "https")
+            .unwrap_or_else(|| "http".to_string());
+        ClientAddr {
+            ip: Some(ip),
+            proto,
+            forwarded_trusted: true,
+        }
+    }
+}
+
+/// Remove client-supplied forwarding headers so they are not passed on as if vouched for.
+pub fn strip_forwarded_headers(headers: &mut HeaderMap) {
+    for name in [X_FORWARDED_FOR, X_FORWARDED_PROTO, "x-forwarded-host", "forwarded"] {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(xff: &str, proto: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, xff.parse().unwrap());
+        headers.insert(X_FORWARDED_PROTO, proto.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_headers_honored_only_from_trusted_peer() {
+        let trusted = TrustedProxies::parse(&["10.0.0.0/8", "::1"]).unwrap();
+        let headers = headers("203.0.113.7", "https");
+
+        let via_proxy = ClientAddr::resolve(Some("10.1.2.3:5000".parse().unwrap()), &headers, &trusted);
+        assert_eq!(via_proxy.ip, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(via_proxy.proto, "https");
+        assert!(via_proxy.forwarded_trusted);
+
+        let direct = ClientAddr::resolve(Some("198.51.100.9:5000".parse().unwrap()), &headers, &trusted);
+        assert_eq!(direct.ip, Some("198.51.100.9".parse().unwrap()));
+        assert_eq!(direct.proto, "http");
+        assert!(!direct.forwarded_trusted);
+
+        // No trusted proxies configured: nobody's headers are believed.
+        let none = ClientAddr::resolve(Some("10.1.2.3:5000".parse().unwrap()), &headers, &TrustedProxies::default());
+        assert_eq!(none.ip, Some("10.1.2.3".parse().unwrap()));
+    }
+
+    #[test]
+    fn client_prepended_hops_are_ignored() {
+        let trusted = TrustedProxies::parse(&["10.0.0.0/8"]).unwrap();
+        // The client sent "X-Forwarded-For: 1.1.1.1"; the trusted proxies appended the rest.
+        let headers = headers("1.1.1.1, 203.0.113.7, 10.0.0.2", "http");
+        let client = ClientAddr::resolve(Some("[::ffff:10.0.0.1]:5000".parse().unwrap()), &headers, &trusted);
+        assert_eq!(client.ip, Some("203.0.113.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn cidr_parsing() {
+        let cidr: Cidr = "192.168.0.0/16".parse().unwrap();
+        assert!(cidr.contains("192.168.44.1".parse().unwrap()));
+        assert!(!cidr.contains("192.169.0.1".parse().unwrap()));
+        assert!(!cidr.contains("::1".parse().unwrap()));
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("not-an-ip".parse::<Cidr>().is_err());
+    }
+}
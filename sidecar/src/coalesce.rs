@@ -0,0 +1,225 @@
+//! Single-flight coalescing of identical idempotent upstream requests (`coalesce_idempotent`)
+//!
+//! While a GET for a given URI, bearer token and body is in flight to the upstream,
+//! identical requests wait for that call instead of sending their own; the (already
+//! buffered) upstream response is cloned to every waiter. Coalescing happens in
+//! the upstream handler, behind the guard, so each request is still authorized on its own
+//! and gets its own correlation ID and minted receipt.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use axum::body::{Body, Bytes};
+use axum::http::{request::Parts, HeaderMap, Method, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
+
+use crate::error::VacError;
+
+/// Buffered upstream response that can be handed to several clients.
+#[derive(Clone)]
+struct SharedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl SharedResponse {
+    async fn buffer(response: Response<Body>) -> Result<Self, VacError> {
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|e| VacError::ProxyError(format!("Failed to read response body: {}", e)))?;
+        Ok(Self {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        })
+    }
+
+    fn to_response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+type Waiter = oneshot::Sender<Result<SharedResponse, VacError>>;
+
+/// In-flight upstream calls keyed by [`RequestCoalescer::key`].
+#[derive(Default)]
+pub struct RequestCoalescer {
+    inflight: Mutex<HashMap<String, Vec<Waiter>>>,
+}
+
+impl RequestCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Coalescing key for a request, or `None` unless it is a GET.
+    ///
+    /// HEAD is left out: the proxy does not forward it, so there is no call to share.
+    ///
+    /// The token and body are hashed so the map never holds credentials.
+    pub fn key(parts: &Parts, body: &[u8]) -> Option<String> {
+        if parts.method != Method::GET {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(
+            parts
+                .headers
+                .get(axum::http::header::AUTHORIZATION)
+                .map(|v| v.as_bytes())
+                .unwrap_or_default(),
+        );
+        hasher.update([0u8]);
+        hasher.update(body);
+        Some(format!("{} {} {}", parts.method, parts.uri, hex::encode(hasher.finalize())))
+    }
+
+    /// Run `forward` for `key` unless an identical call is already in flight, in which case
+    /// wait for and clone its response.
+    pub async fn run<F, Fut>(&self, key: String, forward: F) -> Result<Response<Body>, VacError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Response<Body>, VacError>>,
+    {
+        let waiting = {
+            let mut inflight = self.inflight.lock().unwrap();
+            match inflight.get_mut(&key) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    Some(rx)
+                }
+                None => {
+                    inflight.insert(key.clone(), Vec::new());
+                    None
+                }
+            }
+        };
+
+        if let Some(rx) = waiting {
+            return match rx.await {
+                Ok(Ok(shared)) => Ok(shared.to_response()),
+                Ok(Err(e)) => Err(e),
+                // The leading request was dropped (client went away) before answering.
+                Err(_) => forward().await,
+            };
+        }
+
+        let mut leader = Leader { coalescer: self, key: Some(&key) };
+        let result = match forward().await {
+            Ok(response) => SharedResponse::buffer(response).await,
+            Err(e) => Err(e),
+        };
+        for waiter in leader.finish() {
+            let _ = waiter.send(result.clone());
+        }
+        result.map(|shared| shared.to_response())
+    }
+
+    #[cfg(test)]
+    fn inflight_len(&self) -> usize {
+        self.inflight.lock().unwrap().len()
+    }
+}
+
+/// Removes the leader's in-flight entry, also when its future is dropped mid-call; the
+/// dropped senders then wake the waiters, which fall back to their own upstream call.
+struct Leader<'a> {
+    coalescer: &'a RequestCoalescer,
+    key: Option<&'a String>,
+}
+
+impl Leader<'_> {
+    fn finish(&mut self) -> Vec<Waiter> {
+        self.key
+            .take()
+            .and_then(|key| self.coalescer.inflight.lock().unwrap().remove(key))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn parts(method: Method, uri: &str, token: &str) -> Parts {
+        axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token))
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    #[test]
+    fn key_covers_method_uri_and_token() {
+        let key = RequestCoalescer::key(&parts(Method::GET, "/a?x=1", "t1"), b"").unwrap();
+        assert_eq!(key, RequestCoalescer::key(&parts(Method::GET, "/a?x=1", "t1"), b"").unwrap());
+        assert!(!key.contains("t1"));
+        assert_ne!(key, RequestCoalescer::key(&parts(Method::GET, "/a?x=2", "t1"), b"").unwrap());
+        assert_ne!(key, RequestCoalescer::key(&parts(Method::GET, "/a?x=1", "t2"), b"").unwrap());
+        assert!(RequestCoalescer::key(&parts(Method::HEAD, "/a?x=1", "t1"), b"").is_none());
+        assert!(RequestCoalescer::key(&parts(Method::POST, "/a", "t1"), b"").is_none());
+    }
+
+    #[tokio::test]
+    async fn dropped_leader_releases_waiters() {
+        let coalescer = Arc::new(RequestCoalescer::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let leader = {
+            let (coalescer, calls) = (coalescer.clone(), calls.clone());
+            tokio::spawn(async move {
+                coalescer
+                    .run("k".to_string(), || async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        Ok(Response::new(Body::empty()))
+                    })
+                    .await
+            })
+        };
+        while calls.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let waiter = {
+            let (coalescer, calls) = (coalescer.clone(), calls.clone());
+            tokio::spawn(async move {
+                coalescer
+                    .run("k".to_string(), || async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        Ok(Response::new(Body::from("direct")))
+                    })
+                    .await
+            })
+        };
+        while coalescer.inflight.lock().unwrap().get("k").map_or(0, Vec::len) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        leader.abort();
+        let response = waiter.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(coalescer.inflight_len(), 0);
+    }
+}
@@ -1,8 +1,21 @@
-use crate::error::VacError;
+use crate::error::{ErrorResponseFormat, VacError};
+use crate::adapter::AdapterReservedFacts;
+use crate::client_addr::TrustedProxies;
+use crate::grpc::UpstreamProtocol;
+use crate::rate_limit::{RateLimitAlgorithm, RateLimitBackendKind, RateLimitKey};
+use crate::heartbeat::HeartbeatExitAction;
+use crate::policy::{OptionsAsterisk, PathTrailingSlash};
+use crate::log_sampling::LogSampling;
+use crate::flow_graph::FlowGraph;
+use crate::revocation::RevocationBloomSettings;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::Deserialize;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+
+/// Longest accepted `sidecar_id` (a DNS name, so any Kubernetes pod name fits)
+pub const MAX_SIDECAR_ID_LEN: usize = 253;
 
 /// Configuration loaded from CLI args, environment variables, and/or config files
 /// 
@@ -11,6 +24,9 @@ use clap::Parser;
 /// Config precedence: CLI args > env vars > config file > defaults
 pub struct Config {
     pub root_public_key: Vec<u8>,
+    // Every accepted root key, indexed by biscuit root key ID (empty unless configured;
+    // `root_public_key` is then the first)
+    pub root_public_keys: Vec<Vec<u8>>,
     pub upstream_url: String,
     pub api_key: String,
     pub control_plane_url: String,
@@ -18,19 +34,143 @@ pub struct Config {
     pub session_key_rotation_interval_secs: u64,
     pub adapters_dir: Option<String>,
     pub log_level: String,
+    pub log_redact_fields: Vec<String>,
     // Phase 4.7: Rate limiting configuration
     pub rate_limit_max_requests: u32,
     pub rate_limit_window_secs: u64,
     // Phase 4.8: Replay attack mitigation
     pub replay_cache_enabled: bool,
     pub replay_cache_ttl_secs: u64,
+    // Trailing-slash normalization for `operation` facts
+    pub path_trailing_slash: PathTrailingSlash,
+    // Error body serialization
+    pub error_response_format: ErrorResponseFormat,
+    // Adapter prewarm at startup
+    pub adapter_prewarm: bool,
+    // Delegation summary headers to upstream
+    pub forward_delegation_chain: bool,
+    // Receipt minting method filter
+    pub mint_receipts_for_methods: Option<Vec<String>>,
+    // Return policy denials as 200 with a decision marker
+    pub soft_deny: bool,
+    // SHA-256 the policy_file contents must match
+    pub policy_pin_hash: Option<String>,
+    // Upstream response status allowlist
+    pub upstream_allowed_statuses: Option<Vec<u16>>,
+    // Inbound HTTP/2 (h2c prior knowledge)
+    pub server_http2_enabled: bool,
+    // Require a client-supplied correlation ID
+    pub require_correlation_id: bool,
+    // Token size limit
+    pub max_token_bytes: usize,
+    // Single-flight upstream calls for identical concurrent GET requests
+    pub coalesce_idempotent: bool,
+    // Reject tokens with unexpected blocks or fact predicates
+    pub strict_token_shape: bool,
+    // Interval of the cache-size log line (0 = off)
+    pub cache_size_log_interval_secs: u64,
+    // Cap on revoked token IDs accepted per heartbeat
+    pub max_revocation_list_size: usize,
+    // Audit webhook receiving every minted receipt
+    pub receipt_webhook_url: Option<String>,
+    // Cap on receipts minted under one correlation ID
+    pub max_steps_per_correlation: Option<u32>,
+    // Replay cache keyed on (correlation ID, method, path) instead of correlation ID alone
+    pub replay_key_includes_operation: bool,
+    // Cap on concurrent adapter runs sharing one correlation ID
+    pub max_concurrent_adapters_per_correlation: Option<usize>,
+    // Canonical JSON bodies (sorted keys, no extra whitespace) for adapters
+    pub canonicalize_json_body: bool,
+    // Forward the canonicalized JSON body instead of the original bytes
+    pub forward_canonical_json_body: bool,
+    // Correlation IDs bound to the first token that uses them
+    pub bind_correlation_to_token: bool,
+    // WASM adapter runs logged as slow at or above this duration
+    pub adapter_slow_threshold_ms: u64,
+    // Handling of server-wide `OPTIONS *` requests
+    pub options_asterisk: OptionsAsterisk,
+    // JSON-lines file receiving revocation audit records
+    pub revocation_audit_log: Option<PathBuf>,
+    // Receipts signed by other sidecars in the control plane's session key set
+    pub accept_peer_receipts: bool,
+    // Peers whose X-Forwarded-* headers are honored
+    pub trusted_proxies: TrustedProxies,
+    // Receipts in the compact X-VAC-Receipt-Bin encoding
+    pub accept_compact_receipts: bool,
+    // Response to the heartbeat task exiting or panicking
+    pub heartbeat_exit_action: HeartbeatExitAction,
+    // Handling of adapter facts named like sidecar-trusted predicates
+    pub adapter_reserved_facts: AdapterReservedFacts,
+    // Treat a pinned adapter that yields no facts as an extraction failure
+    pub require_adapter_facts: bool,
+    // Whole-request timeout for upstream calls
+    pub upstream_timeout_secs: Option<u64>,
+    // Byte budget shared by all buffered request bodies
+    pub max_total_body_bytes: Option<usize>,
+    // Separate admin listener for /metrics
+    pub metrics_addr: Option<String>,
+    // X-VAC-Depth header on responses and upstream requests
+    pub expose_delegation_depth: bool,
+    // Upper bound on Datalog policy evaluation
+    pub policy_eval_timeout_ms: Option<u64>,
+    // Bound on draining in-flight requests at shutdown
+    pub shutdown_grace_secs: u64,
+    // Operator Datalog policy added to every authorization
+    pub policy_file: Option<PathBuf>,
+    // Contents of `policy_file`, already compiled once
+    pub policy: Option<String>,
+    // Control planes tried in order for heartbeats (failover)
+    pub control_plane_urls: Vec<String>,
+    // Pinned SHA-256 of the control plane's TLS certificate
+    pub control_plane_cert_fingerprint: Option<[u8; 32]>,
+    // Receipt validity window and clock skew allowance
+    pub receipt_expiry_secs: u64,
+    pub clock_skew_grace_secs: u64,
+    // Bloom filter for revoked token IDs (`[revocation]` section); None = exact set
+    pub revocation_bloom: Option<RevocationBloomSettings>,
+    // Sampling of repeated denial log lines
+    pub log_sampling: Option<LogSampling>,
+    // Replay cache snapshot file, loaded at startup
+    pub replay_cache_path: Option<PathBuf>,
+    pub replay_cache_persist_interval_secs: u64,
+    // Stable sidecar ID (default: a random UUID per process)
+    pub sidecar_id: Option<String>,
+    // Ceiling on a WASM adapter instance's linear memory
+    pub adapter_max_memory_bytes: u64,
+    // Upstream wire protocol (gRPC passthrough)
+    pub protocol: UpstreamProtocol,
+    // Request headers passed to WASM adapters in the context blob
+    pub adapter_context_headers: Vec<String>,
+    // Idle rate limit buckets are dropped after this long
+    pub rate_limit_bucket_max_age_secs: u64,
+    // Declarative flow graph enforced on receipts (`[flow]` section); None = no graph
+    pub flow_graph: Option<FlowGraph>,
+    // Operation -> sub-operations it mints one receipt each for (`[[batch_receipts]]` tables)
+    pub batch_receipts: HashMap<String, Vec<String>>,
+    // What rate limit buckets are kept for
+    pub rate_limit_key: RateLimitKey,
+    // How rate limited requests are counted
+    pub rate_limit_algorithm: RateLimitAlgorithm,
+    // Replay cache keyed on a hash of the whole request instead of the correlation ID
+    pub replay_key_request_hash: bool,
+    // Redis for rate_limit_backend = redis
+    pub redis_url: Option<String>,
+    // Where rate limit buckets are kept
+    pub rate_limit_backend: RateLimitBackendKind,
+    // Hosts requests may be forwarded to
+    pub upstream_host_allowlist: Option<Vec<String>>,
+    // Consecutive successful heartbeats that end a heartbeat-failure lockdown
+    pub lockdown_recovery_successes: u32,
 }
 
 /// CLI arguments structure for clap
-#[derive(Debug, Parser)]
+#[derive(Debug, Default, Parser)]
 #[command(name = "vac-sidecar")]
 #[command(about = "V-A-C Protocol Sidecar - Verifiable Agentic Credential enforcement proxy")]
 pub struct CliArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    
     /// Path to configuration file (TOML or YAML)
     #[arg(long)]
     pub config_file: Option<PathBuf>,
@@ -39,6 +179,10 @@ pub struct CliArgs {
     #[arg(long)]
     pub root_public_key: Option<String>,
     
+    /// Hex-encoded Ed25519 root public keys, comma-separated, indexed by biscuit root key ID; use instead of root_public_key during a key rotation
+    #[arg(long, value_delimiter = ',')]
+    pub root_public_keys: Option<Vec<String>>,
+    
     /// Upstream API base URL (overrides env/config)
     #[arg(long)]
     pub upstream_url: Option<String>,
@@ -47,6 +191,10 @@ pub struct CliArgs {
     #[arg(long)]
     pub api_key: Option<String>,
     
+    /// File holding the API key (trailing whitespace trimmed); used when no api_key is set
+    #[arg(long)]
+    pub api_key_file: Option<PathBuf>,
+    
     /// Control Plane URL for heartbeats (overrides env/config)
     #[arg(long)]
     pub control_plane_url: Option<String>,
@@ -67,6 +215,10 @@ pub struct CliArgs {
     #[arg(long)]
     pub log_level: Option<String>,
     
+    /// Log field names whose values are replaced with `***`, comma-separated (overrides env/config)
+    #[arg(long, value_delimiter = ',')]
+    pub log_redact_fields: Option<Vec<String>>,
+    
     /// Rate limit: Maximum requests per window (overrides env/config)
     #[arg(long)]
     pub rate_limit_max_requests: Option<u32>,
@@ -82,6 +234,245 @@ pub struct CliArgs {
     /// Replay cache: TTL in seconds (overrides env/config)
     #[arg(long)]
     pub replay_cache_ttl_secs: Option<u64>,
+    
+    /// Trailing-slash handling for policy paths: preserve, strip, reject (overrides env/config)
+    #[arg(long)]
+    pub path_trailing_slash: Option<String>,
+    
+    /// Error response body format: json (default), text, or problem+json (RFC 7807)
+    #[arg(long)]
+    pub error_response_format: Option<String>,
+    
+    /// Instantiate each loaded adapter once at startup and fail if any cannot (default: true)
+    #[arg(long)]
+    pub adapter_prewarm: Option<bool>,
+    
+    /// Send X-VAC-Delegation-Depth / X-VAC-Delegation-Chain to the upstream after policy passes (default: false)
+    #[arg(long)]
+    pub forward_delegation_chain: Option<bool>,
+    
+    /// Only mint receipts for these HTTP methods, comma-separated (default: all methods)
+    #[arg(long, value_delimiter = ',')]
+    pub mint_receipts_for_methods: Option<Vec<String>>,
+    
+    /// Return policy denials as 200 with {"vac_decision":"deny"} instead of 403 (never applies to token/signature failures)
+    #[arg(long)]
+    pub soft_deny: Option<bool>,
+    
+    /// Hex SHA-256 of the policy_file contents; a policy that does not match is refused at startup and on reload
+    #[arg(long)]
+    pub policy_pin_hash: Option<String>,
+    
+    /// Upstream response statuses passed to the client, comma-separated; others become 502 (default: all)
+    #[arg(long, value_delimiter = ',')]
+    pub upstream_allowed_statuses: Option<Vec<u16>>,
+    
+    /// Accept HTTP/2 on the inbound listener (prior knowledge / h2c) in addition to HTTP/1.1 (default: false)
+    #[arg(long)]
+    pub server_http2_enabled: Option<bool>,
+    
+    /// Reject requests without a valid X-Correlation-ID with 400 instead of generating one (default: false)
+    #[arg(long)]
+    pub require_correlation_id: Option<bool>,
+    
+    /// Maximum base64 length of a token (bearer, delegation, receipt); longer tokens are rejected before parsing (default: 8192)
+    #[arg(long)]
+    pub max_token_bytes: Option<usize>,
+    
+    /// Share one upstream call between identical concurrent GET requests (same path and token); each still gets its own receipt
+    #[arg(long)]
+    pub coalesce_idempotent: Option<bool>,
+    
+    /// Reject root tokens with more blocks than a delegation chain allows or facts other than depth/adapter_hash (default: off)
+    #[arg(long)]
+    pub strict_token_shape: Option<bool>,
+    
+    /// Seconds between structured log lines with internal cache sizes; 0 disables (default: 300)
+    #[arg(long)]
+    pub cache_size_log_interval_secs: Option<u64>,
+    
+    /// Maximum revoked token IDs accepted from one heartbeat response; larger lists are logged and not applied (default: 100000)
+    #[arg(long)]
+    pub max_revocation_list_size: Option<usize>,
+    
+    /// URL to POST every minted receipt to (JSON, delivered in the background with bounded retries; unset disables)
+    #[arg(long)]
+    pub receipt_webhook_url: Option<String>,
+    
+    /// Maximum steps (minted receipts) per correlation ID; further steps are rejected with 403 (default: unlimited)
+    #[arg(long)]
+    pub max_steps_per_correlation: Option<u32>,
+    
+    /// Key the replay cache on correlation ID, method and path, so a correlation ID is only blocked for the operation it was first used for (default: false)
+    #[arg(long)]
+    pub replay_key_includes_operation: Option<bool>,
+    
+    /// Maximum concurrent WASM adapter runs per correlation ID; excess requests get 429 (default: unlimited)
+    #[arg(long)]
+    pub max_concurrent_adapters_per_correlation: Option<usize>,
+    
+    /// Re-serialize JSON request bodies with sorted keys and no insignificant whitespace before adapter extraction (default: false)
+    #[arg(long)]
+    pub canonicalize_json_body: Option<bool>,
+    
+    /// With canonicalize_json_body, forward the canonical body to the upstream instead of the original bytes (default: true)
+    #[arg(long)]
+    pub forward_canonical_json_body: Option<bool>,
+    
+    /// Bind each correlation ID to the first token that uses it; other tokens reusing it are rejected with 409 (default: false)
+    #[arg(long)]
+    pub bind_correlation_to_token: Option<bool>,
+    
+    /// Log WASM adapter runs taking at least this many milliseconds as slow; 0 disables (default: 1000)
+    #[arg(long)]
+    pub adapter_slow_threshold_ms: Option<u64>,
+    
+    /// Handling of `OPTIONS *` requests: reject (default, 400) or respond (204 locally)
+    #[arg(long)]
+    pub options_asterisk: Option<String>,
+    
+    /// Append an audit record (token ID, source, timestamp) for every revoked token to this file, as JSON lines (default: log only)
+    #[arg(long)]
+    pub revocation_audit_log: Option<String>,
+    
+    /// Also accept receipts signed by any active session key published at the control plane's /session-keys, so flows can continue on another sidecar instance; trusts every key the control plane publishes, so only as safe as the network path to it (default: false)
+    #[arg(long)]
+    pub accept_peer_receipts: Option<bool>,
+    
+    /// CIDRs of reverse proxies whose X-Forwarded-For/Proto headers are honored, comma-separated; from other peers the headers are ignored and stripped (default: none)
+    #[arg(long, value_delimiter = ',')]
+    pub trusted_proxies: Option<Vec<String>>,
+    
+    /// Also accept receipts in the compact X-VAC-Receipt-Bin header (length-prefixed raw biscuits, base64url without padding) (default: false)
+    #[arg(long)]
+    pub accept_compact_receipts: Option<bool>,
+    
+    /// What to do if the heartbeat task exits or panics: restart (default, with backoff) or lockdown; the sidecar is marked unhealthy either way
+    #[arg(long)]
+    pub heartbeat_exit_action: Option<String>,
+    
+    /// What to do with adapter facts named like a trusted predicate (operation, prior_event, ...): allow (default), reject (403) or namespace (prefix with adapter_)
+    #[arg(long)]
+    pub adapter_reserved_facts: Option<String>,
+    
+    /// Reject requests (422) when the pinned adapter extracts zero facts instead of evaluating the policy without them (default: false)
+    #[arg(long)]
+    pub require_adapter_facts: Option<bool>,
+    
+    /// Upstream request timeout in seconds; a timed-out call is answered with 504 (default: no timeout)
+    #[arg(long)]
+    pub upstream_timeout_secs: Option<u64>,
+    
+    /// Total bytes all in-flight request bodies may buffer together; requests beyond it get 503 (default: unlimited)
+    #[arg(long)]
+    pub max_total_body_bytes: Option<usize>,
+    
+    /// Serve /metrics on this address (e.g. 127.0.0.1:9090) instead of /_vac/metrics on the proxy port (default: proxy port)
+    #[arg(long)]
+    pub metrics_addr: Option<String>,
+    
+    /// Add X-VAC-Depth (the verified delegation depth) to upstream requests and client responses (default: false)
+    #[arg(long)]
+    pub expose_delegation_depth: Option<bool>,
+    
+    /// Abort policy evaluation after this many milliseconds with a 500 (default: no timeout)
+    #[arg(long)]
+    pub policy_eval_timeout_ms: Option<u64>,
+    
+    /// Seconds to let in-flight requests finish after SIGTERM/Ctrl-C before open connections are dropped (default: 25)
+    #[arg(long)]
+    pub shutdown_grace_secs: Option<u64>,
+    
+    /// Datalog policy file (allow/deny/check rules) evaluated for every request; validated at startup
+    #[arg(long)]
+    pub policy_file: Option<PathBuf>,
+    
+    /// Control Plane URLs, comma-separated, tried in order on every heartbeat; use instead of control_plane_url for failover to a standby
+    #[arg(long, value_delimiter = ',')]
+    pub control_plane_urls: Option<Vec<String>>,
+    
+    /// SHA-256 fingerprint (hex, ':' allowed) of the control plane's TLS certificate; any other certificate is rejected, even if CA-valid
+    #[arg(long)]
+    pub control_plane_cert_fingerprint: Option<String>,
+    
+    /// Seconds a receipt stays valid after it is minted (default: 300)
+    #[arg(long)]
+    pub receipt_expiry_secs: Option<u64>,
+    
+    /// Extra seconds allowed past receipt_expiry_secs for clock skew between sidecars (default: 30)
+    #[arg(long)]
+    pub clock_skew_grace_secs: Option<u64>,
+    
+    /// Log at most COUNT identical denials per interval, then one summary line, e.g. "100/10s" (default: log every denial)
+    #[arg(long)]
+    pub log_sampling: Option<String>,
+    
+    /// Save the replay cache to this file periodically and on shutdown, and load it at startup (default: in memory only)
+    #[arg(long)]
+    pub replay_cache_path: Option<String>,
+    
+    /// Seconds between replay cache snapshots to replay_cache_path (default: 30)
+    #[arg(long)]
+    pub replay_cache_persist_interval_secs: Option<u64>,
+    
+    /// ID reported to the control plane, recorded in receipts and used as the rate-limit key, e.g. the pod name (default: a random UUID per process)
+    #[arg(long)]
+    pub sidecar_id: Option<String>,
+    
+    /// Maximum linear memory of a WASM adapter instance in bytes; growing past it traps (default: 67108864, 64 MiB)
+    #[arg(long)]
+    pub adapter_max_memory_bytes: Option<u64>,
+    
+    /// Upstream protocol: http (default) or grpc (HTTP/2 passthrough to an h2c upstream; receipts only for calls ending with grpc-status 0; needs server_http2_enabled)
+    #[arg(long)]
+    pub protocol: Option<String>,
+    
+    /// Request headers (comma-separated names) passed to WASM adapters exporting extract_facts_with_context, e.g. x-tenant-id; credential headers are refused (default: none)
+    #[arg(long, value_delimiter = ',')]
+    pub adapter_context_headers: Option<Vec<String>>,
+    
+    /// Seconds after its last refill before an idle sidecar's rate limit bucket is dropped (default: 10x rate_limit_window_secs; at least the window)
+    #[arg(long)]
+    pub rate_limit_bucket_max_age_secs: Option<u64>,
+    
+    /// Rate limit bucket key: sidecar (default; one bucket shared by all agents) or token (one bucket per root token ID)
+    #[arg(long)]
+    pub rate_limit_key: Option<String>,
+    
+    /// Rate limit algorithm: token_bucket (default; bursts of up to rate_limit_max_requests, refilled over the window) or sliding_log (at most rate_limit_max_requests in any trailing window)
+    #[arg(long)]
+    pub rate_limit_algorithm: Option<String>,
+    
+    /// Key the replay cache on a SHA-256 of method, path and query, body and root token ID instead of the correlation ID, so an identical request is blocked whatever its correlation ID (default: false; hashes every body)
+    #[arg(long)]
+    pub replay_key_request_hash: Option<bool>,
+    
+    /// Redis URL for rate_limit_backend = redis, e.g. redis://redis:6379/0
+    #[arg(long)]
+    pub redis_url: Option<String>,
+    
+    /// Rate limit backend: memory (default; per sidecar) or redis (buckets shared through redis_url by every sidecar using it; token_bucket only; needs the redis feature)
+    #[arg(long)]
+    pub rate_limit_backend: Option<String>,
+    
+    /// Hosts the sidecar may forward to, comma-separated; a request resolving to any other host is refused (default: any host)
+    #[arg(long, value_delimiter = ',')]
+    pub upstream_host_allowlist: Option<Vec<String>>,
+    
+    /// Consecutive successful heartbeats after which a lockdown entered on heartbeat failures is lifted; 0 keeps the sidecar locked down until restart (default: 3)
+    #[arg(long)]
+    pub lockdown_recovery_successes: Option<u32>,
+}
+
+/// Subcommands (without one, the sidecar runs)
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print a commented config.toml template with every field and its default
+    GenerateConfig {
+        /// Write the template to this path instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 /// Config file structure (deserialized from TOML/YAML)
@@ -92,15 +483,21 @@ struct ConfigFile {
     #[serde(rename = "logging")]
     logging: Option<LoggingConfig>,
     #[serde(rename = "revocation")]
-    #[allow(dead_code)] // Placeholder for future bloom filter settings
     revocation: Option<RevocationConfig>,
+    #[serde(rename = "flow")]
+    flow: Option<FlowConfig>,
+    #[serde(rename = "batch_receipts")]
+    batch_receipts: Option<Vec<BatchReceiptsConfig>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct SidecarConfig {
     root_public_key: Option<String>,
+    // All accepted root keys, indexed by biscuit root key ID
+    root_public_keys: Option<Vec<String>>,
     upstream_url: Option<String>,
     api_key: Option<String>,
+    api_key_file: Option<PathBuf>,
     control_plane_url: Option<String>,
     heartbeat_interval_secs: Option<u64>,
     session_key_rotation_interval_secs: Option<u64>,
@@ -111,18 +508,149 @@ struct SidecarConfig {
     // Phase 4.8: Replay attack mitigation
     replay_cache_enabled: Option<bool>,
     replay_cache_ttl_secs: Option<u64>,
+    // Trailing-slash normalization for `operation` facts
+    path_trailing_slash: Option<String>,
+    // Error body serialization
+    error_response_format: Option<String>,
+    // Adapter prewarm at startup
+    adapter_prewarm: Option<bool>,
+    // Delegation summary headers to upstream
+    forward_delegation_chain: Option<bool>,
+    // Receipt minting method filter
+    mint_receipts_for_methods: Option<Vec<String>>,
+    // Return policy denials as 200 with a decision marker
+    soft_deny: Option<bool>,
+    // SHA-256 the policy_file contents must match
+    policy_pin_hash: Option<String>,
+    // Upstream response status allowlist
+    upstream_allowed_statuses: Option<Vec<u16>>,
+    // Inbound HTTP/2 (h2c prior knowledge)
+    server_http2_enabled: Option<bool>,
+    // Require a client-supplied correlation ID
+    require_correlation_id: Option<bool>,
+    // Token size limit
+    max_token_bytes: Option<usize>,
+    // Single-flight upstream calls for identical concurrent GET requests
+    coalesce_idempotent: Option<bool>,
+    // Reject tokens with unexpected blocks or fact predicates
+    strict_token_shape: Option<bool>,
+    // Interval of the cache-size log line (0 = off)
+    cache_size_log_interval_secs: Option<u64>,
+    // Cap on revoked token IDs accepted per heartbeat
+    max_revocation_list_size: Option<usize>,
+    // Audit webhook receiving every minted receipt
+    receipt_webhook_url: Option<String>,
+    // Cap on receipts minted under one correlation ID
+    max_steps_per_correlation: Option<u32>,
+    // Replay cache keyed on (correlation ID, method, path) instead of correlation ID alone
+    replay_key_includes_operation: Option<bool>,
+    // Cap on concurrent adapter runs sharing one correlation ID
+    max_concurrent_adapters_per_correlation: Option<usize>,
+    // Canonical JSON bodies (sorted keys, no extra whitespace) for adapters
+    canonicalize_json_body: Option<bool>,
+    // Forward the canonicalized JSON body instead of the original bytes
+    forward_canonical_json_body: Option<bool>,
+    // Correlation IDs bound to the first token that uses them
+    bind_correlation_to_token: Option<bool>,
+    // WASM adapter runs logged as slow at or above this duration
+    adapter_slow_threshold_ms: Option<u64>,
+    // Handling of server-wide `OPTIONS *` requests
+    options_asterisk: Option<String>,
+    // JSON-lines file receiving revocation audit records
+    revocation_audit_log: Option<String>,
+    // Receipts signed by other sidecars in the control plane's session key set
+    accept_peer_receipts: Option<bool>,
+    // Peers whose X-Forwarded-* headers are honored
+    trusted_proxies: Option<Vec<String>>,
+    // Receipts in the compact X-VAC-Receipt-Bin encoding
+    accept_compact_receipts: Option<bool>,
+    // Response to the heartbeat task exiting or panicking
+    heartbeat_exit_action: Option<String>,
+    // Handling of adapter facts named like sidecar-trusted predicates
+    adapter_reserved_facts: Option<String>,
+    // Treat a pinned adapter that yields no facts as an extraction failure
+    require_adapter_facts: Option<bool>,
+    // Whole-request timeout for upstream calls
+    upstream_timeout_secs: Option<u64>,
+    // Byte budget shared by all buffered request bodies
+    max_total_body_bytes: Option<usize>,
+    // Separate admin listener for /metrics
+    metrics_addr: Option<String>,
+    // X-VAC-Depth header on responses and upstream requests
+    expose_delegation_depth: Option<bool>,
+    // Upper bound on Datalog policy evaluation
+    policy_eval_timeout_ms: Option<u64>,
+    // Bound on draining in-flight requests at shutdown
+    shutdown_grace_secs: Option<u64>,
+    // Operator Datalog policy added to every authorization
+    policy_file: Option<PathBuf>,
+    // Control planes tried in order for heartbeats (failover)
+    control_plane_urls: Option<Vec<String>>,
+    // Pinned SHA-256 of the control plane's TLS certificate
+    control_plane_cert_fingerprint: Option<String>,
+    // Receipt validity window and clock skew allowance
+    receipt_expiry_secs: Option<u64>,
+    clock_skew_grace_secs: Option<u64>,
+    // Replay cache snapshot file, loaded at startup
+    replay_cache_path: Option<String>,
+    replay_cache_persist_interval_secs: Option<u64>,
+    // Stable sidecar ID (default: a random UUID per process)
+    sidecar_id: Option<String>,
+    // Ceiling on a WASM adapter instance's linear memory
+    adapter_max_memory_bytes: Option<u64>,
+    // Upstream wire protocol (gRPC passthrough)
+    protocol: Option<String>,
+    // Request headers passed to WASM adapters in the context blob
+    adapter_context_headers: Option<Vec<String>>,
+    // Idle rate limit buckets are dropped after this long
+    rate_limit_bucket_max_age_secs: Option<u64>,
+    // What rate limit buckets are kept for
+    rate_limit_key: Option<String>,
+    // How rate limited requests are counted
+    rate_limit_algorithm: Option<String>,
+    // Replay cache keyed on a hash of the whole request instead of the correlation ID
+    replay_key_request_hash: Option<bool>,
+    // Redis for rate_limit_backend = redis
+    redis_url: Option<String>,
+    // Where rate limit buckets are kept
+    rate_limit_backend: Option<String>,
+    // Hosts requests may be forwarded to
+    upstream_host_allowlist: Option<Vec<String>>,
+    // Consecutive successful heartbeats that end a heartbeat-failure lockdown
+    lockdown_recovery_successes: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct LoggingConfig {
     level: Option<String>,
+    redact_fields: Option<Vec<String>>,
+    // Sampling of repeated denial log lines, e.g. "100/10s"
+    sampling: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct FlowConfig {
+    // Step name -> "METHOD /path"
+    steps: Option<BTreeMap<String, String>>,
+    // "from -> to" pairs
+    transitions: Option<Vec<String>>,
+    // Steps a flow may begin with (default: steps without incoming transitions)
+    start: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BatchReceiptsConfig {
+    // "METHOD /path" of the batch endpoint
+    operation: String,
+    // Sub-operations ("METHOD /path") minted a receipt each, in order
+    receipts: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct RevocationConfig {
-    // Future: bloom filter settings can go here
-    // false_positive_rate: Option<f64>,
-    // capacity: Option<usize>,
+    // Bloom filter sizing; setting capacity switches revocation to a Bloom filter
+    false_positive_rate: Option<f64>,
+    capacity: Option<usize>,
 }
 
 impl Config {
@@ -136,7 +664,8 @@ impl Config {
     /// 
     /// # Required Fields
     /// - `root_public_key`: Hex-encoded Ed25519 public key (MUST be set via CLI, env, or config file)
-    /// - `api_key`: API key to inject into forwarded requests (MUST be set via CLI, env, or config file)
+    /// - `api_key`: API key to inject into forwarded requests (MUST be set via CLI, env, or config file,
+    ///   or read from `api_key_file`)
     /// 
     /// # Notes
     /// - Hex encoding chosen over Base64 because:
@@ -160,25 +689,37 @@ impl Config {
         let env_config = Self::load_from_env()?;
         
         // Step 3: Apply precedence (CLI > env > file > defaults)
+        // Root keys: either one `root_public_key`, or a `root_public_keys` list during a
+        // key rotation (tokens select an entry by biscuit root key ID).
         let root_public_key_str = cli_args.root_public_key
             .as_ref()
             .or_else(|| env_config.root_public_key.as_ref())
-            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.root_public_key.as_ref()))
-            .ok_or_else(|| VacError::ConfigError(
-                "root_public_key must be set via --root-public-key, VAC_ROOT_PUBLIC_KEY env var, or config file".to_string()
-            ))?;
-        
-        let root_public_key = hex::decode(root_public_key_str)
-            .map_err(|_| VacError::ConfigError(
-                "root_public_key must be valid hex-encoded Ed25519 public key (64 hex characters)".to_string()
-            ))?;
-        
-        // Validate key length (Ed25519 public keys are 32 bytes = 64 hex chars)
-        if root_public_key.len() != 32 {
-            return Err(VacError::ConfigError(
-                format!("root_public_key must be 32 bytes (64 hex characters), got {} bytes", root_public_key.len())
-            ));
-        }
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.root_public_key.as_ref()));
+        let root_public_keys_strs = cli_args.root_public_keys
+            .as_ref()
+            .or(env_config.root_public_keys.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.root_public_keys.as_ref()))
+            .filter(|keys| !keys.is_empty());
+        let (root_public_key, root_public_keys) = match (root_public_key_str, root_public_keys_strs) {
+            (Some(_), Some(_)) => {
+                return Err(VacError::ConfigError(
+                    "Set either root_public_key or root_public_keys, not both".to_string()
+                ));
+            }
+            (Some(key), None) => (parse_root_public_key(key, "root_public_key")?, Vec::new()),
+            (None, Some(keys)) => {
+                let keys = keys
+                    .iter()
+                    .map(|key| parse_root_public_key(key, "root_public_keys"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (keys[0].clone(), keys)
+            }
+            (None, None) => {
+                return Err(VacError::ConfigError(
+                    "root_public_key must be set via --root-public-key, VAC_ROOT_PUBLIC_KEY env var, or config file".to_string()
+                ));
+            }
+        };
         
         let upstream_url = cli_args.upstream_url
             .as_ref()
@@ -187,25 +728,65 @@ impl Config {
             .unwrap_or(&"http://localhost:8080".to_string())
             .clone();
         
-        // Precedence: CLI > env > file > defaults
-        let api_key = cli_args.api_key
+        // Precedence: CLI > env > file > defaults; an explicit api_key at any level wins
+        // over api_key_file, which keeps the secret out of the environment and argv.
+        let api_key_file = cli_args.api_key_file
+            .as_ref()
+            .or(env_config.api_key_file.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.api_key_file.as_ref()));
+        let api_key = match cli_args.api_key
             .as_ref()
             .or_else(|| {
                 // Debug: verify env var is being read
                 env_config.api_key.as_ref()
             })
             .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.api_key.as_ref()))
-            .ok_or_else(|| VacError::ConfigError(
-                "api_key must be set via --api-key, VAC_API_KEY env var, or config file".to_string()
-            ))?
-            .clone();
+        {
+            Some(api_key) => api_key.clone(),
+            None => match api_key_file {
+                Some(path) => read_api_key_file(path)?,
+                None => return Err(VacError::ConfigError(
+                    "api_key must be set via --api-key, VAC_API_KEY env var, or config file (or api_key_file)".to_string()
+                )),
+            },
+        };
+        // An empty key would be injected as `Authorization: Bearer ` on every request.
+        if api_key.trim().is_empty() {
+            return Err(VacError::ConfigError(
+                "api_key must not be empty or whitespace".to_string()
+            ));
+        }
+        if let Some(reason) = weak_api_key_reason(&api_key) {
+            tracing::warn!(
+                "api_key looks like a placeholder ({}); the upstream will likely reject it",
+                reason
+            );
+        }
         
-        let control_plane_url = cli_args.control_plane_url
+        // Control planes: either one `control_plane_url`, or a `control_plane_urls` list
+        // tried in order (the first one is also reported as `control_plane_url`).
+        let control_plane_url_str = cli_args.control_plane_url
             .as_ref()
             .or_else(|| env_config.control_plane_url.as_ref())
-            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.control_plane_url.as_ref()))
-            .unwrap_or(&"http://localhost:8081".to_string())
-            .clone();
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.control_plane_url.as_ref()));
+        let control_plane_urls_list = cli_args.control_plane_urls
+            .as_ref()
+            .or(env_config.control_plane_urls.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.control_plane_urls.as_ref()));
+        let control_plane_urls = match (control_plane_url_str, control_plane_urls_list) {
+            (Some(_), Some(_)) => {
+                return Err(VacError::ConfigError(
+                    "Set either control_plane_url or control_plane_urls, not both".to_string()
+                ));
+            }
+            (_, Some(urls)) if urls.is_empty() => {
+                return Err(VacError::ConfigError("control_plane_urls must not be empty".to_string()));
+            }
+            (_, Some(urls)) => urls.clone(),
+            (Some(url), None) => vec![url.clone()],
+            (None, None) => vec!["http://localhost:8081".to_string()],
+        };
+        let control_plane_url = control_plane_urls[0].clone();
         
         let heartbeat_interval_secs = cli_args.heartbeat_interval_secs
             .or(env_config.heartbeat_interval_secs)
@@ -230,6 +811,13 @@ impl Config {
             .unwrap_or(&"info".to_string())
             .clone();
         
+        let log_redact_fields = cli_args.log_redact_fields
+            .as_ref()
+            .or(env_config.log_redact_fields.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.logging.as_ref()?.redact_fields.as_ref()))
+            .cloned()
+            .unwrap_or_default();
+        
         // Phase 4.7: Rate limiting configuration
         use crate::rate_limit::{DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION};
         let rate_limit_max_requests = cli_args.rate_limit_max_requests
@@ -254,8 +842,544 @@ impl Config {
             .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.replay_cache_ttl_secs))
             .unwrap_or(DEFAULT_REPLAY_CACHE_TTL.as_secs());
         
+        // Trailing-slash normalization (default: preserve)
+        let path_trailing_slash = cli_args.path_trailing_slash
+            .as_ref()
+            .or(env_config.path_trailing_slash.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.path_trailing_slash.as_ref()))
+            .map(|s| s.parse::<PathTrailingSlash>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Error body format (default: json)
+        let error_response_format = cli_args.error_response_format
+            .as_ref()
+            .or(env_config.error_response_format.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.error_response_format.as_ref()))
+            .map(|s| s.parse::<ErrorResponseFormat>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Adapter prewarm (default: true; only applies when adapters are loaded)
+        let adapter_prewarm = cli_args.adapter_prewarm
+            .or(env_config.adapter_prewarm)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.adapter_prewarm))
+            .unwrap_or(true);
+        
+        // Forward verified delegation summary upstream (default: false, headers stripped)
+        let forward_delegation_chain = cli_args.forward_delegation_chain
+            .or(env_config.forward_delegation_chain)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.forward_delegation_chain))
+            .unwrap_or(false);
+        
+        // Receipt minting method filter (default: all methods)
+        let mint_receipts_for_methods = cli_args.mint_receipts_for_methods
+            .as_ref()
+            .or(env_config.mint_receipts_for_methods.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.mint_receipts_for_methods.as_ref()))
+            .map(|methods| methods.iter().map(|m| m.trim().to_ascii_uppercase()).collect::<Vec<_>>());
+        
+        // Soft-deny mode for policy denials (default: off)
+        let soft_deny = cli_args.soft_deny
+            .or(env_config.soft_deny)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.soft_deny))
+            .unwrap_or(false);
+        
+        // Policy pin (default: unpinned)
+        let policy_pin_hash = cli_args.policy_pin_hash
+            .clone()
+            .or_else(|| env_config.policy_pin_hash.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.policy_pin_hash.clone()))
+            .filter(|pin| !pin.is_empty())
+            .map(|pin| crate::policy_pin::parse_policy_pin(&pin))
+            .transpose()?;
+        
+        // Upstream response status allowlist (default: all statuses pass through)
+        let upstream_allowed_statuses = cli_args.upstream_allowed_statuses
+            .clone()
+            .or_else(|| env_config.upstream_allowed_statuses.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.upstream_allowed_statuses.clone()));
+        if let Some(statuses) = &upstream_allowed_statuses {
+            if let Some(bad) = statuses.iter().find(|s| !(100..=599).contains(*s)) {
+                return Err(VacError::ConfigError(format!(
+                    "upstream_allowed_statuses: {} is not a valid HTTP status code", bad
+                )));
+            }
+        }
+        
+        // Inbound HTTP/2 (default: HTTP/1.1 only)
+        let server_http2_enabled = cli_args.server_http2_enabled
+            .or(env_config.server_http2_enabled)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.server_http2_enabled))
+            .unwrap_or(false);
+        
+        // Require X-Correlation-ID from clients (default: generate one when missing)
+        let require_correlation_id = cli_args.require_correlation_id
+            .or(env_config.require_correlation_id)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.require_correlation_id))
+            .unwrap_or(false);
+        
+        // Token size limit, checked before decoding (default: header value cap)
+        let max_token_bytes = cli_args.max_token_bytes
+            .or(env_config.max_token_bytes)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.max_token_bytes))
+            .unwrap_or(crate::biscuit::DEFAULT_MAX_TOKEN_BYTES);
+        
+        // Request coalescing for identical idempotent requests (default: off)
+        let coalesce_idempotent = cli_args.coalesce_idempotent
+            .or(env_config.coalesce_idempotent)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.coalesce_idempotent))
+            .unwrap_or(false);
+        
+        // Strict token shape checking (default: off, so tokens may carry application facts)
+        let strict_token_shape = cli_args.strict_token_shape
+            .or(env_config.strict_token_shape)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.strict_token_shape))
+            .unwrap_or(false);
+        
+        // Periodic cache-size log (default: every 5 minutes; 0 disables)
+        let cache_size_log_interval_secs = cli_args.cache_size_log_interval_secs
+            .or(env_config.cache_size_log_interval_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.cache_size_log_interval_secs))
+            .unwrap_or(crate::cache_stats::DEFAULT_CACHE_SIZE_LOG_INTERVAL_SECS);
+        
+        // Heartbeat revocation list bound (default: 100k IDs)
+        let max_revocation_list_size = cli_args.max_revocation_list_size
+            .or(env_config.max_revocation_list_size)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.max_revocation_list_size))
+            .unwrap_or(crate::heartbeat::DEFAULT_MAX_REVOCATION_LIST_SIZE);
+        
+        // Receipt audit webhook (default: disabled)
+        let receipt_webhook_url = cli_args.receipt_webhook_url
+            .clone()
+            .or_else(|| env_config.receipt_webhook_url.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.receipt_webhook_url.clone()))
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &receipt_webhook_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(VacError::ConfigError(format!(
+                    "receipt_webhook_url must be an http(s) URL, got {:?}",
+                    url
+                )));
+            }
+        }
+        
+        // Per-correlation-ID step cap (default: unlimited)
+        let max_steps_per_correlation = cli_args.max_steps_per_correlation
+            .or(env_config.max_steps_per_correlation)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.max_steps_per_correlation));
+        
+        // Operation-scoped replay key (default: correlation ID only)
+        let replay_key_includes_operation = cli_args.replay_key_includes_operation
+            .or(env_config.replay_key_includes_operation)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.replay_key_includes_operation))
+            .unwrap_or(false);
+        
+        // Per-correlation-ID adapter concurrency cap (default: unlimited)
+        let max_concurrent_adapters_per_correlation = cli_args.max_concurrent_adapters_per_correlation
+            .or(env_config.max_concurrent_adapters_per_correlation)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.max_concurrent_adapters_per_correlation));
+        
+        // Canonical JSON bodies for adapters (default: off)
+        let canonicalize_json_body = cli_args.canonicalize_json_body
+            .or(env_config.canonicalize_json_body)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.canonicalize_json_body))
+            .unwrap_or(false);
+        
+        // Forward canonical JSON bodies (default: on; only with canonicalize_json_body)
+        let forward_canonical_json_body = cli_args.forward_canonical_json_body
+            .or(env_config.forward_canonical_json_body)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.forward_canonical_json_body))
+            .unwrap_or(true);
+        
+        // Correlation ID to token binding (default: off)
+        let bind_correlation_to_token = cli_args.bind_correlation_to_token
+            .or(env_config.bind_correlation_to_token)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.bind_correlation_to_token))
+            .unwrap_or(false);
+        
+        // Slow adapter log threshold (default: 1s)
+        let adapter_slow_threshold_ms = cli_args.adapter_slow_threshold_ms
+            .or(env_config.adapter_slow_threshold_ms)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.adapter_slow_threshold_ms))
+            .unwrap_or(crate::adapter::DEFAULT_ADAPTER_SLOW_THRESHOLD_MS);
+        
+        // `OPTIONS *` handling (default: reject)
+        let options_asterisk = cli_args.options_asterisk
+            .as_ref()
+            .or(env_config.options_asterisk.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.options_asterisk.as_ref()))
+            .map(|s| s.parse::<OptionsAsterisk>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Revocation audit file (default: audit events are only logged)
+        let revocation_audit_log = cli_args.revocation_audit_log
+            .clone()
+            .or_else(|| env_config.revocation_audit_log.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.revocation_audit_log.clone()))
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        
+        // Peer receipts via the published session key set (default: off)
+        let accept_peer_receipts = cli_args.accept_peer_receipts
+            .or(env_config.accept_peer_receipts)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.accept_peer_receipts))
+            .unwrap_or(false);
+        
+        // Reverse proxies trusted for X-Forwarded-* (default: none)
+        let trusted_proxies = cli_args.trusted_proxies
+            .as_ref()
+            .or(env_config.trusted_proxies.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.trusted_proxies.as_ref()))
+            .map(|cidrs| TrustedProxies::parse(cidrs))
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Compact receipt transport (default: off)
+        let accept_compact_receipts = cli_args.accept_compact_receipts
+            .or(env_config.accept_compact_receipts)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.accept_compact_receipts))
+            .unwrap_or(false);
+        
+        // Heartbeat supervisor action (default: restart)
+        let heartbeat_exit_action = cli_args.heartbeat_exit_action
+            .as_ref()
+            .or(env_config.heartbeat_exit_action.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.heartbeat_exit_action.as_ref()))
+            .map(|s| s.parse::<HeartbeatExitAction>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Reserved-predicate handling for adapter facts (default: allow)
+        let adapter_reserved_facts = cli_args.adapter_reserved_facts
+            .as_ref()
+            .or(env_config.adapter_reserved_facts.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.adapter_reserved_facts.as_ref()))
+            .map(|s| s.parse::<AdapterReservedFacts>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Zero adapter facts is an extraction failure (default: proceed without them)
+        let require_adapter_facts = cli_args.require_adapter_facts
+            .or(env_config.require_adapter_facts)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.require_adapter_facts))
+            .unwrap_or(false);
+        
+        // Upstream request timeout (default: none; 0 also means none)
+        let upstream_timeout_secs = cli_args.upstream_timeout_secs
+            .or(env_config.upstream_timeout_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.upstream_timeout_secs))
+            .filter(|secs| *secs > 0);
+        
+        // Global budget for buffered request bodies (default: unlimited; 0 also means unlimited)
+        let max_total_body_bytes = cli_args.max_total_body_bytes
+            .or(env_config.max_total_body_bytes)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.max_total_body_bytes))
+            .filter(|bytes| *bytes > 0);
+        
+        // Admin listener for /metrics (default: none, served as /_vac/metrics on the proxy port)
+        let metrics_addr = cli_args.metrics_addr.clone()
+            .or(env_config.metrics_addr)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.metrics_addr.clone()))
+            .filter(|addr| !addr.trim().is_empty());
+        #[cfg(not(feature = "metrics"))]
+        if metrics_addr.is_some() {
+            return Err(VacError::ConfigError(
+                "metrics_addr needs the sidecar built with the `metrics` feature".to_string(),
+            ));
+        }
+        
+        // Verified delegation depth as X-VAC-Depth (default: false; reveals delegation structure to clients)
+        let expose_delegation_depth = cli_args.expose_delegation_depth
+            .or(env_config.expose_delegation_depth)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.expose_delegation_depth))
+            .unwrap_or(false);
+        
+        // Policy evaluation timeout (default: none; 0 also means none)
+        let policy_eval_timeout_ms = cli_args.policy_eval_timeout_ms
+            .or(env_config.policy_eval_timeout_ms)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.policy_eval_timeout_ms))
+            .filter(|ms| *ms > 0);
+        
+        // Shutdown drain bound (default: 25s, inside Kubernetes' default 30s termination grace period)
+        let shutdown_grace_secs = cli_args.shutdown_grace_secs
+            .or(env_config.shutdown_grace_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.shutdown_grace_secs))
+            .unwrap_or(crate::server::DEFAULT_SHUTDOWN_GRACE_SECS);
+        
+        // Operator policy (default: none, so only token checks and the global depth rule apply
+        // and requests are denied for lack of an allow policy). Read and compiled here so a
+        // syntax error fails startup, or a reload, instead of every request.
+        let policy_file = cli_args.policy_file
+            .clone()
+            .or_else(|| env_config.policy_file.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.policy_file.clone()));
+        let policy = policy_file.as_deref().map(read_policy_file).transpose()?;
+        
+        // Control plane certificate pin (default: none, normal CA validation)
+        let control_plane_cert_fingerprint = cli_args.control_plane_cert_fingerprint
+            .clone()
+            .or_else(|| env_config.control_plane_cert_fingerprint.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.control_plane_cert_fingerprint.clone()))
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(|fingerprint| crate::control_plane_client::parse_cert_fingerprint(&fingerprint))
+            .transpose()?;
+        
+        // Receipt validity window (default: 5 minutes)
+        let receipt_expiry_secs = cli_args.receipt_expiry_secs
+            .or(env_config.receipt_expiry_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.receipt_expiry_secs))
+            .unwrap_or(crate::receipt::DEFAULT_RECEIPT_EXPIRY_SECS);
+        
+        // Clock skew allowance on receipt expiry (default: 30s)
+        let clock_skew_grace_secs = cli_args.clock_skew_grace_secs
+            .or(env_config.clock_skew_grace_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.clock_skew_grace_secs))
+            .unwrap_or(crate::receipt::DEFAULT_CLOCK_SKEW_GRACE_SECS);
+        
+        // Revoked token ID storage (default: exact set; Bloom filter with [revocation] capacity)
+        let revocation = file_config.as_ref().and_then(|f| f.revocation.as_ref());
+        let revocation_bloom = match (
+            revocation.and_then(|r| r.capacity),
+            revocation.and_then(|r| r.false_positive_rate),
+        ) {
+            (Some(capacity), false_positive_rate) => {
+                let settings = RevocationBloomSettings {
+                    capacity,
+                    false_positive_rate: false_positive_rate
+                        .unwrap_or(crate::revocation::DEFAULT_BLOOM_FALSE_POSITIVE_RATE),
+                };
+                settings.validate()?;
+                Some(settings)
+            }
+            (None, Some(_)) => {
+                return Err(VacError::ConfigError(
+                    "revocation.false_positive_rate requires revocation.capacity".to_string(),
+                ));
+            }
+            (None, None) => None,
+        };
+        
+        // Flow graph (default: none; operations are only checked by the policy)
+        let flow_graph = match file_config.as_ref().and_then(|f| f.flow.as_ref()) {
+            Some(flow) => Some(FlowGraph::new(
+                flow.steps.as_ref().unwrap_or(&BTreeMap::new()),
+                flow.transitions.as_deref().unwrap_or_default(),
+                flow.start.as_deref(),
+            )?),
+            None => None,
+        };
+        
+        // Batch receipts (default: none; every request mints one receipt for its own operation)
+        let is_operation = |operation: &str| {
+            operation
+                .split_once(' ')
+                .is_some_and(|(method, path)| !method.is_empty() && path.starts_with('/'))
+        };
+        let mut batch_receipts = HashMap::new();
+        for BatchReceiptsConfig { operation, receipts: sub_operations } in
+            file_config.as_ref().and_then(|f| f.batch_receipts.clone()).unwrap_or_default()
+        {
+            if let Some(bad) = std::iter::once(&operation).chain(&sub_operations).find(|op| !is_operation(op)) {
+                return Err(VacError::ConfigError(format!(
+                    "batch_receipts '{}': operations must be \"METHOD /path\" (got '{}')",
+                    operation, bad
+                )));
+            }
+            if sub_operations.is_empty() {
+                return Err(VacError::ConfigError(format!(
+                    "batch_receipts '{}': at least one sub-operation is required",
+                    operation
+                )));
+            }
+            if batch_receipts.insert(operation.clone(), sub_operations).is_some() {
+                return Err(VacError::ConfigError(format!("batch_receipts: '{}' is listed twice", operation)));
+            }
+        }
+        
+        // Denial log sampling (default: every denial is logged)
+        let log_sampling = cli_args.log_sampling
+            .clone()
+            .or_else(|| env_config.log_sampling.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.logging.as_ref()?.sampling.clone()))
+            .filter(|sampling| !sampling.is_empty())
+            .map(|sampling| sampling.parse::<LogSampling>())
+            .transpose()?;
+        
+        // Replay cache persistence (default: in memory only, lost on restart)
+        let replay_cache_path = cli_args.replay_cache_path
+            .clone()
+            .or_else(|| env_config.replay_cache_path.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.replay_cache_path.clone()))
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        
+        let replay_cache_persist_interval_secs = cli_args.replay_cache_persist_interval_secs
+            .or(env_config.replay_cache_persist_interval_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.replay_cache_persist_interval_secs))
+            .unwrap_or(crate::replay_cache::DEFAULT_REPLAY_CACHE_PERSIST_INTERVAL_SECS);
+        if replay_cache_persist_interval_secs == 0 {
+            return Err(VacError::ConfigError(
+                "replay_cache_persist_interval_secs must be at least 1".to_string(),
+            ));
+        }
+        
+        // Sidecar ID (default: a random UUID, new on every start)
+        let sidecar_id = cli_args.sidecar_id
+            .clone()
+            .or_else(|| env_config.sidecar_id.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.sidecar_id.clone()))
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty());
+        if let Some(id) = &sidecar_id {
+            if id.len() > MAX_SIDECAR_ID_LEN || id.chars().any(char::is_control) {
+                return Err(VacError::ConfigError(format!(
+                    "sidecar_id must be at most {} characters without control characters",
+                    MAX_SIDECAR_ID_LEN
+                )));
+            }
+        }
+        
+        // Adapter memory ceiling (default: 64 MiB); at least one WASM page
+        let adapter_max_memory_bytes = cli_args.adapter_max_memory_bytes
+            .or(env_config.adapter_max_memory_bytes)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.adapter_max_memory_bytes))
+            .unwrap_or(crate::adapter::DEFAULT_ADAPTER_MAX_MEMORY_BYTES);
+        if adapter_max_memory_bytes < 64 * 1024 {
+            return Err(VacError::ConfigError(
+                "adapter_max_memory_bytes must be at least 65536 (one WASM page)".to_string(),
+            ));
+        }
+        
+        // Upstream protocol (default: http); gRPC clients speak HTTP/2 to the sidecar too
+        let protocol = cli_args.protocol
+            .as_ref()
+            .or(env_config.protocol.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.protocol.as_ref()))
+            .map(|s| s.parse::<UpstreamProtocol>())
+            .transpose()?
+            .unwrap_or_default();
+        if protocol == UpstreamProtocol::Grpc && !server_http2_enabled {
+            return Err(VacError::ConfigError(
+                "protocol = grpc requires server_http2_enabled = true".to_string(),
+            ));
+        }
+        
+        // Headers adapters may see (default: none); names are matched case-insensitively
+        let adapter_context_headers = cli_args.adapter_context_headers
+            .clone()
+            .or_else(|| env_config.adapter_context_headers.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.adapter_context_headers.clone()))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|h| h.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        for name in &adapter_context_headers {
+            if !crate::security::validate_header_name(name) {
+                return Err(VacError::ConfigError(format!("adapter_context_headers: invalid header name '{}'", name)));
+            }
+            if crate::adapter::ADAPTER_CONTEXT_FORBIDDEN_HEADERS.contains(&name.as_str()) {
+                return Err(VacError::ConfigError(format!(
+                    "adapter_context_headers: '{}' carries credentials and cannot be passed to adapters",
+                    name
+                )));
+            }
+        }
+        
+        // Rate limit bucket retention (default: DEFAULT_BUCKET_MAX_AGE_WINDOWS windows). A bucket
+        // dropped within its window would come back full, so shorter ages are refused.
+        let rate_limit_bucket_max_age_secs = cli_args.rate_limit_bucket_max_age_secs
+            .or(env_config.rate_limit_bucket_max_age_secs)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.rate_limit_bucket_max_age_secs))
+            .unwrap_or(rate_limit_window_secs.saturating_mul(crate::rate_limit::DEFAULT_BUCKET_MAX_AGE_WINDOWS));
+        if rate_limit_bucket_max_age_secs < rate_limit_window_secs {
+            return Err(VacError::ConfigError(format!(
+                "rate_limit_bucket_max_age_secs ({}) must be at least rate_limit_window_secs ({})",
+                rate_limit_bucket_max_age_secs, rate_limit_window_secs
+            )));
+        }
+        
+        // Rate limit bucket key (default: sidecar)
+        let rate_limit_key = cli_args.rate_limit_key
+            .as_ref()
+            .or(env_config.rate_limit_key.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.rate_limit_key.as_ref()))
+            .map(|s| s.parse::<RateLimitKey>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Rate limit algorithm (default: token_bucket)
+        let rate_limit_algorithm = cli_args.rate_limit_algorithm
+            .as_ref()
+            .or(env_config.rate_limit_algorithm.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.rate_limit_algorithm.as_ref()))
+            .map(|s| s.parse::<RateLimitAlgorithm>())
+            .transpose()?
+            .unwrap_or_default();
+        
+        // Request-hash replay key (default: off); replaces the correlation ID key, so it
+        // cannot be combined with the operation-scoped one.
+        let replay_key_request_hash = cli_args.replay_key_request_hash
+            .or(env_config.replay_key_request_hash)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.replay_key_request_hash))
+            .unwrap_or(false);
+        if replay_key_request_hash && replay_key_includes_operation {
+            return Err(VacError::ConfigError(
+                "replay_key_request_hash and replay_key_includes_operation cannot both be set".to_string(),
+            ));
+        }
+        
+        // Redis URL (only used by rate_limit_backend = redis)
+        let redis_url = cli_args.redis_url.clone()
+            .or_else(|| env_config.redis_url.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.redis_url.clone()));
+        
+        // Rate limit backend (default: memory). Redis buckets are token buckets refilled
+        // by a script, so the sliding log stays in-memory only.
+        let rate_limit_backend = cli_args.rate_limit_backend
+            .as_ref()
+            .or(env_config.rate_limit_backend.as_ref())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.rate_limit_backend.as_ref()))
+            .map(|s| s.parse::<RateLimitBackendKind>())
+            .transpose()?
+            .unwrap_or_default();
+        if rate_limit_backend == RateLimitBackendKind::Redis {
+            if redis_url.is_none() {
+                return Err(VacError::ConfigError("rate_limit_backend = redis requires redis_url".to_string()));
+            }
+            if rate_limit_algorithm != RateLimitAlgorithm::TokenBucket {
+                return Err(VacError::ConfigError(
+                    "rate_limit_backend = redis supports rate_limit_algorithm = token_bucket only".to_string(),
+                ));
+            }
+        }
+        
+        // Upstream host allowlist (default: none; upstream_url alone decides where requests go).
+        // upstream_url itself must pass it, or every request would be refused.
+        let upstream_host_allowlist = cli_args.upstream_host_allowlist
+            .clone()
+            .or_else(|| env_config.upstream_host_allowlist.clone())
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.upstream_host_allowlist.clone()))
+            .map(|hosts| hosts.iter().map(|h| h.to_ascii_lowercase()).collect::<Vec<_>>());
+        if let Some(hosts) = &upstream_host_allowlist {
+            let upstream = upstream_url.parse::<axum::http::Uri>()
+                .map_err(|e| VacError::ConfigError(format!("Invalid upstream_url: {}", e)))?;
+            crate::proxy::check_upstream_host(Some(hosts), &upstream)
+                .map_err(|_| VacError::ConfigError(format!(
+                    "upstream_host_allowlist does not include the host of upstream_url ({})", upstream_url
+                )))?;
+        }
+        
+        // Lockdown recovery (default: lifted after 3 consecutive successful heartbeats)
+        let lockdown_recovery_successes = cli_args.lockdown_recovery_successes
+            .or(env_config.lockdown_recovery_successes)
+            .or_else(|| file_config.as_ref().and_then(|f| f.sidecar.as_ref()?.lockdown_recovery_successes))
+            .unwrap_or(crate::heartbeat::DEFAULT_LOCKDOWN_RECOVERY_SUCCESSES);
+        
         Ok(Config {
             root_public_key,
+            root_public_keys,
             upstream_url,
             api_key,
             control_plane_url,
@@ -263,10 +1387,72 @@ impl Config {
             session_key_rotation_interval_secs,
             adapters_dir,
             log_level,
+            log_redact_fields,
             rate_limit_max_requests,
             rate_limit_window_secs,
             replay_cache_enabled,
             replay_cache_ttl_secs,
+            path_trailing_slash,
+            error_response_format,
+            adapter_prewarm,
+            forward_delegation_chain,
+            mint_receipts_for_methods,
+            soft_deny,
+            policy_pin_hash,
+            upstream_allowed_statuses,
+            server_http2_enabled,
+            require_correlation_id,
+            max_token_bytes,
+            coalesce_idempotent,
+            strict_token_shape,
+            cache_size_log_interval_secs,
+            max_revocation_list_size,
+            receipt_webhook_url,
+            max_steps_per_correlation,
+            replay_key_includes_operation,
+            max_concurrent_adapters_per_correlation,
+            canonicalize_json_body,
+            forward_canonical_json_body,
+            bind_correlation_to_token,
+            adapter_slow_threshold_ms,
+            options_asterisk,
+            revocation_audit_log,
+            accept_peer_receipts,
+            trusted_proxies,
+            accept_compact_receipts,
+            heartbeat_exit_action,
+            adapter_reserved_facts,
+            require_adapter_facts,
+            upstream_timeout_secs,
+            max_total_body_bytes,
+            metrics_addr,
+            expose_delegation_depth,
+            policy_eval_timeout_ms,
+            shutdown_grace_secs,
+            policy_file,
+            policy,
+            control_plane_urls,
+            control_plane_cert_fingerprint,
+            receipt_expiry_secs,
+            clock_skew_grace_secs,
+            revocation_bloom,
+            log_sampling,
+            replay_cache_path,
+            replay_cache_persist_interval_secs,
+            sidecar_id,
+            adapter_max_memory_bytes,
+            protocol,
+            adapter_context_headers,
+            rate_limit_bucket_max_age_secs,
+            flow_graph,
+            batch_receipts,
+            rate_limit_key,
+            rate_limit_algorithm,
+            replay_key_request_hash,
+            redis_url,
+            rate_limit_backend,
+            upstream_host_allowlist,
+            lockdown_recovery_successes,
         })
     }
     
@@ -313,8 +1499,15 @@ impl Config {
         // 1. Values set explicitly (e.g., by tests or shell)
         // 2. Values from .env file (if dotenv was called and var wasn't already set)
         let root_public_key = env::var("VAC_ROOT_PUBLIC_KEY").ok();
+        let root_public_keys = env::var("VAC_ROOT_PUBLIC_KEYS").ok().map(|v| {
+            v.split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect::<Vec<_>>()
+        });
         let upstream_url = env::var("VAC_UPSTREAM_URL").ok();
         let api_key = env::var("VAC_API_KEY").ok();
+        let api_key_file = env::var("VAC_API_KEY_FILE").ok().map(PathBuf::from);
         let control_plane_url = env::var("VAC_CONTROL_PLANE_URL").ok();
         let heartbeat_interval_secs = env::var("VAC_HEARTBEAT_INTERVAL_SECS")
             .ok()
@@ -324,6 +1517,12 @@ impl Config {
             .and_then(|v| v.parse::<u64>().ok());
         let adapters_dir = env::var("VAC_ADAPTERS_DIR").ok();
         let log_level = env::var("VAC_LOG_LEVEL").ok();
+        let log_redact_fields = env::var("VAC_LOG_REDACT_FIELDS").ok().map(|v| {
+            v.split(',')
+                .map(|f| f.trim().to_string())
+                .filter(|f| !f.is_empty())
+                .collect::<Vec<_>>()
+        });
         // Phase 4.7: Rate limiting env vars
         let rate_limit_max_requests = env::var("VAC_RATE_LIMIT_MAX_REQUESTS")
             .ok()
@@ -338,20 +1537,237 @@ impl Config {
         let replay_cache_ttl_secs = env::var("VAC_REPLAY_CACHE_TTL_SECS")
             .ok()
             .and_then(|v| v.parse::<u64>().ok());
+        let path_trailing_slash = env::var("VAC_PATH_TRAILING_SLASH").ok();
+        let error_response_format = env::var("VAC_ERROR_RESPONSE_FORMAT").ok();
+        let adapter_prewarm = env::var("VAC_ADAPTER_PREWARM")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let forward_delegation_chain = env::var("VAC_FORWARD_DELEGATION_CHAIN")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let mint_receipts_for_methods = env::var("VAC_MINT_RECEIPTS_FOR_METHODS").ok().map(|v| {
+            v.split(',')
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty())
+                .collect::<Vec<_>>()
+        });
+        let soft_deny = env::var("VAC_SOFT_DENY")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let policy_pin_hash = env::var("VAC_POLICY_PIN_HASH").ok();
+        let upstream_allowed_statuses = env::var("VAC_UPSTREAM_ALLOWED_STATUSES")
+            .ok()
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse::<u16>().map_err(|_| VacError::ConfigError(format!(
+                        "VAC_UPSTREAM_ALLOWED_STATUSES: '{}' is not a status code", s
+                    ))))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let server_http2_enabled = env::var("VAC_SERVER_HTTP2_ENABLED")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let require_correlation_id = env::var("VAC_REQUIRE_CORRELATION_ID")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let max_token_bytes = env::var("VAC_MAX_TOKEN_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok());
+        let coalesce_idempotent = env::var("VAC_COALESCE_IDEMPOTENT")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let strict_token_shape = env::var("VAC_STRICT_TOKEN_SHAPE")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let cache_size_log_interval_secs = env::var("VAC_CACHE_SIZE_LOG_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let max_revocation_list_size = env::var("VAC_MAX_REVOCATION_LIST_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok());
+        let receipt_webhook_url = env::var("VAC_RECEIPT_WEBHOOK_URL").ok();
+        let max_steps_per_correlation = env::var("VAC_MAX_STEPS_PER_CORRELATION")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok());
+        let replay_key_includes_operation = env::var("VAC_REPLAY_KEY_INCLUDES_OPERATION")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let max_concurrent_adapters_per_correlation = env::var("VAC_MAX_CONCURRENT_ADAPTERS_PER_CORRELATION")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok());
+        let canonicalize_json_body = env::var("VAC_CANONICALIZE_JSON_BODY")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let forward_canonical_json_body = env::var("VAC_FORWARD_CANONICAL_JSON_BODY")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let bind_correlation_to_token = env::var("VAC_BIND_CORRELATION_TO_TOKEN")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let adapter_slow_threshold_ms = env::var("VAC_ADAPTER_SLOW_THRESHOLD_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let options_asterisk = env::var("VAC_OPTIONS_ASTERISK").ok();
+        let revocation_audit_log = env::var("VAC_REVOCATION_AUDIT_LOG").ok();
+        let accept_peer_receipts = env::var("VAC_ACCEPT_PEER_RECEIPTS")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let trusted_proxies = env::var("VAC_TRUSTED_PROXIES").ok().map(|v| {
+            v.split(',')
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect::<Vec<_>>()
+        });
+        let accept_compact_receipts = env::var("VAC_ACCEPT_COMPACT_RECEIPTS")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let heartbeat_exit_action = env::var("VAC_HEARTBEAT_EXIT_ACTION").ok();
+        let adapter_reserved_facts = env::var("VAC_ADAPTER_RESERVED_FACTS").ok();
+        let require_adapter_facts = env::var("VAC_REQUIRE_ADAPTER_FACTS")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let upstream_timeout_secs = env::var("VAC_UPSTREAM_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let max_total_body_bytes = env::var("VAC_MAX_TOTAL_BODY_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok());
+        let metrics_addr = env::var("VAC_METRICS_ADDR").ok();
+        let expose_delegation_depth = env::var("VAC_EXPOSE_DELEGATION_DEPTH")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let policy_eval_timeout_ms = env::var("VAC_POLICY_EVAL_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let shutdown_grace_secs = env::var("VAC_SHUTDOWN_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let policy_file = env::var("VAC_POLICY_FILE").ok().map(PathBuf::from);
+        let control_plane_urls = env::var("VAC_CONTROL_PLANE_URLS").ok().map(|v| {
+            v.split(',')
+                .map(|u| u.trim().to_string())
+                .filter(|u| !u.is_empty())
+                .collect::<Vec<_>>()
+        });
+        let control_plane_cert_fingerprint = env::var("VAC_CONTROL_PLANE_CERT_FINGERPRINT").ok();
+        let receipt_expiry_secs = env::var("VAC_RECEIPT_EXPIRY_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let clock_skew_grace_secs = env::var("VAC_CLOCK_SKEW_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let log_sampling = env::var("VAC_LOG_SAMPLING").ok();
+        let replay_cache_path = env::var("VAC_REPLAY_CACHE_PATH").ok();
+        let replay_cache_persist_interval_secs = env::var("VAC_REPLAY_CACHE_PERSIST_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let sidecar_id = env::var("VAC_SIDECAR_ID").ok();
+        let adapter_max_memory_bytes = env::var("VAC_ADAPTER_MAX_MEMORY_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let protocol = env::var("VAC_PROTOCOL").ok();
+        let adapter_context_headers = env::var("VAC_ADAPTER_CONTEXT_HEADERS").ok().map(|v| {
+            v.split(',')
+                .map(|h| h.trim().to_string())
+                .filter(|h| !h.is_empty())
+                .collect::<Vec<_>>()
+        });
+        let rate_limit_bucket_max_age_secs = env::var("VAC_RATE_LIMIT_BUCKET_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok());
+        let rate_limit_key = env::var("VAC_RATE_LIMIT_KEY").ok();
+        let rate_limit_algorithm = env::var("VAC_RATE_LIMIT_ALGORITHM").ok();
+        let replay_key_request_hash = env::var("VAC_REPLAY_KEY_REQUEST_HASH")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok());
+        let redis_url = env::var("VAC_REDIS_URL").ok();
+        let rate_limit_backend = env::var("VAC_RATE_LIMIT_BACKEND").ok();
+        let upstream_host_allowlist = env::var("VAC_UPSTREAM_HOST_ALLOWLIST").ok().map(|v| {
+            v.split(',')
+                .map(|h| h.trim().to_string())
+                .filter(|h| !h.is_empty())
+                .collect::<Vec<_>>()
+        });
+        let lockdown_recovery_successes = env::var("VAC_LOCKDOWN_RECOVERY_SUCCESSES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok());
         
         Ok(EnvConfig {
             root_public_key,
+            root_public_keys,
             upstream_url,
             api_key,
+            api_key_file,
             control_plane_url,
             heartbeat_interval_secs,
             session_key_rotation_interval_secs,
             adapters_dir,
             log_level,
+            log_redact_fields,
             rate_limit_max_requests,
             rate_limit_window_secs,
             replay_cache_enabled,
             replay_cache_ttl_secs,
+            path_trailing_slash,
+            error_response_format,
+            adapter_prewarm,
+            forward_delegation_chain,
+            mint_receipts_for_methods,
+            soft_deny,
+            policy_pin_hash,
+            upstream_allowed_statuses,
+            server_http2_enabled,
+            require_correlation_id,
+            max_token_bytes,
+            coalesce_idempotent,
+            strict_token_shape,
+            cache_size_log_interval_secs,
+            max_revocation_list_size,
+            receipt_webhook_url,
+            max_steps_per_correlation,
+            replay_key_includes_operation,
+            max_concurrent_adapters_per_correlation,
+            canonicalize_json_body,
+            forward_canonical_json_body,
+            bind_correlation_to_token,
+            adapter_slow_threshold_ms,
+            options_asterisk,
+            revocation_audit_log,
+            accept_peer_receipts,
+            trusted_proxies,
+            accept_compact_receipts,
+            heartbeat_exit_action,
+            adapter_reserved_facts,
+            require_adapter_facts,
+            upstream_timeout_secs,
+            max_total_body_bytes,
+            metrics_addr,
+            expose_delegation_depth,
+            policy_eval_timeout_ms,
+            shutdown_grace_secs,
+            policy_file,
+            control_plane_urls,
+            control_plane_cert_fingerprint,
+            receipt_expiry_secs,
+            clock_skew_grace_secs,
+            log_sampling,
+            replay_cache_path,
+            replay_cache_persist_interval_secs,
+            sidecar_id,
+            adapter_max_memory_bytes,
+            protocol,
+            adapter_context_headers,
+            rate_limit_bucket_max_age_secs,
+            rate_limit_key,
+            rate_limit_algorithm,
+            replay_key_request_hash,
+            redis_url,
+            rate_limit_backend,
+            upstream_host_allowlist,
+            lockdown_recovery_successes,
         })
     }
 }
@@ -359,40 +1775,378 @@ impl Config {
 /// Intermediate structure for env var config (all optional for precedence)
 struct EnvConfig {
     root_public_key: Option<String>,
+    root_public_keys: Option<Vec<String>>,
     upstream_url: Option<String>,
     api_key: Option<String>,
+    api_key_file: Option<PathBuf>,
     control_plane_url: Option<String>,
     heartbeat_interval_secs: Option<u64>,
     session_key_rotation_interval_secs: Option<u64>,
     adapters_dir: Option<String>,
     log_level: Option<String>,
+    log_redact_fields: Option<Vec<String>>,
     // Phase 4.7: Rate limiting
     rate_limit_max_requests: Option<u32>,
     rate_limit_window_secs: Option<u64>,
     // Phase 4.8: Replay attack mitigation
     replay_cache_enabled: Option<bool>,
     replay_cache_ttl_secs: Option<u64>,
+    // Trailing-slash normalization for `operation` facts
+    path_trailing_slash: Option<String>,
+    // Error body serialization
+    error_response_format: Option<String>,
+    // Adapter prewarm at startup
+    adapter_prewarm: Option<bool>,
+    // Delegation summary headers to upstream
+    forward_delegation_chain: Option<bool>,
+    // Receipt minting method filter
+    mint_receipts_for_methods: Option<Vec<String>>,
+    // Return policy denials as 200 with a decision marker
+    soft_deny: Option<bool>,
+    // SHA-256 the policy_file contents must match
+    policy_pin_hash: Option<String>,
+    // Upstream response status allowlist
+    upstream_allowed_statuses: Option<Vec<u16>>,
+    // Inbound HTTP/2 (h2c prior knowledge)
+    server_http2_enabled: Option<bool>,
+    // Require a client-supplied correlation ID
+    require_correlation_id: Option<bool>,
+    // Token size limit
+    max_token_bytes: Option<usize>,
+    // Single-flight upstream calls for identical concurrent GET requests
+    coalesce_idempotent: Option<bool>,
+    // Reject tokens with unexpected blocks or fact predicates
+    strict_token_shape: Option<bool>,
+    // Interval of the cache-size log line (0 = off)
+    cache_size_log_interval_secs: Option<u64>,
+    // Cap on revoked token IDs accepted per heartbeat
+    max_revocation_list_size: Option<usize>,
+    // Audit webhook receiving every minted receipt
+    receipt_webhook_url: Option<String>,
+    // Cap on receipts minted under one correlation ID
+    max_steps_per_correlation: Option<u32>,
+    // Replay cache keyed on (correlation ID, method, path) instead of correlation ID alone
+    replay_key_includes_operation: Option<bool>,
+    // Cap on concurrent adapter runs sharing one correlation ID
+    max_concurrent_adapters_per_correlation: Option<usize>,
+    // Canonical JSON bodies (sorted keys, no extra whitespace) for adapters
+    canonicalize_json_body: Option<bool>,
+    // Forward the canonicalized JSON body instead of the original bytes
+    forward_canonical_json_body: Option<bool>,
+    // Correlation IDs bound to the first token that uses them
+    bind_correlation_to_token: Option<bool>,
+    // WASM adapter runs logged as slow at or above this duration
+    adapter_slow_threshold_ms: Option<u64>,
+    // Handling of server-wide `OPTIONS *` requests
+    options_asterisk: Option<String>,
+    // JSON-lines file receiving revocation audit records
+    revocation_audit_log: Option<String>,
+    // Receipts signed by other sidecars in the control plane's session key set
+    accept_peer_receipts: Option<bool>,
+    // Peers whose X-Forwarded-* headers are honored
+    trusted_proxies: Option<Vec<String>>,
+    // Receipts in the compact X-VAC-Receipt-Bin encoding
+    accept_compact_receipts: Option<bool>,
+    // Response to the heartbeat task exiting or panicking
+    heartbeat_exit_action: Option<String>,
+    // Handling of adapter facts named like sidecar-trusted predicates
+    adapter_reserved_facts: Option<String>,
+    // Treat a pinned adapter that yields no facts as an extraction failure
+    require_adapter_facts: Option<bool>,
+    // Whole-request timeout for upstream calls
+    upstream_timeout_secs: Option<u64>,
+    // Byte budget shared by all buffered request bodies
+    max_total_body_bytes: Option<usize>,
+    // Separate admin listener for /metrics
+    metrics_addr: Option<String>,
+    // X-VAC-Depth header on responses and upstream requests
+    expose_delegation_depth: Option<bool>,
+    // Upper bound on Datalog policy evaluation
+    policy_eval_timeout_ms: Option<u64>,
+    // Bound on draining in-flight requests at shutdown
+    shutdown_grace_secs: Option<u64>,
+    // Operator Datalog policy added to every authorization
+    policy_file: Option<PathBuf>,
+    // Control planes tried in order for heartbeats (failover)
+    control_plane_urls: Option<Vec<String>>,
+    // Pinned SHA-256 of the control plane's TLS certificate
+    control_plane_cert_fingerprint: Option<String>,
+    // Receipt validity window and clock skew allowance
+    receipt_expiry_secs: Option<u64>,
+    clock_skew_grace_secs: Option<u64>,
+    // Sampling of repeated denial log lines
+    log_sampling: Option<String>,
+    // Replay cache snapshot file, loaded at startup
+    replay_cache_path: Option<String>,
+    replay_cache_persist_interval_secs: Option<u64>,
+    // Stable sidecar ID (default: a random UUID per process)
+    sidecar_id: Option<String>,
+    // Ceiling on a WASM adapter instance's linear memory
+    adapter_max_memory_bytes: Option<u64>,
+    // Upstream wire protocol (gRPC passthrough)
+    protocol: Option<String>,
+    // Request headers passed to WASM adapters in the context blob
+    adapter_context_headers: Option<Vec<String>>,
+    // Idle rate limit buckets are dropped after this long
+    rate_limit_bucket_max_age_secs: Option<u64>,
+    // What rate limit buckets are kept for
+    rate_limit_key: Option<String>,
+    // How rate limited requests are counted
+    rate_limit_algorithm: Option<String>,
+    // Replay cache keyed on a hash of the whole request instead of the correlation ID
+    replay_key_request_hash: Option<bool>,
+    // Redis for rate_limit_backend = redis
+    redis_url: Option<String>,
+    // Where rate limit buckets are kept
+    rate_limit_backend: Option<String>,
+    // Hosts requests may be forwarded to
+    upstream_host_allowlist: Option<Vec<String>>,
+    // Consecutive successful heartbeats that end a heartbeat-failure lockdown
+    lockdown_recovery_successes: Option<u32>,
+}
+
+/// Decode and validate one hex-encoded root public key; `option` names the setting in errors.
+fn parse_root_public_key(hex_key: &str, option: &str) -> Result<Vec<u8>, VacError> {
+    let root_public_key = hex::decode(hex_key.trim())
+        .map_err(|_| VacError::ConfigError(
+            format!("{} must be valid hex-encoded Ed25519 public key (64 hex characters)", option)
+        ))?;
+    
+    // Validate key length (Ed25519 public keys are 32 bytes = 64 hex chars)
+    if root_public_key.len() != 32 {
+        return Err(VacError::ConfigError(
+            format!("{} must be 32 bytes (64 hex characters), got {} bytes", option, root_public_key.len())
+        ));
+    }
+    if root_public_key.iter().all(|b| *b == 0) {
+        return Err(VacError::ConfigError(
+            format!("{} must not be all zeros (placeholder key; generate a real Ed25519 key)", option)
+        ));
+    }
+    if let Some(reason) = weak_root_key_reason(&root_public_key) {
+        tracing::warn!(
+            "{} looks like a test or placeholder key ({}); do not use it in production",
+            option,
+            reason
+        );
+    }
+    Ok(root_public_key)
+}
+
+/// Read the upstream API key from `api_key_file`, without trailing whitespace/newline.
+fn read_api_key_file(path: &Path) -> Result<String, VacError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        VacError::ConfigError(format!("Failed to read api_key_file '{}': {}", path.display(), e))
+    })?;
+    let api_key = contents.trim_end();
+    if api_key.is_empty() {
+        return Err(VacError::ConfigError(format!(
+            "api_key_file '{}' is empty",
+            path.display()
+        )));
+    }
+    Ok(api_key.to_string())
+}
+
+/// Read `policy_file` and compile it once, so a syntax error is reported with the path.
+fn read_policy_file(path: &Path) -> Result<String, VacError> {
+    let policy = std::fs::read_to_string(path).map_err(|e| {
+        VacError::ConfigError(format!("Failed to read policy_file '{}': {}", path.display(), e))
+    })?;
+    crate::policy::validate_policy(&policy).map_err(|e| {
+        VacError::ConfigError(format!("Invalid policy_file '{}': {}", path.display(), e))
+    })?;
+    Ok(policy)
+}
+
+/// Config file location and template value for a CLI argument: `(section, key, value, required)`.
+///
+/// `value` is the TOML literal `Config::load` falls back to (or an example for fields that
+/// are unset by default). Every CLI argument except `config_file` needs an entry here so
+/// the generated template stays complete.
+fn template_entry(arg: &str) -> Option<(&'static str, &'static str, String, bool)> {
+    use crate::rate_limit::{DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION};
+    use crate::replay_cache::DEFAULT_REPLAY_CACHE_TTL;
+
+    let sidecar = |key: &'static str, value: String| Some(("sidecar", key, value, false));
+    match arg {
+        "root_public_key" => Some(("sidecar", "root_public_key", "\"\"".into(), true)),
+        "api_key" => Some(("sidecar", "api_key", "\"\"".into(), true)),
+        "api_key_file" => sidecar("api_key_file", "\"/run/secrets/vac-api-key\"".into()),
+        "upstream_url" => sidecar("upstream_url", "\"http://localhost:8080\"".into()),
+        "control_plane_url" => sidecar("control_plane_url", "\"http://localhost:8081\"".into()),
+        "heartbeat_interval_secs" => sidecar("heartbeat_interval_secs", "60".into()),
+        "session_key_rotation_interval_secs" => sidecar("session_key_rotation_interval_secs", "300".into()),
+        "adapters_dir" => sidecar("adapters_dir", "\"./adapters\"".into()),
+        "rate_limit_max_requests" => sidecar("rate_limit_max_requests", DEFAULT_MAX_REQUESTS.to_string()),
+        "rate_limit_window_secs" => sidecar("rate_limit_window_secs", DEFAULT_WINDOW_DURATION.as_secs().to_string()),
+        "replay_cache_enabled" => sidecar("replay_cache_enabled", "false".into()),
+        "replay_cache_ttl_secs" => sidecar("replay_cache_ttl_secs", DEFAULT_REPLAY_CACHE_TTL.as_secs().to_string()),
+        "path_trailing_slash" => sidecar("path_trailing_slash", "\"preserve\"".into()),
+        "error_response_format" => sidecar("error_response_format", "\"json\"".into()),
+        "adapter_prewarm" => sidecar("adapter_prewarm", "true".into()),
+        "forward_delegation_chain" => sidecar("forward_delegation_chain", "false".into()),
+        "mint_receipts_for_methods" => sidecar("mint_receipts_for_methods", "[\"POST\", \"PUT\", \"PATCH\", \"DELETE\"]".into()),
+        "soft_deny" => sidecar("soft_deny", "false".into()),
+        "policy_pin_hash" => sidecar("policy_pin_hash", "\"<hex sha256 of policy_file>\"".into()),
+        "upstream_allowed_statuses" => sidecar("upstream_allowed_statuses", "[200, 201, 204, 400, 404]".into()),
+        "server_http2_enabled" => sidecar("server_http2_enabled", "false".into()),
+        "require_correlation_id" => sidecar("require_correlation_id", "false".into()),
+        "max_token_bytes" => sidecar("max_token_bytes", crate::biscuit::DEFAULT_MAX_TOKEN_BYTES.to_string()),
+        "coalesce_idempotent" => sidecar("coalesce_idempotent", "false".into()),
+        "strict_token_shape" => sidecar("strict_token_shape", "false".into()),
+        "cache_size_log_interval_secs" => sidecar("cache_size_log_interval_secs", crate::cache_stats::DEFAULT_CACHE_SIZE_LOG_INTERVAL_SECS.to_string()),
+        "max_revocation_list_size" => sidecar("max_revocation_list_size", crate::heartbeat::DEFAULT_MAX_REVOCATION_LIST_SIZE.to_string()),
+        "receipt_webhook_url" => sidecar("receipt_webhook_url", "\"https://audit.example.com/receipts\"".into()),
+        "max_steps_per_correlation" => sidecar("max_steps_per_correlation", "20".into()),
+        "replay_key_includes_operation" => sidecar("replay_key_includes_operation", "false".into()),
+        "max_concurrent_adapters_per_correlation" => sidecar("max_concurrent_adapters_per_correlation", "2".into()),
+        "canonicalize_json_body" => sidecar("canonicalize_json_body", "false".into()),
+        "forward_canonical_json_body" => sidecar("forward_canonical_json_body", "true".into()),
+        "bind_correlation_to_token" => sidecar("bind_correlation_to_token", "false".into()),
+        "adapter_slow_threshold_ms" => sidecar("adapter_slow_threshold_ms", crate::adapter::DEFAULT_ADAPTER_SLOW_THRESHOLD_MS.to_string()),
+        "options_asterisk" => sidecar("options_asterisk", "\"reject\"".into()),
+        "revocation_audit_log" => sidecar("revocation_audit_log", "\"/var/log/vac/revocations.jsonl\"".into()),
+        "accept_peer_receipts" => sidecar("accept_peer_receipts", "false".into()),
+        "trusted_proxies" => sidecar("trusted_proxies", "[\"10.0.0.0/8\"]".into()),
+        "accept_compact_receipts" => sidecar("accept_compact_receipts", "false".into()),
+        "heartbeat_exit_action" => sidecar("heartbeat_exit_action", "\"restart\"".into()),
+        "adapter_reserved_facts" => sidecar("adapter_reserved_facts", "\"allow\"".into()),
+        "require_adapter_facts" => sidecar("require_adapter_facts", "false".into()),
+        "upstream_timeout_secs" => sidecar("upstream_timeout_secs", "30".into()),
+        "max_total_body_bytes" => sidecar("max_total_body_bytes", "104857600".into()),
+        "metrics_addr" => sidecar("metrics_addr", "\"127.0.0.1:9090\"".into()),
+        "expose_delegation_depth" => sidecar("expose_delegation_depth", "false".into()),
+        "root_public_keys" => sidecar("root_public_keys", "[\"<old key hex>\", \"<new key hex>\"]".into()),
+        "policy_eval_timeout_ms" => sidecar("policy_eval_timeout_ms", "100".into()),
+        "shutdown_grace_secs" => sidecar("shutdown_grace_secs", crate::server::DEFAULT_SHUTDOWN_GRACE_SECS.to_string()),
+        "policy_file" => sidecar("policy_file", "\"/etc/vac/policy.dl\"".into()),
+        "control_plane_urls" => sidecar("control_plane_urls", "[\"http://control-plane-a:8081\", \"http://control-plane-b:8081\"]".into()),
+        "control_plane_cert_fingerprint" => sidecar("control_plane_cert_fingerprint", "\"<sha256 of the control plane certificate>\"".into()),
+        "receipt_expiry_secs" => sidecar("receipt_expiry_secs", crate::receipt::DEFAULT_RECEIPT_EXPIRY_SECS.to_string()),
+        "clock_skew_grace_secs" => sidecar("clock_skew_grace_secs", crate::receipt::DEFAULT_CLOCK_SKEW_GRACE_SECS.to_string()),
+        "replay_cache_path" => sidecar("replay_cache_path", "\"/var/lib/vac/replay-cache.json\"".into()),
+        "replay_cache_persist_interval_secs" => sidecar("replay_cache_persist_interval_secs", crate::replay_cache::DEFAULT_REPLAY_CACHE_PERSIST_INTERVAL_SECS.to_string()),
+        "sidecar_id" => sidecar("sidecar_id", "\"vac-sidecar-0\"".into()),
+        "adapter_max_memory_bytes" => sidecar("adapter_max_memory_bytes", crate::adapter::DEFAULT_ADAPTER_MAX_MEMORY_BYTES.to_string()),
+        "protocol" => sidecar("protocol", "\"http\"".into()),
+        "adapter_context_headers" => sidecar("adapter_context_headers", "[\"x-tenant-id\"]".into()),
+        "rate_limit_bucket_max_age_secs" => sidecar("rate_limit_bucket_max_age_secs", (DEFAULT_WINDOW_DURATION.as_secs() * crate::rate_limit::DEFAULT_BUCKET_MAX_AGE_WINDOWS).to_string()),
+        "rate_limit_key" => sidecar("rate_limit_key", "\"sidecar\"".into()),
+        "rate_limit_algorithm" => sidecar("rate_limit_algorithm", "\"token_bucket\"".into()),
+        "replay_key_request_hash" => sidecar("replay_key_request_hash", "false".into()),
+        "redis_url" => sidecar("redis_url", "\"redis://127.0.0.1:6379\"".into()),
+        "rate_limit_backend" => sidecar("rate_limit_backend", "\"memory\"".into()),
+        "upstream_host_allowlist" => sidecar("upstream_host_allowlist", "[\"api.example.com\"]".into()),
+        "lockdown_recovery_successes" => sidecar("lockdown_recovery_successes", crate::heartbeat::DEFAULT_LOCKDOWN_RECOVERY_SUCCESSES.to_string()),
+        "log_level" => Some(("logging", "level", "\"info\"".into(), false)),
+        "log_redact_fields" => Some(("logging", "redact_fields", "[]".into(), false)),
+        "log_sampling" => Some(("logging", "sampling", "\"100/10s\"".into(), false)),
+        _ => None,
+    }
+}
+
+/// Render a config.toml template covering every CLI-configurable field.
+///
+/// Field descriptions come from the CLI help, so the template follows `CliArgs`.
+/// Required secrets are left as empty strings; everything else is commented out
+/// with its default.
+pub fn config_template() -> String {
+    let cli = CliArgs::command();
+    let mut sections: Vec<(&str, String)> = vec![("sidecar", String::new()), ("logging", String::new())];
+    for arg in cli.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some((section, key, value, required)) = template_entry(id) else {
+            continue;
+        };
+        let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+        let help = help.trim_end_matches(" (overrides env/config)");
+        let out = &mut sections.iter_mut().find(|(name, _)| *name == section).expect("known section").1;
+        if required {
+            out.push_str(&format!("# {} (required)\n{} = {}\n", help, key, value));
+        } else {
+            out.push_str(&format!("# {}\n# {} = {}\n", help, key, value));
+        }
+    }
+
+    let mut template = String::from(
+        "# VAC Sidecar configuration (generated by `vac-sidecar generate-config`)\n\
+         # Precedence: CLI flags > VAC_* environment variables > this file > defaults.\n\
+         # Usage: vac-sidecar --config-file config.toml\n",
+    );
+    for (name, body) in sections {
+        template.push_str(&format!("\n[{}]\n{}", name, body));
+    }
+    template
+}
+
+/// `generate-config`: write [`config_template`] to `output`, or stdout if `None`.
+pub fn generate_config(output: Option<&Path>) -> std::io::Result<()> {
+    let template = config_template();
+    match output {
+        Some(path) => std::fs::write(path, template),
+        None => {
+            print!("{}", template);
+            Ok(())
+        }
+    }
+}
+
+/// Detect obviously non-random root keys (e.g. hand-typed test keys).
+///
+/// Real Ed25519 public keys are effectively random, so a short repeating byte pattern
+/// means someone pasted a placeholder. Returns a short description for the warning.
+fn weak_root_key_reason(key: &[u8]) -> Option<&'static str> {
+    if key.windows(2).all(|w| w[0] == w[1]) {
+        return Some("all bytes identical");
+    }
+    // Period of up to 8 bytes, e.g. "1234567890abcdef" repeated
+    if (2..=8).any(|period| key.iter().skip(period).zip(key.iter()).all(|(a, b)| a == b)) {
+        return Some("short repeating pattern");
+    }
+    None
+}
+
+/// Shortest `api_key` not warned about
+const API_KEY_WARN_MIN_LEN: usize = 16;
+
+/// Well-known placeholder API keys (compared case-insensitively)
+const PLACEHOLDER_API_KEYS: &[&str] = &[
+    "demo-api-key",
+    "your-upstream-api-key",
+    "api-key",
+    "apikey",
+    "changeme",
+    "secret",
+    "password",
+];
+
+/// Detect API keys that are probably a deployment mistake rather than a real credential:
+/// copied placeholders, values too short to be an issued key, or stray whitespace.
+/// Returns a short description for the warning.
+fn weak_api_key_reason(api_key: &str) -> Option<&'static str> {
+    let lower = api_key.to_ascii_lowercase();
+    if PLACEHOLDER_API_KEYS.contains(&lower.as_str()) || lower.starts_with("your-") || lower.contains("placeholder") {
+        return Some("known placeholder value");
+    }
+    if api_key.trim() != api_key {
+        return Some("leading or trailing whitespace");
+    }
+    if api_key.len() < API_KEY_WARN_MIN_LEN {
+        return Some("shorter than 16 characters");
+    }
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
-    use std::io::Write;
     use std::sync::Mutex;
     use tempfile::TempDir;
 
-    // #region agent log
-    /// Serialize config tests that touch VAC_* env to avoid cross-test clearing (hypothesis A).
+    /// Serialize config tests that touch VAC_* env so one test cannot clear another's variables.
     static CONFIG_ENV_LOCK: Mutex<()> = Mutex::new(());
-    fn agent_log(location: &str, message: &str, data: &str, hypothesis_id: &str) {
-        let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
-        let line = format!(r#"{{"location":"{}","message":"{}","data":{},"timestamp":{},"sessionId":"debug-session","hypothesisId":"{}"}}"#, location, message.replace('"', "\\\""), data, ts, hypothesis_id);
-        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open(r"c:\Users\param\Desktop\github-projects\vac\.cursor\debug.log") {
-            let _ = writeln!(f, "{}", line);
-        }
-    }
-    // #endregion
 
     #[test]
     fn test_config_precedence_cli_overrides_env() {
@@ -407,19 +2161,9 @@ mod tests {
         
         // CLI args override env
         let cli_args = CliArgs {
-            config_file: None,
             root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
-            upstream_url: None,
             api_key: Some("cli-api-key".to_string()),
-            control_plane_url: None,
-            heartbeat_interval_secs: None,
-            session_key_rotation_interval_secs: None,
-            adapters_dir: None,
-            log_level: None,
-            rate_limit_max_requests: None,
-            rate_limit_window_secs: None,
-            replay_cache_enabled: None,
-            replay_cache_ttl_secs: None,
+            ..Default::default()
         };
         
         let config = Config::load(&cli_args).unwrap();
@@ -430,6 +2174,70 @@ mod tests {
         std::env::remove_var("VAC_API_KEY");
     }
 
+    #[test]
+    fn test_config_rejects_empty_api_key() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let load = |api_key: &str| {
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some(api_key.to_string()),
+                ..Default::default()
+            })
+        };
+
+        for empty in ["", "   ", "\t\n"] {
+            let err = load(empty).err().expect("empty api_key accepted");
+            assert!(err.to_string().contains("api_key must not be empty"), "{}", err);
+        }
+        assert_eq!(load("sk_live_4f9a2c7e1b8d3f60a5e9").unwrap().api_key, "sk_live_4f9a2c7e1b8d3f60a5e9");
+
+        // Suspicious but usable keys are only warned about.
+        assert!(load("demo-api-key").is_ok());
+        assert_eq!(weak_api_key_reason("Demo-API-Key"), Some("known placeholder value"));
+        assert_eq!(weak_api_key_reason("short"), Some("shorter than 16 characters"));
+        assert_eq!(weak_api_key_reason("sk_live_4f9a2c7e1b8d3f60a5e9 "), Some("leading or trailing whitespace"));
+        assert_eq!(weak_api_key_reason("sk_live_4f9a2c7e1b8d3f60a5e9"), None);
+    }
+
+    #[test]
+    fn test_config_redis_rate_limit_backend() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let load = |redis_url: Option<&str>, algorithm: Option<&str>| {
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some("sk_live_4f9a2c7e1b8d3f60a5e9".to_string()),
+                rate_limit_backend: Some("redis".to_string()),
+                redis_url: redis_url.map(str::to_string),
+                rate_limit_algorithm: algorithm.map(str::to_string),
+                ..Default::default()
+            })
+        };
+
+        let config = load(Some("redis://redis:6379"), None).unwrap();
+        assert_eq!(config.rate_limit_backend, RateLimitBackendKind::Redis);
+        assert_eq!(config.redis_url.as_deref(), Some("redis://redis:6379"));
+        assert!(load(None, None).is_err());
+        assert!(load(Some("redis://redis:6379"), Some("sliding_log")).is_err());
+    }
+
+    #[test]
+    fn test_config_upstream_host_allowlist_must_allow_upstream_url() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let load = |hosts: &[&str]| {
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some("sk_live_4f9a2c7e1b8d3f60a5e9".to_string()),
+                upstream_url: Some("https://API.example.com".to_string()),
+                upstream_host_allowlist: Some(hosts.iter().map(|h| h.to_string()).collect()),
+                ..Default::default()
+            })
+        };
+
+        let config = load(&["Api.Example.com", "backup.example.com"]).unwrap();
+        assert_eq!(config.upstream_host_allowlist.unwrap(), ["api.example.com", "backup.example.com"]);
+        assert!(load(&["backup.example.com"]).is_err());
+    }
+
     #[test]
     fn test_config_load_from_file() {
         // Create temp dir and config file
@@ -451,18 +2259,7 @@ level = "warn"
         
         let cli_args = CliArgs {
             config_file: Some(config_path),
-            root_public_key: None,
-            upstream_url: None,
-            api_key: None,
-            control_plane_url: None,
-            heartbeat_interval_secs: None,
-            session_key_rotation_interval_secs: None,
-            adapters_dir: None,
-            log_level: None,
-            rate_limit_max_requests: None,
-            rate_limit_window_secs: None,
-            replay_cache_enabled: None,
-            replay_cache_ttl_secs: None,
+            ..Default::default()
         };
         
         let config = Config::load(&cli_args).unwrap();
@@ -472,6 +2269,194 @@ level = "warn"
         assert_eq!(config.log_level, "warn");
     }
 
+    #[test]
+    fn test_config_api_key_file() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        std::env::remove_var("VAC_API_KEY");
+        std::env::remove_var("VAC_API_KEY_FILE");
+
+        let temp_dir = TempDir::new().unwrap();
+        let key_path = temp_dir.path().join("api-key");
+        fs::write(&key_path, "file-secret\n").unwrap();
+        let cli_args = |api_key: Option<&str>, api_key_file: PathBuf| CliArgs {
+            root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+            api_key: api_key.map(str::to_string),
+            api_key_file: Some(api_key_file),
+            ..Default::default()
+        };
+        assert_eq!(Config::load(&cli_args(None, key_path.clone())).unwrap().api_key, "file-secret");
+
+        // An explicit api_key wins over the file.
+        let config = Config::load(&cli_args(Some("cli-api-key"), key_path.clone())).unwrap();
+        assert_eq!(config.api_key, "cli-api-key");
+
+        fs::write(&key_path, " \n").unwrap();
+        let err = Config::load(&cli_args(None, key_path)).err().expect("empty api_key_file accepted");
+        assert!(err.to_string().contains("is empty"), "{}", err);
+
+        let err = Config::load(&cli_args(None, temp_dir.path().join("missing")))
+            .err()
+            .expect("missing api_key_file accepted");
+        assert!(err.to_string().contains("Failed to read api_key_file"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_policy_file() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        std::env::remove_var("VAC_POLICY_FILE");
+        std::env::remove_var("VAC_POLICY_PIN_HASH");
+
+        let temp_dir = TempDir::new().unwrap();
+        let policy_path = temp_dir.path().join("policy.dl");
+        let cli_args = |policy_file: PathBuf| CliArgs {
+            root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+            api_key: Some("k".to_string()),
+            policy_file: Some(policy_file),
+            ..Default::default()
+        };
+
+        let policy = "allow if operation(\"GET\", \"/search\");\n";
+        fs::write(&policy_path, policy).unwrap();
+        let config = Config::load(&cli_args(policy_path.clone())).unwrap();
+        assert_eq!(config.policy.as_deref(), Some(policy));
+        assert_eq!(config.policy_file.as_deref(), Some(policy_path.as_path()));
+
+        // Syntax errors fail the load instead of every request.
+        fs::write(&policy_path, "allow if operation(\"GET\"").unwrap();
+        let err = Config::load(&cli_args(policy_path)).err().expect("invalid policy accepted");
+        assert!(err.to_string().contains("Invalid policy_file"), "{}", err);
+
+        let err = Config::load(&cli_args(temp_dir.path().join("missing")))
+            .err()
+            .expect("missing policy_file accepted");
+        assert!(err.to_string().contains("Failed to read policy_file"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_revocation_bloom() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let load = |revocation: &str| {
+            fs::write(&config_path, format!("[revocation]\n{}", revocation)).unwrap();
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some("k".to_string()),
+                config_file: Some(config_path.clone()),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(load("").unwrap().revocation_bloom, None);
+        let bloom = load("capacity = 100000\nfalse_positive_rate = 0.0001\n").unwrap().revocation_bloom.unwrap();
+        assert_eq!(bloom.capacity, 100000);
+        assert_eq!(bloom.false_positive_rate, 0.0001);
+        let bloom = load("capacity = 5000\n").unwrap().revocation_bloom.unwrap();
+        assert_eq!(bloom.false_positive_rate, crate::revocation::DEFAULT_BLOOM_FALSE_POSITIVE_RATE);
+
+        assert!(load("false_positive_rate = 0.01\n").is_err());
+        assert!(load("capacity = 5000\nfalse_positive_rate = 1.5\n").is_err());
+    }
+
+    #[test]
+    fn test_config_flow_graph() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let load = |flow: &str| {
+            fs::write(&config_path, format!("[flow]\n{}", flow)).unwrap();
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some("k".to_string()),
+                config_file: Some(config_path.clone()),
+                ..Default::default()
+            })
+        };
+
+        let graph = load(
+            "steps = { search = \"GET /search\", charge = \"POST /charge\" }\ntransitions = [\"search -> charge\"]\n",
+        )
+        .unwrap()
+        .flow_graph
+        .unwrap();
+        assert_eq!(graph.step("POST /charge"), Some("charge"));
+        assert!(load("steps = { search = \"GET /search\" }\ntransitions = [\"search -> charge\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_config_batch_receipts() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let load = |batch: &str| {
+            fs::write(&config_path, format!("[[batch_receipts]]\noperation = \"POST /batch\"\n{}", batch)).unwrap();
+            Config::load(&CliArgs {
+                root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+                api_key: Some("k".to_string()),
+                config_file: Some(config_path.clone()),
+                ..Default::default()
+            })
+        };
+
+        let config = load("receipts = [\"GET /search\", \"POST /charge\"]\n").unwrap();
+        assert_eq!(config.batch_receipts["POST /batch"], vec!["GET /search", "POST /charge"]);
+        assert!(load("receipts = [\"/search\"]\n").is_err());
+        assert!(load("receipts = []\n").is_err());
+    }
+
+    #[test]
+    fn test_config_control_plane_urls() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        std::env::remove_var("VAC_CONTROL_PLANE_URL");
+        std::env::remove_var("VAC_CONTROL_PLANE_URLS");
+
+        let cli_args = |url: Option<&str>, urls: Option<Vec<&str>>| CliArgs {
+            root_public_key: Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string()),
+            api_key: Some("k".to_string()),
+            control_plane_url: url.map(str::to_string),
+            control_plane_urls: urls.map(|urls| urls.into_iter().map(str::to_string).collect()),
+            ..Default::default()
+        };
+        let config = Config::load(&cli_args(None, Some(vec!["http://cp-a", "http://cp-b"]))).unwrap();
+        assert_eq!(config.control_plane_urls, ["http://cp-a", "http://cp-b"]);
+        assert_eq!(config.control_plane_url, "http://cp-a");
+
+        let config = Config::load(&cli_args(Some("http://cp"), None)).unwrap();
+        assert_eq!(config.control_plane_urls, ["http://cp"]);
+
+        let err = Config::load(&cli_args(Some("http://cp"), Some(vec!["http://cp-b"])))
+            .err()
+            .expect("control_plane_url and control_plane_urls both accepted");
+        assert!(err.to_string().contains("not both"), "{}", err);
+        assert!(Config::load(&cli_args(None, Some(vec![]))).is_err());
+    }
+
+    #[test]
+    fn test_config_root_public_keys() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        std::env::remove_var("VAC_ROOT_PUBLIC_KEY");
+        std::env::remove_var("VAC_ROOT_PUBLIC_KEYS");
+
+        let old_key = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let new_key = "fedcba0987654321fedcba0987654321fedcba0987654321fedcba0987654321";
+        let cli_args = CliArgs {
+            root_public_keys: Some(vec![old_key.to_string(), new_key.to_string()]),
+            api_key: Some("test-api-key".to_string()),
+            ..Default::default()
+        };
+        let config = Config::load(&cli_args).unwrap();
+        assert_eq!(config.root_public_keys, vec![hex::decode(old_key).unwrap(), hex::decode(new_key).unwrap()]);
+        // The first key doubles as the primary root key.
+        assert_eq!(config.root_public_key, hex::decode(old_key).unwrap());
+
+        let both = CliArgs {
+            root_public_key: Some(old_key.to_string()),
+            ..cli_args
+        };
+        let err = Config::load(&both).err().expect("root_public_key and root_public_keys both accepted");
+        assert!(err.to_string().contains("not both"), "{}", err);
+    }
+
     #[test]
     fn test_config_env_overrides_file() {
         let _g = CONFIG_ENV_LOCK.lock().unwrap();
@@ -483,9 +2468,6 @@ level = "warn"
         // This should take precedence over .env file and config file
         std::env::set_var("VAC_ROOT_PUBLIC_KEY", "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef");
         std::env::set_var("VAC_API_KEY", "env-api-key");
-        // #region agent log
-        agent_log("config.rs:test_config_env_overrides_file", "set VAC_API_KEY=env-api-key", r#"{"test":"env_overrides_file"}"#, "A");
-        // #endregion
         // Verify env var is set (debug check)
         assert_eq!(std::env::var("VAC_API_KEY").unwrap(), "env-api-key");
         
@@ -503,25 +2485,11 @@ api_key = "file-api-key"
         
         let cli_args = CliArgs {
             config_file: Some(config_path),
-            root_public_key: None,
-            upstream_url: None,
-            api_key: None,
-            control_plane_url: None,
-            heartbeat_interval_secs: None,
-            session_key_rotation_interval_secs: None,
-            adapters_dir: None,
-            log_level: None,
-            rate_limit_max_requests: None,
-            rate_limit_window_secs: None,
-            replay_cache_enabled: None,
-            replay_cache_ttl_secs: None,
+            ..Default::default()
         };
         
         // Verify env var is still set right before loading
-        // #region agent log
         let api_key_result = std::env::var("VAC_API_KEY");
-        agent_log("config.rs:502", "read VAC_API_KEY before load", &format!(r#"{{"ok":{}}}"#, api_key_result.is_ok()), "A");
-        // #endregion
         match api_key_result {
             Ok(val) => assert_eq!(val, "env-api-key", "Env var must be set before Config::load"),
             Err(e) => panic!("VAC_API_KEY env var not found: {:?}. This suggests test isolation issues.", e),
@@ -542,9 +2510,6 @@ api_key = "file-api-key"
         // Clean up any existing env vars first
         std::env::remove_var("VAC_ROOT_PUBLIC_KEY");
         std::env::remove_var("VAC_API_KEY");
-        // #region agent log
-        agent_log("config.rs:test_config_defaults", "removed VAC_* (test_config_defaults started)", r#"{"test":"defaults"}"#, "A");
-        // #endregion
         
         std::env::set_var("VAC_ROOT_PUBLIC_KEY", "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef");
         std::env::set_var("VAC_API_KEY", "test-api-key");
@@ -553,21 +2518,7 @@ api_key = "file-api-key"
         assert!(std::env::var("VAC_API_KEY").is_ok(), "VAC_API_KEY must be set");
         assert!(std::env::var("VAC_ROOT_PUBLIC_KEY").is_ok(), "VAC_ROOT_PUBLIC_KEY must be set");
         
-        let cli_args = CliArgs {
-            config_file: None,
-            root_public_key: None,
-            upstream_url: None,
-            api_key: None,
-            control_plane_url: None,
-            heartbeat_interval_secs: None,
-            session_key_rotation_interval_secs: None,
-            adapters_dir: None,
-            log_level: None,
-            rate_limit_max_requests: None,
-            rate_limit_window_secs: None,
-            replay_cache_enabled: None,
-            replay_cache_ttl_secs: None,
-        };
+        let cli_args = CliArgs::default();
         
         let config = Config::load(&cli_args).unwrap();
         // Should use defaults
@@ -581,6 +2532,110 @@ api_key = "file-api-key"
         std::env::remove_var("VAC_ROOT_PUBLIC_KEY");
         std::env::remove_var("VAC_API_KEY");
     }
+
+    #[test]
+    fn test_config_rejects_all_zero_root_key() {
+        let cli_args = CliArgs {
+            root_public_key: Some("0".repeat(64)),
+            api_key: Some("cli-api-key".to_string()),
+            ..Default::default()
+        };
+        match Config::load(&cli_args) {
+            Err(VacError::ConfigError(msg)) => assert!(msg.contains("all zeros"), "{}", msg),
+            other => panic!("expected ConfigError, got {:?}", other.map(|_| ())),
+        }
+
+        let real_key = "81a832d1f2e9de1a505a8f5ca1e0158d57ee212cd4bd7cf1886badf9a96b762a";
+        let cli_args = CliArgs {
+            root_public_key: Some(real_key.to_string()),
+            api_key: Some("cli-api-key".to_string()),
+            ..Default::default()
+        };
+        let config = Config::load(&cli_args).unwrap();
+        assert_eq!(config.root_public_key, hex::decode(real_key).unwrap());
+        assert_eq!(weak_root_key_reason(&config.root_public_key), None);
+    }
+
+    #[test]
+    fn test_config_policy_pin_hash() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        std::env::remove_var("VAC_POLICY_PIN_HASH");
+        let cli_args = |pin: &str| CliArgs {
+            root_public_key: Some("81a832d1f2e9de1a505a8f5ca1e0158d57ee212cd4bd7cf1886badf9a96b762a".to_string()),
+            api_key: Some("k".to_string()),
+            policy_pin_hash: Some(pin.to_string()),
+            ..Default::default()
+        };
+
+        let pin = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        let config = Config::load(&cli_args(pin)).unwrap();
+        assert_eq!(config.policy_pin_hash.as_deref(), Some(pin.to_ascii_lowercase().as_str()));
+
+        let config = Config::load(&cli_args("")).unwrap();
+        assert_eq!(config.policy_pin_hash, None);
+
+        let err = Config::load(&cli_args("not-a-hash")).err().expect("malformed pin accepted");
+        assert!(err.to_string().contains("policy_pin_hash"), "{}", err);
+    }
+
+    #[test]
+    fn test_generate_config_template_loads() {
+        let _g = CONFIG_ENV_LOCK.lock().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let template_path = temp_dir.path().join("generated.toml");
+
+        let args = CliArgs::try_parse_from([
+            "vac-sidecar",
+            "generate-config",
+            "--output",
+            template_path.to_str().unwrap(),
+        ])
+        .unwrap();
+        let Some(Command::GenerateConfig { output }) = &args.command else {
+            panic!("expected generate-config, got {:?}", args.command);
+        };
+        generate_config(output.as_deref()).unwrap();
+        let template = fs::read_to_string(&template_path).unwrap();
+
+        // Every CLI field is covered and the output is valid TOML.
+        for arg in CliArgs::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if id == "config_file" {
+                continue;
+            }
+            let (_, key, _, _) = template_entry(id).unwrap_or_else(|| panic!("no template entry for {}", id));
+            assert!(template.contains(&format!("{} = ", key)), "{} missing from template", key);
+        }
+        ::config::Config::builder()
+            .add_source(::config::File::from_str(&template, ::config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let real_key = "81a832d1f2e9de1a505a8f5ca1e0158d57ee212cd4bd7cf1886badf9a96b762a";
+        let filled = template
+            .replace("root_public_key = \"\"", &format!("root_public_key = \"{}\"", real_key))
+            .replace("api_key = \"\"", "api_key = \"generated-api-key\"");
+        let config_path = temp_dir.path().join("config.toml");
+        fs::write(&config_path, filled).unwrap();
+        std::env::remove_var("VAC_ROOT_PUBLIC_KEY");
+        std::env::remove_var("VAC_API_KEY");
+
+        let config = Config::load(&CliArgs {
+            config_file: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.api_key, "generated-api-key");
+        assert_eq!(config.upstream_url, "http://localhost:8080");
+        assert_eq!(config.max_token_bytes, crate::biscuit::DEFAULT_MAX_TOKEN_BYTES);
+    }
+
+    #[test]
+    fn test_weak_root_key_patterns() {
+        assert!(weak_root_key_reason(&[0xff; 32]).is_some());
+        let test_key = hex::decode("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef").unwrap();
+        assert!(weak_root_key_reason(&test_key).is_some());
+    }
 }
 
 // Config integration tests are in integration_test.rs
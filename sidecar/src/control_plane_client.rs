@@ -0,0 +1,116 @@
+//! HTTP client for control plane calls, with optional certificate pinning
+//! (`control_plane_cert_fingerprint`)
+//!
+//! With a pin, the SHA-256 of the control plane's leaf certificate (DER) must equal the
+//! configured fingerprint, whatever CA issued it, so a mis-issued but CA-valid certificate
+//! is refused. The pin replaces CA and hostname validation, so a self-signed control plane
+//! certificate can be pinned directly.
+//!
+//! The certificate is checked on every response, before anything in it is used. Requests
+//! to the control plane carry only the sidecar ID and its session public key, so a
+//! request that reached an impostor before the check discloses nothing secret.
+
+use reqwest::tls::TlsInfo;
+use reqwest::{RequestBuilder, Response};
+use sha2::{Digest, Sha256};
+
+use crate::error::VacError;
+
+/// Parse a hex SHA-256 certificate fingerprint, with or without `:` separators (as printed
+/// by `openssl x509 -noout -fingerprint -sha256`).
+pub fn parse_cert_fingerprint(fingerprint: &str) -> Result<[u8; 32], VacError> {
+    let invalid = || {
+        VacError::ConfigError(
+            "control_plane_cert_fingerprint must be a hex-encoded SHA-256 (64 hex characters, ':' separators allowed)"
+                .to_string(),
+        )
+    };
+    let digits: String = fingerprint.trim().chars().filter(|c| *c != ':').collect();
+    let bytes = hex::decode(digits).map_err(|_| invalid())?;
+    bytes.try_into().map_err(|_| invalid())
+}
+
+/// Client for the control plane (heartbeat, going-away, session key set); cheap to clone.
+#[derive(Debug, Clone)]
+pub struct ControlPlaneClient {
+    client: reqwest::Client,
+    fingerprint: Option<[u8; 32]>,
+}
+
+impl ControlPlaneClient {
+    /// Build a client; with `fingerprint`, only a control plane presenting that certificate
+    /// is trusted.
+    pub fn new(fingerprint: Option<[u8; 32]>) -> Result<Self, VacError> {
+        let client = match fingerprint {
+            None => reqwest::Client::new(),
+            Some(_) => reqwest::Client::builder()
+                .tls_info(true)
+                .danger_accept_invalid_certs(true)
+                .build()
+                .map_err(|e| VacError::ConfigError(format!("Failed to build control plane client: {}", e)))?,
+        };
+        Ok(Self { client, fingerprint })
+    }
+
+    /// Pinned certificate fingerprint, if any
+    pub fn fingerprint(&self) -> Option<[u8; 32]> {
+        self.fingerprint
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Reject `response` unless it came over TLS from the pinned certificate (a no-op
+    /// without a pin). Call before reading anything from the response.
+    pub fn check_pin(&self, response: &Response) -> Result<(), VacError> {
+        let Some(expected) = self.fingerprint else {
+            return Ok(());
+        };
+        let presented = response
+            .extensions()
+            .get::<TlsInfo>()
+            .and_then(TlsInfo::peer_certificate)
+            .ok_or_else(|| {
+                VacError::ProxyError(
+                    "Control plane did not present a TLS certificate (control_plane_cert_fingerprint is set)"
+                        .to_string(),
+                )
+            })?;
+        let actual: [u8; 32] = Sha256::digest(presented).into();
+        if actual != expected {
+            return Err(VacError::ProxyError(format!(
+                "Control plane certificate fingerprint {} does not match control_plane_cert_fingerprint",
+                hex::encode(actual)
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ControlPlaneClient {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            fingerprint: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_accepts_hex_with_or_without_colons() {
+        let plain = "91fc05e084eeb79081294a3010a5f84123081b4e6217cc419236995c88bc7cdd";
+        let openssl = "91:FC:05:E0:84:EE:B7:90:81:29:4A:30:10:A5:F8:41:23:08:1B:4E:62:17:CC:41:92:36:99:5C:88:BC:7C:DD";
+        assert_eq!(parse_cert_fingerprint(plain).unwrap(), parse_cert_fingerprint(openssl).unwrap());
+        assert!(parse_cert_fingerprint("91fc05").is_err());
+        assert!(parse_cert_fingerprint(&"zz".repeat(32)).is_err());
+    }
+}
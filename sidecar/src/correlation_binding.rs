@@ -0,0 +1,100 @@
+//! Binding correlation IDs to a single token (`bind_correlation_to_token`)
+//!
+//! Receipts are tied to a correlation ID, so two different tokens sharing one could
+//! weave a flow neither was authorized for on its own. With binding on, the first
+//! verified token to use a correlation ID claims it for the window, and requests
+//! presenting the same correlation ID with any other token are rejected.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::VacError;
+
+/// How long a correlation ID stays bound to its token after the last request (1 hour).
+pub const DEFAULT_CORRELATION_BINDING_TTL: Duration = Duration::from_secs(3600);
+
+struct Binding {
+    token_id: [u8; 32],
+    last_seen: Instant,
+}
+
+/// Correlation ID -> token ID bindings.
+#[derive(Clone)]
+pub struct CorrelationBindings {
+    bindings: Arc<DashMap<String, Binding>>,
+    ttl: Duration,
+}
+
+impl CorrelationBindings {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            bindings: Arc::new(DashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Bind `correlation_id` to `token_id`, or check that it is already bound to it.
+    ///
+    /// Returns `Err(CorrelationTokenMismatch)` if another token holds the binding.
+    pub fn check_and_bind(&self, correlation_id: &str, token_id: &[u8; 32]) -> Result<(), VacError> {
+        let now = Instant::now();
+        match self.bindings.entry(correlation_id.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let binding = entry.get_mut();
+                if binding.token_id != *token_id && now.duration_since(binding.last_seen) < self.ttl {
+                    return Err(VacError::CorrelationTokenMismatch);
+                }
+                binding.token_id = *token_id;
+                binding.last_seen = now;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Binding {
+                    token_id: *token_id,
+                    last_seen: now,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop bindings idle for longer than the TTL.
+    pub fn cleanup_expired(&self) {
+        let now = Instant::now();
+        self.bindings
+            .retain(|_, binding| now.duration_since(binding.last_seen) < self.ttl);
+    }
+
+    /// Number of bound correlation IDs (for monitoring)
+    pub fn size(&self) -> usize {
+        self.bindings.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_token_cannot_continue_a_bound_flow() {
+        let bindings = CorrelationBindings::new(DEFAULT_CORRELATION_BINDING_TTL);
+        bindings.check_and_bind("flow-1", &[1u8; 32]).unwrap();
+        bindings.check_and_bind("flow-1", &[1u8; 32]).unwrap();
+        assert!(matches!(
+            bindings.check_and_bind("flow-1", &[2u8; 32]),
+            Err(VacError::CorrelationTokenMismatch)
+        ));
+        bindings.check_and_bind("flow-2", &[2u8; 32]).unwrap();
+    }
+
+    #[test]
+    fn binding_expires_after_idle_ttl() {
+        let bindings = CorrelationBindings::new(Duration::from_millis(20));
+        bindings.check_and_bind("flow-1", &[1u8; 32]).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        bindings.check_and_bind("flow-1", &[2u8; 32]).unwrap();
+        bindings.cleanup_expired();
+        assert_eq!(bindings.size(), 1);
+    }
+}
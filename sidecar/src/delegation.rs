@@ -1,6 +1,8 @@
 use biscuit_auth::{Authorizer, Biscuit, PublicKey};
 use biscuit_auth::builder::{BlockBuilder, Fact};
 
+use std::time::SystemTime;
+
 use crate::error::VacError;
 use crate::revocation::extract_token_id;
 
@@ -14,6 +16,17 @@ pub const DEFAULT_MAX_DELEGATION_DEPTH: i64 = 5;
 /// - The last token in the chain MUST match the token in `Authorization: Bearer ...`.
 pub const DELEGATION_HEADER: &str = "X-VAC-Delegation";
 
+/// Hard cap on the number of `X-VAC-Delegation` headers, independent of any `depth(N)`
+/// value. Anything longer is rejected before a single token is parsed.
+pub const MAX_DELEGATION_CHAIN_LENGTH: usize = 32;
+
+/// Advance the expected depth by one hop; overflow means the chain is malformed.
+fn next_depth(depth: i64) -> Result<i64, VacError> {
+    depth.checked_add(1).ok_or_else(|| {
+        VacError::PolicyViolation("Malformed delegation chain: depth overflow".into())
+    })
+}
+
 /// Extract the declared delegation depth from the token facts.
 ///
 /// Convention (VAC model v4):
@@ -32,10 +45,12 @@ pub fn extract_depth(authorizer: &mut Authorizer) -> Result<Option<i64>, VacErro
 
 /// Like `extract_depth`, but returns the maximum depth when a token has multiple
 /// (e.g. attenuated biscuits with accumulated blocks). Used for delegation chain verification.
+///
+/// Queries facts from every block, since delegation appends `depth(N)` in a new block.
 pub fn extract_max_depth(authorizer: &mut Authorizer) -> Result<Option<i64>, VacError> {
     let query = "depth_value($d) <- depth($d)";
     let result: Vec<(i64,)> = authorizer
-        .query(query)
+        .query_all(query)
         .map_err(|e| VacError::InternalError(format!("Failed to query depth: {:?}", e)))?;
 
     Ok(result.iter().map(|(d,)| *d).max())
@@ -71,13 +86,88 @@ pub fn create_delegated_token(parent: &Biscuit, new_depth: i64) -> Result<Biscui
         .map_err(|e| VacError::InternalError(format!("Failed to append delegation block: {:?}", e)))
 }
 
+/// Attenuation applied by [`delegate`].
+#[derive(Debug, Clone, Default)]
+pub struct DelegationClaims {
+    /// Operations the delegated token may perform, as `"METHOD /path"` (the receipt
+    /// `operation` format). Empty means no restriction beyond the parent's.
+    pub allowed_operations: Vec<String>,
+    /// Expiry of the delegated token (can only narrow the parent's)
+    pub valid_until: Option<SystemTime>,
+}
+
+/// Mint a delegated token from `parent` in one call.
+///
+/// Appends a single block holding the incremented `depth(N + 1)` plus the attenuation
+/// checks, so the result always passes `verify_delegation_chain` when presented after
+/// its parent. The parent must declare a depth (issue roots with `depth: Some(0)`).
+pub fn delegate(parent: &Biscuit, claims: DelegationClaims) -> Result<Biscuit, VacError> {
+    let mut parent_authorizer = parent
+        .authorizer()
+        .map_err(|e| VacError::InternalError(format!("Failed to read parent token: {:?}", e)))?;
+    let parent_depth = extract_max_depth(&mut parent_authorizer)?.ok_or_else(|| {
+        VacError::PolicyViolation("Parent token missing depth(N) fact; cannot delegate".into())
+    })?;
+    let new_depth = next_depth(parent_depth)?;
+    if new_depth > DEFAULT_MAX_DELEGATION_DEPTH {
+        return Err(VacError::PolicyViolation(format!(
+            "Delegation would exceed max depth {} (parent depth {})",
+            DEFAULT_MAX_DELEGATION_DEPTH, parent_depth
+        )));
+    }
+
+    let mut block = BlockBuilder::new();
+    block
+        .add_fact(Fact::new(
+            "depth".to_string(),
+            vec![biscuit_auth::builder::int(new_depth)],
+        ))
+        .map_err(|e| VacError::InternalError(format!("Failed to add depth fact: {:?}", e)))?;
+
+    if !claims.allowed_operations.is_empty() {
+        let alternatives = claims
+            .allowed_operations
+            .iter()
+            .map(|op| operation_predicate(op))
+            .collect::<Result<Vec<_>, _>>()?;
+        let check = format!("check if {}", alternatives.join(" or "));
+        block
+            .add_check(check.as_str())
+            .map_err(|e| VacError::InternalError(format!("Failed to add operation check: {:?}", e)))?;
+    }
+
+    if let Some(valid_until) = claims.valid_until {
+        block.check_expiration_date(valid_until);
+    }
+
+    parent
+        .append(block)
+        .map_err(|e| VacError::InternalError(format!("Failed to append delegation block: {:?}", e)))
+}
+
+/// `"GET /search"` -> `operation("GET", "/search")`, rejecting anything that could
+/// break out of the Datalog string literal.
+fn operation_predicate(op: &str) -> Result<String, VacError> {
+    let invalid = || VacError::ConfigError(format!("Invalid allowed operation '{}': expected \"METHOD /path\"", op));
+    let (method, path) = op.split_once(' ').ok_or_else(invalid)?;
+    if method.is_empty()
+        || !method.chars().all(|c| c.is_ascii_uppercase())
+        || !path.starts_with('/')
+        || path.chars().any(|c| c == '"' || c == '\\' || c.is_control() || c.is_whitespace())
+    {
+        return Err(invalid());
+    }
+    Ok(format!("operation(\"{}\", \"{}\")", method, path))
+}
+
 /// Verify a delegation chain and return ordered token IDs (hex), plus the final depth.
 ///
 /// Verification:
 /// - Each token must verify under the provided root public key.
 /// - Each token must contain exactly one `depth(N)` fact.
 /// - Depth must be strictly increasing by 1 starting at 0.
-/// - The last token's token-id must match the Authorization token-id.
+/// - The last token's token-id must match the Authorization token-id
+///   (otherwise [`VacError::DelegationAuthorizationMismatch`]).
 pub fn verify_delegation_chain(
     root_public_key: &PublicKey,
     chain_tokens_b64: &[String],
@@ -90,6 +180,14 @@ pub fn verify_delegation_chain(
         return Ok((vec![token_id_hex], 0));
     }
 
+    if chain_tokens_b64.len() > MAX_DELEGATION_CHAIN_LENGTH {
+        return Err(VacError::PolicyViolation(format!(
+            "Malformed delegation chain: {} tokens exceeds limit of {}",
+            chain_tokens_b64.len(),
+            MAX_DELEGATION_CHAIN_LENGTH
+        )));
+    }
+
     let auth_id = extract_token_id(authorization_token_b64)?;
     let mut expected_depth: i64 = 0;
     let mut ids: Vec<String> = Vec::with_capacity(chain_tokens_b64.len());
@@ -111,7 +209,7 @@ pub fn verify_delegation_chain(
             )));
         }
         if idx + 1 < chain_tokens_b64.len() {
-            expected_depth += 1;
+            expected_depth = next_depth(expected_depth)?;
         }
 
         let id_hex = hex::encode(extract_token_id(t)?);
@@ -122,9 +220,8 @@ pub fn verify_delegation_chain(
     let last_b64 = chain_tokens_b64.last().ok_or(VacError::Deny)?;
     let last_id = extract_token_id(last_b64)?;
     if last_id != auth_id {
-        return Err(VacError::PolicyViolation(
-            "Delegation chain does not end in Authorization token".into(),
-        ));
+        // A client-side header construction bug, not an authorization decision.
+        return Err(VacError::DelegationAuthorizationMismatch);
     }
 
     // Verify Authorization token has the expected depth
@@ -175,5 +272,94 @@ mod tests {
         assert_eq!(depth, 2);
         assert_eq!(ids.len(), 3);
     }
-}
 
+    #[test]
+    fn verify_chain_with_unrelated_bearer_is_mismatch() {
+        let kp = KeyPair::new();
+        let t0 = root_with_depth(&kp, 0);
+        let t1 = root_with_depth(&kp, 1);
+        let chain = vec![t0.to_base64().unwrap(), t1.to_base64().unwrap()];
+        let unrelated = root_with_depth(&kp, 1).to_base64().unwrap();
+        let r = verify_delegation_chain(&kp.public(), &chain, &unrelated);
+        assert!(matches!(r, Err(VacError::DelegationAuthorizationMismatch)));
+    }
+
+    #[test]
+    fn next_depth_overflow_is_an_error() {
+        assert_eq!(next_depth(4).unwrap(), 5);
+        assert!(matches!(next_depth(i64::MAX), Err(VacError::PolicyViolation(_))));
+    }
+
+    #[test]
+    fn verify_chain_starting_at_max_depth_rejected_cleanly() {
+        let kp = KeyPair::new();
+        let t0 = root_with_depth(&kp, i64::MAX);
+        let t1 = root_with_depth(&kp, i64::MAX);
+        let chain = vec![t0.to_base64().unwrap(), t1.to_base64().unwrap()];
+        let auth = t1.to_base64().unwrap();
+        let r = verify_delegation_chain(&kp.public(), &chain, &auth);
+        assert!(matches!(r, Err(VacError::PolicyViolation(_))));
+    }
+
+    #[test]
+    fn verify_chain_longer_than_cap_rejected() {
+        let kp = KeyPair::new();
+        let t0 = root_with_depth(&kp, 0).to_base64().unwrap();
+        let chain = vec![t0.clone(); MAX_DELEGATION_CHAIN_LENGTH + 1];
+        let r = verify_delegation_chain(&kp.public(), &chain, &t0);
+        match r {
+            Err(VacError::PolicyViolation(msg)) => assert!(msg.contains("exceeds limit")),
+            other => panic!("expected chain length error, got {:?}", other.map(|(ids, _)| ids.len())),
+        }
+    }
+
+    #[test]
+    fn delegate_scoped_to_search_round_trips() {
+        let kp = KeyPair::new();
+        let t0 = root_with_depth(&kp, 0);
+        let t1 = delegate(
+            &t0,
+            DelegationClaims {
+                allowed_operations: vec!["GET /search".to_string()],
+                valid_until: Some(SystemTime::now() + std::time::Duration::from_secs(600)),
+            },
+        )
+        .unwrap();
+
+        let chain = vec![t0.to_base64().unwrap(), t1.to_base64().unwrap()];
+        let auth = t1.to_base64().unwrap();
+        let (ids, depth) = verify_delegation_chain(&kp.public(), &chain, &auth).unwrap();
+        assert_eq!(depth, 1);
+        assert_eq!(ids.len(), 2);
+
+        let evaluate = |method: &str, path: &str| {
+            let mut a = Authorizer::new();
+            a.add_token(&t1).unwrap();
+            crate::policy::add_context_facts(&mut a, method, path, "cid-1").unwrap();
+            a.add_code("allow if true;").unwrap();
+            crate::policy::evaluate_policy(&mut a)
+        };
+        assert!(evaluate("GET", "/search").is_ok());
+        assert!(matches!(evaluate("POST", "/charge"), Err(VacError::PolicyViolation(_))));
+    }
+
+    #[test]
+    fn delegate_requires_parent_depth() {
+        let kp = KeyPair::new();
+        let parent = Biscuit::builder().build(&kp).unwrap();
+        assert!(delegate(&parent, DelegationClaims::default()).is_err());
+    }
+
+    #[test]
+    fn delegate_rejects_unsafe_operations() {
+        let kp = KeyPair::new();
+        let t0 = root_with_depth(&kp, 0);
+        for op in ["search", "get /search", "GET search", "GET /a\"), allow if true"] {
+            let claims = DelegationClaims {
+                allowed_operations: vec![op.to_string()],
+                ..Default::default()
+            };
+            assert!(matches!(delegate(&t0, claims), Err(VacError::ConfigError(_))), "{}", op);
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
 use thiserror::Error;
 
 /// V-A-C Sidecar error types with explicit fail-closed enforcement
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum VacError {
     #[error("Missing authorization token")]
     MissingToken,
@@ -19,12 +22,42 @@ pub enum VacError {
     #[error("Correlation ID mismatch")]
     CorrelationIdMismatch,
     
+    #[error("Correlation ID is bound to a different token")]
+    CorrelationTokenMismatch,
+    
     #[error("Policy violation: {0}")]
     PolicyViolation(String),
     
     #[error("Request denied by fail-closed policy")]
     Deny,
     
+    #[error("Request denied: correlation ID already used")]
+    Replay,
+    
+    #[error("Request denied: rate limit exceeded (retry after {retry_after_secs}s)")]
+    RateLimited { retry_after_secs: u64 },
+    
+    #[error("Request denied: too many steps under this correlation ID")]
+    StepLimitExceeded,
+    
+    #[error("Request denied: {0}")]
+    FlowViolation(String),
+    
+    #[error("Request denied: too many concurrent adapter runs for this correlation ID")]
+    AdapterBusy,
+    
+    #[error("Adapter extracted no facts from the request body")]
+    AdapterNoFacts,
+    
+    #[error("Request shed: buffered request bodies are using the whole memory budget")]
+    BodyBudgetExhausted,
+    
+    #[error("Token has been revoked")]
+    TokenRevoked,
+    
+    #[error("Token is not valid yet (not_before is in the future)")]
+    TokenNotYetValid,
+    
     #[error("Configuration error: {0}")]
     ConfigError(String),
     
@@ -34,8 +67,20 @@ pub enum VacError {
     #[error("Proxy error: {0}")]
     ProxyError(String),
     
+    #[error("Upstream response truncated: {0}")]
+    UpstreamTruncated(String),
+    
+    #[error("Upstream timed out: {0}")]
+    UpstreamTimeout(String),
+    
     #[error("Receipt verification failed: {0}")]
     ReceiptError(String),
+    
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    
+    #[error("Delegation chain does not end in the Authorization token: the last X-VAC-Delegation token must be the bearer token")]
+    DelegationAuthorizationMismatch,
 }
 
 impl From<VacError> for StatusCode {
@@ -46,9 +91,133 @@ impl From<VacError> for StatusCode {
 
 impl IntoResponse for VacError {
     fn into_response(self) -> axum::response::Response {
-        let status: StatusCode = From::from(&self);
-        let body = format!("{}", self);
-        (status, body).into_response()
+        self.to_response(ErrorResponseFormat::default(), None)
+    }
+}
+
+/// Body serialization for error responses (`error_response_format` config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorResponseFormat {
+    /// Plain-text `Display` message (the format before JSON became the default).
+    Text,
+    /// `application/json`: `{"error": code, "message": ..., "correlation_id": ...}`
+    #[default]
+    Json,
+    /// RFC 7807 `application/problem+json` with `instance` set to the correlation ID.
+    ProblemJson,
+}
+
+impl std::str::FromStr for ErrorResponseFormat {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(ErrorResponseFormat::Text),
+            "json" => Ok(ErrorResponseFormat::Json),
+            "problem+json" => Ok(ErrorResponseFormat::ProblemJson),
+            other => Err(VacError::ConfigError(format!(
+                "error_response_format must be one of text, json, problem+json (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+impl VacError {
+    /// Stable, machine-readable snake_case code for this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            VacError::MissingToken => "missing_token",
+            VacError::InvalidTokenFormat => "invalid_token_format",
+            VacError::InvalidSignature => "invalid_signature",
+            VacError::ReceiptExpired => "receipt_expired",
+            VacError::CorrelationIdMismatch => "correlation_id_mismatch",
+            VacError::CorrelationTokenMismatch => "correlation_token_mismatch",
+            VacError::PolicyViolation(_) => "policy_violation",
+            VacError::Deny => "deny",
+            VacError::Replay => "replay",
+            VacError::RateLimited { .. } => "rate_limit",
+            VacError::StepLimitExceeded => "step_limit",
+            VacError::FlowViolation(_) => "flow_violation",
+            VacError::AdapterBusy => "adapter_busy",
+            VacError::AdapterNoFacts => "adapter_no_facts",
+            VacError::BodyBudgetExhausted => "body_budget_exhausted",
+            VacError::TokenRevoked => "revoked",
+            VacError::TokenNotYetValid => "token_not_yet_valid",
+            VacError::ConfigError(_) => "config_error",
+            VacError::InternalError(_) => "internal_error",
+            VacError::ProxyError(_) => "proxy_error",
+            VacError::UpstreamTruncated(_) => "upstream_truncated",
+            VacError::UpstreamTimeout(_) => "upstream_timeout",
+            VacError::ReceiptError(_) => "receipt_error",
+            VacError::BadRequest(_) => "bad_request",
+            VacError::DelegationAuthorizationMismatch => "delegation_authorization_mismatch",
+        }
+    }
+
+    /// Short human-readable summary that does not vary between occurrences
+    /// (RFC 7807 `title`); the full message goes in `detail`.
+    fn title(&self) -> &'static str {
+        match self {
+            VacError::MissingToken => "Missing authorization token",
+            VacError::InvalidTokenFormat => "Invalid token format",
+            VacError::InvalidSignature => "Invalid biscuit signature",
+            VacError::ReceiptExpired => "Receipt expired",
+            VacError::CorrelationIdMismatch => "Correlation ID mismatch",
+            VacError::CorrelationTokenMismatch => "Correlation ID bound to another token",
+            VacError::PolicyViolation(_) => "Policy violation",
+            VacError::Deny => "Request denied",
+            VacError::Replay => "Replay detected",
+            VacError::RateLimited { .. } => "Rate limit exceeded",
+            VacError::StepLimitExceeded => "Step limit exceeded",
+            VacError::FlowViolation(_) => "Out-of-order flow step",
+            VacError::AdapterBusy => "Too many concurrent adapter runs",
+            VacError::AdapterNoFacts => "Adapter extracted no facts",
+            VacError::BodyBudgetExhausted => "Body memory budget exhausted",
+            VacError::TokenRevoked => "Token revoked",
+            VacError::TokenNotYetValid => "Token not yet valid",
+            VacError::ConfigError(_) => "Configuration error",
+            VacError::InternalError(_) => "Internal server error",
+            VacError::ProxyError(_) => "Proxy error",
+            VacError::UpstreamTruncated(_) => "Upstream response truncated",
+            VacError::UpstreamTimeout(_) => "Upstream timed out",
+            VacError::ReceiptError(_) => "Receipt verification failed",
+            VacError::BadRequest(_) => "Bad request",
+            VacError::DelegationAuthorizationMismatch => "Delegation chain and Authorization token disagree",
+        }
+    }
+
+    /// Render this error as an HTTP response in the requested format.
+    ///
+    /// `correlation_id` is included in JSON bodies (and as the problem `instance`)
+    /// so a client can match the denial to sidecar logs.
+    pub fn to_response(&self, format: ErrorResponseFormat, correlation_id: Option<&str>) -> Response {
+        let status: StatusCode = From::from(self);
+        let mut response = match format {
+            ErrorResponseFormat::Text => (status, self.to_string()).into_response(),
+            ErrorResponseFormat::Json => {
+                let body = serde_json::json!({
+                    "error": self.code(),
+                    "message": self.to_string(),
+                    "correlation_id": correlation_id,
+                });
+                (status, [(header::CONTENT_TYPE, "application/json")], body.to_string()).into_response()
+            }
+            ErrorResponseFormat::ProblemJson => {
+                let body = serde_json::json!({
+                    "type": format!("urn:vac:error:{}", self.code()),
+                    "title": self.title(),
+                    "status": status.as_u16(),
+                    "detail": self.to_string(),
+                    "instance": correlation_id,
+                });
+                (status, [(header::CONTENT_TYPE, "application/problem+json")], body.to_string()).into_response()
+            }
+        };
+        if let VacError::RateLimited { retry_after_secs } = self {
+            response.headers_mut().insert(header::RETRY_AFTER, (*retry_after_secs).into());
+        }
+        response
     }
 }
 
@@ -60,12 +229,26 @@ impl From<&VacError> for StatusCode {
             VacError::InvalidSignature => StatusCode::FORBIDDEN,
             VacError::ReceiptExpired => StatusCode::FORBIDDEN,
             VacError::CorrelationIdMismatch => StatusCode::CONFLICT,
+            VacError::CorrelationTokenMismatch => StatusCode::CONFLICT,
             VacError::PolicyViolation(_) => StatusCode::FORBIDDEN,
             VacError::Deny => StatusCode::FORBIDDEN,
+            VacError::Replay => StatusCode::FORBIDDEN,
+            VacError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            VacError::StepLimitExceeded => StatusCode::FORBIDDEN,
+            VacError::FlowViolation(_) => StatusCode::FORBIDDEN,
+            VacError::AdapterBusy => StatusCode::TOO_MANY_REQUESTS,
+            VacError::AdapterNoFacts => StatusCode::UNPROCESSABLE_ENTITY,
+            VacError::BodyBudgetExhausted => StatusCode::SERVICE_UNAVAILABLE,
+            VacError::TokenRevoked => StatusCode::FORBIDDEN,
+            VacError::TokenNotYetValid => StatusCode::FORBIDDEN,
             VacError::ConfigError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             VacError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             VacError::ProxyError(_) => StatusCode::BAD_GATEWAY,
+            VacError::UpstreamTruncated(_) => StatusCode::BAD_GATEWAY,
+            VacError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
             VacError::ReceiptError(_) => StatusCode::FORBIDDEN,
+            VacError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            VacError::DelegationAuthorizationMismatch => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -84,3 +267,63 @@ impl From<reqwest::Error> for VacError {
         VacError::ProxyError(format!("HTTP request failed: {}", err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(resp: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(resp.into_body(), 64 * 1024).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn problem_json_policy_violation() {
+        let err = VacError::PolicyViolation("no matching allow".to_string());
+        let resp = err.to_response(ErrorResponseFormat::ProblemJson, Some("cid-123"));
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = body_json(resp).await;
+        assert_eq!(body["type"], "urn:vac:error:policy_violation");
+        assert_eq!(body["title"], "Policy violation");
+        assert_eq!(body["status"], 403);
+        assert_eq!(body["detail"], "Policy violation: no matching allow");
+        assert_eq!(body["instance"], "cid-123");
+    }
+
+    #[tokio::test]
+    async fn json_format_carries_code_and_correlation_id() {
+        let resp = VacError::MissingToken.to_response(ErrorResponseFormat::Json, Some("cid-1"));
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+        let body = body_json(resp).await;
+        assert_eq!(body["error"], "missing_token");
+        assert_eq!(body["correlation_id"], "cid-1");
+    }
+
+    #[test]
+    fn rate_limited_is_429_with_retry_after() {
+        for format in [ErrorResponseFormat::Text, ErrorResponseFormat::Json, ErrorResponseFormat::ProblemJson] {
+            let resp = VacError::RateLimited { retry_after_secs: 12 }.to_response(format, None);
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(resp.headers()[header::RETRY_AFTER], "12");
+        }
+    }
+
+    #[test]
+    fn json_format_is_default() {
+        let resp = VacError::Deny.into_response();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+    }
+
+    #[test]
+    fn error_response_format_from_str() {
+        assert_eq!("problem+json".parse::<ErrorResponseFormat>().unwrap(), ErrorResponseFormat::ProblemJson);
+        assert_eq!("JSON".parse::<ErrorResponseFormat>().unwrap(), ErrorResponseFormat::Json);
+        assert!("xml".parse::<ErrorResponseFormat>().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Declarative flow graph (`[flow]` section)
+//!
+//! Multi-step flows such as `search -> select -> charge` can be written as Datalog rules
+//! over `prior_event`, but every transition then needs its own hand-written rule. A flow
+//! graph declares the steps (a name for each `"METHOD /path"` operation) and the allowed
+//! transitions between them, and the guard enforces it before the policy runs:
+//!
+//! ```toml
+//! [flow]
+//! steps = { search = "GET /search", select = "POST /select", charge = "POST /charge" }
+//! transitions = ["search -> select", "select -> charge"]
+//! ```
+//!
+//! The current state of a correlation ID is the step of its latest presented receipt
+//! (by timestamp, ties in presentation order). A request for a step is only let through
+//! if there is a transition from the current state to it or, when no receipt is for a
+//! step yet, if it is a start step (by default, the steps no transition leads to).
+//! Requests for operations that are not steps are left to the policy.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::error::VacError;
+use crate::receipt::ReceiptInfo;
+
+/// Steps and allowed transitions of a flow, validated at load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowGraph {
+    /// Operation (`"METHOD /path"`) -> step name
+    steps: HashMap<String, String>,
+    /// Step -> steps that may follow it
+    transitions: BTreeMap<String, BTreeSet<String>>,
+    /// Steps a flow may begin with
+    start: BTreeSet<String>,
+}
+
+impl FlowGraph {
+    /// Build a graph from `steps` (step name -> operation), `transitions` (`"a -> b"`)
+    /// and optional `start` steps.
+    pub fn new(
+        steps: &BTreeMap<String, String>,
+        transitions: &[String],
+        start: Option<&[String]>,
+    ) -> Result<Self, VacError> {
+        let mut operations = HashMap::new();
+        for (name, operation) in steps {
+            let valid = operation
+                .split_once(' ')
+                .is_some_and(|(method, path)| !method.is_empty() && path.starts_with('/'));
+            if !valid {
+                return Err(VacError::ConfigError(format!(
+                    "flow.steps.{}: operation must be \"METHOD /path\" (got '{}')",
+                    name, operation
+                )));
+            }
+            if let Some(other) = operations.insert(operation.clone(), name.clone()) {
+                return Err(VacError::ConfigError(format!(
+                    "flow.steps: '{}' and '{}' are the same operation ({})",
+                    other, name, operation
+                )));
+            }
+        }
+        let known = |step: &str, what: &str| {
+            if steps.contains_key(step) {
+                Ok(step.to_string())
+            } else {
+                Err(VacError::ConfigError(format!("flow.{}: unknown step '{}'", what, step)))
+            }
+        };
+
+        let mut edges: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for transition in transitions {
+            let (from, to) = transition.split_once("->").ok_or_else(|| {
+                VacError::ConfigError(format!("flow.transitions: expected \"from -> to\" (got '{}')", transition))
+            })?;
+            let (from, to) = (known(from.trim(), "transitions")?, known(to.trim(), "transitions")?);
+            edges.entry(from).or_default().insert(to);
+        }
+
+        let start: BTreeSet<String> = match start {
+            Some(start) => start.iter().map(|step| known(step, "start")).collect::<Result<_, _>>()?,
+            None => steps
+                .keys()
+                .filter(|step| !edges.values().any(|next| next.contains(*step)))
+                .cloned()
+                .collect(),
+        };
+        if !steps.is_empty() && start.is_empty() {
+            return Err(VacError::ConfigError(
+                "flow: no start step (every step has an incoming transition); set flow.start".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            steps: operations,
+            transitions: edges,
+            start,
+        })
+    }
+
+    /// Step name of `operation`, if it is part of the flow.
+    pub fn step(&self, operation: &str) -> Option<&str> {
+        self.steps.get(operation).map(String::as_str)
+    }
+
+    /// Check that `operation` may follow the flow steps recorded in `receipts`.
+    pub fn check(&self, operation: &str, receipts: &[ReceiptInfo]) -> Result<(), VacError> {
+        let Some(next) = self.step(operation) else {
+            return Ok(());
+        };
+        let mut prior: Vec<&ReceiptInfo> = receipts.iter().filter(|r| self.step(&r.operation).is_some()).collect();
+        prior.sort_by_key(|r| r.timestamp);
+        match prior.last().and_then(|r| self.step(&r.operation)) {
+            None if self.start.contains(next) => Ok(()),
+            None => Err(VacError::FlowViolation(format!("flow step '{}' cannot start a flow", next))),
+            Some(current) if self.transitions.get(current).is_some_and(|to| to.contains(next)) => Ok(()),
+            Some(current) => Err(VacError::FlowViolation(format!(
+                "flow step '{}' cannot follow '{}'",
+                next, current
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> FlowGraph {
+        let steps = [("search", "GET /search"), ("select", "POST /select"), ("charge", "POST /charge")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        FlowGraph::new(&steps, &["search -> select".to_string(), "select -> charge".to_string()], None).unwrap()
+    }
+
+    fn receipt(operation: &str, timestamp: i64) -> ReceiptInfo {
+        ReceiptInfo {
+            operation: operation.to_string(),
+            correlation_id: "cid".to_string(),
+            timestamp,
+            minted_by: None,
+        }
+    }
+
+    #[test]
+    fn current_state_is_the_latest_step_receipt() {
+        let graph = graph();
+        assert!(graph.check("GET /search", &[]).is_ok());
+        assert!(graph.check("POST /charge", &[]).is_err());
+        assert!(graph.check("POST /charge", &[receipt("GET /search", 1)]).is_err());
+        // Presented out of order, and with an unrelated receipt in between.
+        let receipts = [receipt("POST /select", 2), receipt("GET /other", 3), receipt("GET /search", 1)];
+        assert!(graph.check("POST /charge", &receipts).is_ok());
+        assert!(graph.check("GET /search", &receipts).is_err());
+        // Not a step: left to the policy.
+        assert!(graph.check("GET /other", &[]).is_ok());
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let steps: BTreeMap<String, String> = [("a".to_string(), "GET /a".to_string())].into();
+        assert!(FlowGraph::new(&steps, &["a -> b".to_string()], None).is_err());
+        assert!(FlowGraph::new(&steps, &["a => a".to_string()], None).is_err());
+        // A cycle through every step has no implicit start.
+        assert!(FlowGraph::new(&steps, &["a -> a".to_string()], None).is_err());
+        assert!(FlowGraph::new(&steps, &["a -> a".to_string()], Some(&["a".to_string()])).is_ok());
+        let bad: BTreeMap<String, String> = [("a".to_string(), "/a".to_string())].into();
+        assert!(FlowGraph::new(&bad, &[], None).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+//! gRPC passthrough (`protocol = "grpc"`)
+//!
+//! gRPC runs over HTTP/2 and reports the outcome of a call in the `grpc-status` trailer,
+//! after the response body: the HTTP status is 200 for failed calls too. In gRPC mode the
+//! sidecar forwards calls to the upstream over HTTP/2 (h2c) with [`GrpcProxy`] and streams
+//! the response back frame by frame, trailers included. The step's receipt is only
+//! delivered once the trailers say the call succeeded (`grpc-status: 0`): it is appended
+//! to the trailers as `x-vac-receipt` ([`deliver_on_grpc_ok`]).
+//!
+//! The RPC method is the request path (`/<package>.<Service>/<Method>`), so policies
+//! authorize calls with the usual `operation` fact, e.g.
+//! `allow if operation("POST", "/payments.Payments/Charge");`, and the receipt records
+//! the operation `POST /payments.Payments/Charge`.
+//!
+//! `upstream_timeout_secs` bounds connecting to the upstream and waiting for the response
+//! headers (connecting alone is bounded by [`GRPC_CONNECT_TIMEOUT`] when it is unset); a
+//! response streaming past it is not cut off. The request body is read whole before it is
+//! forwarded, since the guard needs it for adapters and replay keys: unary and
+//! server-streaming calls are unaffected, but a client-streaming or bidirectional call
+//! reaches the upstream only once the client has finished sending, capped at the request
+//! body size limit.
+
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{header, HeaderMap, HeaderValue, Method, Request, Response, Uri};
+use hyper::body::Frame;
+use hyper::client::conn::http2::SendRequest;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+
+use crate::error::VacError;
+
+/// Trailer (or header, for a trailers-only response) carrying the gRPC call status
+pub const GRPC_STATUS: &str = "grpc-status";
+
+/// gRPC status of a successful call
+pub const GRPC_STATUS_OK: &str = "0";
+
+/// Trailer carrying the receipt of a successful gRPC call
+pub const GRPC_RECEIPT_TRAILER: &str = "x-vac-receipt";
+
+/// Longest wait for the TCP connect and HTTP/2 handshake with the upstream when
+/// `upstream_timeout_secs` is unset
+pub const GRPC_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wire protocol between the sidecar and its upstream (`protocol`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpstreamProtocol {
+    /// Buffered HTTP/1.1 requests and responses; receipts on 2xx responses.
+    #[default]
+    Http,
+    /// gRPC over HTTP/2; receipts in the trailers of calls ending with `grpc-status: 0`.
+    Grpc,
+}
+
+impl FromStr for UpstreamProtocol {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "http" => Ok(UpstreamProtocol::Http),
+            "grpc" => Ok(UpstreamProtocol::Grpc),
+            other => Err(VacError::ConfigError(format!(
+                "protocol must be one of http, grpc (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Whether a request is a gRPC call: `POST /<service>/<method>` with an
+/// `application/grpc` (or `application/grpc+<codec>`) content type.
+pub fn is_grpc_call(method: &Method, path: &str, headers: &HeaderMap) -> bool {
+    let grpc_content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct == "application/grpc" || ct.starts_with("application/grpc+"));
+    *method == Method::POST && grpc_content_type && rpc_method(path).is_some()
+}
+
+/// `(service, method)` of an RPC path `/<package>.<Service>/<Method>`.
+pub fn rpc_method(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    let valid = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    (valid(service) && valid(method)).then_some((service, method))
+}
+
+/// Headers that are connection-specific in HTTP/1.1 and forbidden in HTTP/2.
+const CONNECTION_HEADERS: &[&str] = &["connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade"];
+
+/// Forwards gRPC calls to the upstream over one shared HTTP/2 connection (h2c, i.e.
+/// plaintext with prior knowledge), reconnecting when it closes.
+#[derive(Default)]
+pub struct GrpcProxy {
+    // Upstream authority and the connection to it
+    connection: tokio::sync::Mutex<Option<(String, SendRequest<Body>)>>,
+}
+
+impl GrpcProxy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward an authorized call, with the same header handling as the HTTP proxy: the
+    /// client's `Authorization`, `Host` and `x-vac-*` headers are dropped, `extra_headers`
+    /// added and the upstream API key injected. The response body, trailers included, is
+    /// streamed back as it arrives; `timeout` bounds everything up to its headers.
+    pub async fn forward(
+        &self,
+        parts: &axum::http::request::Parts,
+        body_bytes: Bytes,
+        api_key: &str,
+        upstream_url: &str,
+        extra_headers: &HeaderMap,
+        timeout: Option<Duration>,
+    ) -> Result<Response<Body>, VacError> {
+        let upstream = Uri::from_str(upstream_url)
+            .map_err(|e| VacError::ProxyError(format!("Invalid upstream URL: {}", e)))?;
+        if upstream.scheme_str() != Some("http") {
+            return Err(VacError::ProxyError(
+                "protocol = grpc needs a plaintext http:// upstream (h2c)".to_string(),
+            ));
+        }
+        let authority = upstream
+            .authority()
+            .ok_or_else(|| VacError::ProxyError("Invalid upstream URL: no host".to_string()))?;
+        let path_and_query = parts.uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+        let uri = Uri::from_str(&format!("http://{}{}", authority, path_and_query))
+            .map_err(|e| VacError::ProxyError(format!("Invalid upstream URL: {}", e)))?;
+
+        let mut request = Request::builder()
+            .method(parts.method.clone())
+            .uri(uri)
+            .body(Body::from(body_bytes))
+            .map_err(|e| VacError::ProxyError(format!("Failed to build gRPC request: {}", e)))?;
+        let headers = request.headers_mut();
+        for (name, value) in &parts.headers {
+            let name_str = name.as_str();
+            if name_str == "authorization"
+                || name_str == "host"
+                || name_str.starts_with("x-vac-")
+                || CONNECTION_HEADERS.contains(&name_str)
+            {
+                continue;
+            }
+            headers.append(name, value.clone());
+        }
+        for (name, value) in extra_headers {
+            headers.insert(name, value.clone());
+        }
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| VacError::ProxyError(format!("Invalid API key header: {}", e)))?,
+        );
+
+        let mut sender = self.sender(authority.as_str(), timeout.unwrap_or(GRPC_CONNECT_TIMEOUT)).await?;
+        let call = async {
+            sender
+                .ready()
+                .await
+                .map_err(|e| VacError::ProxyError(format!("gRPC upstream connection failed: {}", e)))?;
+            sender
+                .send_request(request)
+                .await
+                .map_err(|e| VacError::ProxyError(format!("Upstream gRPC call failed: {}", e)))
+        };
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| {
+                VacError::UpstreamTimeout(format!("no gRPC response headers within {:?}", timeout))
+            })??,
+            None => call.await?,
+        };
+        Ok(response.map(Body::new))
+    }
+
+    /// The open connection to `authority`, or a new one made within `connect_timeout`.
+    ///
+    /// Calls wait on the lock while a connection is being made, so the timeout also bounds
+    /// how long an unreachable upstream holds up every other call.
+    async fn sender(&self, authority: &str, connect_timeout: Duration) -> Result<SendRequest<Body>, VacError> {
+        let mut connection = self.connection.lock().await;
+        if let Some((connected_to, sender)) = connection.as_ref() {
+            if connected_to == authority && !sender.is_closed() {
+                return Ok(sender.clone());
+            }
+        }
+        let connect = async {
+            let stream = tokio::net::TcpStream::connect(authority)
+                .await
+                .map_err(|e| VacError::ProxyError(format!("gRPC upstream connection failed: {}", e)))?;
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .map_err(|e| VacError::ProxyError(format!("gRPC upstream HTTP/2 handshake failed: {}", e)))
+        };
+        let (sender, conn) = tokio::time::timeout(connect_timeout, connect).await.map_err(|_| {
+            VacError::UpstreamTimeout(format!("no gRPC upstream connection within {:?}", connect_timeout))
+        })??;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::debug!(error = %e, "gRPC upstream connection closed");
+            }
+        });
+        *connection = Some((authority.to_string(), sender.clone()));
+        Ok(sender)
+    }
+}
+
+/// Add `receipt_headers` to a gRPC response, and run `on_ok`, only if the call succeeds.
+///
+/// A trailers-only response carries `grpc-status` in its headers and is decided at once;
+/// otherwise the body is wrapped so the decision is made when the trailers arrive. A
+/// response that ends without a `grpc-status` gets no receipt.
+pub fn deliver_on_grpc_ok(
+    response: Response<Body>,
+    receipt_headers: HeaderMap,
+    on_ok: impl FnOnce() + Send + 'static,
+) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    if let Some(status) = parts.headers.get(GRPC_STATUS) {
+        if status == GRPC_STATUS_OK {
+            parts.headers.extend(receipt_headers);
+            on_ok();
+        }
+        return Response::from_parts(parts, body);
+    }
+    let body = GrpcStatusBody {
+        inner: body,
+        pending: Some((receipt_headers, Box::new(on_ok))),
+    };
+    Response::from_parts(parts, Body::new(body))
+}
+
+/// Response body that appends receipt trailers once `grpc-status: 0` arrives.
+struct GrpcStatusBody {
+    inner: Body,
+    pending: Option<(HeaderMap, Box<dyn FnOnce() + Send>)>,
+}
+
+impl HttpBody for GrpcStatusBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let frame = match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            other => return other,
+        };
+        let frame = match frame.into_trailers() {
+            Ok(mut trailers) => {
+                if trailers.get(GRPC_STATUS).is_some_and(|s| s == GRPC_STATUS_OK) {
+                    if let Some((receipt_headers, on_ok)) = self.pending.take() {
+                        trailers.extend(receipt_headers);
+                        on_ok();
+                    }
+                }
+                Frame::trailers(trailers)
+            }
+            Err(frame) => frame,
+        };
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grpc_calls_are_recognized() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/grpc+proto"));
+        assert!(is_grpc_call(&Method::POST, "/payments.Payments/Charge", &headers));
+        assert!(!is_grpc_call(&Method::GET, "/payments.Payments/Charge", &headers));
+        assert!(!is_grpc_call(&Method::POST, "/charge", &headers));
+        assert!(!is_grpc_call(&Method::POST, "/payments.Payments/Charge/extra", &headers));
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        assert!(!is_grpc_call(&Method::POST, "/payments.Payments/Charge", &headers));
+
+        assert_eq!(rpc_method("/payments.Payments/Charge"), Some(("payments.Payments", "Charge")));
+        assert_eq!("GRPC".parse::<UpstreamProtocol>().unwrap(), UpstreamProtocol::Grpc);
+        assert!("websocket".parse::<UpstreamProtocol>().is_err());
+    }
+}
@@ -0,0 +1,1161 @@
+//! VAC guard as Tower middleware
+//!
+//! [`VacGuardLayer`] wraps any inner service with the full VAC check: token and
+//! delegation verification, receipts, replay/rate limiting, adapters and Datalog policy.
+//! Authorized requests are passed to the inner service with a [`VacContext`] (and the
+//! resolved [`crate::client_addr::ClientAddr`]) in their extensions; a receipt is minted onto 2xx responses (with `protocol = grpc`, into the trailers of calls ending with `grpc-status: 0`). Denied requests never reach it.
+//!
+//! The sidecar binary uses [`crate::proxy::upstream_handler`] as the inner service;
+//! library users can put the layer in front of their own Axum routes instead:
+//!
+//! ```ignore
+//! let app = Router::new()
+//!     .route("/charge", post(charge))
+//!     .layer(VacGuardLayer::new(state));
+//! ```
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, HttpBody};
+use axum::extract::ConnectInfo;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::Response;
+use biscuit_auth::Authorizer;
+use tower::{Layer, Service, ServiceExt};
+use uuid::Uuid;
+
+use crate::adapter::{extract_facts_from_request, screen_adapter_facts, AdapterContext};
+use crate::client_addr::{strip_forwarded_headers, ClientAddr};
+use crate::body_budget::BodyBudget;
+use crate::biscuit::{
+    check_not_before, check_token_size, parse_bearer_token, token_shape_violation, verify_receipt_biscuit_with_keys,
+    verify_root_biscuit_with_verifier,
+};
+use crate::delegation::{extract_depth, verify_delegation_chain, DELEGATION_HEADER};
+use crate::error::VacError;
+use crate::grpc::{deliver_on_grpc_ok, is_grpc_call, UpstreamProtocol};
+use crate::json_canon::{canonicalize_json, is_json_content_type};
+use crate::rate_limit::RateLimitKey;
+use crate::policy::{
+    add_context_facts, add_idempotency_key_fact, add_operator_policy, add_receipt_count_fact, add_receipt_facts,
+    evaluate_policy_with_timeout, extract_adapter_hash, has_valid_idempotency_key, normalize_trailing_slash, origin_form,
+    trace_authorizer_world, OptionsAsterisk,
+};
+use crate::receipt::{compact_receipt_tokens, extract_receipt_info, mint_completion_receipt, receipt_tokens, COMPLETE_FLOW_HEADER, COMPLETION_RECEIPT_HEADER, MAX_COMPLETION_STEPS, verify_correlation_id_match, verify_receipt_expiry_within, NewReceipt, ReceiptInfo};
+use crate::receipt_webhook::ReceiptEvent;
+use crate::replay_cache::request_replay_key;
+use crate::revocation::extract_token_id;
+use crate::session_keys::SessionKeySet;
+use crate::state::SharedState;
+
+/// Correlation ID request header, echoed on every response the guard returns once the
+/// ID is resolved (allowed or denied), so a client can find the request in sidecar logs.
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-ID";
+
+/// Verified request context, inserted into the request extensions before the
+/// inner service is called.
+#[derive(Debug, Clone)]
+pub struct VacContext {
+    /// Correlation ID the request was authorized (and its receipt minted) under
+    pub correlation_id: String,
+    /// Hex token IDs of the verified delegation chain (root → current), empty if none
+    pub delegation_chain: Vec<String>,
+    /// Delegation depth from the token facts, if present (embedded into receipts)
+    pub depth: Option<i64>,
+    /// Verified position in the delegation chain (0 for a root token)
+    pub delegation_depth: i64,
+}
+
+/// Source of correlation IDs for requests that arrive without a (valid) `X-Correlation-ID`.
+///
+/// The sidecar uses [`UuidCorrelationIds`]; tests can swap in a deterministic generator
+/// (any `Fn() -> String` works) via `SidecarState::correlation_id_generator`.
+pub trait CorrelationIdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// Random UUIDv4 correlation IDs (production default).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidCorrelationIds;
+
+impl CorrelationIdGenerator for UuidCorrelationIds {
+    fn generate(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+impl<F> CorrelationIdGenerator for F
+where
+    F: Fn() -> String + Send + Sync,
+{
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+/// Tower layer that puts the VAC guard in front of an inner service.
+#[derive(Clone)]
+pub struct VacGuardLayer {
+    state: SharedState,
+}
+
+impl VacGuardLayer {
+    pub fn new(state: SharedState) -> Self {
+        Self { state }
+    }
+}
+
+impl<S> Layer<S> for VacGuardLayer {
+    type Service = VacGuard<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        VacGuard {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Service produced by [`VacGuardLayer`].
+#[derive(Clone)]
+pub struct VacGuard<S> {
+    inner: S,
+    state: SharedState,
+}
+
+impl<S> Service<Request> for VacGuard<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // Take the service that was driven to readiness and leave a fresh clone behind.
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let state = self.state.clone();
+        let arrived = Instant::now();
+        Box::pin(async move { Ok(guard(state, inner, req, arrived).await) })
+    }
+}
+
+/// Run the guard for one request and render any denial as an error response.
+async fn guard<S>(state: SharedState, inner: S, req: Request, arrived: Instant) -> Response
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+{
+    let (error_format, metrics, soft_deny, id_generator, require_correlation_id) = {
+        let s = state.read().await;
+        (
+            s.error_response_format,
+            s.metrics.clone(),
+            s.soft_deny,
+            s.correlation_id_generator.clone(),
+            s.require_correlation_id,
+        )
+    };
+    // Queue time ends once the request gets the state lock: under saturation, this is
+    // where it waits behind writers (reloads, key rotation) and other requests.
+    let queue_duration = arrived.elapsed();
+    metrics.observe_queue_duration(queue_duration);
+    // Resolve the correlation ID up front so error bodies can reference it too.
+    let correlation_id = match resolve_correlation_id(
+        req.headers(),
+        id_generator.as_ref(),
+        require_correlation_id,
+    ) {
+        Ok(cid) => cid,
+        Err(e) => {
+            metrics.record_error(&e);
+            return e.to_response(error_format, None);
+        }
+    };
+    let mut response = match guard_request(state, inner, req, correlation_id.clone(), queue_duration).await {
+        Ok(response) => {
+            metrics.record_allow();
+            response
+        }
+        Err(e) => {
+            metrics.record_error(&e);
+            if soft_deny && is_policy_denial(&e) {
+                soft_deny_response(&e, &correlation_id)
+            } else {
+                e.to_response(error_format, Some(&correlation_id))
+            }
+        }
+    };
+    // Validated or generated above, so always a legal header value.
+    if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+        response.headers_mut().insert(CORRELATION_ID_HEADER, value);
+    }
+    response
+}
+
+/// Denials that `soft_deny` may downgrade: the caller was authenticated and the
+/// Datalog policy said no. Token, signature, replay and rate-limit failures are
+/// never softened, and neither is the lockdown `Deny`, which runs before any token
+/// is verified.
+fn is_policy_denial(err: &VacError) -> bool {
+    matches!(err, VacError::PolicyViolation(_))
+}
+
+/// `200 OK` with `{"vac_decision": "deny", ...}`; the request was not forwarded.
+fn soft_deny_response(err: &VacError, correlation_id: &str) -> Response {
+    use axum::response::IntoResponse;
+
+    let body = serde_json::json!({
+        "vac_decision": "deny",
+        "reason": err.code(),
+        "message": err.to_string(),
+        "correlation_id": correlation_id,
+    });
+    (
+        axum::http::StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Take the caller's X-Correlation-ID if it is valid, otherwise generate a fresh one.
+///
+/// With `required` (`require_correlation_id`), a missing or invalid ID is a 400 instead.
+fn resolve_correlation_id(
+    headers: &HeaderMap,
+    generator: &dyn CorrelationIdGenerator,
+    required: bool,
+) -> Result<String, VacError> {
+    use tracing::warn;
+
+    let supplied = headers.get(CORRELATION_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| {
+            // Validate correlation ID if provided
+            if !crate::security::validate_correlation_id(s) {
+                warn!(
+                    correlation_id = s,
+                    "Invalid correlation ID format, {}",
+                    if required { "rejecting request" } else { "generating new one" }
+                );
+                None
+            } else {
+                Some(s.to_string())
+            }
+        });
+    match supplied {
+        Some(cid) => Ok(cid),
+        None if required => Err(VacError::BadRequest(
+            "a valid X-Correlation-ID header is required".to_string(),
+        )),
+        None => Ok(generator.generate()),
+    }
+}
+
+/// Take a token from the `key` rate limit bucket (a sidecar ID or a hex token ID), or
+/// deny with 429 and the wait until the bucket has one again.
+async fn check_rate_limit(state: &SharedState, key: &str) -> Result<(), VacError> {
+    use tracing::warn;
+
+    let backend = state.read().await.rate_limit_backend.clone();
+    if backend.check(key).await {
+        return Ok(());
+    }
+    warn!(
+        policy_decision = "deny",
+        reason = "rate_limit_exceeded",
+        rate_limit_bucket = %key,
+        "Request denied: Rate limit exceeded"
+    );
+    // Whole seconds, rounded up so a client waiting that long finds a token.
+    let wait = backend.time_until_token(key).await;
+    Err(VacError::RateLimited {
+        retry_after_secs: (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1),
+    })
+}
+
+async fn guard_request<S>(
+    state: SharedState,
+    inner: S,
+    req: Request,
+    correlation_id: String,
+    queue_duration: Duration,
+) -> Result<Response, VacError>
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+{
+    use tracing::{error, info, warn};
+    
+    let (mut parts, body) = req.into_parts();
+    
+    // Extract method and path early for logging
+    let method_str = parts.method.to_string();
+    
+    // Request targets other than origin-form: `OPTIONS *` has no path to authorize, and
+    // an absolute-form URI is reduced to its path and query (the upstream authority is
+    // configured, never taken from the request).
+    let (trailing_slash, options_asterisk, protocol) = {
+        let s = state.read().await;
+        (s.path_trailing_slash, s.options_asterisk, s.protocol)
+    };
+    if parts.method == Method::OPTIONS && parts.uri.path() == "*" {
+        return match options_asterisk {
+            OptionsAsterisk::Respond => {
+                info!("Answered OPTIONS * locally");
+                Ok(Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .map_err(|e| VacError::InternalError(format!("Failed to build response: {}", e)))?)
+            }
+            OptionsAsterisk::Reject => {
+                warn!("Request denied: OPTIONS * is not supported");
+                Err(VacError::BadRequest("OPTIONS * is not supported".to_string()))
+            }
+        };
+    }
+    if let Some(uri) = origin_form(&parts.uri) {
+        parts.uri = uri;
+    }
+
+    // Normalize the path once so the `operation` fact and the upstream request agree.
+    let path = match normalize_trailing_slash(parts.uri.path(), trailing_slash) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(
+                path = parts.uri.path(),
+                "Request denied: Ambiguous trailing slash in path"
+            );
+            return Err(e);
+        }
+    };
+    if path != parts.uri.path() {
+        let path_and_query = match parts.uri.query() {
+            Some(q) => format!("{}?{}", path, q),
+            None => path.clone(),
+        };
+        parts.uri = path_and_query
+            .parse()
+            .map_err(|e| VacError::InternalError(format!("Failed to rewrite request path: {}", e)))?;
+    }
+
+    // With `protocol = grpc` the upstream only speaks gRPC, and receipts depend on the
+    // call's grpc-status, so anything else is turned away before it is authorized.
+    if protocol == UpstreamProtocol::Grpc && !is_grpc_call(&parts.method, &path, &parts.headers) {
+        warn!(
+            path = %path,
+            "Request denied: protocol = grpc accepts only gRPC calls"
+        );
+        return Err(VacError::BadRequest(
+            "only gRPC calls (POST /<service>/<method>, content-type application/grpc) are accepted".to_string(),
+        ));
+    }
+    
+    // Phase 4.8: Replay attack mitigation check (with `replay_key_request_hash`, once the
+    // body has been read instead)
+    let replay_key_request_hash = state.read().await.replay_key_request_hash;
+    if !replay_key_request_hash {
+        let s = state.read().await;
+        // With `replay_key_includes_operation`, a correlation ID is bound to the operation
+        // it was first used for rather than blocked everywhere.
+        let replay_key = if s.replay_key_includes_operation {
+            format!("{} {} {}", correlation_id, method_str, path)
+        } else {
+            correlation_id.clone()
+        };
+        match s.replay_cache.check_and_insert(&replay_key) {
+            Ok(true) => {
+                // New correlation ID - allowed
+            }
+            Ok(false) => {
+                // Replay detected - reject
+                warn!(
+                    policy_decision = "deny",
+                    reason = "replay_attack_detected",
+                    correlation_id = %correlation_id,
+                    "Request denied: Correlation ID already used (potential replay attack)"
+                );
+                return Err(VacError::Replay);
+            }
+            Err(_) => {
+                // Replay mitigation disabled - allow
+            }
+        }
+    }
+    
+    // Per-correlation-ID step cap; the step only counts if a receipt is minted below.
+    let step = match state.read().await.step_limiter.try_reserve(&correlation_id) {
+        Ok(step) => step,
+        Err(e) => {
+            warn!(
+                policy_decision = "deny",
+                reason = "step_limit_exceeded",
+                correlation_id = %correlation_id,
+                "Request denied: max_steps_per_correlation reached"
+            );
+            return Err(e);
+        }
+    };
+    
+    // Validate headers (Phase 4.7: Input validation)
+    for (name, value) in parts.headers.iter() {
+        let name_str = name.as_str();
+        let value_str = match value.to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                warn!(
+                    header_name = name_str,
+                    "Invalid header value encoding (non-UTF-8), rejecting request"
+                );
+                return Err(VacError::InvalidTokenFormat);
+            }
+        };
+        
+        if !crate::security::validate_header_name(name_str) {
+            warn!(
+                header_name = name_str,
+                "Invalid header name, rejecting request"
+            );
+            return Err(VacError::InvalidTokenFormat);
+        }
+        
+        if !crate::security::validate_header_value(value_str) {
+            warn!(
+                header_name = name_str,
+                header_value_length = value_str.len(),
+                "Invalid header value (too long or contains control chars), rejecting request"
+            );
+            return Err(VacError::InvalidTokenFormat);
+        }
+    }
+    
+    // Client address: `X-Forwarded-*` only count when the socket peer is a trusted proxy;
+    // from anyone else they are spoofable and are dropped rather than passed on.
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let client = ClientAddr::resolve(peer, &parts.headers, &state.read().await.trusted_proxies);
+    if !client.forwarded_trusted {
+        strip_forwarded_headers(&mut parts.headers);
+    }
+
+    // Create request span with structured fields for observability
+    let span = tracing::span!(
+        tracing::Level::INFO,
+        "request",
+        correlation_id = %correlation_id,
+        method = %method_str,
+        path = %path,
+        client_ip = ?client.ip,
+        client_proto = %client.proto,
+        queue_duration_ms = queue_duration.as_secs_f64() * 1000.0
+    );
+    let _guard = span.enter();
+    
+    // Phase 4.7: Rate limiting check (before processing request). With `rate_limit_key =
+    // token`, a request with a bearer token is checked against its own bucket once the
+    // root token has verified instead, so forged tokens cannot each get a fresh bucket.
+    let (sidecar_id, rate_limit_key) = {
+        let s = state.read().await;
+        (s.sidecar_id.clone(), s.rate_limit_key)
+    };
+    let has_bearer_token = parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| parse_bearer_token(h).is_ok());
+    if rate_limit_key == RateLimitKey::Sidecar || !has_bearer_token {
+        check_rate_limit(&state, &sidecar_id).await?;
+    }
+    
+    // Check lockdown mode (before processing request)
+    let lockdown_mode = {
+        let s = state.read().await;
+        s.lockdown_mode
+    };
+    
+    if lockdown_mode {
+        // In lockdown mode, only allow read-only requests
+        if !state.read().await.is_read_only(&method_str) {
+            warn!(
+                policy_decision = "deny",
+                reason = "lockdown_mode_active",
+                "Request denied: Lockdown mode active, only read-only requests allowed"
+            );
+            return Err(VacError::Deny); // Reject non-read-only requests
+        }
+        info!("Request allowed in lockdown mode (read-only)");
+    }
+
+    // A. Extract Token. With more than one Authorization header, the one verified here
+    // and the one another hop acts on could differ, so the request is malformed.
+    if parts.headers.get_all(header::AUTHORIZATION).iter().nth(1).is_some() {
+        warn!(
+            policy_decision = "deny",
+            reason = "duplicate_authorization",
+            "Request denied: Multiple Authorization headers"
+        );
+        return Err(VacError::BadRequest("multiple Authorization headers".to_string()));
+    }
+    let token_str = parts.headers.get(header::AUTHORIZATION)
+        .ok_or(VacError::MissingToken)
+        .and_then(|h| h.to_str().map_err(|_| VacError::InvalidTokenFormat))
+        .and_then(parse_bearer_token)
+        .map(|t| t.to_string())
+        .inspect_err(|e| match e {
+            VacError::MissingToken => warn!(
+                policy_decision = "deny",
+                reason = "missing_token",
+                "Request denied: Missing Authorization token"
+            ),
+            _ => warn!(
+                policy_decision = "deny",
+                reason = "malformed_authorization",
+                "Request denied: Malformed Bearer Authorization header"
+            ),
+        })?;
+
+    // C. Verify Root Biscuit (with revocation check)
+    let (root_keys, root_token_verifier, session_key_pub, peer_session_keys, revocation_filter, max_token_bytes, strict_token_shape, accept_compact_receipts, receipt_expiry_secs, clock_skew_grace_secs) = {
+        let s = state.read().await;
+        (
+            s.root_keys(),
+            s.root_token_verifier.clone(),
+            s.session_key.public(), 
+            if s.accept_peer_receipts { s.session_key_set.clone() } else { SessionKeySet::default() },
+            s.revocation_filter.clone(),
+            s.max_token_bytes,
+            s.strict_token_shape,
+            s.accept_compact_receipts,
+            s.receipt_expiry_secs,
+            s.clock_skew_grace_secs,
+        )
+    };
+
+    // Receipts from the compact `X-VAC-Receipt-Bin` transport, in standard base64 form so
+    // they take the same size check and verification path as `X-VAC-Receipt` tokens.
+    let compact_receipts = if accept_compact_receipts {
+        compact_receipt_tokens(&parts.headers).inspect_err(|_| {
+            warn!(
+                receipt_error = "invalid_format",
+                "Receipt verification failed: Invalid X-VAC-Receipt-Bin encoding"
+            );
+        })?
+    } else {
+        Vec::new()
+    };
+    
+    // A.1 Size-check every token before any base64 decode / Biscuit parse
+    let oversized = std::iter::once(token_str.as_str())
+        .chain(parts.headers.get_all(DELEGATION_HEADER).iter().filter_map(|h| h.to_str().ok()))
+        .chain(receipt_tokens(&parts.headers).unwrap_or_default())
+        .chain(compact_receipts.iter().map(String::as_str))
+        .find(|t| check_token_size(t, max_token_bytes).is_err());
+    if let Some(t) = oversized {
+        warn!(
+            policy_decision = "deny",
+            reason = "token_too_large",
+            token_bytes = t.len(),
+            max_token_bytes = max_token_bytes,
+            "Request denied: Token exceeds max_token_bytes"
+        );
+        return Err(VacError::InvalidTokenFormat);
+    }
+    
+    // With an external verifier, the key it vouches for also checks the delegation chain.
+    let (root_biscuit, user_root_key) = verify_root_biscuit_with_verifier(
+        &token_str,
+        &root_keys,
+        Some(&revocation_filter),
+        root_token_verifier.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        match &e {
+            VacError::InvalidSignature => {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "invalid_biscuit_signature",
+                    llm_readable_error = true,
+                    "Root Biscuit verification failed: Invalid signature - Agent should verify token is signed with correct root key"
+                );
+            }
+            VacError::TokenRevoked => {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "token_revoked",
+                    "Request denied: Root Biscuit has been revoked"
+                );
+            }
+            _ => {
+                error!(
+                    error = %e,
+                    "Root Biscuit verification error"
+                );
+            }
+        }
+        e
+    })?;
+    
+    info!("Root Biscuit verified successfully");
+
+    // Phase 4.7, per token (`rate_limit_key = token`): the verified root token's own bucket
+    if rate_limit_key == RateLimitKey::Token {
+        check_rate_limit(&state, &hex::encode(extract_token_id(&token_str)?)).await?;
+    }
+
+    // C.0 Activation time: `not_before` in the authority block
+    if let Err(e) = check_not_before(&root_biscuit, SystemTime::now()) {
+        let reason = match e {
+            VacError::TokenNotYetValid => "token_not_yet_valid",
+            _ => "malformed_not_before",
+        };
+        warn!(
+            policy_decision = "deny",
+            reason = reason,
+            "Request denied: Root Biscuit not_before check failed"
+        );
+        return Err(e);
+    }
+
+    // C.0.1 Optional structural check: only the blocks and predicates the issuer writes
+    if strict_token_shape {
+        if let Some(violation) = token_shape_violation(&root_biscuit) {
+            warn!(
+                policy_decision = "deny",
+                reason = "unexpected_token_shape",
+                violation = %violation,
+                "Request denied: Root Biscuit shape not allowed under strict_token_shape"
+            );
+            return Err(VacError::InvalidTokenFormat);
+        }
+    }
+
+    // C.0.2 Optional flow binding: the first token to use a correlation ID owns it
+    {
+        let s = state.read().await;
+        if s.bind_correlation_to_token {
+            let token_id = extract_token_id(&token_str)?;
+            if let Err(e) = s.correlation_bindings.check_and_bind(&correlation_id, &token_id) {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "correlation_bound_to_other_token",
+                    correlation_id = %correlation_id,
+                    "Request denied: Correlation ID already in use by a different token"
+                );
+                return Err(e);
+            }
+        }
+    }
+
+    // C.1 Verify delegation chain (Phase 4.3)
+    // If present, one `X-VAC-Delegation` header per hop (root → ... → current).
+    let delegation_chain_b64: Vec<String> = parts
+        .headers
+        .get_all(DELEGATION_HEADER)
+        .iter()
+        .filter_map(|h| h.to_str().ok().map(|s| s.to_string()))
+        .collect();
+    
+    if !delegation_chain_b64.is_empty() {
+        info!(
+            delegation_chain_length = delegation_chain_b64.len(),
+            "Verifying delegation chain"
+        );
+    }
+    
+    let (delegation_chain_ids_hex, final_depth) =
+        verify_delegation_chain(&user_root_key, &delegation_chain_b64, &token_str)
+            .map_err(|e| {
+                warn!(
+                    delegation_error = %e,
+                    delegation_chain_length = delegation_chain_b64.len(),
+                    "Delegation chain verification failed"
+                );
+                e
+            })?;
+    
+    if !delegation_chain_ids_hex.is_empty() {
+        info!(
+            delegation_chain_length = delegation_chain_ids_hex.len(),
+            delegation_depth = final_depth,
+            "Delegation chain verified successfully"
+        );
+    }
+
+    // Reserve the body's bytes in the global budget (`max_total_body_bytes`) before
+    // buffering it; the reservation is held until the inner service has answered.
+    let body_budget = state.read().await.body_budget.clone();
+    let body_reservation = BodyBudget::reservation_for(HttpBody::size_hint(&body).upper());
+    let _body_permit = body_budget.try_acquire(body_reservation).inspect_err(|_| {
+        warn!(
+            body_reservation,
+            reason = "body_budget_exhausted",
+            "Request shed: body memory budget exhausted"
+        );
+    })?;
+
+    // Read request body bytes now (we may need it for adapter fact extraction).
+    // Note: we rebuild the request body afterwards so proxy forwarding stays identical.
+    // Phase 4.7: Use security module constant for body size limit
+    let mut body_bytes = axum::body::to_bytes(body, crate::security::MAX_REQUEST_BODY_SIZE)
+        .await
+        .map_err(|e| {
+            // Check if error is due to body size limit
+            let error_msg = e.to_string();
+            if error_msg.contains("too large") || error_msg.contains("limit") {
+                warn!(
+                    body_size_limit = crate::security::MAX_REQUEST_BODY_SIZE,
+                    "Request body exceeds size limit"
+                );
+                VacError::InvalidTokenFormat // Use InvalidTokenFormat for size violations
+            } else {
+                VacError::InternalError(format!("Failed to read request body: {}", e))
+            }
+        })?;
+    
+    // Phase 4.7: Validate body size
+    if !crate::security::validate_body_size(body_bytes.len()) {
+        warn!(
+            body_size = body_bytes.len(),
+            body_size_limit = crate::security::MAX_REQUEST_BODY_SIZE,
+            "Request body size validation failed"
+        );
+        return Err(VacError::InvalidTokenFormat);
+    }
+
+    // A body that does not match its declared Content-Length is a request-smuggling
+    // vector: the upstream could frame the forwarded request differently than we did.
+    if !crate::security::validate_content_length(&parts.headers, body_bytes.len()) {
+        warn!(
+            body_size = body_bytes.len(),
+            content_length = ?parts.headers.get(header::CONTENT_LENGTH),
+            "Request body does not match declared Content-Length"
+        );
+        return Err(VacError::BadRequest(
+            "Content-Length does not match request body".to_string(),
+        ));
+    }
+
+    // Optional canonical JSON (`canonicalize_json_body`): adapters see sorted keys and no
+    // insignificant whitespace, so equivalent bodies yield identical facts. Bodies that
+    // are not valid JSON are left alone.
+    let mut adapter_body = body_bytes.clone();
+    let (canonicalize_json_body, forward_canonical_json_body) = {
+        let s = state.read().await;
+        (s.canonicalize_json_body, s.forward_canonical_json_body)
+    };
+    if canonicalize_json_body && is_json_content_type(&parts.headers) {
+        if let Some(canonical) = canonicalize_json(&body_bytes) {
+            adapter_body = canonical.into();
+            if forward_canonical_json_body {
+                body_bytes = adapter_body.clone();
+                if parts.headers.contains_key(header::CONTENT_LENGTH) {
+                    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_bytes.len()));
+                }
+            }
+        }
+    }
+
+    // Phase 4.8 with `replay_key_request_hash`: the same request under any correlation ID
+    // is a replay. The body is hashed as adapters see it, so with `canonicalize_json_body`
+    // reformatting the JSON does not make a new request.
+    if replay_key_request_hash {
+        let path_and_query = parts.uri.path_and_query().map_or(path.as_str(), |pq| pq.as_str());
+        let replay_key = request_replay_key(&method_str, path_and_query, &adapter_body, &extract_token_id(&token_str)?);
+        if let Ok(false) = state.read().await.replay_cache.check_and_insert(&replay_key) {
+            warn!(
+                policy_decision = "deny",
+                reason = "replay_attack_detected",
+                correlation_id = %correlation_id,
+                "Request denied: Identical request already seen (potential replay attack)"
+            );
+            return Err(VacError::Replay);
+        }
+    }
+
+    // D. Build Authorizer 
+    // We use Authorizer::new() to guarantee a clean slate.
+    let mut authorizer = Authorizer::new();
+    authorizer.add_token(&root_biscuit)
+        .map_err(|e| VacError::InternalError(format!("Failed to add root token: {:?}", e)))?;
+
+    // E. Verify & Add Receipt(s) 
+    let receipt_strs = match receipt_tokens(&parts.headers) {
+        Ok(mut tokens) => {
+            tokens.extend(compact_receipts.iter().map(String::as_str));
+            tokens
+        }
+        Err(e) => {
+            warn!(
+                receipt_error = "invalid_format",
+                "Receipt verification failed: Invalid token format"
+            );
+            return Err(e);
+        }
+    };
+    let receipt_count = receipt_strs.len();
+    // With `X-VAC-Complete-Flow`, a completion receipt covering the presented receipts
+    // and this step is minted alongside this step's receipt.
+    let complete_flow = parts.headers.contains_key(COMPLETE_FLOW_HEADER);
+    if complete_flow && receipt_count >= MAX_COMPLETION_STEPS {
+        return Err(VacError::BadRequest(format!(
+            "a completion receipt covers at most {} steps",
+            MAX_COMPLETION_STEPS
+        )));
+    }
+    if receipt_count > 0 {
+        info!(
+            receipt_count = receipt_count,
+            "Verifying {} receipt(s)",
+            receipt_count
+        );
+    }
+    
+    let mut verified_receipts = 0usize;
+    let mut flow_steps = Vec::new();
+    for receipt_str in receipt_strs {
+        let receipt = verify_receipt_biscuit_with_keys(receipt_str, &session_key_pub, &peer_session_keys)
+            .map_err(|e| {
+                warn!(
+                    receipt_error = "invalid_signature",
+                    receipt_error_detail = %e,
+                    "Receipt verification failed: Invalid signature"
+                );
+                e
+            })?;
+        
+        let receipt_info = extract_receipt_info(&receipt)
+            .map_err(|e| {
+                warn!(
+                    receipt_error = "extraction_failed",
+                    receipt_error_detail = %e,
+                    "Receipt verification failed: Failed to extract receipt info"
+                );
+                e
+            })?;
+        
+        verify_receipt_expiry_within(receipt_info.timestamp, receipt_expiry_secs, clock_skew_grace_secs)
+            .map_err(|e| {
+                warn!(
+                    receipt_error = "expired",
+                    receipt_timestamp = receipt_info.timestamp,
+                    receipt_operation = %receipt_info.operation,
+                    receipt_minted_by = receipt_info.minted_by.as_deref().unwrap_or("unknown"),
+                    "Receipt verification failed: Receipt expired"
+                );
+                e
+            })?;
+        
+        verify_correlation_id_match(&receipt_info.correlation_id, &correlation_id)
+            .map_err(|e| {
+                warn!(
+                    receipt_error = "correlation_id_mismatch",
+                    receipt_correlation_id = %receipt_info.correlation_id,
+                    request_correlation_id = %correlation_id,
+                    receipt_operation = %receipt_info.operation,
+                    receipt_minted_by = receipt_info.minted_by.as_deref().unwrap_or("unknown"),
+                    "Receipt verification failed: Correlation ID mismatch"
+                );
+                e
+            })?;
+        
+        info!(
+            receipt_operation = %receipt_info.operation,
+            receipt_correlation_id = %receipt_info.correlation_id,
+            receipt_timestamp = receipt_info.timestamp,
+            receipt_minted_by = receipt_info.minted_by.as_deref().unwrap_or("unknown"),
+            "Receipt verified successfully"
+        );
+        
+        // FIX: Pass the extracted info, not the token
+        add_receipt_facts(&mut authorizer, &receipt_info)?;
+        verified_receipts += 1;
+        // Kept for the flow graph check and the completion receipt
+        flow_steps.push(receipt_info);
+    }
+    add_receipt_count_fact(&mut authorizer, verified_receipts)?;
+
+    // F. Add Context Facts (After all tokens are loaded)
+    let method_str = parts.method.to_string();
+    let path = parts.uri.path().to_string();
+    add_context_facts(&mut authorizer, &method_str, &path, &correlation_id)?;
+    add_idempotency_key_fact(&mut authorizer, has_valid_idempotency_key(&parts.headers))?;
+
+    // Declarative flow graph (`[flow]`): this step must follow the presented receipts
+    let flow_graph = state.read().await.flow_graph.clone();
+    if let Some(flow_graph) = flow_graph {
+        flow_graph
+            .check(&format!("{} {}", method_str, path), &flow_steps)
+            .inspect_err(|e| {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "flow_violation",
+                    error = %e,
+                    "Request denied: operation is not a valid next step of the flow"
+                );
+            })?;
+    }
+
+    // F.0 Delegation chain facts (Phase 4.3)
+    // Inject as facts so policies can audit/limit based on chain.
+    for id_hex in &delegation_chain_ids_hex {
+        authorizer
+            .add_fact(biscuit_auth::builder::Fact::new(
+                "delegation_chain".to_string(),
+                vec![biscuit_auth::builder::string(id_hex)],
+            ))
+            .map_err(|e| VacError::InternalError(format!("Failed to add delegation_chain fact: {:?}", e)))?;
+    }
+
+    // F.1 Optional WASM adapter facts (pinned by hash in the Root Biscuit), one run per
+    // adapter in `extract_adapter_hash` order
+    let adapter_hashes = extract_adapter_hash(&mut authorizer)?;
+    if !adapter_hashes.is_empty() {
+        let (registry, adapter_concurrency, reserved_facts, require_adapter_facts, context) = {
+            let s = state.read().await;
+            (
+                s.adapter_registry.clone(),
+                s.adapter_concurrency.clone(),
+                s.adapter_reserved_facts,
+                s.require_adapter_facts,
+                AdapterContext::from_request(&method_str, &path, &parts.headers, &s.adapter_context_headers),
+            )
+        };
+
+        // Fail closed before running any of them if one is missing.
+        if let Some(missing) = adapter_hashes.iter().find(|hash| !registry.is_loaded(hash)) {
+            warn!(
+                policy_decision = "deny",
+                reason = "adapter_not_loaded",
+                adapter_hash = %missing,
+                "Request denied: pinned adapter is not loaded"
+            );
+            return Err(VacError::ConfigError(format!("Adapter not found: {}", missing)));
+        }
+
+        // Held until the last adapter run finishes (or times out).
+        let _permit = match adapter_concurrency.try_acquire(&correlation_id) {
+            Ok(permit) => permit,
+            Err(e) => {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "adapter_concurrency_exceeded",
+                    adapter_hash = %adapter_hashes.join(","),
+                    "Request denied: too many concurrent adapter runs for this correlation ID"
+                );
+                return Err(e);
+            }
+        };
+        for adapter_hash in &adapter_hashes {
+            let adapter_facts = extract_facts_from_request(adapter_hash, &adapter_body, &context, &registry).await?;
+            if adapter_facts.is_empty() && require_adapter_facts {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "adapter_no_facts",
+                    adapter_hash = %adapter_hash,
+                    "Request denied: pinned adapter extracted no facts from the body"
+                );
+                return Err(VacError::AdapterNoFacts);
+            }
+            let adapter_facts = screen_adapter_facts(adapter_facts, reserved_facts).inspect_err(|e| {
+                warn!(
+                    policy_decision = "deny",
+                    reason = "adapter_reserved_fact",
+                    adapter_hash = %adapter_hash,
+                    error = %e,
+                    "Request denied: adapter returned a reserved fact"
+                );
+            })?;
+            for af in adapter_facts {
+                let fact = af.to_biscuit_fact()?;
+                authorizer
+                    .add_fact(fact)
+                    .map_err(|e| VacError::InternalError(format!("Failed to add adapter fact: {:?}", e)))?;
+            }
+        }
+    }
+
+    // F.2 Operator policy (`policy_file`), written against all of the facts above
+    let (policy, policy_eval_timeout) = {
+        let s = state.read().await;
+        (s.policy.clone(), s.policy_eval_timeout)
+    };
+    if let Some(policy) = &policy {
+        add_operator_policy(&mut authorizer, policy.source())?;
+    }
+
+    // G. Run Policy (`vac_sidecar::policy::world=trace` logs the facts it runs on)
+    trace_authorizer_world(&authorizer, &correlation_id);
+    let mut authorizer = evaluate_policy_with_timeout(authorizer, policy_eval_timeout)
+        .await
+        .map_err(|e| {
+            // Log LLM-readable error messages for agent debugging
+            match &e {
+                VacError::PolicyViolation(msg) => {
+                    warn!(
+                        policy_decision = "deny",
+                        policy_reason = %msg,
+                        llm_readable_error = true,
+                        "Policy violation: {} - Agent should review required facts/operations",
+                        msg
+                    );
+                }
+                _ => {
+                    error!(error = %e, "Policy evaluation error");
+                }
+            }
+            e
+        })?;
+
+    info!("Request authorized, passing to inner service");
+
+    // H. Call the inner service (the upstream proxy in the sidecar binary) with the
+    // verified context attached. The body was already read and validated, so it is
+    // handed over from memory.
+    let context = VacContext {
+        correlation_id: correlation_id.clone(),
+        delegation_chain: delegation_chain_ids_hex.clone(),
+        depth: extract_depth(&mut authorizer)?,
+        delegation_depth: final_depth,
+    };
+    let mut req = Request::from_parts(parts, Body::from(body_bytes));
+    req.extensions_mut().insert(context.clone());
+    req.extensions_mut().insert(client);
+    let response = match inner.oneshot(req).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+
+    // I. Mint Receipt (skipped for methods excluded by `mint_receipts_for_methods`)
+    let mint_receipt = state.read().await.mints_receipt_for(&method_str);
+    if response.status().is_success() && !mint_receipt {
+        tracing::debug!(method = %method_str, "Receipt minting skipped for method");
+    }
+    if response.status().is_success() && mint_receipt {
+        let state_read = state.read().await;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let operation = format!("{} {}", method_str, path);
+
+        // Depth for logging (if available)
+        let receipt_depth = context.depth.unwrap_or(0i64);
+
+        // A batch endpoint (`[[batch_receipts]]`) gets one receipt per sub-operation it
+        // performs, each in its own X-VAC-Receipt header, instead of one for itself.
+        let operations = state_read
+            .batch_receipts
+            .get(&operation)
+            .cloned()
+            .unwrap_or_else(|| vec![operation.clone()]);
+        let mut receipt_headers = HeaderMap::new();
+        let mut events = Vec::with_capacity(operations.len());
+        if complete_flow {
+            flow_steps.sort_by_key(|step| step.timestamp);
+        }
+        for operation in operations {
+            let receipt_biscuit = crate::receipt::mint_receipt(
+                &state_read.session_key,
+                &NewReceipt {
+                    operation: &operation,
+                    correlation_id: &correlation_id,
+                    timestamp: timestamp as i64,
+                    delegation_chain: &delegation_chain_ids_hex,
+                    depth: context.depth,
+                    sidecar_id: &state_read.sidecar_id,
+                },
+            )?;
+
+            let receipt_b64 = receipt_biscuit.to_base64()
+                .map_err(|e| VacError::InternalError(format!("Encode error: {:?}", e)))?;
+            receipt_headers.append(
+                "X-VAC-Receipt", 
+                HeaderValue::from_str(&receipt_b64)
+                    .map_err(|e| VacError::InternalError(format!("Failed to create header: {}", e)))?
+            );
+            if complete_flow {
+                flow_steps.push(ReceiptInfo {
+                    operation: operation.clone(),
+                    correlation_id: correlation_id.clone(),
+                    timestamp: timestamp as i64,
+                    minted_by: Some(state_read.sidecar_id.clone()),
+                });
+            }
+            events.push(ReceiptEvent {
+                receipt: receipt_b64,
+                operation,
+                correlation_id: correlation_id.clone(),
+                timestamp: timestamp as i64,
+                sidecar_id: state_read.sidecar_id.clone(),
+                depth: context.depth,
+                delegation_chain: delegation_chain_ids_hex.clone(),
+            });
+        }
+
+        if complete_flow {
+            let completion_b64 = mint_completion_receipt(
+                &state_read.session_key,
+                &correlation_id,
+                &flow_steps,
+                &state_read.sidecar_id,
+            )?
+            .to_base64()
+            .map_err(|e| VacError::InternalError(format!("Encode error: {:?}", e)))?;
+            info!(
+                receipt_correlation_id = %correlation_id,
+                flow_steps = flow_steps.len(),
+                "Completion receipt minted"
+            );
+            receipt_headers.insert(
+                COMPLETION_RECEIPT_HEADER,
+                HeaderValue::from_str(&completion_b64)
+                    .map_err(|e| VacError::InternalError(format!("Failed to create header: {}", e)))?
+            );
+        }
+
+        // The step counts, and the receipts are reported, once they reach the client: right
+        // away over HTTP, only after `grpc-status: 0` for a gRPC call.
+        let metrics = state_read.metrics.clone();
+        let webhook = state_read.receipt_webhook.clone();
+        let delivered = move || {
+            if let Some(step) = step {
+                step.commit();
+            }
+            for event in events {
+                metrics.record_receipt_minted();
+                info!(
+                    receipt_operation = %event.operation,
+                    receipt_correlation_id = %event.correlation_id,
+                    receipt_timestamp = timestamp,
+                    receipt_depth = receipt_depth,
+                    delegation_chain_length = event.delegation_chain.len(),
+                    "Receipt minted successfully"
+                );
+                if let Some(webhook) = &webhook {
+                    webhook.emit(event);
+                }
+            }
+        };
+
+        if protocol == UpstreamProtocol::Grpc {
+            return Ok(deliver_on_grpc_ok(response, receipt_headers, delivered));
+        }
+        delivered();
+        let (mut parts, body) = response.into_parts();
+        parts.headers.extend(receipt_headers);
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,32 @@
+//! Liveness and readiness probes (`/_vac/healthz`, `/_vac/readyz`)
+//!
+//! Served by the sidecar itself ahead of the guarded catch-all route, so probes need no
+//! token and skip rate limiting and replay checks. The `/_vac/` prefix keeps them from
+//! shadowing upstream paths such as `/healthz`, which are still proxied as usual.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+use crate::state::SharedState;
+
+pub const HEALTHZ_PATH: &str = "/_vac/healthz";
+pub const READYZ_PATH: &str = "/_vac/readyz";
+
+/// Liveness: 200 whenever the process can answer.
+pub async fn healthz_handler() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+/// Readiness: 200 while the heartbeat is healthy and the sidecar is not in lockdown,
+/// 503 otherwise.
+pub async fn readyz_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    let state = state.read().await;
+    if state.lockdown_mode {
+        (StatusCode::SERVICE_UNAVAILABLE, "lockdown")
+    } else if !state.heartbeat_healthy {
+        (StatusCode::SERVICE_UNAVAILABLE, "heartbeat unhealthy")
+    } else {
+        (StatusCode::OK, "ready")
+    }
+}
@@ -1,13 +1,71 @@
+use crate::control_plane_client::ControlPlaneClient;
 use crate::error::VacError;
 use crate::state::SharedState;
+use crate::revocation::RevocationSource;
+use crate::session_keys::refresh_session_keys;
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, SystemTime};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 use base64::{Engine as _, engine::general_purpose};
+use rand::Rng;
 
 /// Maximum heartbeat failures before entering lockdown mode
 const MAX_HEARTBEAT_FAILURES: u32 = 3;
 
+/// Default consecutive successful heartbeats before a heartbeat-failure lockdown is lifted
+pub const DEFAULT_LOCKDOWN_RECOVERY_SUCCESSES: u32 = 3;
+
+/// Default cap on `revoked_token_ids` (and on `unrevoked_token_ids`) entries accepted from
+/// one heartbeat response
+pub const DEFAULT_MAX_REVOCATION_LIST_SIZE: usize = 100_000;
+
+/// Response body budget per revocation entry: a 32-byte ID as a JSON number array, every
+/// byte at its longest (`255,`) plus the brackets, i.e. 130 bytes. Whitespace beyond that
+/// comes out of [`HEARTBEAT_BASE_BODY_BYTES`].
+const REVOCATION_ENTRY_JSON_BYTES: usize = 32 * "255,".len() + "[]".len();
+/// Response body budget for everything besides the revocation list
+const HEARTBEAT_BASE_BODY_BYTES: usize = 64 * 1024;
+
+/// Upper bound on the delay between heartbeats while the Control Plane keeps failing
+/// (or the heartbeat interval, if longer)
+pub const HEARTBEAT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Largest fraction of a retry delay taken off at random
+const HEARTBEAT_BACKOFF_JITTER: f64 = 0.25;
+
+/// First delay before a restarted heartbeat task runs (`heartbeat_exit_action = "restart"`)
+pub const HEARTBEAT_RESTART_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound on the delay between heartbeat task restarts
+const HEARTBEAT_RESTART_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What the supervisor does when the heartbeat task exits or panics.
+///
+/// Either way the sidecar is marked unhealthy first: without heartbeats its revocation
+/// data goes stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeartbeatExitAction {
+    /// Restart the task with exponential backoff.
+    #[default]
+    Restart,
+    /// Enter lockdown and leave the task stopped.
+    Lockdown,
+}
+
+impl std::str::FromStr for HeartbeatExitAction {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "restart" => Ok(HeartbeatExitAction::Restart),
+            "lockdown" => Ok(HeartbeatExitAction::Lockdown),
+            other => Err(VacError::ConfigError(format!(
+                "heartbeat_exit_action must be one of restart, lockdown (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
 /// Heartbeat request payload
 #[derive(Debug, Serialize)]
 struct HeartbeatRequest {
@@ -16,49 +74,228 @@ struct HeartbeatRequest {
     timestamp: u64,
 }
 
+/// Going-away notice sent when the sidecar shuts down
+#[derive(Debug, Serialize)]
+struct GoingAwayRequest {
+    sidecar_id: String,
+}
+
 /// Heartbeat response payload
 #[derive(Debug, Deserialize)]
 struct HeartbeatResponse {
     healthy: bool,
     #[serde(default)]
     revoked_token_ids: Option<Vec<[u8; 32]>>, // List of revoked token IDs (32-byte arrays)
+    #[serde(default)]
+    unrevoked_token_ids: Option<Vec<[u8; 32]>>, // Revocations rescinded since (removed from the filter)
+}
+
+/// Delay before the next heartbeat after `failures` consecutive failed ones.
+///
+/// The interval while heartbeats succeed; after failures it doubles per failure
+/// (interval, 2x, 4x, ...) up to [`HEARTBEAT_MAX_BACKOFF`], less up to a quarter at
+/// random, so sidecars that lost the Control Plane together do not all retry (and
+/// reconnect) in lockstep.
+pub fn heartbeat_retry_delay(interval: Duration, failures: u32) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    let backoff = interval
+        .saturating_mul(1 << (failures - 1).min(16))
+        .min(HEARTBEAT_MAX_BACKOFF.max(interval));
+    backoff.mul_f64(1.0 - rand::thread_rng().gen_range(0.0..HEARTBEAT_BACKOFF_JITTER))
 }
 
 /// Start the heartbeat task
 /// 
-/// This runs in the background and pings the Control Plane every `interval_secs` seconds.
-/// On failure, it increments the failure count. After MAX_HEARTBEAT_FAILURES failures,
-/// it enters lockdown mode.
+/// This runs in the background and pings the Control Plane every `interval_secs` seconds,
+/// trying `control_plane_urls` in order until one answers. Only when none does, it
+/// increments the failure count and backs off ([`heartbeat_retry_delay`]) until the next
+/// success. After MAX_HEARTBEAT_FAILURES consecutive failures, it enters lockdown mode,
+/// and leaves it again after `lockdown_recovery_successes` consecutive successes. When
+/// `shutdown` is cancelled it lets an in-flight heartbeat finish, sends a going-away
+/// notice to the Control Plane, and returns.
 pub async fn start_heartbeat_task(
     state: SharedState,
-    control_plane_url: String,
+    control_plane_urls: Vec<String>,
     interval_secs: u64,
     rotation_interval_secs: u64,
+    shutdown: CancellationToken,
 ) {
     let interval = Duration::from_secs(interval_secs);
-    let mut interval_timer = tokio::time::interval(interval);
-    interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    // The first heartbeat is sent right away.
+    let mut delay = Duration::ZERO;
+    let mut successes: u32 = 0;
     
-    info!("💓 Heartbeat task started (interval: {}s, control plane: {})", interval_secs, control_plane_url);
+    info!(
+        "💓 Heartbeat task started (interval: {}s, control plane: {})",
+        interval_secs,
+        control_plane_urls.join(", ")
+    );
     
     loop {
-        interval_timer.tick().await;
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                if let Err(e) = send_going_away_to_any(&state, &control_plane_urls).await {
+                    warn!("💓 Going-away notice failed: {}", e);
+                }
+                info!("💓 Heartbeat task stopped (shutdown)");
+                break;
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
         
-        match send_heartbeat(&state, &control_plane_url, rotation_interval_secs).await {
-            Ok(should_continue) => {
+        match send_heartbeat_to_any(&state, &control_plane_urls, rotation_interval_secs).await {
+            Ok((should_continue, control_plane_url)) => {
                 if !should_continue {
                     warn!("💓 Control Plane requested shutdown");
                     break;
                 }
+                successes = successes.saturating_add(1);
+                {
+                    let mut s = state.write().await;
+                    if s.lockdown_mode && s.lockdown_recovery_successes > 0 && successes >= s.lockdown_recovery_successes {
+                        s.exit_lockdown();
+                        info!(
+                            "✅ Lockdown mode lifted after {} consecutive successful heartbeats - accepting all requests again",
+                            successes
+                        );
+                    }
+                }
+                if state.read().await.accept_peer_receipts {
+                    // A failed refresh keeps the cached set; it does not count against the heartbeat.
+                    if let Err(e) = refresh_session_keys(&state, control_plane_url).await {
+                        warn!("🔑 Session key set refresh failed: {}", e);
+                    }
+                }
             }
             Err(e) => {
                 error!("💓 Heartbeat failed: {}", e);
+                successes = 0;
                 let count = state.read().await.heartbeat_failure_count;
                 if count >= MAX_HEARTBEAT_FAILURES {
                     error!("🚨 Lockdown mode activated - all non-read-only requests will be rejected");
                 }
             }
         }
+        delay = heartbeat_retry_delay(interval, state.read().await.heartbeat_failure_count);
+        if delay != interval {
+            warn!("💓 Next heartbeat in {:?} (backing off)", delay);
+        }
+    }
+}
+
+/// Send the going-away notice to the first of `control_plane_urls` that accepts it.
+async fn send_going_away_to_any(state: &SharedState, control_plane_urls: &[String]) -> Result<(), VacError> {
+    let mut last_error = VacError::ConfigError("No control plane URL configured".to_string());
+    for control_plane_url in control_plane_urls {
+        match send_going_away(state, control_plane_url).await {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Tell the Control Plane this sidecar is shutting down (`POST /going-away`).
+pub async fn send_going_away(state: &SharedState, control_plane_url: &str) -> Result<(), VacError> {
+    let request = GoingAwayRequest {
+        sidecar_id: state.read().await.sidecar_id.clone(),
+    };
+    let client = state.read().await.control_plane_client.clone();
+    let response = client
+        .post(&format!("{}/going-away", control_plane_url))
+        .json(&request)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| VacError::ProxyError(format!("Going-away request failed: {}", e)))?;
+    client.check_pin(&response)?;
+    if !response.status().is_success() {
+        return Err(VacError::ProxyError(format!(
+            "Going-away returned status: {}",
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+/// Run the heartbeat task under supervision.
+///
+/// The sidecar binary spawns this instead of [`start_heartbeat_task`], so a loop that
+/// ends (e.g. the control plane reported the sidecar unhealthy) or panics is noticed.
+pub async fn supervise_heartbeat_task(
+    state: SharedState,
+    control_plane_urls: Vec<String>,
+    interval_secs: u64,
+    rotation_interval_secs: u64,
+    on_exit: HeartbeatExitAction,
+    shutdown: CancellationToken,
+) {
+    let task_state = state.clone();
+    let task_shutdown = shutdown.clone();
+    supervise_heartbeat(state, on_exit, HEARTBEAT_RESTART_INITIAL_BACKOFF, shutdown, move || {
+        start_heartbeat_task(
+            task_state.clone(),
+            control_plane_urls.clone(),
+            interval_secs,
+            rotation_interval_secs,
+            task_shutdown.clone(),
+        )
+    })
+    .await
+}
+
+/// Spawn the task built by `make_task`, and whenever it exits or panics: log, mark the
+/// sidecar unhealthy, then restart it after `restart_backoff` (doubling, capped at 60s)
+/// or enter lockdown, per `on_exit`. Public for tests; returns after lockdown, or once
+/// the task has exited after `shutdown` was cancelled.
+pub async fn supervise_heartbeat<F, Fut>(
+    state: SharedState,
+    on_exit: HeartbeatExitAction,
+    restart_backoff: Duration,
+    shutdown: CancellationToken,
+    make_task: F,
+) where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let mut backoff = restart_backoff;
+    loop {
+        let started = std::time::Instant::now();
+        let result = tokio::spawn(make_task()).await;
+        if shutdown.is_cancelled() {
+            return;
+        }
+        match result {
+            Ok(()) => error!("🚨 Heartbeat task exited; revocation data is no longer refreshed"),
+            Err(e) if e.is_panic() => {
+                error!("🚨 Heartbeat task panicked; revocation data is no longer refreshed")
+            }
+            // Cancelled: the runtime is shutting down.
+            Err(_) => return,
+        }
+        {
+            let mut s = state.write().await;
+            s.heartbeat_healthy = false;
+            if on_exit == HeartbeatExitAction::Lockdown {
+                s.enter_lockdown();
+            }
+        }
+        if on_exit == HeartbeatExitAction::Lockdown {
+            error!("🚨 Lockdown mode activated - heartbeat task stopped (heartbeat_exit_action = lockdown)");
+            return;
+        }
+        // A task that ran for a while before failing starts the backoff over.
+        if started.elapsed() > HEARTBEAT_RESTART_MAX_BACKOFF {
+            backoff = restart_backoff;
+        }
+        warn!("💓 Restarting heartbeat task in {:?}", backoff);
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(HEARTBEAT_RESTART_MAX_BACKOFF);
     }
 }
 
@@ -69,6 +306,21 @@ pub async fn send_heartbeat(
     control_plane_url: &str,
     rotation_interval_secs: u64,
 ) -> Result<bool, VacError> {
+    send_heartbeat_to_any(state, std::slice::from_ref(&control_plane_url), rotation_interval_secs)
+        .await
+        .map(|(should_continue, _)| should_continue)
+}
+
+/// Send a heartbeat to each of `control_plane_urls` in order until one answers, and apply
+/// that response (health, revocations). Returns the URL that answered.
+///
+/// A control plane that cannot be reached, errors, or sends an unreadable response is
+/// skipped; the heartbeat only counts as failed when every one of them did.
+pub async fn send_heartbeat_to_any<'a, S: AsRef<str>>(
+    state: &SharedState,
+    control_plane_urls: &'a [S],
+    rotation_interval_secs: u64,
+) -> Result<(bool, &'a str), VacError> {
     // Extract state needed for heartbeat
     let (sidecar_id, should_rotate) = {
         let s = state.read().await;
@@ -105,50 +357,74 @@ pub async fn send_heartbeat(
         timestamp,
     };
     
-    // Send heartbeat
-    let client = reqwest::Client::new();
-    let url = format!("{}/heartbeat", control_plane_url);
-    
-    let response = match client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-    {
-        Ok(resp) => resp,
-        Err(e) => {
-            update_heartbeat_failure_state(state).await;
-            return Err(VacError::ProxyError(format!("Heartbeat request failed: {}", e)));
-        }
-    };
+    // Bound the body before parsing: a compromised control plane must not be able to
+    // make the sidecar buffer (and deserialize) an arbitrarily large revocation list.
+    // Both lists (revoked and unrevoked) are capped at max_revocation_list_size.
+    let max_revocation_list_size = state.read().await.max_revocation_list_size;
+    let body_limit = HEARTBEAT_BASE_BODY_BYTES.saturating_add(
+        max_revocation_list_size
+            .saturating_mul(REVOCATION_ENTRY_JSON_BYTES)
+            .saturating_mul(2),
+    );
 
-    if !response.status().is_success() {
-        update_heartbeat_failure_state(state).await;
-        return Err(VacError::ProxyError(format!(
-            "Heartbeat returned status: {}",
-            response.status()
-        )));
-    }
-
-    let heartbeat_response: HeartbeatResponse = match response
-        .json()
-        .await
-    {
-        Ok(resp) => resp,
-        Err(e) => {
-            update_heartbeat_failure_state(state).await;
-            return Err(VacError::InternalError(format!("Failed to parse heartbeat response: {}", e)));
+    // Send heartbeat, failing over to the next control plane
+    let client = state.read().await.control_plane_client.clone();
+    let mut answered = None;
+    let mut last_error = VacError::ConfigError("No control plane URL configured".to_string());
+    for control_plane_url in control_plane_urls {
+        let control_plane_url = control_plane_url.as_ref();
+        match post_heartbeat(&client, control_plane_url, &request, body_limit).await {
+            Ok(response) => {
+                answered = Some((control_plane_url, response));
+                break;
+            }
+            Err(e) => {
+                if control_plane_urls.len() > 1 {
+                    warn!(control_plane_url = %control_plane_url, error = %e, "💓 Control plane unreachable, trying the next one");
+                }
+                last_error = e;
+            }
         }
+    }
+    let Some((control_plane_url, heartbeat_response)) = answered else {
+        update_heartbeat_failure_state(state).await;
+        return Err(last_error);
     };
     
     if !heartbeat_response.healthy {
         warn!("💓 Control Plane marked sidecar as unhealthy");
-        return Ok(false);
+        return Ok((false, control_plane_url));
     }
 
     // Update revocation filter if provided
-    if let Some(revoked_ids) = heartbeat_response.revoked_token_ids {
-        update_revocation_filter_from_ids(state, revoked_ids).await?;
+    match heartbeat_response.revoked_token_ids {
+        Some(revoked_ids) if revoked_ids.len() > max_revocation_list_size => {
+            warn!(
+                revoked_count = revoked_ids.len(),
+                max_revocation_list_size = max_revocation_list_size,
+                "💓 Revocation list exceeds max_revocation_list_size, not applying it"
+            );
+        }
+        Some(revoked_ids) => update_revocation_filter_from_ids(state, revoked_ids).await?,
+        None => {}
+    }
+
+    // Rescinded revocations are applied after the revocation list, so a token the
+    // control plane has since un-revoked ends up allowed.
+    match heartbeat_response.unrevoked_token_ids {
+        Some(unrevoked_ids) if unrevoked_ids.len() > max_revocation_list_size => {
+            warn!(
+                unrevoked_count = unrevoked_ids.len(),
+                max_revocation_list_size = max_revocation_list_size,
+                "💓 Un-revocation list exceeds max_revocation_list_size, not applying it"
+            );
+        }
+        Some(unrevoked_ids) => {
+            if let Err(e) = unrevoke_ids_in_filter(state, unrevoked_ids).await {
+                warn!(error = %e, "💓 Failed to apply unrevoked_token_ids");
+            }
+        }
+        None => {}
     }
 
     // Update heartbeat state on success (task and direct callers e.g. tests)
@@ -158,7 +434,60 @@ pub async fn send_heartbeat(
         s.heartbeat_failure_count = 0;
         s.last_heartbeat = SystemTime::now();
     }
-    Ok(true)
+    Ok((true, control_plane_url))
+}
+
+/// POST one heartbeat to `control_plane_url` and read its (size-limited) response.
+async fn post_heartbeat(
+    client: &ControlPlaneClient,
+    control_plane_url: &str,
+    request: &HeartbeatRequest,
+    body_limit: usize,
+) -> Result<HeartbeatResponse, VacError> {
+    let url = format!("{}/heartbeat", control_plane_url);
+    let response = client
+        .post(&url)
+        .json(request)
+        .send()
+        .await
+        .map_err(|e| VacError::ProxyError(format!("Heartbeat request failed: {}", e)))?;
+    client.check_pin(&response)?;
+    if !response.status().is_success() {
+        return Err(VacError::ProxyError(format!(
+            "Heartbeat returned status: {}",
+            response.status()
+        )));
+    }
+    let body = read_body_limited(response, body_limit, "Heartbeat").await?;
+    serde_json::from_slice(&body)
+        .map_err(|e| VacError::InternalError(format!("Failed to parse heartbeat response: {}", e)))
+}
+
+/// Read a control plane response body, failing once it grows past `limit` bytes, whether
+/// or not the response declares a Content-Length. `what` names the response in errors.
+pub(crate) async fn read_body_limited(
+    mut response: reqwest::Response,
+    limit: usize,
+    what: &str,
+) -> Result<Vec<u8>, VacError> {
+    let too_large = || {
+        VacError::ProxyError(format!("{} response exceeds {} bytes", what, limit))
+    };
+    if response.content_length().is_some_and(|len| len > limit as u64) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| VacError::ProxyError(format!("Failed to read {} response: {}", what.to_lowercase(), e)))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
 }
 
 async fn update_heartbeat_failure_state(state: &SharedState) {
@@ -182,7 +511,16 @@ async fn update_revocation_filter_from_ids(
         VacError::InternalError("Failed to acquire revocation filter lock".to_string())
     })?;
     
-    filter.update_from_ids(revoked_ids);
+    filter.update_from_ids(revoked_ids, RevocationSource::Heartbeat);
     
     Ok(())
 }
+
+/// Remove rescinded revocations (`unrevoked_token_ids`) from the revocation filter
+async fn unrevoke_ids_in_filter(state: &SharedState, unrevoked_ids: Vec<[u8; 32]>) -> Result<(), VacError> {
+    let state_guard = state.read().await;
+    let mut filter = state_guard.revocation_filter.write().map_err(|_| {
+        VacError::InternalError("Failed to acquire revocation filter lock".to_string())
+    })?;
+    filter.unrevoke_ids(unrevoked_ids, RevocationSource::Heartbeat)
+}
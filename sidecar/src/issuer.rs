@@ -0,0 +1,171 @@
+//! Root Biscuit issuance helpers
+//!
+//! Issuers (control plane, CLIs, tests) should mint root tokens through
+//! [`build_root_biscuit`] so the facts use exactly the names and types the sidecar reads:
+//!
+//! - `adapter_hash("<hex sha256>")` — pins a WASM adapter (see `policy::extract_adapter_hash`)
+//! - `depth(N)` — delegation depth (see `delegation::extract_depth`)
+//! - `check if time($time), $time <= <valid_until>` — expiry, checked against the
+//!   `time` fact the sidecar adds to every request
+//! - `not_before(<date>)` — activation time (see `biscuit::check_not_before`)
+
+use std::time::SystemTime;
+
+use biscuit_auth::builder::Fact;
+use biscuit_auth::{Biscuit, KeyPair};
+
+use crate::error::VacError;
+
+/// Standard claims for a Root Biscuit.
+#[derive(Debug, Clone, Default)]
+pub struct RootClaims {
+    /// SHA-256 (hex) of the WASM adapter to run for this token
+    pub adapter_hash: Option<String>,
+    /// Delegation depth; root tokens that will be delegated should start at 0
+    pub depth: Option<i64>,
+    /// Token expiry
+    pub valid_until: Option<SystemTime>,
+    /// Activation time; the sidecar rejects the token before it
+    pub not_before: Option<SystemTime>,
+    /// Additional application facts, added as-is
+    pub facts: Vec<Fact>,
+}
+
+/// Build and sign a Root Biscuit carrying `claims`.
+pub fn build_root_biscuit(keypair: &KeyPair, claims: RootClaims) -> Result<Biscuit, VacError> {
+    let mut builder = Biscuit::builder();
+
+    if let Some(hash) = &claims.adapter_hash {
+        // The adapter registry keys adapters by lowercase hex SHA-256.
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(VacError::ConfigError(format!(
+                "adapter_hash must be a 64-character hex SHA-256 (got '{}')",
+                hash
+            )));
+        }
+        builder
+            .add_fact(Fact::new(
+                "adapter_hash".to_string(),
+                vec![biscuit_auth::builder::string(&hash.to_ascii_lowercase())],
+            ))
+            .map_err(|e| VacError::InternalError(format!("Failed to add adapter_hash fact: {:?}", e)))?;
+    }
+
+    if let Some(depth) = claims.depth {
+        if depth < 0 {
+            return Err(VacError::ConfigError(format!("depth must not be negative (got {})", depth)));
+        }
+        builder
+            .add_fact(Fact::new(
+                "depth".to_string(),
+                vec![biscuit_auth::builder::int(depth)],
+            ))
+            .map_err(|e| VacError::InternalError(format!("Failed to add depth fact: {:?}", e)))?;
+    }
+
+    if let Some(valid_until) = claims.valid_until {
+        builder.check_expiration_date(valid_until);
+    }
+
+    if let Some(not_before) = claims.not_before {
+        builder
+            .add_fact(Fact::new(
+                "not_before".to_string(),
+                vec![biscuit_auth::builder::date(&not_before)],
+            ))
+            .map_err(|e| VacError::InternalError(format!("Failed to add not_before fact: {:?}", e)))?;
+    }
+
+    for fact in claims.facts {
+        builder
+            .add_fact(fact)
+            .map_err(|e| VacError::InternalError(format!("Failed to add fact: {:?}", e)))?;
+    }
+
+    builder
+        .build(keypair)
+        .map_err(|e| VacError::InternalError(format!("Failed to sign root biscuit: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delegation::extract_depth;
+    use crate::policy::{add_context_facts, evaluate_policy, extract_adapter_hash};
+    use biscuit_auth::Authorizer;
+    use std::time::Duration;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn authorizer_for(token: &Biscuit) -> Authorizer {
+        let mut auth = Authorizer::new();
+        auth.add_token(token).unwrap();
+        auth
+    }
+
+    #[test]
+    fn adapter_hash_and_depth_read_back_by_sidecar_extractors() {
+        let kp = KeyPair::new();
+        let token = build_root_biscuit(
+            &kp,
+            RootClaims {
+                adapter_hash: Some(HASH.to_uppercase()),
+                depth: Some(0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Round-trip through the wire format like a real request would.
+        let token = crate::biscuit::verify_root_biscuit(&token.to_base64().unwrap(), &kp.public(), None).unwrap();
+        let mut auth = authorizer_for(&token);
+        assert_eq!(extract_adapter_hash(&mut auth).unwrap(), vec![HASH.to_string()]);
+        assert_eq!(extract_depth(&mut auth).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn valid_until_is_enforced_against_request_time() {
+        let kp = KeyPair::new();
+        let expired = build_root_biscuit(
+            &kp,
+            RootClaims {
+                valid_until: Some(SystemTime::now() - Duration::from_secs(60)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut auth = authorizer_for(&expired);
+        add_context_facts(&mut auth, "GET", "/search", "cid-1").unwrap();
+        auth.add_code("allow if true;").unwrap();
+        assert!(matches!(evaluate_policy(&mut auth), Err(VacError::PolicyViolation(_))));
+
+        let live = build_root_biscuit(
+            &kp,
+            RootClaims {
+                valid_until: Some(SystemTime::now() + Duration::from_secs(3600)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut auth = authorizer_for(&live);
+        add_context_facts(&mut auth, "GET", "/search", "cid-1").unwrap();
+        auth.add_code("allow if true;").unwrap();
+        assert!(evaluate_policy(&mut auth).is_ok());
+    }
+
+    #[test]
+    fn invalid_claims_rejected() {
+        let kp = KeyPair::new();
+        let bad_hash = RootClaims {
+            adapter_hash: Some("not-a-hash".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(build_root_biscuit(&kp, bad_hash), Err(VacError::ConfigError(_))));
+
+        let bad_depth = RootClaims {
+            depth: Some(-1),
+            ..Default::default()
+        };
+        assert!(matches!(build_root_biscuit(&kp, bad_depth), Err(VacError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,92 @@
+//! Canonical JSON request bodies (`canonicalize_json_body`)
+//!
+//! Semantically identical JSON bodies can differ in key order and whitespace, and an
+//! adapter that is sensitive to formatting would then extract different facts for them.
+//! With the option on, JSON bodies are re-serialized with object keys sorted and no
+//! insignificant whitespace before adapter extraction (and, by default, forwarding).
+
+use axum::http::{header, HeaderMap};
+use serde_json::Value;
+
+/// Whether the request declares a JSON body (`application/json` or `application/*+json`).
+pub fn is_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence == "application/json" || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+/// Canonical form of a JSON document, or `None` if `body` is not valid JSON.
+pub fn canonicalize_json(body: &[u8]) -> Option<Vec<u8>> {
+    let value: Value = serde_json::from_slice(body).ok()?;
+    let mut out = String::with_capacity(body.len());
+    write_canonical(&value, &mut out);
+    Some(out.into_bytes())
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            // Sorted explicitly: `Map` keeps insertion order if serde_json's
+            // `preserve_order` feature is enabled anywhere in the build.
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equivalent_bodies_canonicalize_identically() {
+        let a = br#"{"b": {"y": [1, 2, {"d": null, "c": "x y"}], "x": true}, "a": 1.5}"#;
+        let b = b"{\n  \"a\" : 1.5,\n  \"b\" : { \"x\": true, \"y\": [ 1, 2, { \"c\": \"x y\", \"d\": null } ] }\n}";
+        let canonical = canonicalize_json(a).unwrap();
+        assert_eq!(canonical, canonicalize_json(b).unwrap());
+        assert_eq!(
+            String::from_utf8(canonical).unwrap(),
+            r#"{"a":1.5,"b":{"x":true,"y":[1,2,{"c":"x y","d":null}]}}"#
+        );
+        assert!(canonicalize_json(b"{not json").is_none());
+    }
+
+    #[test]
+    fn json_content_types() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_json_content_type(&headers));
+        for (value, json) in [
+            ("application/json", true),
+            ("Application/JSON; charset=utf-8", true),
+            ("application/problem+json", true),
+            ("text/plain", false),
+            ("application/x-www-form-urlencoded", false),
+        ] {
+            headers.insert(header::CONTENT_TYPE, value.parse().unwrap());
+            assert_eq!(is_json_content_type(&headers), json, "{}", value);
+        }
+    }
+}
@@ -11,27 +11,88 @@ pub mod adapter;
 pub mod delegation;
 pub mod security;
 pub mod rate_limit;
+#[cfg(feature = "redis")]
+pub mod rate_limit_redis;
 pub mod replay_cache;
+pub mod metrics;
+pub mod guard;
+pub mod issuer;
+pub mod log_redact;
+pub mod log_sampling;
+pub mod server;
+pub mod coalesce;
+pub mod cache_stats;
+pub mod policy_pin;
+pub mod receipt_webhook;
+pub mod step_limit;
+pub mod adapter_limit;
+pub mod json_canon;
+pub mod correlation_binding;
+pub mod session_keys;
+pub mod client_addr;
+pub mod health;
+pub mod body_budget;
+pub mod reload;
+pub mod control_plane_client;
+pub mod app;
+pub mod grpc;
+pub mod flow_graph;
+#[cfg(feature = "test-util")]
+pub mod testutil;
 
 pub use config::{Config, CliArgs};
-pub use error::VacError;
+pub use error::{VacError, ErrorResponseFormat};
 pub use state::{SidecarState, SharedState};
-pub use receipt::{ReceiptInfo, extract_receipt_info, verify_receipt_expiry, verify_correlation_id_match};
-pub use policy::{evaluate_policy, authorize_only, add_context_facts, add_receipt_facts};
+pub use receipt::{ReceiptInfo, NewReceipt, RECEIPT_HEADER, RECEIPT_BIN_HEADER, receipt_tokens, compact_receipt_tokens, encode_receipts_compact, decode_receipts_compact, extract_receipt_info, mint_receipt, verify_receipt_expiry, verify_receipt_expiry_within, verify_correlation_id_match, DEFAULT_RECEIPT_EXPIRY_SECS, DEFAULT_CLOCK_SKEW_GRACE_SECS, CompletionInfo, COMPLETE_FLOW_HEADER, COMPLETION_RECEIPT_HEADER, MAX_COMPLETION_STEPS, mint_completion_receipt, extract_completion_info};
+pub use policy::{evaluate_policy, evaluate_policy_with_timeout, authorize_only, add_context_facts, add_receipt_facts, add_receipt_count_fact, add_operator_policy, validate_policy};
+pub use policy::{add_idempotency_key_fact, has_valid_idempotency_key, IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN};
 pub use policy::extract_adapter_hash;
+pub use policy::{trace_authorizer_world, POLICY_WORLD_TARGET};
+pub use policy::{OptionsAsterisk, PathTrailingSlash, normalize_trailing_slash, origin_form};
 pub use delegation::{
     DEFAULT_MAX_DELEGATION_DEPTH,
     DELEGATION_HEADER,
+    MAX_DELEGATION_CHAIN_LENGTH,
     create_delegated_token,
+    delegate,
+    DelegationClaims,
     extract_depth,
     enforce_max_depth,
     verify_delegation_chain,
 };
-pub use proxy::{Proxy, AxumProxy};
-pub use biscuit::{verify_root_biscuit, verify_receipt_biscuit};
-pub use heartbeat::{start_heartbeat_task, send_heartbeat};
-pub use revocation::{RevocationFilter, extract_token_id};
-pub use adapter::{AdapterRegistry, AdapterFact, load_adapter_from_file, load_adapters_from_dir, load_adapter_from_url, extract_facts_from_body};
+pub use proxy::{Proxy, AxumProxy, UpstreamClientSettings, upstream_handler, DELEGATION_DEPTH_HEADER, DELEGATION_CHAIN_HEADER, DEPTH_HEADER};
+pub use biscuit::{parse_bearer_token, verify_root_biscuit, verify_root_biscuit_with_keys, verify_root_biscuit_with_verifier, RootTokenVerifier, RootVerifyFuture, verify_receipt_biscuit, verify_receipt_biscuit_with_keys, check_not_before, check_token_size, DEFAULT_MAX_TOKEN_BYTES, token_shape_violation, STRICT_TOKEN_PREDICATES};
+pub use heartbeat::{heartbeat_retry_delay, HEARTBEAT_MAX_BACKOFF, start_heartbeat_task, send_heartbeat, send_heartbeat_to_any, send_going_away, supervise_heartbeat, supervise_heartbeat_task, HeartbeatExitAction};
+pub use client_addr::{ClientAddr, TrustedProxies};
+pub use session_keys::{fetch_session_keys, refresh_session_keys, PublishedSessionKey, SessionKeySet};
+pub use control_plane_client::{parse_cert_fingerprint, ControlPlaneClient};
+pub use revocation::{RevocationAction, RevocationAuditRecord, RevocationBloomSettings, RevocationFilter, RevocationSource, DEFAULT_BLOOM_FALSE_POSITIVE_RATE, extract_token_id};
+pub use adapter::{AdapterRegistry, AdapterFact, AdapterArg, AdapterArgType, AdapterReservedFacts, RESERVED_FACT_NAMES, DEFAULT_ADAPTER_SLOW_THRESHOLD_MS, DEFAULT_ADAPTER_MAX_MEMORY_BYTES, screen_adapter_facts, load_adapter_from_file, load_adapters_from_dir, load_adapter_from_url, extract_facts_from_body, extract_facts_from_request, AdapterContext, ADAPTER_CONTEXT_FORBIDDEN_HEADERS, read_adapter_hashed};
 pub use security::{SecureString, validate_correlation_id, validate_header_name, validate_header_value, validate_body_size, MAX_REQUEST_BODY_SIZE, lock_string_memory};
-pub use rate_limit::{RateLimiter, DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION};
-pub use replay_cache::{ReplayCache, DEFAULT_REPLAY_CACHE_TTL};
\ No newline at end of file
+pub use rate_limit::{RateLimitAlgorithm, RateLimitBackend, RateLimitBackendKind, RateLimitFuture, RateLimitKey, RateLimiter, DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION};
+#[cfg(feature = "redis")]
+pub use rate_limit_redis::{RedisRateLimiter, REDIS_KEY_PREFIX, REDIS_TIMEOUT};
+pub use replay_cache::{request_replay_key, ReplayCache, DEFAULT_REPLAY_CACHE_PERSIST_INTERVAL_SECS, DEFAULT_REPLAY_CACHE_TTL, REPLAY_CLEANUP_INTERVAL, start_replay_cleanup_task, start_replay_persist_task};
+pub use metrics::{RequestMetrics, METRICS_PATH};
+pub use health::{healthz_handler, readyz_handler, HEALTHZ_PATH, READYZ_PATH};
+pub use coalesce::RequestCoalescer;
+pub use cache_stats::{CacheSizes, cache_sizes, log_cache_sizes, start_cache_size_log_task};
+pub use policy_pin::{PinnedPolicy, policy_hash, parse_policy_pin};
+pub use receipt_webhook::{ReceiptWebhook, ReceiptEvent, RECEIPT_WEBHOOK_MAX_ATTEMPTS};
+pub use step_limit::{StepLimiter, StepReservation, DEFAULT_STEP_COUNT_TTL};
+pub use adapter_limit::{AdapterConcurrencyLimiter, AdapterPermit};
+pub use body_budget::{BodyBudget, BodyBudgetPermit};
+pub use log_sampling::{LogSampler, LogSampling, start_log_summary_task, log_suppressed};
+pub use reload::{reload_config, upstream_client_settings};
+#[cfg(unix)]
+pub use reload::start_reload_on_sighup;
+pub use json_canon::{canonicalize_json, is_json_content_type};
+pub use correlation_binding::{CorrelationBindings, DEFAULT_CORRELATION_BINDING_TTL};
+pub use guard::{VacGuardLayer, VacGuard, VacContext, CorrelationIdGenerator, UuidCorrelationIds, CORRELATION_ID_HEADER};
+pub use issuer::{build_root_biscuit, RootClaims};
+pub use grpc::{GrpcProxy, UpstreamProtocol, deliver_on_grpc_ok, is_grpc_call, rpc_method, GRPC_CONNECT_TIMEOUT, GRPC_RECEIPT_TRAILER, GRPC_STATUS, GRPC_STATUS_OK};
+pub use app::{build_router, build_state, save_replay_cache, spawn_background_tasks};
+#[cfg(feature = "metrics")]
+pub use app::metrics_router;
+pub use tokio_util::sync::CancellationToken;
+pub use tokio_util::task::TaskTracker;
\ No newline at end of file
@@ -0,0 +1,130 @@
+//! Log field redaction (`log_redact_fields`)
+//!
+//! [`RedactingFields`] is a field formatter for the `tracing_subscriber` fmt layer. Span and
+//! event fields whose name is in the redaction list are written as `name=***`, so values
+//! like `correlation_id` never reach the log sink. All other fields are written the same
+//! way the default formatter writes them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::field::{Field, Visit};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FormatFields;
+
+/// Replacement written in place of a redacted value.
+pub const REDACTED: &str = "***";
+
+/// Field formatter that masks the values of configured field names.
+#[derive(Debug, Clone, Default)]
+pub struct RedactingFields {
+    redact: Arc<HashSet<String>>,
+}
+
+impl RedactingFields {
+    pub fn new<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            redact: Arc::new(fields.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = RedactVisitor {
+            writer,
+            redact: &self.redact,
+            result: Ok(()),
+            first: true,
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+struct RedactVisitor<'a, 'w> {
+    writer: Writer<'w>,
+    redact: &'a HashSet<String>,
+    result: fmt::Result,
+    first: bool,
+}
+
+impl RedactVisitor<'_, '_> {
+    fn write_field(&mut self, field: &Field, value: fmt::Arguments<'_>) {
+        if self.result.is_err() {
+            return;
+        }
+        let sep = if self.first { "" } else { " " };
+        self.first = false;
+        self.result = if self.redact.contains(field.name()) {
+            write!(self.writer, "{}{}={}", sep, field.name(), REDACTED)
+        } else if field.name() == "message" {
+            write!(self.writer, "{}{}", sep, value)
+        } else {
+            write!(self.writer, "{}{}={}", sep, field.name(), value)
+        };
+    }
+}
+
+impl Visit for RedactVisitor<'_, '_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.write_field(field, format_args!("{}", value));
+        } else {
+            self.write_field(field, format_args!("{:?}", value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.write_field(field, format_args!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn redacted_fields_are_masked_in_output() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .fmt_fields(RedactingFields::new(["correlation_id"]))
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", correlation_id = %"cid-secret-1", method = "GET");
+            let _guard = span.enter();
+            tracing::info!(correlation_id = "cid-secret-2", path = "/search", "Request authorized");
+        });
+
+        let out = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(!out.contains("cid-secret"), "{}", out);
+        assert!(out.contains("correlation_id=***"), "{}", out);
+        assert!(out.contains("path=\"/search\""), "{}", out);
+        assert!(out.contains("Request authorized"), "{}", out);
+    }
+}
@@ -0,0 +1,229 @@
+//! Sampled logging of high-frequency denials (`log_sampling`)
+//!
+//! Under a flood of bad requests (invalid tokens, replays, rate limiting) one `warn!` per
+//! denial can overwhelm the logging pipeline. [`LogSampler`] is a per-layer filter for the
+//! fmt layer: denial events (`policy_decision = "deny"`, keyed by `reason`, and receipt
+//! failures, keyed by `receipt_error`) are logged individually only for the first
+//! `max_per_interval` of each kind per interval. The rest are counted and reported once
+//! per interval by [`start_log_summary_task`]. Allow decisions and all other events are
+//! never sampled.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Metadata};
+use tracing_subscriber::layer::{Context, Filter};
+
+use crate::error::VacError;
+
+/// How many identical denials are logged individually per interval (`log_sampling`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSampling {
+    pub max_per_interval: u64,
+    pub interval: Duration,
+}
+
+impl std::str::FromStr for LogSampling {
+    type Err = VacError;
+
+    /// `"<count>/<seconds>s"`, e.g. `"100/10s"`: at most 100 lines per denial reason every 10 seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            VacError::ConfigError(format!(
+                "log_sampling must look like \"100/10s\" (count per interval in seconds), got '{}'",
+                s
+            ))
+        };
+        let (count, secs) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let max_per_interval = count.trim().parse::<u64>().map_err(|_| invalid())?;
+        let secs = secs.trim().strip_suffix('s').unwrap_or(secs.trim());
+        let secs = secs.parse::<u64>().map_err(|_| invalid())?;
+        if secs == 0 {
+            return Err(invalid());
+        }
+        Ok(LogSampling {
+            max_per_interval,
+            interval: Duration::from_secs(secs),
+        })
+    }
+}
+
+/// Per-layer filter that drops denial events past the per-interval budget; cheap to clone.
+#[derive(Debug, Clone)]
+pub struct LogSampler {
+    settings: LogSampling,
+    // Denials seen this interval, by kind
+    seen: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl LogSampler {
+    pub fn new(settings: LogSampling) -> Self {
+        Self {
+            settings,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn settings(&self) -> LogSampling {
+        self.settings
+    }
+
+    /// Count one denial of `kind`; true if it is still within this interval's budget.
+    fn admit(&self, kind: String) -> bool {
+        let Ok(mut seen) = self.seen.lock() else {
+            return true;
+        };
+        let count = seen.entry(kind).or_insert(0);
+        *count += 1;
+        *count <= self.settings.max_per_interval
+    }
+
+    /// End the interval: `(kind, seen, suppressed)` for every kind that went over budget,
+    /// sorted by kind. Counts start again from zero.
+    pub fn take_suppressed(&self) -> Vec<(String, u64, u64)> {
+        let Ok(mut seen) = self.seen.lock() else {
+            return Vec::new();
+        };
+        let max = self.settings.max_per_interval;
+        let mut suppressed: Vec<_> = seen
+            .drain()
+            .filter(|(_, count)| *count > max)
+            .map(|(kind, count)| (kind, count, count - max))
+            .collect();
+        suppressed.sort();
+        suppressed
+    }
+}
+
+impl<S> Filter<S> for LogSampler {
+    fn enabled(&self, _meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        true
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, _cx: &Context<'_, S>) -> bool {
+        let mut visitor = DenialVisitor::default();
+        event.record(&mut visitor);
+        match visitor.kind() {
+            Some(kind) => self.admit(kind),
+            None => true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct DenialVisitor {
+    deny: bool,
+    reason: Option<String>,
+    receipt_error: Option<String>,
+}
+
+impl DenialVisitor {
+    fn kind(self) -> Option<String> {
+        match (self.deny, self.reason, self.receipt_error) {
+            (_, _, Some(receipt_error)) => Some(format!("receipt_{}", receipt_error)),
+            (true, Some(reason), None) => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+impl Visit for DenialVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "policy_decision" => self.deny = value == "deny",
+            "reason" => self.reason = Some(value.to_string()),
+            "receipt_error" => self.receipt_error = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Once per interval, log a summary line for every denial kind that went over budget
+/// (e.g. `12430 invalid_biscuit_signature denials in last 10s`).
+pub async fn start_log_summary_task(sampler: LogSampler, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(sampler.settings().interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => log_suppressed(&sampler),
+        }
+    }
+    log_suppressed(&sampler);
+}
+
+/// Log (and reset) the current interval's suppressed denials.
+pub fn log_suppressed(sampler: &LogSampler) {
+    let secs = sampler.settings().interval.as_secs();
+    for (kind, seen, suppressed) in sampler.take_suppressed() {
+        tracing::warn!(
+            denial_kind = %kind,
+            denials = seen,
+            suppressed,
+            "{} {} denials in last {}s ({} not logged individually)",
+            seen,
+            kind,
+            secs,
+            suppressed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_sampling_parsing() {
+        let sampling: LogSampling = "100/10s".parse().unwrap();
+        assert_eq!(sampling.max_per_interval, 100);
+        assert_eq!(sampling.interval, Duration::from_secs(10));
+        assert_eq!("5/60".parse::<LogSampling>().unwrap().interval, Duration::from_secs(60));
+        assert!("100".parse::<LogSampling>().is_err());
+        assert!("100/0s".parse::<LogSampling>().is_err());
+        assert!("many/10s".parse::<LogSampling>().is_err());
+    }
+
+    #[test]
+    fn only_denials_over_budget_are_dropped() {
+        use tracing_subscriber::prelude::*;
+
+        let sampler = LogSampler::new(LogSampling {
+            max_per_interval: 2,
+            interval: Duration::from_secs(10),
+        });
+        let logged = Arc::new(Mutex::new(0u32));
+        let counter = CountingLayer(logged.clone()).with_filter(sampler.clone());
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(counter), || {
+            for _ in 0..10 {
+                tracing::warn!(policy_decision = "deny", reason = "replay_attack_detected", "denied");
+                tracing::warn!(receipt_error = "expired", "receipt expired");
+                tracing::info!(policy_decision = "allow", reason = "ok", "allowed");
+            }
+        });
+        // 2 replays + 2 expired receipts + every allow
+        assert_eq!(*logged.lock().unwrap(), 2 + 2 + 10);
+        assert_eq!(
+            sampler.take_suppressed(),
+            vec![
+                ("receipt_expired".to_string(), 10, 8),
+                ("replay_attack_detected".to_string(), 10, 8),
+            ]
+        );
+        assert!(sampler.take_suppressed().is_empty());
+    }
+
+    struct CountingLayer(Arc<Mutex<u32>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for CountingLayer {
+        fn on_event(&self, _event: &Event<'_>, _cx: tracing_subscriber::layer::Context<'_, S>) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Request decision metrics
+//!
+//! Counts guard decisions, minted receipts, upstream failures, request queue time,
+//! upstream latency and adapter run time with the `metrics` crate, and renders them with
+//! `metrics-exporter-prometheus` for `/_vac/metrics` on the proxy port, or `/metrics` on
+//! the `metrics_addr` admin listener when set. All of it is behind the `metrics` feature (on by default):
+//! without it the recording methods do nothing and no endpoint is built.
+//!
+//! Each [`RequestMetrics`] has its own recorder rather than the process-global one, so
+//! sidecar states (and tests) do not share counters.
+//!
+//! Label cardinality is bounded by construction: `decision` is one of a fixed set and
+//! `reason` is always a `&'static str` from [`VacError::code`], never request input.
+
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use axum::{extract::State, http::header, response::IntoResponse};
+#[cfg(feature = "metrics")]
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusRecorder};
+#[cfg(feature = "metrics")]
+use tokio_util::sync::CancellationToken;
+
+use crate::error::VacError;
+#[cfg(feature = "metrics")]
+use crate::state::SharedState;
+
+/// Upper bounds (seconds) of the `vac_queue_duration_seconds` histogram buckets.
+#[cfg(feature = "metrics")]
+const QUEUE_DURATION_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Upper bounds (seconds) of the `vac_upstream_latency_seconds` histogram buckets.
+#[cfg(feature = "metrics")]
+const UPSTREAM_LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Upper bounds (seconds) of the `vac_adapter_duration_seconds` histogram buckets; the
+/// last one is the adapter execution limit.
+#[cfg(feature = "metrics")]
+const ADAPTER_DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Metrics path on the proxy port; the `/_vac/` prefix keeps it from shadowing an
+/// upstream `/metrics`, like the health probes.
+pub const METRICS_PATH: &str = "/_vac/metrics";
+
+/// How often histogram samples are folded into their buckets between scrapes.
+#[cfg(feature = "metrics")]
+pub const METRICS_UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Guard decision, receipt, upstream and adapter metrics of one sidecar.
+#[derive(Clone)]
+pub struct RequestMetrics {
+    #[cfg(feature = "metrics")]
+    recorder: Arc<PrometheusRecorder>,
+}
+
+impl Default for RequestMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestMetrics {
+    #[cfg(feature = "metrics")]
+    pub fn new() -> Self {
+        let builder = [
+            ("vac_queue_duration_seconds", &QUEUE_DURATION_BUCKETS),
+            ("vac_upstream_latency_seconds", &UPSTREAM_LATENCY_BUCKETS),
+            ("vac_adapter_duration_seconds", &ADAPTER_DURATION_BUCKETS),
+        ]
+        .into_iter()
+        .fold(PrometheusBuilder::new(), |builder, (name, buckets)| {
+            builder
+                .set_buckets_for_metric(Matcher::Full(name.to_string()), buckets)
+                .expect("histogram buckets are not empty")
+        });
+        let metrics = Self {
+            recorder: Arc::new(builder.build_recorder()),
+        };
+        metrics.record(|| {
+            metrics::describe_counter!(
+                "vac_requests_total",
+                "Requests handled by the sidecar guard, by decision and deny reason."
+            );
+            metrics::describe_histogram!(
+                "vac_queue_duration_seconds",
+                "Time requests waited before the guard started processing them."
+            );
+            metrics::describe_counter!(
+                "vac_receipts_minted_total",
+                "Receipts minted onto successful upstream responses."
+            );
+            metrics::describe_counter!("vac_upstream_errors_total", "Failed upstream calls, by reason.");
+            metrics::describe_histogram!(
+                "vac_upstream_latency_seconds",
+                "Duration of upstream calls, failed ones included."
+            );
+            metrics::describe_histogram!(
+                "vac_adapter_duration_seconds",
+                "WASM adapter execution time, by adapter hash."
+            );
+            metrics::describe_counter!(
+                "vac_receipt_webhook_dropped_total",
+                "Minted receipts the receipt webhook never accepted."
+            );
+            // Shown from the first scrape, before any receipt is minted
+            metrics::counter!("vac_receipts_minted_total").increment(0);
+        });
+        metrics
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Record a request that passed the guard.
+    pub fn record_allow(&self) {
+        #[cfg(feature = "metrics")]
+        self.record(|| metrics::counter!("vac_requests_total", "decision" => "allow").increment(1));
+    }
+
+    /// Record a request rejected by the guard.
+    ///
+    /// Client-attributable failures (4xx) count as `deny`; sidecar/upstream failures
+    /// (5xx) count as `error`, so a broken upstream doesn't look like an attack.
+    pub fn record_error(&self, err: &VacError) {
+        #[cfg(feature = "metrics")]
+        {
+            let status: axum::http::StatusCode = err.into();
+            let decision = if status.is_server_error() { "error" } else { "deny" };
+            let reason = err.code();
+            self.record(|| {
+                metrics::counter!("vac_requests_total", "decision" => decision, "reason" => reason).increment(1)
+            });
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = err;
+    }
+
+    /// Record how long a request waited before the guard started processing it.
+    pub fn observe_queue_duration(&self, waited: Duration) {
+        #[cfg(feature = "metrics")]
+        self.record(|| metrics::histogram!("vac_queue_duration_seconds").record(waited));
+        #[cfg(not(feature = "metrics"))]
+        let _ = waited;
+    }
+
+    /// Record a receipt minted onto a successful response.
+    pub fn record_receipt_minted(&self) {
+        #[cfg(feature = "metrics")]
+        self.record(|| metrics::counter!("vac_receipts_minted_total").increment(1));
+    }
+
+    /// Record an upstream call that failed (or returned a status the sidecar refused to pass on).
+    pub fn record_upstream_error(&self, err: &VacError) {
+        #[cfg(feature = "metrics")]
+        {
+            let reason = err.code();
+            self.record(|| metrics::counter!("vac_upstream_errors_total", "reason" => reason).increment(1));
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = err;
+    }
+
+    /// Record how long one upstream call took.
+    pub fn observe_upstream_latency(&self, elapsed: Duration) {
+        #[cfg(feature = "metrics")]
+        self.record(|| metrics::histogram!("vac_upstream_latency_seconds").record(elapsed));
+        #[cfg(not(feature = "metrics"))]
+        let _ = elapsed;
+    }
+
+    /// Record one run of the adapter pinned to `hash`. Callers only pass hashes of loaded
+    /// adapters, which keeps the label set bounded.
+    pub fn observe_adapter_duration(&self, hash: &str, elapsed: Duration) {
+        #[cfg(feature = "metrics")]
+        {
+            let hash = hash.to_string();
+            self.record(|| metrics::histogram!("vac_adapter_duration_seconds", "hash" => hash).record(elapsed));
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = (hash, elapsed);
+    }
+
+    /// Set `vac_receipt_webhook_dropped_total` to the webhook's own drop count.
+    #[cfg(feature = "metrics")]
+    pub fn set_receipt_webhook_dropped(&self, dropped: u64) {
+        self.record(|| metrics::counter!("vac_receipt_webhook_dropped_total").absolute(dropped));
+    }
+
+    /// Render every recorded metric in Prometheus text format.
+    #[cfg(feature = "metrics")]
+    pub fn render(&self) -> String {
+        self.recorder.handle().render()
+    }
+
+    /// Fold pending histogram samples into their buckets, so memory stays bounded when
+    /// nobody scrapes the metrics.
+    #[cfg(feature = "metrics")]
+    pub fn run_upkeep(&self) {
+        self.recorder.handle().run_upkeep();
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record(&self, f: impl FnOnce()) {
+        metrics::with_local_recorder(self.recorder.as_ref(), f);
+    }
+}
+
+/// Run [`RequestMetrics::run_upkeep`] every `interval` until `shutdown` is cancelled.
+#[cfg(feature = "metrics")]
+pub async fn start_metrics_upkeep_task(metrics: RequestMetrics, interval: Duration, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => metrics.run_upkeep(),
+        }
+    }
+}
+
+/// `GET /_vac/metrics` (proxy port) and `GET /metrics` (`metrics_addr`) handler.
+#[cfg(feature = "metrics")]
+pub async fn metrics_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    let state = state.read().await;
+    if let Some(webhook) = &state.receipt_webhook {
+        state.metrics.set_receipt_webhook_dropped(webhook.dropped_count());
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render())
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deny_reasons_are_labeled_by_code() {
+        let m = RequestMetrics::new();
+        m.record_error(&VacError::MissingToken);
+        m.record_error(&VacError::MissingToken);
+        m.record_error(&VacError::Replay);
+        m.record_allow();
+
+        let text = m.render();
+        assert!(text.contains("vac_requests_total{decision=\"deny\",reason=\"missing_token\"} 2\n"), "{}", text);
+        assert!(text.contains("vac_requests_total{decision=\"deny\",reason=\"replay\"} 1\n"), "{}", text);
+        assert!(text.contains("vac_requests_total{decision=\"allow\"} 1\n"), "{}", text);
+        assert!(!text.contains("reason=\"rate_limit\""), "{}", text);
+    }
+
+    #[test]
+    fn server_errors_are_not_counted_as_denials() {
+        let m = RequestMetrics::new();
+        m.record_error(&VacError::ProxyError("connection refused".to_string()));
+        let text = m.render();
+        assert!(text.contains("vac_requests_total{decision=\"error\",reason=\"proxy_error\"} 1\n"), "{}", text);
+        assert!(!text.contains("decision=\"deny\""), "{}", text);
+    }
+
+    #[test]
+    fn queue_duration_histogram_is_cumulative() {
+        let m = RequestMetrics::new();
+        m.observe_queue_duration(Duration::from_micros(200));
+        m.observe_queue_duration(Duration::from_millis(30));
+        m.observe_queue_duration(Duration::from_secs(10));
+
+        let text = m.render();
+        assert!(text.contains("# TYPE vac_queue_duration_seconds histogram\n"), "{}", text);
+        assert!(text.contains("vac_queue_duration_seconds_bucket{le=\"0.0005\"} 1\n"), "{}", text);
+        assert!(text.contains("vac_queue_duration_seconds_bucket{le=\"0.05\"} 2\n"), "{}", text);
+        assert!(text.contains("vac_queue_duration_seconds_bucket{le=\"2.5\"} 2\n"), "{}", text);
+        assert!(text.contains("vac_queue_duration_seconds_bucket{le=\"+Inf\"} 3\n"), "{}", text);
+        assert!(text.contains("vac_queue_duration_seconds_count 3\n"), "{}", text);
+    }
+
+    #[test]
+    fn receipts_and_upstream_calls_are_counted() {
+        let m = RequestMetrics::new();
+        assert!(m.render().contains("vac_receipts_minted_total 0\n"));
+
+        m.record_receipt_minted();
+        m.record_upstream_error(&VacError::UpstreamTimeout("30s".to_string()));
+        m.record_upstream_error(&VacError::ProxyError("connection refused".to_string()));
+        m.record_upstream_error(&VacError::ProxyError("connection refused".to_string()));
+        m.observe_upstream_latency(Duration::from_millis(80));
+        m.observe_upstream_latency(Duration::from_secs(31));
+
+        let text = m.render();
+        assert!(text.contains("vac_receipts_minted_total 1\n"), "{}", text);
+        assert!(text.contains("vac_upstream_errors_total{reason=\"upstream_timeout\"} 1\n"), "{}", text);
+        assert!(text.contains("vac_upstream_errors_total{reason=\"proxy_error\"} 2\n"), "{}", text);
+        assert!(text.contains("vac_upstream_latency_seconds_bucket{le=\"0.1\"} 1\n"), "{}", text);
+        assert!(text.contains("vac_upstream_latency_seconds_bucket{le=\"+Inf\"} 2\n"), "{}", text);
+    }
+
+    #[test]
+    fn each_instance_has_its_own_counters() {
+        let a = RequestMetrics::new();
+        let b = RequestMetrics::new();
+        a.record_allow();
+        assert!(!b.render().contains("decision=\"allow\""));
+    }
+}
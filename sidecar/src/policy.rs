@@ -1,3 +1,4 @@
+use axum::http::Uri;
 use biscuit_auth::Authorizer;
 use crate::error::VacError;
 use crate::receipt::ReceiptInfo; // Ensure ReceiptInfo is public in receipt.rs
@@ -20,6 +21,29 @@ pub fn authorize_only(authorizer: &mut Authorizer) -> Result<(), VacError> {
     }
 }
 
+/// Log target of the authorizer world dump, e.g.
+/// `RUST_LOG=info,vac_sidecar::policy::world=trace`
+pub const POLICY_WORLD_TARGET: &str = "vac_sidecar::policy::world";
+
+/// Log every fact in `authorizer` (token, receipt, context and adapter facts alike) as
+/// one TRACE event on [`POLICY_WORLD_TARGET`], for debugging why a policy did or did not
+/// match. Facts are logged verbatim, so this is off unless that target is enabled.
+pub fn trace_authorizer_world(authorizer: &Authorizer, correlation_id: &str) {
+    if !tracing::enabled!(target: POLICY_WORLD_TARGET, tracing::Level::TRACE) {
+        return;
+    }
+    let (facts, _, _, _) = authorizer.dump();
+    let mut facts: Vec<String> = facts.iter().map(ToString::to_string).collect();
+    facts.sort();
+    tracing::trace!(
+        target: POLICY_WORLD_TARGET,
+        correlation_id,
+        fact_count = facts.len(),
+        facts = %facts.join("; "),
+        "Authorizer world before authorization"
+    );
+}
+
 /// Evaluate Datalog policy using Biscuit Authorizer
 pub fn evaluate_policy(authorizer: &mut Authorizer) -> Result<(), VacError> {
     // Global VAC policy: delegation depth must be bounded.
@@ -27,6 +51,52 @@ pub fn evaluate_policy(authorizer: &mut Authorizer) -> Result<(), VacError> {
     authorize_only(authorizer)
 }
 
+/// [`evaluate_policy`] bounded by `timeout` (`policy_eval_timeout_ms`); `None` evaluates
+/// inline as before.
+///
+/// Authorization is synchronous, so with a timeout it runs on the blocking pool and the
+/// request stops waiting once the bound is exceeded. The authorizer is handed back for
+/// queries made after authorization.
+pub async fn evaluate_policy_with_timeout(
+    mut authorizer: Authorizer,
+    timeout: Option<std::time::Duration>,
+) -> Result<Authorizer, VacError> {
+    let Some(timeout) = timeout else {
+        evaluate_policy(&mut authorizer)?;
+        return Ok(authorizer);
+    };
+    let evaluation = tokio::task::spawn_blocking(move || {
+        evaluate_policy(&mut authorizer).map(|()| authorizer)
+    });
+    match tokio::time::timeout(timeout, evaluation).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(VacError::InternalError(format!("Policy evaluation task failed: {}", e))),
+        Err(_) => {
+            tracing::warn!(
+                policy_eval_timeout_ms = timeout.as_millis() as u64,
+                "Policy evaluation exceeded its timeout"
+            );
+            Err(VacError::InternalError("policy evaluation timeout".to_string()))
+        }
+    }
+}
+
+/// Compile `policy` (Datalog rules, checks and allow/deny policies) against a throwaway
+/// authorizer, so a malformed `policy_file` is caught before any request.
+pub fn validate_policy(policy: &str) -> Result<(), VacError> {
+    Authorizer::new()
+        .add_code(policy)
+        .map_err(|e| VacError::ConfigError(format!("Policy does not compile: {:?}", e)))
+}
+
+/// Add the operator policy (`policy_file`), after the token, receipt, context and adapter
+/// facts it is written against.
+pub fn add_operator_policy(authorizer: &mut Authorizer, policy: &str) -> Result<(), VacError> {
+    authorizer
+        .add_code(policy)
+        .map_err(|e| VacError::InternalError(format!("Failed to add operator policy: {:?}", e)))
+}
+
 pub fn add_context_facts(
     authorizer: &mut Authorizer,
     method: &str,
@@ -48,21 +118,131 @@ pub fn add_context_facts(
         vec![biscuit_auth::builder::string(correlation_id)],
     )).map_err(|e| VacError::InternalError(format!("Failed to add correlation_id fact: {:?}", e)))?;
     
+    // Request time, so token expiry checks (`check if time($t), $t <= ...`) can be evaluated.
+    authorizer.add_fact(Fact::new(
+        "time".to_string(),
+        vec![biscuit_auth::builder::date(&std::time::SystemTime::now())],
+    )).map_err(|e| VacError::InternalError(format!("Failed to add time fact: {:?}", e)))?;
+    
     Ok(())
 }
 
-/// Extract an optional WASM adapter hash from the Root Biscuit facts.
+/// How a trailing slash on the request path is treated before it becomes the
+/// `operation(method, path)` fact.
+///
+/// `/charge` and `/charge/` are distinct strings in Datalog, so a policy written
+/// for one silently misses the other unless the path is normalized first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathTrailingSlash {
+    /// Use the path exactly as received (historical behavior).
+    #[default]
+    Preserve,
+    /// Remove trailing slashes (`/charge/` → `/charge`); the root path `/` is kept.
+    Strip,
+    /// Reject any non-root path ending in `/` with 400.
+    Reject,
+}
+
+impl std::str::FromStr for PathTrailingSlash {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preserve" => Ok(PathTrailingSlash::Preserve),
+            "strip" => Ok(PathTrailingSlash::Strip),
+            "reject" => Ok(PathTrailingSlash::Reject),
+            other => Err(VacError::ConfigError(format!(
+                "path_trailing_slash must be one of preserve, strip, reject (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Apply the configured trailing-slash behavior to a request path.
+///
+/// The returned path is what both the `operation` fact and the upstream request use,
+/// so the policy and the upstream always see the same resource.
+pub fn normalize_trailing_slash(path: &str, mode: PathTrailingSlash) -> Result<String, VacError> {
+    let has_trailing_slash = path.len() > 1 && path.ends_with('/');
+    match mode {
+        PathTrailingSlash::Preserve => Ok(path.to_string()),
+        PathTrailingSlash::Strip if has_trailing_slash => {
+            let trimmed = path.trim_end_matches('/');
+            Ok(if trimmed.is_empty() { "/".to_string() } else { trimmed.to_string() })
+        }
+        PathTrailingSlash::Strip => Ok(path.to_string()),
+        PathTrailingSlash::Reject if has_trailing_slash => Err(VacError::BadRequest(format!(
+            "Ambiguous path with trailing slash: {}",
+            path
+        ))),
+        PathTrailingSlash::Reject => Ok(path.to_string()),
+    }
+}
+
+/// How a server-wide `OPTIONS *` request is handled.
+///
+/// `*` is not a path, so it must not become an `operation` fact or an upstream URI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsAsterisk {
+    /// Reject with 400.
+    #[default]
+    Reject,
+    /// Answer locally with `204 No Content`; nothing is forwarded or authorized.
+    Respond,
+}
+
+impl std::str::FromStr for OptionsAsterisk {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(OptionsAsterisk::Reject),
+            "respond" => Ok(OptionsAsterisk::Respond),
+            other => Err(VacError::ConfigError(format!(
+                "options_asterisk must be one of reject, respond (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Reduce an absolute-form request target (`http://host/path?q`) to origin-form (`/path?q`).
+///
+/// Only the path and query take part in the `operation` fact and the upstream request;
+/// the upstream authority always comes from configuration, never from the request.
+/// Returns `None` if the target is already origin-form.
+pub fn origin_form(uri: &Uri) -> Option<Uri> {
+    if uri.scheme().is_none() && uri.authority().is_none() {
+        return None;
+    }
+    let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let path_and_query = if path_and_query.starts_with('/') {
+        path_and_query.to_string()
+    } else {
+        format!("/{}", path_and_query)
+    };
+    path_and_query.parse().ok()
+}
+
+/// Extract the WASM adapter hashes pinned in the Root Biscuit facts.
 ///
 /// Convention (Phase 4.1):
-/// - Root Biscuit may include a fact: `adapter_hash("<hex sha256>")`
-/// - If present, the Sidecar will execute the pinned adapter and inject returned facts.
-pub fn extract_adapter_hash(authorizer: &mut Authorizer) -> Result<Option<String>, VacError> {
+/// - Root Biscuit may include facts: `adapter_hash("<hex sha256>")`, one per adapter
+/// - The Sidecar will execute every pinned adapter and inject returned facts.
+///
+/// Datalog facts are unordered, so the hashes come back sorted (and deduplicated) to
+/// give every request the same adapter order.
+pub fn extract_adapter_hash(authorizer: &mut Authorizer) -> Result<Vec<String>, VacError> {
     let query = "adapter_hash($h) <- adapter_hash($h)";
     let result: Vec<(String,)> = authorizer
         .query(query)
         .map_err(|e| VacError::InternalError(format!("Failed to query adapter_hash: {:?}", e)))?;
 
-    Ok(result.first().map(|(h,)| h.clone()))
+    let mut hashes: Vec<String> = result.into_iter().map(|(h,)| h).collect();
+    hashes.sort();
+    hashes.dedup();
+    Ok(hashes)
 }
 
 /// FIX: Manually inject receipt facts instead of using add_token()
@@ -87,6 +267,53 @@ pub fn add_receipt_facts(
     Ok(())
 }
 
+/// Inject `receipt_count(n)`: the number of receipts that verified for this request.
+///
+/// Always added (0 when no receipts were presented), so policies can require a minimum
+/// number of prior steps, e.g. `deny if operation("POST", "/charge"), receipt_count($n), $n < 2`.
+pub fn add_receipt_count_fact(authorizer: &mut Authorizer, count: usize) -> Result<(), VacError> {
+    use biscuit_auth::builder::Fact;
+
+    authorizer.add_fact(Fact::new(
+        "receipt_count".to_string(),
+        vec![biscuit_auth::builder::int(count as i64)],
+    )).map_err(|e| VacError::InternalError(format!("Failed to add receipt_count fact: {:?}", e)))?;
+
+    Ok(())
+}
+
+/// Header whose presence is exposed to policies as `idempotency_key_present(bool)`
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Longest accepted `Idempotency-Key` (Stripe's limit)
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// True if exactly one `Idempotency-Key` header is present and its value is 1 to
+/// `MAX_IDEMPOTENCY_KEY_LEN` visible ASCII characters.
+pub fn has_valid_idempotency_key(headers: &axum::http::HeaderMap) -> bool {
+    let mut values = headers.get_all(IDEMPOTENCY_KEY_HEADER).iter();
+    let (Some(value), None) = (values.next(), values.next()) else {
+        return false;
+    };
+    let bytes = value.as_bytes();
+    !bytes.is_empty() && bytes.len() <= MAX_IDEMPOTENCY_KEY_LEN && bytes.iter().all(u8::is_ascii_graphic)
+}
+
+/// Inject `idempotency_key_present(bool)`: whether the request carries a valid
+/// `Idempotency-Key` (never the key itself, which would give every request its own fact).
+///
+/// Always added, `false` when the key is missing or invalid, since Datalog has no
+/// negation: `deny if operation("POST", "/charge"), idempotency_key_present(false)`.
+pub fn add_idempotency_key_fact(authorizer: &mut Authorizer, present: bool) -> Result<(), VacError> {
+    use biscuit_auth::builder::Fact;
+
+    authorizer.add_fact(Fact::new(
+        "idempotency_key_present".to_string(),
+        vec![biscuit_auth::builder::boolean(present)],
+    )).map_err(|e| VacError::InternalError(format!("Failed to add idempotency_key_present fact: {:?}", e)))?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,6 +325,54 @@ mod tests {
         Biscuit::builder().build(&kp).unwrap()
     }
 
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn world_dump(max_level: tracing::Level) -> String {
+        let mut auth = Authorizer::new();
+        auth.add_token(&root_biscuit_no_depth()).unwrap();
+        add_context_facts(&mut auth, "POST", "/charge", "cid-world").unwrap();
+        let info = ReceiptInfo {
+            operation: "GET /search".to_string(),
+            correlation_id: "cid-world".to_string(),
+            timestamp: 1,
+            minted_by: None,
+        };
+        add_receipt_facts(&mut auth, &info).unwrap();
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(max_level)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || trace_authorizer_world(&auth, "cid-world"));
+        let out = captured.0.lock().unwrap().clone();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn authorizer_world_is_dumped_at_trace() {
+        let out = world_dump(tracing::Level::TRACE);
+        assert_eq!(out.lines().count(), 1, "{}", out);
+        assert!(out.contains("correlation_id=\"cid-world\""), "{}", out);
+        assert!(out.contains(r#"operation("POST", "/charge")"#), "{}", out);
+        assert!(out.contains(r#"prior_event("GET /search", "cid-world", 1)"#), "{}", out);
+
+        assert!(world_dump(tracing::Level::DEBUG).is_empty());
+    }
+
     #[test]
     fn evaluate_policy_allow_if_true() {
         let root = root_biscuit_no_depth();
@@ -140,6 +415,78 @@ mod tests {
         assert!(evaluate_policy(&mut auth).is_ok());
     }
 
+    #[test]
+    fn normalize_trailing_slash_modes() {
+        assert_eq!(normalize_trailing_slash("/charge/", PathTrailingSlash::Preserve).unwrap(), "/charge/");
+        assert_eq!(normalize_trailing_slash("/charge/", PathTrailingSlash::Strip).unwrap(), "/charge");
+        assert_eq!(normalize_trailing_slash("/charge//", PathTrailingSlash::Strip).unwrap(), "/charge");
+        assert_eq!(normalize_trailing_slash("/", PathTrailingSlash::Strip).unwrap(), "/");
+        assert_eq!(normalize_trailing_slash("/", PathTrailingSlash::Reject).unwrap(), "/");
+        assert_eq!(normalize_trailing_slash("/charge", PathTrailingSlash::Reject).unwrap(), "/charge");
+        assert!(matches!(
+            normalize_trailing_slash("/charge/", PathTrailingSlash::Reject),
+            Err(VacError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn stripped_trailing_slash_matches_policy() {
+        let root = root_biscuit_no_depth();
+        let mut auth = Authorizer::new();
+        auth.add_token(&root).unwrap();
+        let path = normalize_trailing_slash("/charge/", PathTrailingSlash::Strip).unwrap();
+        add_context_facts(&mut auth, "POST", &path, "cid-1").unwrap();
+        auth.add_code(r#"allow if operation("POST", "/charge");"#).unwrap();
+        assert!(evaluate_policy(&mut auth).is_ok());
+    }
+
+    #[tokio::test]
+    async fn slow_policy_evaluation_times_out() {
+        let root = root_biscuit_no_depth();
+        let mut auth = Authorizer::new();
+        auth.add_token(&root).unwrap();
+        // Lift biscuit's own run limits so only the timeout can stop this.
+        auth.set_limits(biscuit_auth::AuthorizerLimits {
+            max_facts: 10_000_000,
+            max_iterations: 1_000,
+            max_time: std::time::Duration::from_secs(2),
+        });
+        for i in 0..1_000 {
+            auth.add_code(format!("n({});", i)).unwrap();
+        }
+        auth.add_code("pair($a, $b) <- n($a), n($b); allow if true;").unwrap();
+
+        let result = evaluate_policy_with_timeout(auth, Some(std::time::Duration::from_millis(1))).await;
+        match result {
+            Err(VacError::InternalError(msg)) => assert_eq!(msg, "policy evaluation timeout"),
+            other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+        }
+
+        // Without a timeout, evaluation runs inline.
+        let mut auth = Authorizer::new();
+        auth.add_token(&root).unwrap();
+        auth.add_code("allow if true;").unwrap();
+        assert!(evaluate_policy_with_timeout(auth, None).await.is_ok());
+    }
+
+    #[test]
+    fn absolute_form_reduced_to_origin_form() {
+        let uri: Uri = "http://api.example.com:8080/charge?amount=5".parse().unwrap();
+        assert_eq!(origin_form(&uri).unwrap(), "/charge?amount=5");
+        let uri: Uri = "http://api.example.com".parse().unwrap();
+        assert_eq!(origin_form(&uri).unwrap(), "/");
+        assert!(origin_form(&"/charge".parse().unwrap()).is_none());
+        assert_eq!("Respond".parse::<OptionsAsterisk>().unwrap(), OptionsAsterisk::Respond);
+        assert!("allow".parse::<OptionsAsterisk>().is_err());
+    }
+
+    #[test]
+    fn path_trailing_slash_from_str() {
+        assert_eq!("strip".parse::<PathTrailingSlash>().unwrap(), PathTrailingSlash::Strip);
+        assert_eq!("REJECT".parse::<PathTrailingSlash>().unwrap(), PathTrailingSlash::Reject);
+        assert!("trim".parse::<PathTrailingSlash>().is_err());
+    }
+
     #[test]
     fn evaluate_policy_depth_over_limit_denied() {
         let kp = biscuit_auth::KeyPair::new();
@@ -166,6 +513,7 @@ mod tests {
             operation: "GET /search".into(),
             correlation_id: "cid-1".into(),
             timestamp: 1704067200,
+            minted_by: None,
         };
         let mut auth = Authorizer::new();
         auth.add_token(&root).unwrap();
@@ -177,4 +525,49 @@ mod tests {
         .unwrap();
         assert!(evaluate_policy(&mut auth).is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn receipt_count_gates_on_number_of_receipts() {
+        let policy = r#"deny if operation("POST", "/charge"), receipt_count($n), $n < 2;
+            allow if true;"#;
+        let receipt = |op: &str| ReceiptInfo {
+            operation: op.into(),
+            correlation_id: "cid-1".into(),
+            timestamp: 1704067200,
+            minted_by: None,
+        };
+        let evaluate = |receipts: &[ReceiptInfo]| {
+            let mut auth = Authorizer::new();
+            auth.add_token(&root_biscuit_no_depth()).unwrap();
+            add_context_facts(&mut auth, "POST", "/charge", "cid-1").unwrap();
+            for info in receipts {
+                add_receipt_facts(&mut auth, info).unwrap();
+            }
+            add_receipt_count_fact(&mut auth, receipts.len()).unwrap();
+            auth.add_code(policy).unwrap();
+            evaluate_policy(&mut auth)
+        };
+        assert!(matches!(
+            evaluate(&[receipt("GET /search")]),
+            Err(VacError::PolicyViolation(_))
+        ));
+        assert!(evaluate(&[receipt("GET /search"), receipt("GET /details")]).is_ok());
+    }
+
+    #[test]
+    fn idempotency_key_must_be_single_and_visible_ascii() {
+        let headers = |values: &[&str]| {
+            let mut headers = axum::http::HeaderMap::new();
+            for value in values {
+                headers.append(IDEMPOTENCY_KEY_HEADER, axum::http::HeaderValue::from_str(value).unwrap());
+            }
+            headers
+        };
+        assert!(has_valid_idempotency_key(&headers(&["charge-7f3a"])));
+        assert!(!has_valid_idempotency_key(&headers(&[])));
+        assert!(!has_valid_idempotency_key(&headers(&[""])));
+        assert!(!has_valid_idempotency_key(&headers(&["has space"])));
+        assert!(!has_valid_idempotency_key(&headers(&["a", "b"])));
+        assert!(!has_valid_idempotency_key(&headers(&[&"k".repeat(MAX_IDEMPOTENCY_KEY_LEN + 1)])));
+    }
+}
@@ -0,0 +1,135 @@
+//! SHA-256 pinning of the policy file (`policy_pin_hash`)
+//!
+//! A pinned policy only ever changes to content whose hash matches the pin: a load with a
+//! different hash is rejected and the last-known-good policy stays active, so an attacker
+//! with filesystem access cannot swap in a permissive policy for a hot reload to pick up.
+//! The initial load is checked the same way, so a mismatch at startup refuses to start.
+
+use sha2::{Digest, Sha256};
+
+use crate::error::VacError;
+
+/// Hex SHA-256 of the policy file contents, as `sha256sum` prints it.
+pub fn policy_hash(source: &[u8]) -> String {
+    hex::encode(Sha256::digest(source))
+}
+
+/// Validate a configured pin (64 hex characters); returns it lowercased.
+pub fn parse_policy_pin(pin: &str) -> Result<String, VacError> {
+    let pin = pin.trim();
+    if pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(VacError::ConfigError(
+            "policy_pin_hash must be a hex-encoded SHA-256 (64 characters)".to_string(),
+        ));
+    }
+    Ok(pin.to_ascii_lowercase())
+}
+
+/// The active policy source, guarded by an optional pin.
+#[derive(Debug, Clone)]
+pub struct PinnedPolicy {
+    pin: Option<String>,
+    source: String,
+    hash: String,
+}
+
+impl PinnedPolicy {
+    /// Initial load; fails if the source does not match `pin`.
+    pub fn load(source: String, pin: Option<&str>) -> Result<Self, VacError> {
+        let pin = pin.map(parse_policy_pin).transpose()?;
+        let hash = policy_hash(source.as_bytes());
+        check_pin(pin.as_deref(), &hash)?;
+        Ok(Self { pin, source, hash })
+    }
+
+    /// Replace the active source on reload. On a pin mismatch the reload is rejected and
+    /// the current (last-known-good) source is kept.
+    pub fn reload(&mut self, source: String) -> Result<(), VacError> {
+        let hash = policy_hash(source.as_bytes());
+        if let Err(e) = check_pin(self.pin.as_deref(), &hash) {
+            tracing::warn!(
+                expected = self.pin.as_deref().unwrap_or_default(),
+                actual = %hash,
+                "Policy reload rejected: hash does not match policy_pin_hash; keeping last-known-good policy"
+            );
+            return Err(e);
+        }
+        self.source = source;
+        self.hash = hash;
+        Ok(())
+    }
+
+    /// Whether the policy is guarded by `policy_pin_hash`.
+    pub fn is_pinned(&self) -> bool {
+        self.pin.is_some()
+    }
+
+    /// Currently active policy source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Hash of the currently active source.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+fn check_pin(pin: Option<&str>, hash: &str) -> Result<(), VacError> {
+    match pin {
+        Some(pin) if pin != hash => Err(VacError::ConfigError(format!(
+            "Policy hash {} does not match policy_pin_hash {}",
+            hash, pin
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str = "allow if operation(\"GET\");";
+    const PERMISSIVE: &str = "allow if true;";
+
+    #[test]
+    fn matching_pin_loads() {
+        let pin = policy_hash(GOOD.as_bytes());
+        let policy = PinnedPolicy::load(GOOD.to_string(), Some(&pin.to_uppercase())).unwrap();
+        assert_eq!(policy.source(), GOOD);
+        assert_eq!(policy.hash(), pin);
+
+        assert!(PinnedPolicy::load(PERMISSIVE.to_string(), Some(&pin)).is_err());
+        assert!(PinnedPolicy::load(GOOD.to_string(), Some("abc")).is_err());
+    }
+
+    #[test]
+    fn mismatched_reload_keeps_last_known_good() {
+        let pin = policy_hash(GOOD.as_bytes());
+        let mut policy = PinnedPolicy::load(GOOD.to_string(), Some(&pin)).unwrap();
+
+        let err = policy.reload(PERMISSIVE.to_string()).unwrap_err();
+        assert!(matches!(err, VacError::ConfigError(_)));
+        assert_eq!(policy.source(), GOOD);
+        assert_eq!(policy.hash(), pin);
+
+        // Unpinned policies reload freely.
+        let mut unpinned = PinnedPolicy::load(GOOD.to_string(), None).unwrap();
+        unpinned.reload(PERMISSIVE.to_string()).unwrap();
+        assert_eq!(unpinned.source(), PERMISSIVE);
+    }
+
+    #[test]
+    fn hash_matches_sha256sum() {
+        // `printf 'allow if true;\n' | sha256sum`
+        assert_eq!(
+            policy_hash(b"allow if true;\n"),
+            "73a02af1b0f2c813e7cb1e0a4afe811ebcd0397fdd2cd0e3abdbc0943f45e898"
+        );
+        // `sha256sum /dev/null`
+        assert_eq!(
+            policy_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
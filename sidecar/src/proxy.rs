@@ -1,10 +1,12 @@
 use axum::{
     body::{Body, Bytes},
-    http::{Response, StatusCode, HeaderValue, Method, Uri},
+    http::{Response, StatusCode, HeaderMap, HeaderValue, Method, Uri},
 };
+use crate::coalesce::RequestCoalescer;
 use crate::error::VacError;
 use reqwest::Client;
 use std::str::FromStr;
+use std::time::Duration;
 
 /// HTTP proxy trait for future framework abstraction
 /// 
@@ -21,17 +23,51 @@ pub trait Proxy: Send + Sync {
     ) -> Result<Response<Body>, VacError>;
 }
 
+/// Settings that shape the upstream `reqwest::Client` and its connection pool.
+///
+/// Everything else (keys, upstream URL, policy switches) can be reloaded without
+/// rebuilding the client, so warm upstream connections survive a reload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpstreamClientSettings {
+    /// Whole-request timeout (None = no timeout)
+    pub timeout: Option<Duration>,
+    /// Idle connections kept per upstream host (None = reqwest default)
+    pub pool_max_idle_per_host: Option<usize>,
+}
+
 /// Axum-based HTTP proxy implementation
 pub struct AxumProxy {
     client: Client,
+    settings: UpstreamClientSettings,
 }
 
 impl AxumProxy {
     pub fn new() -> Self {
+        Self::with_settings(UpstreamClientSettings::default())
+    }
+
+    pub fn with_settings(settings: UpstreamClientSettings) -> Self {
+        // Redirects go back to the client like any other response (and through
+        // `upstream_allowed_statuses`); following them would let the upstream point the
+        // sidecar at any host, past `upstream_host_allowlist`.
+        let mut builder = Client::builder().redirect(reqwest::redirect::Policy::none());
+        if let Some(timeout) = settings.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max_idle) = settings.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
         Self {
-            client: Client::new(),
+            // Same failure mode as `Client::new()`: only fails if the TLS backend cannot load.
+            client: builder.build().expect("Failed to build upstream HTTP client"),
+            settings,
         }
     }
+
+    /// Settings the client was built with
+    pub fn settings(&self) -> &UpstreamClientSettings {
+        &self.settings
+    }
 }
 
 impl Proxy for AxumProxy {
@@ -42,17 +78,24 @@ impl Proxy for AxumProxy {
         api_key: &str,
         upstream_url: &str,
     ) -> Result<Response<Body>, VacError> {
-        // Build upstream URL
-        let path = parts.uri.path();
-        let query = parts.uri.query().unwrap_or("");
-        let upstream_uri = if query.is_empty() {
-            format!("{}{}", upstream_url, path)
-        } else {
-            format!("{}{}?{}", upstream_url, path, query)
-        };
-        
-        let uri = Uri::from_str(&upstream_uri)
-            .map_err(|e| VacError::ProxyError(format!("Invalid upstream URL: {}", e)))?;
+        self.forward_with_headers(parts, body_bytes, api_key, upstream_url, &HeaderMap::new()).await
+    }
+}
+
+impl AxumProxy {
+    /// Like [`Proxy::forward`], but also sends `extra_headers` to the upstream.
+    ///
+    /// Extra headers are added after client headers are filtered, so the sidecar can
+    /// set `x-vac-*` headers of its own that a client could not smuggle through.
+    pub async fn forward_with_headers(
+        &self,
+        parts: &axum::http::request::Parts,
+        body_bytes: Bytes,
+        api_key: &str,
+        upstream_url: &str,
+        extra_headers: &HeaderMap,
+    ) -> Result<Response<Body>, VacError> {
+        let uri = upstream_uri(upstream_url, &parts.uri)?;
         
         // Build reqwest request — body bytes are already read and validated
         let reqwest_method = match parts.method {
@@ -89,14 +132,23 @@ impl Proxy for AxumProxy {
             }
         }
         
+        for (name, value) in extra_headers {
+            if let Ok(value_str) = value.to_str() {
+                reqwest_req = reqwest_req.header(name.as_str(), value_str);
+            }
+        }
+        
         // CRITICAL: Inject real API key only after policy verification
         reqwest_req = reqwest_req.header("Authorization", format!("Bearer {}", api_key));
         
         // Execute request
-        let response = reqwest_req
-            .send()
-            .await
-            .map_err(|e| VacError::ProxyError(format!("Upstream request failed: {}", e)))?;
+        let response = reqwest_req.send().await.map_err(|e| {
+            if e.is_timeout() {
+                VacError::UpstreamTimeout(format!("no response within the upstream timeout: {}", e))
+            } else {
+                VacError::ProxyError(format!("Upstream request failed: {}", e))
+            }
+        })?;
         
         // Convert reqwest::Response to axum::Response
         let status = StatusCode::from_u16(response.status().as_u16())
@@ -116,9 +168,23 @@ impl Proxy for AxumProxy {
             }
         }
         
-        // Convert response body
-        let body_bytes = response.bytes().await
-            .map_err(|e| VacError::ProxyError(format!("Failed to read response body: {}", e)))?;
+        // Convert response body. Headers (and the status) already arrived, so a failure
+        // here means the upstream went away mid-body: the operation may not have
+        // completed, whatever the status said.
+        let body_bytes = response.bytes().await.map_err(|e| {
+            if e.is_timeout() {
+                return VacError::UpstreamTimeout(format!(
+                    "the {} response body did not finish within the upstream timeout: {}",
+                    status.as_u16(),
+                    e
+                ));
+            }
+            VacError::UpstreamTruncated(format!(
+                "upstream closed the connection while sending the {} response body: {}",
+                status.as_u16(),
+                e
+            ))
+        })?;
         
         let body = Body::from(body_bytes);
         
@@ -133,3 +199,170 @@ impl Default for AxumProxy {
         Self::new()
     }
 }
+
+/// Upstream header with the verified delegation depth (`forward_delegation_chain`).
+pub const DELEGATION_DEPTH_HEADER: &str = "x-vac-delegation-depth";
+
+/// Upstream request and client response header with the verified delegation depth
+/// (`expose_delegation_depth`).
+pub const DEPTH_HEADER: &str = "x-vac-depth";
+
+/// Upstream header with the verified delegation chain as comma-separated hex token IDs,
+/// root first (`forward_delegation_chain`).
+pub const DELEGATION_CHAIN_HEADER: &str = "x-vac-delegation-chain";
+
+/// Axum handler that forwards an (already authorized) request to the configured upstream.
+///
+/// This is the inner service behind [`crate::guard::VacGuardLayer`] in the sidecar binary.
+/// It must only be mounted behind the guard: it injects the real upstream API key.
+pub async fn upstream_handler(
+    axum::extract::State(state): axum::extract::State<crate::state::SharedState>,
+    req: axum::extract::Request,
+) -> Response<Body> {
+    use tracing::{error, info, warn};
+
+    let (api_key, upstream_url, host_allowlist, proxy, grpc_proxy, error_format, forward_delegation_chain, expose_delegation_depth, allowed_statuses, coalescer, metrics) = {
+        let s = state.read().await;
+        (
+            s.api_key().to_string(),
+            s.upstream_url.clone(),
+            s.upstream_host_allowlist.clone(),
+            s.proxy.clone(),
+            (s.protocol == crate::grpc::UpstreamProtocol::Grpc).then(|| s.grpc_proxy.clone()),
+            s.error_response_format,
+            s.forward_delegation_chain,
+            s.expose_delegation_depth,
+            s.upstream_allowed_statuses.clone(),
+            s.coalesce_idempotent.then(|| s.coalescer.clone()),
+            s.metrics.clone(),
+        )
+    };
+    let context = req.extensions().get::<crate::guard::VacContext>().cloned();
+    let correlation_id = context.as_ref().map(|c| c.correlation_id.clone());
+
+    let mut extra_headers = HeaderMap::new();
+    if let (true, Some(ctx)) = (forward_delegation_chain, &context) {
+        extra_headers.insert(DELEGATION_DEPTH_HEADER, HeaderValue::from(ctx.delegation_depth));
+        if let Ok(chain) = HeaderValue::from_str(&ctx.delegation_chain.join(",")) {
+            extra_headers.insert(DELEGATION_CHAIN_HEADER, chain);
+        }
+    }
+    let depth_header = context
+        .as_ref()
+        .filter(|_| expose_delegation_depth)
+        .map(|ctx| HeaderValue::from(ctx.delegation_depth));
+    if let Some(depth) = &depth_header {
+        extra_headers.insert(DEPTH_HEADER, depth.clone());
+    }
+
+    let (parts, body) = req.into_parts();
+    let result = async {
+        // Defense in depth: whatever upstream_url and the request path resolve to, never
+        // forward to a host outside upstream_host_allowlist.
+        if let Some(allowlist) = host_allowlist.as_deref() {
+            check_upstream_host(Some(allowlist), &upstream_uri(&upstream_url, &parts.uri)?).map_err(|e| {
+                error!(error = %e, upstream_url = %upstream_url, "Refusing to forward to a host outside upstream_host_allowlist");
+                e
+            })?;
+        }
+        // The guard already read and size-checked the body; this just takes it back out.
+        let body_bytes = axum::body::to_bytes(body, crate::security::MAX_REQUEST_BODY_SIZE)
+            .await
+            .map_err(|e| VacError::InternalError(format!("Failed to read request body: {}", e)))?;
+        let forward = || proxy.forward_with_headers(&parts, body_bytes.clone(), &api_key, &upstream_url, &extra_headers);
+        let key = coalescer.as_ref().and_then(|_| RequestCoalescer::key(&parts, &body_bytes));
+        let started = std::time::Instant::now();
+        let forwarded = match (&grpc_proxy, &coalescer, key) {
+            // gRPC calls are POSTs, never coalesced.
+            (Some(grpc_proxy), _, _) => {
+                grpc_proxy
+                    .forward(&parts, body_bytes.clone(), &api_key, &upstream_url, &extra_headers, proxy.settings().timeout)
+                    .await
+            }
+            (None, Some(coalescer), Some(key)) => coalescer.run(key, forward).await,
+            _ => forward().await,
+        };
+        metrics.observe_upstream_latency(started.elapsed());
+        forwarded
+            .map_err(|e| {
+                metrics.record_upstream_error(&e);
+                error!(
+                    proxy_error = %e,
+                    upstream_url = %upstream_url,
+                    "Failed to forward request to upstream"
+                );
+                match e {
+                    // Keep their own 502/504 so the client can tell them apart from a failed call.
+                    VacError::UpstreamTruncated(_) | VacError::UpstreamTimeout(_) => e,
+                    e => VacError::InternalError(format!("Proxy error: {:?}", e)),
+                }
+            })
+    }
+    .await;
+
+    match result {
+        Ok(response) if !status_allowed(allowed_statuses.as_deref(), response.status()) => {
+            // e.g. a 3xx pointing somewhere else or a 101 upgrade the client never asked the policy about
+            warn!(
+                upstream_status = response.status().as_u16(),
+                upstream_url = %upstream_url,
+                "Upstream response status not in upstream_allowed_statuses, returning 502"
+            );
+            let e = VacError::ProxyError(format!(
+                "Upstream returned disallowed status {}",
+                response.status().as_u16()
+            ));
+            metrics.record_upstream_error(&e);
+            e.to_response(error_format, correlation_id.as_deref())
+        }
+        Ok(mut response) => {
+            info!(
+                upstream_status = response.status().as_u16(),
+                "Request forwarded successfully"
+            );
+            if let Some(depth) = depth_header {
+                response.headers_mut().insert(DEPTH_HEADER, depth);
+            }
+            response
+        }
+        Err(e) => e.to_response(error_format, correlation_id.as_deref()),
+    }
+}
+
+/// The upstream URI a request for `uri` is forwarded to: its path and query appended to
+/// `upstream_url`.
+pub fn upstream_uri(upstream_url: &str, uri: &Uri) -> Result<Uri, VacError> {
+    let path = uri.path();
+    let upstream_uri = match uri.query() {
+        Some(query) if !query.is_empty() => format!("{}{}?{}", upstream_url, path, query),
+        _ => format!("{}{}", upstream_url, path),
+    };
+    Uri::from_str(&upstream_uri).map_err(|e| VacError::ProxyError(format!("Invalid upstream URL: {}", e)))
+}
+
+/// Refuse to forward to `uri` unless its host is in `allowlist` (`upstream_host_allowlist`,
+/// lowercase; `None` = any host).
+///
+/// Only the first hop needs checking: the upstream client never follows redirects.
+pub fn check_upstream_host(allowlist: Option<&[String]>, uri: &Uri) -> Result<(), VacError> {
+    let Some(allowlist) = allowlist else {
+        return Ok(());
+    };
+    let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+    if allowlist.contains(&host) {
+        Ok(())
+    } else {
+        Err(VacError::InternalError(format!(
+            "Upstream host '{}' is not in upstream_host_allowlist",
+            host
+        )))
+    }
+}
+
+/// Whether an upstream status may be passed to the client (`None` = no allowlist).
+fn status_allowed(allowed: Option<&[u16]>, status: StatusCode) -> bool {
+    match allowed {
+        Some(list) => list.contains(&status.as_u16()),
+        None => true,
+    }
+}
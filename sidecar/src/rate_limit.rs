@@ -4,20 +4,127 @@
 
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
 
-/// Token bucket rate limiter
+use tokio_util::sync::CancellationToken;
+
+use crate::error::VacError;
+
+/// What a rate limit bucket is kept for (`rate_limit_key`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitKey {
+    /// One bucket for the whole sidecar, shared by every agent behind it.
+    #[default]
+    Sidecar,
+    /// One bucket per root token (hex token ID), so one busy agent cannot starve the
+    /// others. Requests without a bearer token still count against the sidecar's bucket.
+    Token,
+}
+
+impl FromStr for RateLimitKey {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sidecar" => Ok(RateLimitKey::Sidecar),
+            "token" => Ok(RateLimitKey::Token),
+            other => Err(VacError::ConfigError(format!(
+                "rate_limit_key must be one of sidecar, token (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Where rate limit buckets are kept (`rate_limit_backend`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitBackendKind {
+    /// In this process ([`RateLimiter`]): each sidecar replica counts only the requests
+    /// it sees itself.
+    #[default]
+    Memory,
+    /// In Redis (`redis_url`), shared by every sidecar using it; needs the `redis` feature.
+    Redis,
+}
+
+impl FromStr for RateLimitBackendKind {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(RateLimitBackendKind::Memory),
+            "redis" => Ok(RateLimitBackendKind::Redis),
+            other => Err(VacError::ConfigError(format!(
+                "rate_limit_backend must be one of memory, redis (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Future returned by [`RateLimitBackend`] methods
+pub type RateLimitFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Rate limit buckets as the guard uses them: [`RateLimiter`] in memory, or a shared
+/// store such as Redis (`RedisRateLimiter`, with the `redis` feature).
+///
+/// Async so a backend can ask a remote store; a backend that cannot reach its store
+/// decides for itself whether to let requests through.
+pub trait RateLimitBackend: Send + Sync {
+    /// Take a token from `key`'s bucket; `true` if the request is allowed.
+    fn check<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, bool>;
+
+    /// How long until `key` has a token again (the `Retry-After` of a limited request).
+    fn time_until_token<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, Duration>;
+}
+
+/// How requests are counted against `max_requests` (`rate_limit_algorithm`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitAlgorithm {
+    /// Token bucket refilled continuously over the window: a full bucket can be spent in
+    /// one burst, and is then refilled a token at a time.
+    #[default]
+    TokenBucket,
+    /// Log of recent request times: a request is allowed only if fewer than
+    /// `max_requests` were allowed within the trailing window.
+    SlidingLog,
+}
+
+impl FromStr for RateLimitAlgorithm {
+    type Err = VacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "token_bucket" => Ok(RateLimitAlgorithm::TokenBucket),
+            "sliding_log" => Ok(RateLimitAlgorithm::SlidingLog),
+            other => Err(VacError::ConfigError(format!(
+                "rate_limit_algorithm must be one of token_bucket, sliding_log (got '{}')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Per-key rate limiter
 /// 
-/// Allows a certain number of requests per time window.
-/// Uses a sliding window approach with token bucket algorithm.
+/// Allows a certain number of requests per time window, counted with a token bucket
+/// (default) or a sliding window log ([`RateLimitAlgorithm`]).
 #[derive(Clone)]
 pub struct RateLimiter {
     /// Maximum requests per window
     max_requests: u32,
     /// Time window duration
     window_duration: Duration,
-    /// Per-sidecar state (sidecar_id -> bucket state)
+    /// How requests are counted
+    algorithm: RateLimitAlgorithm,
+    /// Per-sidecar state (sidecar_id -> bucket state), token bucket only
     buckets: Arc<Mutex<HashMap<String, BucketState>>>,
+    /// Per-key times of the requests allowed within the window, oldest first (at most
+    /// `max_requests`), sliding log only
+    logs: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
 }
 
 struct BucketState {
@@ -34,10 +141,17 @@ impl RateLimiter {
     /// * `max_requests` - Maximum number of requests allowed per window
     /// * `window_duration` - Time window duration (e.g., Duration::from_secs(60) for 60 seconds)
     pub fn new(max_requests: u32, window_duration: Duration) -> Self {
+        Self::with_algorithm(max_requests, window_duration, RateLimitAlgorithm::default())
+    }
+    
+    /// Create a rate limiter counting requests with `algorithm`.
+    pub fn with_algorithm(max_requests: u32, window_duration: Duration, algorithm: RateLimitAlgorithm) -> Self {
         Self {
             max_requests,
             window_duration,
+            algorithm,
             buckets: Arc::new(Mutex::new(HashMap::new())),
+            logs: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     
@@ -45,6 +159,13 @@ impl RateLimiter {
     /// 
     /// Returns `true` if the request should be allowed, `false` if rate limited.
     pub fn check(&self, sidecar_id: &str) -> bool {
+        match self.algorithm {
+            RateLimitAlgorithm::TokenBucket => self.check_token_bucket(sidecar_id),
+            RateLimitAlgorithm::SlidingLog => self.check_sliding_log(sidecar_id),
+        }
+    }
+    
+    fn check_token_bucket(&self, sidecar_id: &str) -> bool {
         let mut buckets = self.buckets.lock().unwrap();
         
         // Get or create bucket state for this sidecar
@@ -85,7 +206,55 @@ impl RateLimiter {
         }
     }
     
+    fn check_sliding_log(&self, key: &str) -> bool {
+        let mut logs = self.logs.lock().unwrap();
+        let now = Instant::now();
+        let log = logs.entry(key.to_string()).or_default();
+        while log.front().is_some_and(|t| now.duration_since(*t) >= self.window_duration) {
+            log.pop_front();
+        }
+        if log.len() < self.max_requests as usize {
+            log.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+    
+    /// How long until `sidecar_id` has a token again: zero if it has one now (or has no
+    /// bucket yet), otherwise the rest of the time one token takes to refill
+    /// (`window_duration / max_requests`) since the bucket's last refill.
+    ///
+    /// With the sliding log, it is the time until the oldest request in the window
+    /// leaves it, if the window is full.
+    pub fn time_until_token(&self, sidecar_id: &str) -> Duration {
+        if self.algorithm == RateLimitAlgorithm::SlidingLog {
+            let logs = self.logs.lock().unwrap();
+            let Some(log) = logs.get(sidecar_id) else {
+                return Duration::ZERO;
+            };
+            let live: Vec<&Instant> = log.iter().filter(|t| t.elapsed() < self.window_duration).collect();
+            return match live.first() {
+                Some(oldest) if live.len() >= self.max_requests as usize => {
+                    self.window_duration.saturating_sub(oldest.elapsed())
+                }
+                _ => Duration::ZERO,
+            };
+        }
+        let buckets = self.buckets.lock().unwrap();
+        match buckets.get(sidecar_id) {
+            Some(bucket) if bucket.tokens == 0 => {
+                let per_token = self.window_duration / self.max_requests.max(1);
+                per_token.saturating_sub(bucket.last_refill.elapsed())
+            }
+            _ => Duration::ZERO,
+        }
+    }
+    
     /// Clean up old bucket states (call periodically to prevent memory leak)
+    ///
+    /// Sliding logs are pruned of requests older than the window, and dropped once empty
+    /// or idle for `max_age`.
     pub fn cleanup_old_buckets(&self, max_age: Duration) {
         let mut buckets = self.buckets.lock().unwrap();
         let now = Instant::now();
@@ -93,6 +262,26 @@ impl RateLimiter {
         buckets.retain(|_, bucket| {
             now.duration_since(bucket.last_refill) < max_age
         });
+        
+        self.logs.lock().unwrap().retain(|_, log| {
+            log.retain(|t| now.duration_since(*t) < self.window_duration);
+            log.back().is_some_and(|t| now.duration_since(*t) < max_age)
+        });
+    }
+    
+    /// Number of tracked buckets (for monitoring)
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.lock().unwrap().len() + self.logs.lock().unwrap().len()
+    }
+}
+
+impl RateLimitBackend for RateLimiter {
+    fn check<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, bool> {
+        Box::pin(std::future::ready(RateLimiter::check(self, key)))
+    }
+
+    fn time_until_token<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, Duration> {
+        Box::pin(std::future::ready(RateLimiter::time_until_token(self, key)))
     }
 }
 
@@ -100,6 +289,26 @@ impl RateLimiter {
 pub const DEFAULT_MAX_REQUESTS: u32 = 100;
 pub const DEFAULT_WINDOW_DURATION: Duration = Duration::from_secs(60);
 
+/// Default `rate_limit_bucket_max_age_secs`, in rate limit windows
+pub const DEFAULT_BUCKET_MAX_AGE_WINDOWS: u64 = 10;
+
+/// Longest pause between bucket cleanup passes
+const BUCKET_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Drop buckets idle for `max_age` until `shutdown` is cancelled.
+///
+/// Passes run every `max_age` (at most every minute), so a bucket is gone at the latest
+/// one interval after reaching `max_age`.
+pub async fn start_rate_limit_cleanup_task(limiter: RateLimiter, max_age: Duration, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(max_age.clamp(Duration::from_secs(1), BUCKET_CLEANUP_INTERVAL));
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => limiter.cleanup_old_buckets(max_age),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,6 +344,91 @@ mod tests {
         assert!(!limiter.check("sidecar2"));
     }
     
+    #[test]
+    fn test_rate_limit_key_parse() {
+        assert_eq!("Token".parse::<RateLimitKey>().unwrap(), RateLimitKey::Token);
+        assert_eq!("sidecar".parse::<RateLimitKey>().unwrap(), RateLimitKey::Sidecar);
+        assert!("client_ip".parse::<RateLimitKey>().is_err());
+    }
+    
+    #[test]
+    fn test_rate_limit_backend_parse() {
+        assert_eq!("Redis".parse::<RateLimitBackendKind>().unwrap(), RateLimitBackendKind::Redis);
+        assert_eq!("memory".parse::<RateLimitBackendKind>().unwrap(), RateLimitBackendKind::Memory);
+        assert!("memcached".parse::<RateLimitBackendKind>().is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_in_memory_backend_shares_buckets_with_limiter() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let backend: Arc<dyn RateLimitBackend> = Arc::new(limiter.clone());
+        assert!(backend.check("sidecar1").await);
+        assert!(!backend.check("sidecar1").await);
+        assert!(!limiter.check("sidecar1"));
+        assert!(backend.time_until_token("sidecar1").await > Duration::ZERO);
+    }
+    
+    #[test]
+    fn test_rate_limit_algorithm_parse() {
+        assert_eq!("sliding_log".parse::<RateLimitAlgorithm>().unwrap(), RateLimitAlgorithm::SlidingLog);
+        assert_eq!("TOKEN_BUCKET".parse::<RateLimitAlgorithm>().unwrap(), RateLimitAlgorithm::TokenBucket);
+        assert!("leaky_bucket".parse::<RateLimitAlgorithm>().is_err());
+    }
+    
+    #[test]
+    fn test_burst_then_partial_window() {
+        let window = Duration::from_millis(200);
+        let bucket = RateLimiter::with_algorithm(4, window, RateLimitAlgorithm::TokenBucket);
+        let log = RateLimiter::with_algorithm(4, window, RateLimitAlgorithm::SlidingLog);
+        
+        // Both let a full burst through, and nothing more.
+        for limiter in [&bucket, &log] {
+            for _ in 0..4 {
+                assert!(limiter.check("sidecar1"));
+            }
+            assert!(!limiter.check("sidecar1"));
+        }
+        
+        // Halfway through the window the token bucket has refilled two tokens, so six
+        // requests get through within one window; the sliding log still holds the burst.
+        thread::sleep(Duration::from_millis(110));
+        assert!(bucket.check("sidecar1"));
+        assert!(bucket.check("sidecar1"));
+        assert!(!log.check("sidecar1"));
+        let wait = log.time_until_token("sidecar1");
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(90), "{:?}", wait);
+        
+        // Once the burst has left the window, the whole allowance is back.
+        thread::sleep(Duration::from_millis(100));
+        for _ in 0..4 {
+            assert!(log.check("sidecar1"));
+        }
+        assert!(!log.check("sidecar1"));
+    }
+    
+    #[test]
+    fn test_sliding_log_cleanup_prunes_old_requests() {
+        let limiter = RateLimiter::with_algorithm(2, Duration::from_millis(50), RateLimitAlgorithm::SlidingLog);
+        assert!(limiter.check("sidecar1"));
+        assert_eq!(limiter.bucket_count(), 1);
+        thread::sleep(Duration::from_millis(60));
+        limiter.cleanup_old_buckets(Duration::from_secs(60));
+        assert_eq!(limiter.bucket_count(), 0);
+    }
+    
+    #[test]
+    fn test_time_until_token() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert_eq!(limiter.time_until_token("sidecar1"), Duration::ZERO);
+        assert!(limiter.check("sidecar1"));
+        assert_eq!(limiter.time_until_token("sidecar1"), Duration::ZERO);
+        assert!(limiter.check("sidecar1"));
+        
+        // One token every 30s
+        let wait = limiter.time_until_token("sidecar1");
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30), "{:?}", wait);
+    }
+    
     #[test]
     fn test_rate_limiter_refill() {
         let limiter = RateLimiter::new(10, Duration::from_millis(100));
@@ -0,0 +1,147 @@
+//! Redis rate limit backend (`rate_limit_backend = "redis"`, `redis` feature)
+//!
+//! Sidecar replicas behind a load balancer each see only part of a client's traffic, so
+//! their in-memory buckets under-count. Here the token bucket lives in Redis and is
+//! refilled and spent by one Lua script, atomically and on Redis's clock, so every
+//! sidecar using the same Redis sees the same bucket. The refill follows
+//! [`RateLimiter`]'s, and an idle bucket expires in Redis after
+//! `rate_limit_bucket_max_age_secs`.
+//!
+//! When Redis cannot be reached (or does not answer within [`REDIS_TIMEOUT`]), the
+//! request is counted against this sidecar's in-memory limiter instead, so an outage
+//! degrades to per-replica limits rather than to no limits or no traffic.
+
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::Script;
+use tokio::sync::OnceCell;
+
+use crate::error::VacError;
+use crate::rate_limit::{RateLimitBackend, RateLimitFuture, RateLimiter};
+
+/// Prefix of the Redis keys holding buckets
+pub const REDIS_KEY_PREFIX: &str = "vac:rate_limit:";
+
+/// Longest wait for Redis (connecting included) before falling back to the local limiter
+pub const REDIS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Refill the bucket in `KEYS[1]` and, with `ARGV[4] == "1"`, take a token from it.
+/// `ARGV`: max requests, window (ms), bucket TTL (ms), take. Returns `{allowed, wait_ms}`,
+/// `wait_ms` being the time until the next token when the bucket is empty.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local max = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'refilled')
+local tokens = tonumber(bucket[1]) or max
+local refilled = tonumber(bucket[2]) or now
+local elapsed = now - refilled
+if elapsed >= window then
+  tokens = max
+  refilled = now
+else
+  local earned = math.floor(elapsed * max / window)
+  if earned >= 1 then
+    tokens = math.min(max, tokens + earned)
+    refilled = now
+  end
+end
+local allowed = 0
+local wait = 0
+if tokens > 0 then
+  allowed = 1
+else
+  wait = math.max(0, math.ceil(window / max) - (now - refilled))
+end
+if ARGV[4] == '1' then
+  if allowed == 1 then
+    tokens = tokens - 1
+  end
+  redis.call('HSET', KEYS[1], 'tokens', tokens, 'refilled', refilled)
+  redis.call('PEXPIRE', KEYS[1], ARGV[3])
+end
+return {allowed, wait}
+"#;
+
+/// Token buckets shared through Redis, with an in-memory fallback.
+pub struct RedisRateLimiter {
+    client: redis::Client,
+    /// Opened on first use, so startup does not wait for Redis
+    connection: OnceCell<ConnectionManager>,
+    script: Script,
+    max_requests: u32,
+    window: Duration,
+    bucket_ttl: Duration,
+    fallback: RateLimiter,
+}
+
+impl RedisRateLimiter {
+    /// Buckets of `max_requests` per `window` in the Redis at `redis_url`, expiring after
+    /// `bucket_ttl` idle; `fallback` counts requests while Redis is unreachable.
+    pub fn new(
+        redis_url: &str,
+        max_requests: u32,
+        window: Duration,
+        bucket_ttl: Duration,
+        fallback: RateLimiter,
+    ) -> Result<Self, VacError> {
+        let client = redis::Client::open(redis_url)
+            .map_err(|e| VacError::ConfigError(format!("Invalid redis_url: {}", e)))?;
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            script: Script::new(TOKEN_BUCKET_SCRIPT),
+            max_requests: max_requests.max(1),
+            window,
+            bucket_ttl,
+            fallback,
+        })
+    }
+
+    /// Run the bucket script for `key`: `(allowed, wait_ms)`.
+    async fn run(&self, key: &str, take: bool) -> Result<(i64, i64), String> {
+        let call = async {
+            let mut connection = self
+                .connection
+                .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+                .await?
+                .clone();
+            let mut invocation = self.script.key(format!("{}{}", REDIS_KEY_PREFIX, key));
+            invocation
+                .arg(self.max_requests)
+                .arg(self.window.as_millis() as u64)
+                .arg(self.bucket_ttl.as_millis() as u64)
+                .arg(if take { "1" } else { "0" });
+            invocation.invoke_async::<_, (i64, i64)>(&mut connection).await
+        };
+        match tokio::time::timeout(REDIS_TIMEOUT, call).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("no reply within {}ms", REDIS_TIMEOUT.as_millis())),
+        }
+    }
+}
+
+impl RateLimitBackend for RedisRateLimiter {
+    fn check<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, bool> {
+        Box::pin(async move {
+            match self.run(key, true).await {
+                Ok((allowed, _)) => allowed == 1,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Redis rate limit backend unavailable; using the local limiter");
+                    self.fallback.check(key)
+                }
+            }
+        })
+    }
+
+    fn time_until_token<'a>(&'a self, key: &'a str) -> RateLimitFuture<'a, Duration> {
+        Box::pin(async move {
+            match self.run(key, false).await {
+                Ok((_, wait_ms)) => Duration::from_millis(wait_ms.max(0) as u64),
+                Err(_) => self.fallback.time_until_token(key),
+            }
+        })
+    }
+}
@@ -1,11 +1,82 @@
-use biscuit_auth::Biscuit;
+use biscuit_auth::{Biscuit, KeyPair, builder::Fact};
 use crate::error::VacError;
+use base64::{engine::general_purpose, Engine as _};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Receipt expiry time: 5 minutes (300 seconds)
-const RECEIPT_EXPIRY_SECONDS: u64 = 300;
-/// Grace period for clock skew: 30 seconds
-const CLOCK_SKEW_GRACE_SECONDS: u64 = 30;
+/// Default receipt expiry time (`receipt_expiry_secs`): 5 minutes
+pub const DEFAULT_RECEIPT_EXPIRY_SECS: u64 = 300;
+/// Default grace period for clock skew (`clock_skew_grace_secs`): 30 seconds
+pub const DEFAULT_CLOCK_SKEW_GRACE_SECS: u64 = 30;
+
+/// Request header carrying receipts from earlier steps
+pub const RECEIPT_HEADER: &str = "x-vac-receipt";
+
+/// Receipt tokens from every `X-VAC-Receipt` header.
+///
+/// Clients and intermediaries may fold repeated headers into one comma-separated value;
+/// base64 tokens never contain commas, so each value is split on `,`. Empty entries are
+/// skipped. A value that is not valid ASCII is an `InvalidTokenFormat`.
+pub fn receipt_tokens(headers: &axum::http::HeaderMap) -> Result<Vec<&str>, VacError> {
+    let mut tokens = Vec::new();
+    for value in headers.get_all(RECEIPT_HEADER) {
+        let value = value.to_str().map_err(|_| VacError::InvalidTokenFormat)?;
+        tokens.extend(value.split(',').map(str::trim).filter(|t| !t.is_empty()));
+    }
+    Ok(tokens)
+}
+
+/// Request header carrying receipts in the compact encoding (`accept_compact_receipts`)
+pub const RECEIPT_BIN_HEADER: &str = "x-vac-receipt-bin";
+
+/// Encode serialized receipts (`Biscuit::to_vec`) for `X-VAC-Receipt-Bin`.
+///
+/// Each receipt is prefixed with its length as a big-endian `u32`; the concatenation is
+/// base64url without padding, so any number of receipts travel in one header value
+/// without per-token padding or separators.
+pub fn encode_receipts_compact<B: AsRef<[u8]>>(receipts: &[B]) -> String {
+    let mut buf = Vec::with_capacity(receipts.iter().map(|r| r.as_ref().len() + 4).sum());
+    for receipt in receipts {
+        let receipt = receipt.as_ref();
+        buf.extend_from_slice(&(receipt.len() as u32).to_be_bytes());
+        buf.extend_from_slice(receipt);
+    }
+    general_purpose::URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Decode an `X-VAC-Receipt-Bin` value into serialized receipts.
+pub fn decode_receipts_compact(value: &str) -> Result<Vec<Vec<u8>>, VacError> {
+    let buf = general_purpose::URL_SAFE_NO_PAD
+        .decode(value.trim())
+        .map_err(|_| VacError::InvalidTokenFormat)?;
+    let mut receipts = Vec::new();
+    let mut rest = buf.as_slice();
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_first_chunk::<4>().ok_or(VacError::InvalidTokenFormat)?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if len == 0 || len > tail.len() {
+            return Err(VacError::InvalidTokenFormat);
+        }
+        let (receipt, tail) = tail.split_at(len);
+        receipts.push(receipt.to_vec());
+        rest = tail;
+    }
+    Ok(receipts)
+}
+
+/// Receipt tokens from every `X-VAC-Receipt-Bin` header, re-encoded in the standard
+/// base64 form so they are verified exactly like `X-VAC-Receipt` tokens.
+pub fn compact_receipt_tokens(headers: &axum::http::HeaderMap) -> Result<Vec<String>, VacError> {
+    let mut tokens = Vec::new();
+    for value in headers.get_all(RECEIPT_BIN_HEADER) {
+        let value = value.to_str().map_err(|_| VacError::InvalidTokenFormat)?;
+        tokens.extend(
+            decode_receipts_compact(value)?
+                .into_iter()
+                .map(|receipt| general_purpose::URL_SAFE.encode(receipt)),
+        );
+    }
+    Ok(tokens)
+}
 
 /// Information extracted from a receipt Biscuit
 /// 
@@ -15,6 +86,67 @@ pub struct ReceiptInfo {
     pub operation: String,
     pub correlation_id: String,
     pub timestamp: i64, // Datalog uses i64 for integers
+    /// `sidecar_id` of the sidecar that minted the receipt (None for receipts minted
+    /// before the `minted_by_sidecar` fact existed)
+    pub minted_by: Option<String>,
+}
+
+/// Facts embedded into a newly minted receipt.
+#[derive(Debug, Clone)]
+pub struct NewReceipt<'a> {
+    /// `"METHOD /path"` of the request that succeeded
+    pub operation: &'a str,
+    pub correlation_id: &'a str,
+    /// Unix seconds
+    pub timestamp: i64,
+    /// Hex token IDs of the verified delegation chain (root → current)
+    pub delegation_chain: &'a [String],
+    pub depth: Option<i64>,
+    /// ID of the minting sidecar, recorded as `minted_by_sidecar(id)`
+    pub sidecar_id: &'a str,
+}
+
+/// Build and sign a receipt with the sidecar's session key.
+pub fn mint_receipt(session_key: &KeyPair, receipt: &NewReceipt) -> Result<Biscuit, VacError> {
+    let fact_err = |e| VacError::InternalError(format!("Fact error: {:?}", e));
+    let mut builder = Biscuit::builder();
+
+    builder.add_fact(Fact::new(
+        "prior_event".to_string(),
+        vec![
+            biscuit_auth::builder::string(receipt.operation),
+            biscuit_auth::builder::string(receipt.correlation_id),
+            biscuit_auth::builder::int(receipt.timestamp),
+        ],
+    )).map_err(fact_err)?;
+
+    // Phase 4.3: Embed delegation chain into receipts (audit trail).
+    for id_hex in receipt.delegation_chain {
+        builder
+            .add_fact(Fact::new(
+                "delegation_chain".to_string(),
+                vec![biscuit_auth::builder::string(id_hex)],
+            ))
+            .map_err(fact_err)?;
+    }
+    if let Some(depth) = receipt.depth {
+        builder
+            .add_fact(Fact::new(
+                "depth".to_string(),
+                vec![biscuit_auth::builder::int(depth)],
+            ))
+            .map_err(fact_err)?;
+    }
+
+    builder
+        .add_fact(Fact::new(
+            "minted_by_sidecar".to_string(),
+            vec![biscuit_auth::builder::string(receipt.sidecar_id)],
+        ))
+        .map_err(fact_err)?;
+
+    builder.build(session_key)
+        .map_err(|e| VacError::InternalError(format!("Sign error: {:?}", e)))
 }
 
 /// Extract receipt information from a Biscuit using Datalog queries
@@ -49,20 +181,131 @@ pub fn extract_receipt_info(
     // We take the first matching row (tuple)
     let (operation, correlation_id, timestamp) = &result[0];
 
+    let minted_by: Vec<(String,)> = authorizer.query("minted_by_data($id) <- minted_by_sidecar($id)")
+        .map_err(|e| VacError::ReceiptError(format!("Query failed: {:?}", e)))?;
+
     Ok(ReceiptInfo {
         operation: operation.clone(),
         correlation_id: correlation_id.clone(),
         timestamp: *timestamp,
+        minted_by: minted_by.into_iter().next().map(|(id,)| id),
+    })
+}
+
+/// Request header asking for a completion receipt on the final step of a flow
+pub const COMPLETE_FLOW_HEADER: &str = "x-vac-complete-flow";
+
+/// Response header carrying the completion receipt
+pub const COMPLETION_RECEIPT_HEADER: &str = "x-vac-completion-receipt";
+
+/// Most steps (presented receipts plus the final request) a completion receipt covers
+pub const MAX_COMPLETION_STEPS: usize = 32;
+
+/// Build and sign a completion receipt: one `flow_step(index, operation, timestamp)` fact
+/// per step of `correlation_id`'s flow, in order, plus `completed_flow(correlation_id,
+/// step_count)`.
+///
+/// Steps are recorded as `flow_step`, not `prior_event`, so a completion receipt cannot
+/// be presented in place of the receipts it summarizes.
+pub fn mint_completion_receipt(
+    session_key: &KeyPair,
+    correlation_id: &str,
+    steps: &[ReceiptInfo],
+    sidecar_id: &str,
+) -> Result<Biscuit, VacError> {
+    if steps.len() > MAX_COMPLETION_STEPS {
+        return Err(VacError::BadRequest(format!(
+            "a completion receipt covers at most {} steps",
+            MAX_COMPLETION_STEPS
+        )));
+    }
+    let fact_err = |e| VacError::InternalError(format!("Fact error: {:?}", e));
+    let mut builder = Biscuit::builder();
+
+    for (index, step) in steps.iter().enumerate() {
+        builder
+            .add_fact(Fact::new(
+                "flow_step".to_string(),
+                vec![
+                    biscuit_auth::builder::int(index as i64),
+                    biscuit_auth::builder::string(&step.operation),
+                    biscuit_auth::builder::int(step.timestamp),
+                ],
+            ))
+            .map_err(fact_err)?;
+    }
+    builder
+        .add_fact(Fact::new(
+            "completed_flow".to_string(),
+            vec![
+                biscuit_auth::builder::string(correlation_id),
+                biscuit_auth::builder::int(steps.len() as i64),
+            ],
+        ))
+        .map_err(fact_err)?;
+    builder
+        .add_fact(Fact::new(
+            "minted_by_sidecar".to_string(),
+            vec![biscuit_auth::builder::string(sidecar_id)],
+        ))
+        .map_err(fact_err)?;
+
+    builder.build(session_key)
+        .map_err(|e| VacError::InternalError(format!("Sign error: {:?}", e)))
+}
+
+/// Contents of a completion receipt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionInfo {
+    pub correlation_id: String,
+    /// `(operation, timestamp)` per step, in flow order
+    pub steps: Vec<(String, i64)>,
+}
+
+/// Read a (signature-verified) completion receipt.
+pub fn extract_completion_info(receipt: &Biscuit) -> Result<CompletionInfo, VacError> {
+    let mut authorizer = receipt.authorizer()
+        .map_err(|_| VacError::InvalidSignature)?;
+    let query_err = |e| VacError::ReceiptError(format!("Query failed: {:?}", e));
+
+    let flow: Vec<(String, i64)> = authorizer
+        .query("flow_data($id, $n) <- completed_flow($id, $n)")
+        .map_err(query_err)?;
+    let Some((correlation_id, step_count)) = flow.into_iter().next() else {
+        return Err(VacError::ReceiptError("No 'completed_flow' fact found in completion receipt".to_string()));
+    };
+    let mut steps: Vec<(i64, String, i64)> = authorizer
+        .query("step_data($i, $op, $ts) <- flow_step($i, $op, $ts)")
+        .map_err(query_err)?;
+    if steps.len() as i64 != step_count {
+        return Err(VacError::ReceiptError("Completion receipt step count mismatch".to_string()));
+    }
+    steps.sort_by_key(|(index, _, _)| *index);
+
+    Ok(CompletionInfo {
+        correlation_id,
+        steps: steps.into_iter().map(|(_, op, ts)| (op, ts)).collect(),
     })
 }
 
 /// Verify receipt has not expired
 /// 
-/// Receipts are valid for RECEIPT_EXPIRY_SECONDS (5 minutes) with
-/// a grace period of CLOCK_SKEW_GRACE_SECONDS (30 seconds) for clock skew.
+/// Receipts are valid for DEFAULT_RECEIPT_EXPIRY_SECS (5 minutes) with
+/// a grace period of DEFAULT_CLOCK_SKEW_GRACE_SECS (30 seconds) for clock skew.
+/// See [`verify_receipt_expiry_within`] for configured windows.
+pub fn verify_receipt_expiry(timestamp: i64) -> Result<(), VacError> {
+    verify_receipt_expiry_within(timestamp, DEFAULT_RECEIPT_EXPIRY_SECS, DEFAULT_CLOCK_SKEW_GRACE_SECS)
+}
+
+/// Verify receipt was minted no more than `expiry_secs` + `clock_skew_grace_secs` ago
+/// (`receipt_expiry_secs`, `clock_skew_grace_secs`).
 /// 
 /// Note: timestamp is i64 (Datalog format), converted to u64 for comparison
-pub fn verify_receipt_expiry(timestamp: i64) -> Result<(), VacError> {
+pub fn verify_receipt_expiry_within(
+    timestamp: i64,
+    expiry_secs: u64,
+    clock_skew_grace_secs: u64,
+) -> Result<(), VacError> {
     // Convert i64 timestamp to u64 (Datalog uses i64, but we store as u64 internally)
     let timestamp_u64 = timestamp as u64;
     
@@ -71,7 +314,9 @@ pub fn verify_receipt_expiry(timestamp: i64) -> Result<(), VacError> {
         .map_err(|e| VacError::InternalError(format!("System clock error: {}", e)))?
         .as_secs();
     
-    let expiry_time = timestamp_u64 + RECEIPT_EXPIRY_SECONDS + CLOCK_SKEW_GRACE_SECONDS;
+    let expiry_time = timestamp_u64
+        .saturating_add(expiry_secs)
+        .saturating_add(clock_skew_grace_secs);
     
     if now > expiry_time {
         return Err(VacError::ReceiptExpired);
@@ -95,7 +340,6 @@ pub fn verify_correlation_id_match(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use biscuit_auth::{KeyPair, builder::Fact};
 
     fn build_receipt_biscuit(operation: &str, correlation_id: &str, timestamp: i64) -> Biscuit {
         let kp = KeyPair::new();
@@ -113,6 +357,64 @@ mod tests {
         builder.build(&kp).unwrap()
     }
 
+    #[test]
+    fn receipt_tokens_split_combined_headers() {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.append(RECEIPT_HEADER, "EnA1, EnB2".parse().unwrap());
+        headers.append(RECEIPT_HEADER, "EnC3".parse().unwrap());
+        headers.append(RECEIPT_HEADER, ",".parse().unwrap());
+        assert_eq!(receipt_tokens(&headers).unwrap(), vec!["EnA1", "EnB2", "EnC3"]);
+
+        headers.append(RECEIPT_HEADER, axum::http::HeaderValue::from_bytes(b"\xffEn").unwrap());
+        assert!(matches!(receipt_tokens(&headers), Err(VacError::InvalidTokenFormat)));
+    }
+
+    #[test]
+    fn compact_encoding_round_trips() {
+        let receipts = [vec![1u8, 2, 3], vec![0xff; 300]];
+        let encoded = encode_receipts_compact(&receipts);
+        assert!(!encoded.contains('='));
+        assert_eq!(decode_receipts_compact(&encoded).unwrap(), receipts);
+        // Truncated or zero-length entries are rejected.
+        assert!(decode_receipts_compact(&encoded[..encoded.len() - 4]).is_err());
+        assert!(decode_receipts_compact(&encode_receipts_compact(&[Vec::<u8>::new()])).is_err());
+        assert!(decode_receipts_compact("not base64!").is_err());
+    }
+
+    #[test]
+    fn compact_receipt_matches_base64_form() {
+        let kp = KeyPair::new();
+        let receipt = mint_receipt(
+            &kp,
+            &NewReceipt {
+                operation: "GET /search",
+                correlation_id: "cid-123",
+                timestamp: 1704067200,
+                delegation_chain: &[],
+                depth: None,
+                sidecar_id: "sidecar-1",
+            },
+        )
+        .unwrap();
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(
+            RECEIPT_BIN_HEADER,
+            encode_receipts_compact(&[receipt.to_vec().unwrap()]).parse().unwrap(),
+        );
+        let tokens = compact_receipt_tokens(&headers).unwrap();
+        assert_eq!(tokens.len(), 1);
+
+        // The compact transport yields the same token, verified the same way.
+        let from_compact = Biscuit::from_base64(&tokens[0], kp.public()).unwrap();
+        let from_base64 = Biscuit::from_base64(receipt.to_base64().unwrap(), kp.public()).unwrap();
+        assert_eq!(from_compact.to_vec().unwrap(), from_base64.to_vec().unwrap());
+        let (a, b) = (
+            extract_receipt_info(&from_compact).unwrap(),
+            extract_receipt_info(&from_base64).unwrap(),
+        );
+        assert_eq!((a.operation, a.correlation_id, a.timestamp), (b.operation, b.correlation_id, b.timestamp));
+    }
+
     #[test]
     fn extract_receipt_info_ok() {
         let receipt = build_receipt_biscuit("GET /search", "cid-123", 1704067200);
@@ -122,6 +424,57 @@ mod tests {
         assert_eq!(info.timestamp, 1704067200);
     }
 
+    #[test]
+    fn completion_receipt_lists_steps_in_order() {
+        let kp = KeyPair::new();
+        let step = |operation: &str, timestamp| ReceiptInfo {
+            operation: operation.to_string(),
+            correlation_id: "cid-1".to_string(),
+            timestamp,
+            minted_by: None,
+        };
+        let steps = [step("GET /search", 100), step("POST /select", 101), step("POST /charge", 102)];
+        let receipt = mint_completion_receipt(&kp, "cid-1", &steps, "sidecar-1").unwrap();
+
+        let info = extract_completion_info(&receipt).unwrap();
+        assert_eq!(info.correlation_id, "cid-1");
+        assert_eq!(
+            info.steps,
+            vec![("GET /search".to_string(), 100), ("POST /select".to_string(), 101), ("POST /charge".to_string(), 102)]
+        );
+        // Not usable as an ordinary receipt for any of its steps.
+        assert!(extract_receipt_info(&receipt).is_err());
+
+        let too_many = vec![step("GET /search", 100); MAX_COMPLETION_STEPS + 1];
+        assert!(matches!(mint_completion_receipt(&kp, "cid-1", &too_many, "s"), Err(VacError::BadRequest(_))));
+    }
+
+    #[test]
+    fn minted_receipt_records_sidecar_id() {
+        let session_key = KeyPair::new();
+        let chain = vec!["aa01".to_string()];
+        let receipt = mint_receipt(
+            &session_key,
+            &NewReceipt {
+                operation: "GET /search",
+                correlation_id: "cid-123",
+                timestamp: 1704067200,
+                delegation_chain: &chain,
+                depth: Some(0),
+                sidecar_id: "sidecar-eu-1",
+            },
+        )
+        .unwrap();
+
+        let info = extract_receipt_info(&receipt).unwrap();
+        assert_eq!(info.operation, "GET /search");
+        assert_eq!(info.minted_by.as_deref(), Some("sidecar-eu-1"));
+
+        // Receipts from before the fact existed still decode.
+        let legacy = build_receipt_biscuit("GET /search", "cid-123", 1704067200);
+        assert_eq!(extract_receipt_info(&legacy).unwrap().minted_by, None);
+    }
+
     #[test]
     fn extract_receipt_info_no_prior_event_fails() {
         let kp = KeyPair::new();
@@ -147,6 +500,23 @@ mod tests {
         assert!(matches!(err, VacError::ReceiptExpired));
     }
 
+    #[test]
+    fn configured_expiry_window_accepts_older_receipts() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        // Eight minutes old: past the default window, inside a 10-minute one.
+        let eight_minutes_ago = (now - 8 * 60) as i64;
+        assert!(matches!(verify_receipt_expiry(eight_minutes_ago), Err(VacError::ReceiptExpired)));
+        assert!(verify_receipt_expiry_within(eight_minutes_ago, 600, DEFAULT_CLOCK_SKEW_GRACE_SECS).is_ok());
+        let eleven_minutes_ago = (now - 11 * 60) as i64;
+        assert!(matches!(
+            verify_receipt_expiry_within(eleven_minutes_ago, 600, DEFAULT_CLOCK_SKEW_GRACE_SECS),
+            Err(VacError::ReceiptExpired)
+        ));
+    }
+
     #[test]
     fn verify_correlation_id_match_same_ok() {
         assert!(verify_correlation_id_match("a", "a").is_ok());
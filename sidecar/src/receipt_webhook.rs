@@ -0,0 +1,131 @@
+//! Receipt audit webhook (`receipt_webhook_url`)
+//!
+//! After a receipt is minted, the sidecar POSTs it with its metadata as JSON to the
+//! configured webhook, giving audit systems a receipt feed that does not depend on
+//! clients forwarding `X-VAC-Receipt`. Delivery runs in a background task, so the client
+//! response is never delayed. Failed deliveries are retried a bounded number of times and
+//! then dropped and counted (`vac_receipt_webhook_dropped_total`), as are receipts that
+//! arrive while too many deliveries are already pending.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+/// Delivery attempts per receipt before it is dropped.
+pub const RECEIPT_WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; doubled for each further retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+/// Per-attempt request timeout.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Deliveries (including retries) pending at once; further receipts are dropped.
+const MAX_PENDING_DELIVERIES: usize = 1024;
+
+/// Body POSTed to the webhook for each minted receipt.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceiptEvent {
+    /// Base64 receipt biscuit, as sent in `X-VAC-Receipt`
+    pub receipt: String,
+    /// `"METHOD /path"` of the request that succeeded
+    pub operation: String,
+    pub correlation_id: String,
+    /// Unix seconds
+    pub timestamp: i64,
+    pub sidecar_id: String,
+    pub depth: Option<i64>,
+    /// Hex token IDs of the verified delegation chain (root → current)
+    pub delegation_chain: Vec<String>,
+}
+
+/// Background receipt delivery to one webhook URL.
+pub struct ReceiptWebhook {
+    url: String,
+    client: reqwest::Client,
+    pending: Arc<Semaphore>,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ReceiptWebhook {
+    pub fn new(url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            url,
+            client,
+            pending: Arc::new(Semaphore::new(MAX_PENDING_DELIVERIES)),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Queue `event` for delivery and return immediately.
+    pub fn emit(self: &Arc<Self>, event: ReceiptEvent) {
+        let Ok(permit) = self.pending.clone().try_acquire_owned() else {
+            self.drop_event(&event, "too many pending deliveries");
+            return;
+        };
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            webhook.deliver(&event).await;
+            drop(permit);
+        });
+    }
+
+    /// Deliver `event`, retrying up to [`RECEIPT_WEBHOOK_MAX_ATTEMPTS`] times.
+    ///
+    /// Returns whether the webhook accepted it (any 2xx); otherwise it is dropped and counted.
+    pub async fn deliver(&self, event: &ReceiptEvent) -> bool {
+        let mut backoff = RETRY_BACKOFF;
+        for attempt in 1..=RECEIPT_WEBHOOK_MAX_ATTEMPTS {
+            let error = match self.client.post(&self.url).json(event).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    self.delivered.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                Ok(resp) => format!("status {}", resp.status()),
+                Err(e) => e.to_string(),
+            };
+            tracing::debug!(
+                correlation_id = %event.correlation_id,
+                attempt = attempt,
+                error = %error,
+                "Receipt webhook delivery failed"
+            );
+            if attempt < RECEIPT_WEBHOOK_MAX_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        self.drop_event(event, "retries exhausted");
+        false
+    }
+
+    /// Receipts the webhook accepted.
+    pub fn delivered_count(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Receipts given up on (dead-lettered).
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn drop_event(&self, event: &ReceiptEvent, reason: &str) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(
+            correlation_id = %event.correlation_id,
+            receipt_operation = %event.operation,
+            reason = reason,
+            "Receipt webhook delivery dropped"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Runtime configuration reload (SIGHUP)
+//!
+//! Re-reads the configuration with the same precedence as startup (CLI > env > file >
+//! defaults) and applies it with [`SidecarState::apply_config`]: the upstream URL, API
+//! key, root key and request-handling switches change for the next request, while the
+//! session key, caches and the warm upstream connection pool are kept. Settings that
+//! shape the process itself (listener, log level, background task intervals) still need
+//! a restart.
+//!
+//! [`SidecarState::apply_config`]: crate::state::SidecarState::apply_config
+
+use std::sync::Arc;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::{CliArgs, Config};
+use crate::error::VacError;
+use crate::proxy::UpstreamClientSettings;
+use crate::state::SharedState;
+
+/// Upstream client settings derived from `config`.
+pub fn upstream_client_settings(config: &Config) -> UpstreamClientSettings {
+    UpstreamClientSettings {
+        timeout: config.upstream_timeout_secs.map(std::time::Duration::from_secs),
+        ..Default::default()
+    }
+}
+
+/// Reload the configuration into `state`.
+///
+/// The new configuration is loaded before the state is touched, so an invalid one
+/// leaves the running settings in place.
+pub async fn reload_config(state: &SharedState, cli_args: &CliArgs) -> Result<(), VacError> {
+    let config = Config::load(cli_args)?;
+    let mut s = state.write().await;
+    s.apply_config(&config)?;
+    let client_rebuilt = s.set_upstream_client_settings(upstream_client_settings(&config));
+    info!(
+        upstream_url = %s.upstream_url,
+        upstream_client_rebuilt = client_rebuilt,
+        policy_hash = s.policy.as_ref().map(|p| p.hash()).unwrap_or_default(),
+        "Configuration reloaded"
+    );
+    Ok(())
+}
+
+/// Reload the configuration on every SIGHUP until `shutdown` is cancelled.
+#[cfg(unix)]
+pub async fn start_reload_on_sighup(state: SharedState, cli_args: Arc<CliArgs>, shutdown: CancellationToken) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            warn!("Failed to listen for SIGHUP, configuration reload disabled: {}", e);
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            received = hangups.recv() => {
+                if received.is_none() {
+                    break;
+                }
+                if let Err(e) = reload_config(&state, &cli_args).await {
+                    warn!("Configuration reload failed, keeping the current settings: {}", e);
+                }
+            }
+        }
+    }
+}
@@ -2,10 +2,23 @@
 //! 
 //! Implements a correlation ID cache to prevent immediate replay attacks.
 //! This is optional - most upstream APIs (Stripe, etc.) handle idempotency themselves.
+//!
+//! With `replay_cache_path` set, the live entries are also saved to disk periodically and
+//! on graceful shutdown, and loaded back at startup, so a restart does not reopen the
+//! replay window for correlation IDs seen just before it.
+//!
+//! With `replay_key_request_hash`, the key is a hash of the request itself
+//! ([`request_replay_key`]) instead, so an identical request is blocked whatever its
+//! correlation ID, while a different request may reuse one.
 
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::VacError;
 
 /// Cache entry for correlation IDs
 struct CacheEntry {
@@ -13,6 +26,28 @@ struct CacheEntry {
     first_seen: Instant,
 }
 
+/// On-disk form of the replay cache (`replay_cache_path`)
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplayCacheSnapshot {
+    /// Unix milliseconds when the snapshot was written
+    saved_at_ms: u64,
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEntry {
+    key: String,
+    /// How long before `saved_at_ms` the key was first seen
+    age_ms: u64,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 /// Replay cache to prevent duplicate correlation IDs
 /// 
 /// This cache stores correlation IDs that have been used recently.
@@ -107,16 +142,144 @@ impl ReplayCache {
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
+
+    /// Write the live (non-expired) entries to `path`, with how long ago each was first
+    /// seen. Written to a temporary file and renamed, so a crash mid-write leaves the
+    /// previous snapshot intact. Returns the number of entries saved.
+    ///
+    /// Iterates the map shard by shard; request handling is not blocked for the duration.
+    pub fn save_to(&self, path: &Path) -> Result<usize, VacError> {
+        let now = Instant::now();
+        let entries: Vec<SnapshotEntry> = self
+            .cache
+            .iter()
+            .filter_map(|entry| {
+                let age = now.duration_since(entry.value().first_seen);
+                (age < self.ttl).then(|| SnapshotEntry {
+                    key: entry.key().clone(),
+                    age_ms: age.as_millis() as u64,
+                })
+            })
+            .collect();
+        let snapshot = ReplayCacheSnapshot {
+            saved_at_ms: unix_millis(),
+            entries,
+        };
+        let json = serde_json::to_vec(&snapshot)
+            .map_err(|e| VacError::InternalError(format!("Failed to serialize replay cache: {}", e)))?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, json)
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| VacError::InternalError(format!("Failed to write replay cache {}: {}", path.display(), e)))?;
+        Ok(snapshot.entries.len())
+    }
+
+    /// Load entries saved by [`ReplayCache::save_to`], skipping any already past the TTL
+    /// (counting the time since the snapshot was written). A missing file loads nothing.
+    /// Returns the number of entries loaded.
+    pub fn load_from(&self, path: &Path) -> Result<usize, VacError> {
+        let json = match std::fs::read(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(VacError::ConfigError(format!(
+                    "Failed to read replay cache {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let snapshot: ReplayCacheSnapshot = serde_json::from_slice(&json)
+            .map_err(|e| VacError::ConfigError(format!("Invalid replay cache file {}: {}", path.display(), e)))?;
+        let since_save = Duration::from_millis(unix_millis().saturating_sub(snapshot.saved_at_ms));
+        let now = Instant::now();
+        let mut loaded = 0;
+        for entry in snapshot.entries {
+            let age = Duration::from_millis(entry.age_ms).saturating_add(since_save);
+            if age >= self.ttl {
+                continue;
+            }
+            // Shortly after boot the monotonic clock may not reach back that far; the
+            // entry then counts as seen now, which only lengthens its replay window.
+            let first_seen = now.checked_sub(age).unwrap_or(now);
+            self.cache.entry(entry.key).or_insert(CacheEntry { first_seen });
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
 }
 
 /// Default TTL for replay cache (5 minutes)
 pub const DEFAULT_REPLAY_CACHE_TTL: Duration = Duration::from_secs(300);
 
+/// Interval between replay cache cleanup passes
+pub const REPLAY_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default interval between replay cache snapshots (`replay_cache_persist_interval_secs`)
+pub const DEFAULT_REPLAY_CACHE_PERSIST_INTERVAL_SECS: u64 = 30;
+
+/// Remove expired correlation IDs every `interval` until `shutdown` is cancelled.
+pub async fn start_replay_cleanup_task(cache: ReplayCache, interval: Duration, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => cache.cleanup_expired(),
+        }
+    }
+}
+
+/// Save the replay cache to `path` every `interval` until `shutdown` is cancelled.
+///
+/// The final snapshot is not taken here but after in-flight requests have drained (see
+/// `app::save_replay_cache`), so correlation IDs used during the drain are kept too.
+pub async fn start_replay_persist_task(cache: ReplayCache, path: PathBuf, interval: Duration, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {
+                if let Err(e) = cache.save_to(&path) {
+                    tracing::warn!(error = %e, "Failed to persist replay cache");
+                }
+            }
+        }
+    }
+}
+
+/// Replay cache key for `replay_key_request_hash`: hex SHA-256 over the method, path and
+/// query, body and root token ID, each length-prefixed so no two requests share an encoding.
+pub fn request_replay_key(method: &str, path_and_query: &str, body: &[u8], token_id: &[u8; 32]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for part in [method.as_bytes(), path_and_query.as_bytes(), body, token_id] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    format!("request:{}", hex::encode(hasher.finalize()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::thread;
     
+    #[test]
+    fn test_request_replay_key_covers_every_part() {
+        let key = request_replay_key("POST", "/charge", b"{\"amount\":1}", &[1; 32]);
+        assert_eq!(key, request_replay_key("POST", "/charge", b"{\"amount\":1}", &[1; 32]));
+        assert_ne!(key, request_replay_key("POST", "/charge", b"{\"amount\":2}", &[1; 32]));
+        assert_ne!(key, request_replay_key("POST", "/charge?x=1", b"{\"amount\":1}", &[1; 32]));
+        assert_ne!(key, request_replay_key("PUT", "/charge", b"{\"amount\":1}", &[1; 32]));
+        assert_ne!(key, request_replay_key("POST", "/charge", b"{\"amount\":1}", &[2; 32]));
+        // Length prefixes keep a byte moving between fields from giving the same key.
+        assert_ne!(request_replay_key("GET", "/ab", b"c", &[0; 32]), request_replay_key("GET", "/a", b"bc", &[0; 32]));
+    }
+    
     #[test]
     fn test_replay_cache_allows_new_ids() {
         let cache = ReplayCache::new(Duration::from_secs(60), true);
@@ -185,4 +348,42 @@ mod tests {
         // Size should be 0 (or very small if timing is off)
         assert!(cache.size() <= 1); // Allow some timing variance
     }
+    
+    #[test]
+    fn test_replay_cache_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.json");
+        let cache = ReplayCache::new(Duration::from_secs(60), true);
+        cache.check_and_insert("id1").unwrap();
+        cache.check_and_insert("id2").unwrap();
+        assert_eq!(cache.save_to(&path).unwrap(), 2);
+        
+        // After a "restart", the saved IDs are still replays; new ones are not.
+        let restarted = ReplayCache::new(Duration::from_secs(60), true);
+        assert_eq!(restarted.load_from(&path).unwrap(), 2);
+        assert!(!restarted.check_and_insert("id1").unwrap());
+        assert!(!restarted.check_and_insert("id2").unwrap());
+        assert!(restarted.check_and_insert("id3").unwrap());
+        
+        // No snapshot yet: nothing to load.
+        assert_eq!(restarted.load_from(&dir.path().join("missing.json")).unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_replay_cache_reload_drops_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.json");
+        let cache = ReplayCache::new(Duration::from_millis(200), true);
+        cache.check_and_insert("old").unwrap();
+        thread::sleep(Duration::from_millis(120));
+        cache.check_and_insert("new").unwrap();
+        cache.save_to(&path).unwrap();
+        
+        // Time spent down counts against the TTL: by now "old" has expired.
+        thread::sleep(Duration::from_millis(100));
+        let restarted = ReplayCache::new(Duration::from_millis(200), true);
+        assert_eq!(restarted.load_from(&path).unwrap(), 1);
+        assert!(restarted.check_and_insert("old").unwrap());
+        assert!(!restarted.check_and_insert("new").unwrap());
+    }
 }
@@ -1,23 +1,245 @@
 use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
 use crate::error::VacError;
 
+/// Where a revocation came from (recorded in the revocation audit)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevocationSource {
+    /// Revocation list delivered by the control plane heartbeat
+    Heartbeat,
+    /// Revoked directly by an operator or the embedding application
+    Admin,
+    /// Loaded at startup, before the first heartbeat
+    Bootstrap,
+}
+
+impl RevocationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevocationSource::Heartbeat => "heartbeat",
+            RevocationSource::Admin => "admin",
+            RevocationSource::Bootstrap => "bootstrap",
+        }
+    }
+}
+
+/// Whether a token was added to or removed from the revocation filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevocationAction {
+    Revoke,
+    /// A revocation rescinded (e.g. issued in error)
+    Unrevoke,
+}
+
+/// One change to the revocation filter: which token, from where, and when
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RevocationAuditRecord {
+    /// Hex token ID
+    pub token_id: String,
+    pub action: RevocationAction,
+    pub source: RevocationSource,
+    /// Unix seconds
+    pub timestamp: u64,
+}
+
+/// Default `[revocation] false_positive_rate` when only `capacity` is set
+pub const DEFAULT_BLOOM_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// Bloom filter sizing (`[revocation]` config section)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevocationBloomSettings {
+    /// Revoked IDs the filter is sized for; past it the false-positive rate climbs
+    pub capacity: usize,
+    /// Target false-positive rate at `capacity`, in (0, 1)
+    pub false_positive_rate: f64,
+}
+
+impl RevocationBloomSettings {
+    pub fn validate(&self) -> Result<(), VacError> {
+        if self.capacity == 0 {
+            return Err(VacError::ConfigError("revocation.capacity must be greater than 0".to_string()));
+        }
+        if !(self.false_positive_rate > 0.0 && self.false_positive_rate < 1.0) {
+            return Err(VacError::ConfigError(format!(
+                "revocation.false_positive_rate must be between 0 and 1 (got {})",
+                self.false_positive_rate
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Fixed-size Bloom filter over 32-byte token IDs.
+///
+/// Token IDs are SHA-256 digests, so the `k` bit positions are derived from the ID bytes
+/// directly by double hashing instead of hashing again.
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    fn new(settings: RevocationBloomSettings) -> Self {
+        let n = settings.capacity as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-n * settings.false_positive_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn positions(&self, id: &[u8; 32]) -> impl Iterator<Item = u64> + '_ {
+        let h1 = u64::from_le_bytes(id[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(id[8..16].try_into().unwrap()) | 1;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    /// Set the ID's bits; true if any was unset (the ID was definitely not in the filter).
+    fn insert(&mut self, id: &[u8; 32]) -> bool {
+        let positions: Vec<u64> = self.positions(id).collect();
+        let mut new = false;
+        for bit in positions {
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            new |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        new
+    }
+
+    fn contains(&self, id: &[u8; 32]) -> bool {
+        self.positions(id)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1u64 << (bit % 64)) != 0)
+    }
+}
+
+/// Revoked token IDs: an exact set, or a Bloom filter (`[revocation] capacity`)
+enum RevokedIds {
+    Exact(HashSet<[u8; 32]>),
+    Bloom {
+        filter: BloomFilter,
+        settings: RevocationBloomSettings,
+        count: usize,
+        unrevoke_warned: bool, // Skipped un-revocation lists were warned about
+    },
+}
+
+impl RevokedIds {
+    /// Add `id`; true if it was not revoked before.
+    fn insert(&mut self, id: [u8; 32]) -> bool {
+        match self {
+            RevokedIds::Exact(set) => set.insert(id),
+            RevokedIds::Bloom { filter, settings, count, .. } => {
+                if !filter.insert(&id) {
+                    return false;
+                }
+                *count += 1;
+                if *count == settings.capacity + 1 {
+                    warn!(
+                        capacity = settings.capacity,
+                        "Revocation Bloom filter is over capacity; its false-positive rate now exceeds revocation.false_positive_rate"
+                    );
+                }
+                true
+            }
+        }
+    }
+
+    /// Remove `id`; true if it was revoked. Bloom filter entries cannot be removed.
+    fn remove(&mut self, id: &[u8; 32]) -> Result<bool, VacError> {
+        match self {
+            RevokedIds::Exact(set) => Ok(set.remove(id)),
+            RevokedIds::Bloom { .. } => Err(VacError::InternalError(
+                "Cannot un-revoke with a Bloom filter revocation list ([revocation] capacity); restart to rebuild it"
+                    .to_string(),
+            )),
+        }
+    }
+
+    fn contains(&self, id: &[u8; 32]) -> bool {
+        match self {
+            RevokedIds::Exact(set) => set.contains(id),
+            RevokedIds::Bloom { filter, .. } => filter.contains(id),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            RevokedIds::Exact(set) => set.len(),
+            RevokedIds::Bloom { count, .. } => *count,
+        }
+    }
+}
+
 /// Revocation filter for efficient token revocation checking
 /// 
-/// Phase 3: Using HashSet for simplicity. In production, this should use a Bloom Filter
-/// for memory efficiency (100k sessions → ~100KB vs ~3.2MB with HashSet).
-/// 
-/// For Phase 3, HashSet provides O(1) lookup with no false positives (simpler to debug).
+/// By default revoked IDs are kept in a `HashSet`: exact, but ~32 bytes (plus overhead)
+/// per ID, i.e. several MB for 100k revoked sessions. With `[revocation] capacity` set
+/// ([`RevocationFilter::with_bloom`]) they go into a Bloom filter instead, sized once
+/// for `capacity` IDs at `false_positive_rate` (100k at 0.1% is ~180 KB).
+///
+/// The Bloom filter never misses a revoked token, but a token that was never revoked is
+/// rejected as revoked with probability about `false_positive_rate` (more once over
+/// capacity). Such a token stays rejected until the filter is rebuilt at restart, so
+/// choose the rate by how many wrongly denied tokens the deployment can tolerate. A
+/// revoked ID that collides with earlier ones is counted and audited as already revoked.
+///
+/// Every newly revoked token emits a `Token revoked` audit event (tracing target
+/// `vac_sidecar::revocation_audit`) and, with `revocation_audit_log` set, is appended to
+/// that file as a JSON line.
 pub struct RevocationFilter {
-    revoked_tokens: HashSet<[u8; 32]>, // Set of revoked token IDs
+    revoked_tokens: RevokedIds,  // Revoked token IDs
+    audit_log: Option<PathBuf>,  // JSON-lines revocation audit file
 }
 
 impl RevocationFilter {
     /// Create a new revocation filter
     pub fn new() -> Self {
         Self {
-            revoked_tokens: HashSet::new(),
+            revoked_tokens: RevokedIds::Exact(HashSet::new()),
+            audit_log: None,
+        }
+    }
+
+    /// Create a Bloom-filter-backed revocation filter sized for `capacity` revoked IDs
+    /// at `false_positive_rate`.
+    pub fn with_bloom(capacity: usize, false_positive_rate: f64) -> Result<Self, VacError> {
+        let settings = RevocationBloomSettings { capacity, false_positive_rate };
+        settings.validate()?;
+        Ok(Self {
+            revoked_tokens: RevokedIds::Bloom {
+                filter: BloomFilter::new(settings),
+                settings,
+                count: 0,
+                unrevoke_warned: false,
+            },
+            audit_log: None,
+        })
+    }
+
+    /// Bloom filter sizing, or `None` for the exact set
+    pub fn bloom_settings(&self) -> Option<RevocationBloomSettings> {
+        match &self.revoked_tokens {
+            RevokedIds::Exact(_) => None,
+            RevokedIds::Bloom { settings, .. } => Some(*settings),
         }
     }
+
+    /// Persist revocation audit records to `path` (JSON lines, appended); `None` only logs them.
+    pub fn set_audit_log(&mut self, path: Option<PathBuf>) {
+        self.audit_log = path;
+    }
     
     /// Check if a token ID is revoked
     pub fn is_revoked(&self, token_id: &[u8]) -> bool {
@@ -32,7 +254,7 @@ impl RevocationFilter {
     }
     
     /// Add a token ID to the revocation list
-    pub fn revoke(&mut self, token_id: &[u8]) -> Result<(), VacError> {
+    pub fn revoke(&mut self, token_id: &[u8], source: RevocationSource) -> Result<(), VacError> {
         if token_id.len() != 32 {
             return Err(VacError::InternalError(
                 format!("Invalid token ID length: expected 32 bytes, got {}", token_id.len())
@@ -41,18 +263,96 @@ impl RevocationFilter {
         
         let mut hash = [0u8; 32];
         hash.copy_from_slice(token_id);
-        self.revoked_tokens.insert(hash);
+        if self.revoked_tokens.insert(hash) {
+            self.audit(&hash, source, RevocationAction::Revoke);
+        }
+        Ok(())
+    }
+
+    /// Remove a token ID from the revocation list, rescinding a revocation issued in error.
+    ///
+    /// Fails with a Bloom filter backend, whose entries cannot be removed.
+    pub fn unrevoke(&mut self, token_id: &[u8], source: RevocationSource) -> Result<(), VacError> {
+        let hash: [u8; 32] = token_id.try_into().map_err(|_| {
+            VacError::InternalError(format!("Invalid token ID length: expected 32 bytes, got {}", token_id.len()))
+        })?;
+        if self.revoked_tokens.remove(&hash)? {
+            self.audit(&hash, source, RevocationAction::Unrevoke);
+        }
         Ok(())
     }
     
-    /// Update the filter with a list of revoked token IDs (from heartbeat response)
-    pub fn update_from_ids(&mut self, revoked_ids: Vec<[u8; 32]>) {
+    /// Update the filter with a list of revoked token IDs (e.g. from a heartbeat response)
+    pub fn update_from_ids(&mut self, revoked_ids: Vec<[u8; 32]>, source: RevocationSource) {
         for id in revoked_ids {
-            self.revoked_tokens.insert(id);
+            if self.revoked_tokens.insert(id) {
+                self.audit(&id, source, RevocationAction::Revoke);
+            }
+        }
+    }
+
+    /// Remove a list of token IDs (e.g. `unrevoked_token_ids` from a heartbeat response)
+    ///
+    /// With a Bloom filter backend the list is skipped (its entries cannot be removed);
+    /// this is warned about once, not on every heartbeat that repeats the list.
+    pub fn unrevoke_ids(&mut self, unrevoked_ids: Vec<[u8; 32]>, source: RevocationSource) -> Result<(), VacError> {
+        if let RevokedIds::Bloom { unrevoke_warned, .. } = &mut self.revoked_tokens {
+            if !unrevoked_ids.is_empty() && !*unrevoke_warned {
+                *unrevoke_warned = true;
+                warn!(
+                    unrevoked_count = unrevoked_ids.len(),
+                    "Ignoring unrevoked_token_ids: a Bloom filter revocation list ([revocation] capacity) cannot un-revoke; restart to rebuild it"
+                );
+            }
+            return Ok(());
+        }
+        for id in unrevoked_ids {
+            self.unrevoke(&id, source)?;
+        }
+        Ok(())
+    }
+
+    /// Record a newly revoked token. Audit failures never undo the revocation.
+    fn audit(&self, token_id: &[u8; 32], source: RevocationSource, action: RevocationAction) {
+        let record = RevocationAuditRecord {
+            token_id: hex::encode(token_id),
+            action,
+            source,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+        info!(
+            target: "vac_sidecar::revocation_audit",
+            token_id = %record.token_id,
+            revocation_source = source.as_str(),
+            timestamp = record.timestamp,
+            "{}",
+            match action {
+                RevocationAction::Revoke => "Token revoked",
+                RevocationAction::Unrevoke => "Token unrevoked",
+            }
+        );
+        let Some(path) = &self.audit_log else {
+            return;
+        };
+        let written = serde_json::to_string(&record)
+            .map_err(std::io::Error::other)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = written {
+            warn!(
+                audit_log = %path.display(),
+                error = %e,
+                "Failed to write revocation audit record"
+            );
         }
     }
     
-    /// Get the number of revoked tokens
+    /// Get the number of revoked tokens (with a Bloom filter, the IDs it took as new)
     pub fn revoked_count(&self) -> usize {
         self.revoked_tokens.len()
     }
@@ -81,7 +381,7 @@ mod tests {
         let mut f = RevocationFilter::new();
         let id = [1u8; 32];
         assert!(!f.is_revoked(&id));
-        f.revoke(&id).unwrap();
+        f.revoke(&id, RevocationSource::Admin).unwrap();
         assert!(f.is_revoked(&id));
         assert_eq!(f.revoked_count(), 1);
     }
@@ -91,7 +391,7 @@ mod tests {
         let mut f = RevocationFilter::new();
         let id1 = [1u8; 32];
         let id2 = [2u8; 32];
-        f.update_from_ids(vec![id1, id2]);
+        f.update_from_ids(vec![id1, id2], RevocationSource::Heartbeat);
         assert!(f.is_revoked(&id1));
         assert!(f.is_revoked(&id2));
         assert_eq!(f.revoked_count(), 2);
@@ -101,7 +401,7 @@ mod tests {
     fn revocation_filter_revoke_invalid_length() {
         let mut f = RevocationFilter::new();
         let short = [0u8; 16];
-        let err = f.revoke(&short).unwrap_err();
+        let err = f.revoke(&short, RevocationSource::Admin).unwrap_err();
         assert!(matches!(err, VacError::InternalError(_)));
     }
 
@@ -112,6 +412,101 @@ mod tests {
         assert!(f.is_revoked(&short));
     }
 
+    #[test]
+    fn revocations_are_audited_with_their_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("revocations.jsonl");
+        let mut f = RevocationFilter::new();
+        f.set_audit_log(Some(path.clone()));
+        f.update_from_ids(vec![[1u8; 32]], RevocationSource::Heartbeat);
+        f.revoke(&[2u8; 32], RevocationSource::Admin).unwrap();
+        // Already revoked: not a change, so not audited again.
+        f.update_from_ids(vec![[1u8; 32], [2u8; 32]], RevocationSource::Heartbeat);
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["token_id"], hex::encode([1u8; 32]));
+        assert_eq!(records[0]["source"], "heartbeat");
+        assert_eq!(records[1]["token_id"], hex::encode([2u8; 32]));
+        assert_eq!(records[1]["source"], "admin");
+        assert!(records[1]["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn unrevoke_removes_a_revocation_and_is_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("revocations.jsonl");
+        let mut f = RevocationFilter::new();
+        f.set_audit_log(Some(path.clone()));
+        f.update_from_ids(vec![[1u8; 32], [2u8; 32]], RevocationSource::Heartbeat);
+        f.unrevoke(&[1u8; 32], RevocationSource::Admin).unwrap();
+        // Not revoked: nothing to remove, nothing audited.
+        f.unrevoke_ids(vec![[1u8; 32], [3u8; 32]], RevocationSource::Heartbeat).unwrap();
+        assert!(!f.is_revoked(&[1u8; 32]));
+        assert!(f.is_revoked(&[2u8; 32]));
+        assert_eq!(f.revoked_count(), 1);
+        assert!(f.unrevoke(&[1u8; 16], RevocationSource::Admin).is_err());
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["action"], "revoke");
+        assert_eq!(records[2]["action"], "unrevoke");
+        assert_eq!(records[2]["token_id"], hex::encode([1u8; 32]));
+        assert_eq!(records[2]["source"], "admin");
+    }
+
+    #[test]
+    fn bloom_filter_cannot_unrevoke() {
+        let mut f = RevocationFilter::with_bloom(100, 0.01).unwrap();
+        f.revoke(&[1u8; 32], RevocationSource::Admin).unwrap();
+        assert!(f.unrevoke(&[1u8; 32], RevocationSource::Admin).is_err());
+        assert!(f.is_revoked(&[1u8; 32]));
+        // Heartbeat lists are skipped rather than failing every heartbeat.
+        f.unrevoke_ids(vec![[1u8; 32]], RevocationSource::Heartbeat).unwrap();
+        f.unrevoke_ids(vec![[1u8; 32]], RevocationSource::Heartbeat).unwrap();
+        assert!(f.is_revoked(&[1u8; 32]));
+    }
+
+    #[test]
+    fn bloom_filter_has_no_false_negatives() {
+        let mut f = RevocationFilter::with_bloom(1_000, 0.01).unwrap();
+        let revoked: Vec<[u8; 32]> = (0..1_000).map(|i| extract_token_id(&format!("revoked-{}", i)).unwrap()).collect();
+        f.update_from_ids(revoked[..500].to_vec(), RevocationSource::Heartbeat);
+        for id in &revoked[500..] {
+            f.revoke(id, RevocationSource::Admin).unwrap();
+        }
+        assert!(revoked.iter().all(|id| f.is_revoked(id)));
+        assert!(f.revoked_count() <= 1_000);
+
+        // Never-revoked IDs: false positives stay near the configured rate.
+        let false_positives = (0..10_000)
+            .map(|i| extract_token_id(&format!("live-{}", i)).unwrap())
+            .filter(|id| f.is_revoked(id))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+
+        // Malformed IDs are still rejected.
+        assert!(f.is_revoked(&[0u8; 16]));
+    }
+
+    #[test]
+    fn bloom_settings_are_validated() {
+        assert!(RevocationFilter::with_bloom(0, 0.01).is_err());
+        assert!(RevocationFilter::with_bloom(100, 0.0).is_err());
+        assert!(RevocationFilter::with_bloom(100, 1.0).is_err());
+        let f = RevocationFilter::with_bloom(100, 0.01).unwrap();
+        assert_eq!(f.bloom_settings().unwrap().capacity, 100);
+        assert!(RevocationFilter::new().bloom_settings().is_none());
+    }
+
     #[test]
     fn extract_token_id_deterministic() {
         let id1 = extract_token_id("abc").unwrap();
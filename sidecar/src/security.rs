@@ -167,6 +167,25 @@ pub fn validate_body_size(size: usize) -> bool {
     size <= MAX_REQUEST_BODY_SIZE
 }
 
+/// Validate declared `Content-Length` against the body bytes actually received
+///
+/// A mismatch (or an unparseable or repeated-but-different `Content-Length`) means the
+/// client and the sidecar disagree about where the body ends, which is how request
+/// smuggling starts. Requests without `Content-Length` (e.g. chunked) pass.
+pub fn validate_content_length(headers: &axum::http::HeaderMap, actual: usize) -> bool {
+    headers
+        .get_all(axum::http::header::CONTENT_LENGTH)
+        .iter()
+        .all(|value| {
+            value
+                .to_str()
+                .ok()
+                .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|v| v.parse::<usize>().ok())
+                == Some(actual)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,6 +220,22 @@ mod tests {
         assert!(validate_body_size(1024));
         assert!(!validate_body_size(MAX_REQUEST_BODY_SIZE + 1));
     }
+
+    #[test]
+    fn test_validate_content_length() {
+        use axum::http::{header::CONTENT_LENGTH, HeaderMap};
+        let mut headers = HeaderMap::new();
+        assert!(validate_content_length(&headers, 5)); // Not declared
+        headers.insert(CONTENT_LENGTH, "5".parse().unwrap());
+        assert!(validate_content_length(&headers, 5));
+        assert!(!validate_content_length(&headers, 4)); // Shorter than declared
+        assert!(!validate_content_length(&headers, 6)); // Longer than declared
+        headers.insert(CONTENT_LENGTH, "+5".parse().unwrap());
+        assert!(!validate_content_length(&headers, 5));
+        headers.insert(CONTENT_LENGTH, "5".parse().unwrap());
+        headers.append(CONTENT_LENGTH, "6".parse().unwrap());
+        assert!(!validate_content_length(&headers, 5)); // Conflicting values
+    }
     
     #[test]
     fn test_secure_string() {
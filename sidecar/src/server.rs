@@ -0,0 +1,162 @@
+//! Inbound HTTP listener
+//!
+//! Serves the sidecar router over HTTP/1.1 and, when `server_http2_enabled` is set,
+//! HTTP/2 as well. The protocol is detected per connection: cleartext clients use HTTP/2
+//! prior knowledge (h2c) by sending the HTTP/2 connection preface; everything else is
+//! served as HTTP/1.1. Agents that issue many requests can then multiplex them over one
+//! connection instead of opening a connection per in-flight request.
+//!
+//! When the shutdown token is cancelled the listener stops accepting, every open
+//! connection is told to finish its in-flight requests and close, and `serve` returns
+//! once they have. [`serve_with_grace`] bounds that drain (`shutdown_grace_secs`), and
+//! [`shutdown_signal`] is what cancels the token in the sidecar binary.
+
+use std::time::Duration;
+
+use axum::extract::ConnectInfo;
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tower::ServiceExt;
+
+/// Default bound on the shutdown drain, inside Kubernetes' default 30s termination grace period.
+pub const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 25;
+
+/// Accept connections on `listener` and serve `app` until `shutdown` is cancelled.
+///
+/// With `http2_enabled = false` every connection is served as HTTP/1.1 (the
+/// historical behavior) and HTTP/2 prefaces are rejected.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    http2_enabled: bool,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let connections = TaskTracker::new();
+    loop {
+        let accepted = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => accepted,
+        };
+        let (stream, peer) = match accepted {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Usually transient (e.g. too many open files); back off instead of spinning.
+                tracing::warn!(error = %e, "Failed to accept connection");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        let io = TokioIo::new(stream);
+        // Record the socket peer for the guard's `trusted_proxies` check.
+        let service = TowerToHyperService::new(app.clone().map_request(move |mut req: hyper::Request<hyper::body::Incoming>| {
+            req.extensions_mut().insert(ConnectInfo(peer));
+            req
+        }));
+        let shutdown = shutdown.clone();
+        connections.spawn(async move {
+            let result = if http2_enabled {
+                let builder = auto::Builder::new(TokioExecutor::new());
+                let conn = builder.serve_connection_with_upgrades(io, service);
+                tokio::pin!(conn);
+                tokio::select! {
+                    result = conn.as_mut() => result,
+                    _ = shutdown.cancelled() => {
+                        conn.as_mut().graceful_shutdown();
+                        conn.await
+                    }
+                }
+            } else {
+                // Not `auto::Builder::http1_only`: the upgrade-capable variant ignores it.
+                let conn = http1::Builder::new().serve_connection(io, service).with_upgrades();
+                tokio::pin!(conn);
+                tokio::select! {
+                    result = conn.as_mut() => result,
+                    _ = shutdown.cancelled() => {
+                        conn.as_mut().graceful_shutdown();
+                        conn.await
+                    }
+                }
+                .map_err(Into::into)
+            };
+            if let Err(e) = result {
+                tracing::debug!(peer = %peer, error = %e, "Connection closed with error");
+            }
+        });
+    }
+
+    drop(listener);
+    connections.close();
+    tracing::info!(open_connections = connections.len(), "Listener closed, draining connections");
+    connections.wait().await;
+    Ok(())
+}
+
+/// [`serve`], giving in-flight requests at most `grace` to finish once `shutdown` is
+/// cancelled.
+///
+/// Returns when the drain completes or the grace period runs out; connections still
+/// open then are abandoned and die with the process.
+pub async fn serve_with_grace(
+    listener: TcpListener,
+    app: Router,
+    http2_enabled: bool,
+    shutdown: CancellationToken,
+    grace: Duration,
+) -> std::io::Result<()> {
+    let server = serve(listener, app, http2_enabled, shutdown.clone());
+    tokio::pin!(server);
+    tokio::select! {
+        result = server.as_mut() => return result,
+        _ = shutdown.cancelled() => {}
+    }
+    match tokio::time::timeout(grace, server).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!(
+                shutdown_grace_secs = grace.as_secs(),
+                "Shutdown grace period elapsed, dropping connections with requests still in flight"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Resolve on Ctrl-C (SIGINT) or, on Unix, SIGTERM.
+///
+/// A signal that cannot be listened for is logged and never fires, so a broken handler
+/// does not shut the sidecar down.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
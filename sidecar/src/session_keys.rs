@@ -0,0 +1,165 @@
+//! Published session key set (`accept_peer_receipts`)
+//!
+//! Receipts are signed with the minting sidecar's ephemeral session key, so by default
+//! only that instance can verify them and a flow must stay on one sidecar. With
+//! `accept_peer_receipts`, the sidecar also fetches the control plane's
+//! `GET /session-keys` (the session public key of every sidecar that is still
+//! heartbeating, with an expiry) and accepts receipts signed by any unexpired key in it.
+
+use base64::{engine::general_purpose, Engine as _};
+use biscuit_auth::PublicKey;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::control_plane_client::ControlPlaneClient;
+use crate::error::VacError;
+use crate::heartbeat::read_body_limited;
+use crate::state::SharedState;
+
+/// Upper bound on the `/session-keys` response body
+const SESSION_KEYS_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// One entry of the control plane's `GET /session-keys` response
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublishedSessionKey {
+    pub sidecar_id: String,
+    /// Base64-encoded session public key (as sent in the heartbeat)
+    pub session_key_pub: String,
+    /// Unix seconds after which receipts signed by this key are no longer accepted
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionKeysResponse {
+    keys: Vec<PublishedSessionKey>,
+}
+
+#[derive(Debug, Clone)]
+struct SessionKey {
+    public_key: PublicKey,
+    expires_at: u64,
+}
+
+/// Cached set of peer session public keys, as last fetched from the control plane.
+#[derive(Debug, Clone, Default)]
+pub struct SessionKeySet {
+    keys: Arc<Vec<SessionKey>>,
+}
+
+impl SessionKeySet {
+    /// Build the set from published entries; entries whose key does not decode are skipped.
+    pub fn from_published(published: Vec<PublishedSessionKey>) -> Self {
+        let keys = published
+            .into_iter()
+            .filter_map(|entry| {
+                let public_key = general_purpose::STANDARD
+                    .decode(&entry.session_key_pub)
+                    .ok()
+                    .and_then(|bytes| PublicKey::from_bytes(&bytes).ok());
+                if public_key.is_none() {
+                    warn!(
+                        sidecar_id = %entry.sidecar_id,
+                        "Skipping undecodable published session key"
+                    );
+                }
+                Some(SessionKey {
+                    public_key: public_key?,
+                    expires_at: entry.expires_at,
+                })
+            })
+            .collect();
+        Self { keys: Arc::new(keys) }
+    }
+
+    /// Keys that have not expired at `now` (Unix seconds).
+    pub fn active_keys(&self, now: u64) -> impl Iterator<Item = &PublicKey> {
+        self.keys
+            .iter()
+            .filter(move |key| key.expires_at > now)
+            .map(|key| &key.public_key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Fetch the published session key set from `GET {control_plane_url}/session-keys`.
+pub async fn fetch_session_keys(
+    client: &ControlPlaneClient,
+    control_plane_url: &str,
+) -> Result<SessionKeySet, VacError> {
+    let url = format!("{}/session-keys", control_plane_url);
+    let response = client
+        .get(&url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| VacError::ProxyError(format!("Session key request failed: {}", e)))?;
+    client.check_pin(&response)?;
+    if !response.status().is_success() {
+        return Err(VacError::ProxyError(format!(
+            "Session key request returned status: {}",
+            response.status()
+        )));
+    }
+    let body = read_body_limited(response, SESSION_KEYS_MAX_BODY_BYTES, "Session key").await?;
+    let parsed: SessionKeysResponse = serde_json::from_slice(&body)
+        .map_err(|e| VacError::InternalError(format!("Failed to parse session keys: {}", e)))?;
+    Ok(SessionKeySet::from_published(parsed.keys))
+}
+
+/// Fetch the session key set and cache it in the sidecar state; returns the number of keys.
+///
+/// On failure the previously cached set is kept; its entries still expire on schedule.
+pub async fn refresh_session_keys(state: &SharedState, control_plane_url: &str) -> Result<usize, VacError> {
+    let client = state.read().await.control_plane_client.clone();
+    let keys = fetch_session_keys(&client, control_plane_url).await?;
+    let count = keys.len();
+    state.write().await.session_key_set = keys;
+    Ok(count)
+}
+
+/// Current Unix time in seconds (0 if the clock is before the epoch)
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use biscuit_auth::KeyPair;
+
+    fn published(kp: &KeyPair, expires_at: u64) -> PublishedSessionKey {
+        PublishedSessionKey {
+            sidecar_id: "sidecar-a".to_string(),
+            session_key_pub: general_purpose::STANDARD.encode(kp.public().to_bytes()),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn expired_and_undecodable_keys_are_not_active() {
+        let live = KeyPair::new();
+        let expired = KeyPair::new();
+        let mut garbage = published(&KeyPair::new(), 2_000);
+        garbage.session_key_pub = "not base64!".to_string();
+        let set = SessionKeySet::from_published(vec![
+            published(&live, 2_000),
+            published(&expired, 1_000),
+            garbage,
+        ]);
+        assert_eq!(set.len(), 2);
+        let active: Vec<_> = set.active_keys(1_500).collect();
+        assert_eq!(active, vec![&live.public()]);
+    }
+}
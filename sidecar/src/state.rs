@@ -1,12 +1,31 @@
 use biscuit_auth::{KeyPair, PublicKey};
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::SystemTime;
-use crate::proxy::AxumProxy;
+use std::time::{Duration, SystemTime};
+use crate::proxy::{AxumProxy, UpstreamClientSettings};
+use crate::grpc::{GrpcProxy, UpstreamProtocol};
 use crate::revocation::RevocationFilter;
-use crate::adapter::AdapterRegistry;
+use crate::adapter::{AdapterRegistry, AdapterReservedFacts};
+use crate::flow_graph::FlowGraph;
 use crate::security::SecureString;
-use crate::rate_limit::RateLimiter;
+use crate::rate_limit::{RateLimitBackend, RateLimitKey, RateLimiter};
 use crate::replay_cache::ReplayCache;
+use crate::policy::{OptionsAsterisk, PathTrailingSlash};
+use crate::error::{ErrorResponseFormat, VacError};
+use crate::config::Config;
+use crate::metrics::RequestMetrics;
+use crate::guard::{CorrelationIdGenerator, UuidCorrelationIds};
+use crate::coalesce::RequestCoalescer;
+use crate::receipt_webhook::ReceiptWebhook;
+use crate::step_limit::StepLimiter;
+use crate::adapter_limit::AdapterConcurrencyLimiter;
+use crate::body_budget::BodyBudget;
+use crate::correlation_binding::CorrelationBindings;
+use crate::session_keys::SessionKeySet;
+use crate::client_addr::TrustedProxies;
+use crate::biscuit::RootTokenVerifier;
+use crate::policy_pin::PinnedPolicy;
+use crate::control_plane_client::ControlPlaneClient;
 
 /// Sidecar state (Orange Zone - Semi-Trusted)
 /// 
@@ -21,6 +40,9 @@ use crate::replay_cache::ReplayCache;
 pub struct SidecarState {
     pub session_key: KeyPair,
     pub user_root_public_key: PublicKey,
+    // Root keys by biscuit root key ID during a rotation (`root_public_keys`); empty =
+    // only `user_root_public_key`
+    pub root_public_keys: Vec<PublicKey>,
     pub api_key: SecureString, // Secure memory for API key
     pub proxy: Arc<AxumProxy>,
     pub upstream_url: String,
@@ -37,8 +59,101 @@ pub struct SidecarState {
     pub adapter_registry: AdapterRegistry,
     // Rate limiting
     pub rate_limiter: RateLimiter,
+    // Where the guard takes tokens: `rate_limiter` itself unless `rate_limit_backend`
+    // says otherwise (set at startup)
+    pub rate_limit_backend: Arc<dyn RateLimitBackend>,
+    pub rate_limit_key: RateLimitKey,
     // Phase 4.8: Replay attack mitigation
     pub replay_cache: ReplayCache,
+    // Trailing-slash normalization for `operation` facts and forwarding
+    pub path_trailing_slash: PathTrailingSlash,
+    // `OPTIONS *` handling
+    pub options_asterisk: OptionsAsterisk,
+    // Serialization of error response bodies
+    pub error_response_format: ErrorResponseFormat,
+    // Guard, upstream and adapter metrics served on `/_vac/metrics` (or `metrics_addr`)
+    pub metrics: RequestMetrics,
+    // Send the verified delegation summary to the upstream
+    pub forward_delegation_chain: bool,
+    // Send the verified delegation depth as `X-VAC-Depth` upstream and back to the client
+    pub expose_delegation_depth: bool,
+    // Methods that get a receipt on 2xx (None = all methods)
+    pub mint_receipts_for_methods: Option<Vec<String>>,
+    // Answer policy denials with 200 + decision marker instead of 403
+    pub soft_deny: bool,
+    // Upstream statuses passed through to the client (None = all)
+    pub upstream_allowed_statuses: Option<Vec<u16>>,
+    // Hosts requests may be forwarded to (`upstream_host_allowlist`); None = any host
+    pub upstream_host_allowlist: Option<Vec<String>>,
+    // Generates correlation IDs for requests without one (swappable in tests)
+    pub correlation_id_generator: Arc<dyn CorrelationIdGenerator>,
+    // Reject requests without a valid client-supplied X-Correlation-ID
+    pub require_correlation_id: bool,
+    // Longest accepted base64 token (bearer, delegation, receipt), checked before parsing
+    pub max_token_bytes: usize,
+    // Share one upstream call between identical concurrent GET requests
+    pub coalesce_idempotent: bool,
+    // In-flight upstream calls used by `coalesce_idempotent`
+    pub coalescer: Arc<RequestCoalescer>,
+    // Reject root tokens whose blocks or predicates go beyond what the issuer writes
+    pub strict_token_shape: bool,
+    // Largest revocation list applied from a single heartbeat response
+    pub max_revocation_list_size: usize,
+    // Consecutive successful heartbeats that lift a heartbeat-failure lockdown (0 = never)
+    pub lockdown_recovery_successes: u32,
+    // Background delivery of minted receipts to `receipt_webhook_url` (None = disabled)
+    pub receipt_webhook: Option<Arc<ReceiptWebhook>>,
+    // Receipts minted per correlation ID, capped by `max_steps_per_correlation`
+    pub step_limiter: StepLimiter,
+    // Replay cache key is `correlation_id METHOD path` instead of the correlation ID alone
+    pub replay_key_includes_operation: bool,
+    // Replay cache key is a hash of method, path and query, body and root token ID
+    // (checked once the body is read) instead of the correlation ID
+    pub replay_key_request_hash: bool,
+    // Adapter runs in flight per correlation ID, capped by `max_concurrent_adapters_per_correlation`
+    pub adapter_concurrency: AdapterConcurrencyLimiter,
+    // Bytes reserved by buffered request bodies, capped by `max_total_body_bytes`
+    pub body_budget: BodyBudget,
+    // Hand adapters canonical JSON (sorted keys, no extra whitespace)
+    pub canonicalize_json_body: bool,
+    // With `canonicalize_json_body`, forward the canonical body rather than the original
+    pub forward_canonical_json_body: bool,
+    // Reject a correlation ID presented with a token other than the one that first used it
+    pub bind_correlation_to_token: bool,
+    pub correlation_bindings: CorrelationBindings,
+    // Accept receipts signed by keys in the control plane's published session key set
+    pub accept_peer_receipts: bool,
+    pub session_key_set: SessionKeySet,
+    // Client for heartbeats and other control plane calls (pins `control_plane_cert_fingerprint`)
+    pub control_plane_client: ControlPlaneClient,
+    // Peers whose X-Forwarded-* headers are honored
+    pub trusted_proxies: TrustedProxies,
+    // Accept receipts in the compact `X-VAC-Receipt-Bin` header
+    pub accept_compact_receipts: bool,
+    // Receipt validity window (`receipt_expiry_secs`) and clock skew allowance (`clock_skew_grace_secs`)
+    pub receipt_expiry_secs: u64,
+    pub clock_skew_grace_secs: u64,
+    // External root token signature check (HSM/KMS); None = in-process with user_root_public_key
+    pub root_token_verifier: Option<Arc<dyn RootTokenVerifier>>,
+    // Handling of adapter facts named like trusted predicates
+    pub adapter_reserved_facts: AdapterReservedFacts,
+    // Bound on policy evaluation (`policy_eval_timeout_ms`); None = unbounded
+    pub policy_eval_timeout: Option<Duration>,
+    // Operator policy from `policy_file`, guarded by `policy_pin_hash`; None = no allow
+    // policy, so every request is denied
+    pub policy: Option<Arc<PinnedPolicy>>,
+    // Zero facts from a pinned adapter rejects the request
+    pub require_adapter_facts: bool,
+    // Request headers passed to adapters in their context (`adapter_context_headers`)
+    pub adapter_context_headers: Vec<String>,
+    // Declarative flow graph checked against presented receipts (`[flow]`); None = off
+    pub flow_graph: Option<Arc<FlowGraph>>,
+    // Operations minting one receipt per sub-operation (`[[batch_receipts]]`)
+    pub batch_receipts: HashMap<String, Vec<String>>,
+    // Upstream wire protocol (`protocol`); fixed at startup
+    pub protocol: UpstreamProtocol,
+    // HTTP/2 connection to a gRPC upstream, used with `protocol = grpc`
+    pub grpc_proxy: Arc<GrpcProxy>,
 }
 
 /// Shared state for use across async tasks
@@ -60,10 +175,17 @@ impl SidecarState {
         
         // Attempt to lock API key memory (best-effort, logs warning on failure)
         crate::security::lock_string_memory(secure_api_key.as_str());
+        let rate_limiter = RateLimiter::new(
+            rate_limit_max_requests,
+            std::time::Duration::from_secs(rate_limit_window_secs),
+        );
+        // Shared with the adapter registry, which records adapter run times into it
+        let metrics = RequestMetrics::new();
         
         Self {
             session_key: KeyPair::new(), // Generate new ephemeral session key
             user_root_public_key,
+            root_public_keys: Vec::new(),
             api_key: secure_api_key,
             proxy: Arc::new(AxumProxy::new()),
             upstream_url,
@@ -74,15 +196,70 @@ impl SidecarState {
             last_heartbeat: now,
             last_key_rotation: now,
             revocation_filter: Arc::new(std::sync::RwLock::new(RevocationFilter::new())),
-            adapter_registry: AdapterRegistry::new(),
-            rate_limiter: RateLimiter::new(
-                rate_limit_max_requests,
-                std::time::Duration::from_secs(rate_limit_window_secs),
-            ),
+            adapter_registry: AdapterRegistry::with_metrics(metrics.clone()),
+            rate_limit_backend: Arc::new(rate_limiter.clone()),
+            rate_limiter,
+            rate_limit_key: RateLimitKey::default(),
             replay_cache: ReplayCache::new(
                 std::time::Duration::from_secs(replay_cache_ttl_secs),
                 replay_cache_enabled,
             ),
+            path_trailing_slash: PathTrailingSlash::default(),
+            options_asterisk: OptionsAsterisk::default(),
+            error_response_format: ErrorResponseFormat::default(),
+            metrics,
+            forward_delegation_chain: false,
+            expose_delegation_depth: false,
+            mint_receipts_for_methods: None,
+            soft_deny: false,
+            upstream_allowed_statuses: None,
+            upstream_host_allowlist: None,
+            correlation_id_generator: Arc::new(UuidCorrelationIds),
+            require_correlation_id: false,
+            max_token_bytes: crate::biscuit::DEFAULT_MAX_TOKEN_BYTES,
+            coalesce_idempotent: false,
+            coalescer: Arc::new(RequestCoalescer::new()),
+            strict_token_shape: false,
+            max_revocation_list_size: crate::heartbeat::DEFAULT_MAX_REVOCATION_LIST_SIZE,
+            lockdown_recovery_successes: crate::heartbeat::DEFAULT_LOCKDOWN_RECOVERY_SUCCESSES,
+            receipt_webhook: None,
+            step_limiter: StepLimiter::new(None, crate::step_limit::DEFAULT_STEP_COUNT_TTL),
+            replay_key_includes_operation: false,
+            replay_key_request_hash: false,
+            adapter_concurrency: AdapterConcurrencyLimiter::new(None),
+            body_budget: BodyBudget::new(None),
+            canonicalize_json_body: false,
+            forward_canonical_json_body: true,
+            bind_correlation_to_token: false,
+            correlation_bindings: CorrelationBindings::new(
+                crate::correlation_binding::DEFAULT_CORRELATION_BINDING_TTL,
+            ),
+            accept_peer_receipts: false,
+            session_key_set: SessionKeySet::default(),
+            control_plane_client: ControlPlaneClient::default(),
+            trusted_proxies: TrustedProxies::default(),
+            accept_compact_receipts: false,
+            receipt_expiry_secs: crate::receipt::DEFAULT_RECEIPT_EXPIRY_SECS,
+            clock_skew_grace_secs: crate::receipt::DEFAULT_CLOCK_SKEW_GRACE_SECS,
+            root_token_verifier: None,
+            adapter_reserved_facts: AdapterReservedFacts::default(),
+            policy_eval_timeout: None,
+            policy: None,
+            require_adapter_facts: false,
+            adapter_context_headers: Vec::new(),
+            flow_graph: None,
+            batch_receipts: HashMap::new(),
+            protocol: UpstreamProtocol::Http,
+            grpc_proxy: Arc::new(GrpcProxy::new()),
+        }
+    }
+    
+    /// Root keys a token may be signed with, indexed by biscuit root key ID.
+    pub fn root_keys(&self) -> Vec<PublicKey> {
+        if self.root_public_keys.is_empty() {
+            vec![self.user_root_public_key]
+        } else {
+            self.root_public_keys.clone()
         }
     }
     
@@ -91,6 +268,140 @@ impl SidecarState {
         self.api_key.as_str()
     }
     
+    /// Apply the reloadable settings from `config`: root key, API key, upstream URL and
+    /// request-handling switches.
+    ///
+    /// The session key, caches, counters and the upstream client are left alone, so a
+    /// reload neither invalidates receipts nor drops warm upstream connections. The
+    /// client is only rebuilt through [`Self::set_upstream_client_settings`].
+    ///
+    /// Everything that can fail is built before any field is assigned, so a rejected
+    /// config leaves the running settings exactly as they were.
+    pub fn apply_config(&mut self, config: &Config) -> Result<(), VacError> {
+        // Once loaded, the pin itself only changes on restart.
+        // Dropping policy_file would switch a pinned policy off, so that is refused too.
+        let policy = match (&config.policy, &self.policy) {
+            (None, Some(current)) if current.is_pinned() => {
+                tracing::warn!("Policy reload rejected: policy_file removed while pinned; keeping last-known-good policy");
+                return Err(VacError::ConfigError(
+                    "policy_file cannot be removed while the policy is pinned by policy_pin_hash".to_string(),
+                ));
+            }
+            (None, _) => None,
+            (Some(source), Some(current)) => {
+                let mut policy = PinnedPolicy::clone(current);
+                policy.reload(source.clone())?;
+                Some(Arc::new(policy))
+            }
+            (Some(source), None) => Some(Arc::new(PinnedPolicy::load(
+                source.clone(),
+                config.policy_pin_hash.as_deref(),
+            )?)),
+        };
+        let user_root_public_key = PublicKey::from_bytes(&config.root_public_key)
+            .map_err(|e| VacError::ConfigError(format!("Invalid public key format: {}", e)))?;
+        let root_public_keys = config
+            .root_public_keys
+            .iter()
+            .map(|key| PublicKey::from_bytes(key))
+            .collect::<Result<_, _>>()
+            .map_err(|e| VacError::ConfigError(format!("Invalid public key format: {}", e)))?;
+        let control_plane_client = (self.control_plane_client.fingerprint() != config.control_plane_cert_fingerprint)
+            .then(|| ControlPlaneClient::new(config.control_plane_cert_fingerprint))
+            .transpose()?;
+        // The backing store can only change while it is empty: Bloom filters cannot be
+        // enumerated, so revocations would be lost switching away from one.
+        let revocation_filter = match self.revocation_filter.read() {
+            Ok(filter) if filter.bloom_settings() != config.revocation_bloom && filter.revoked_count() == 0 => {
+                Some(match config.revocation_bloom {
+                    Some(b) => RevocationFilter::with_bloom(b.capacity, b.false_positive_rate)?,
+                    None => RevocationFilter::new(),
+                })
+            }
+            _ => None,
+        };
+
+        self.user_root_public_key = user_root_public_key;
+        self.root_public_keys = root_public_keys;
+        if self.api_key() != config.api_key {
+            self.api_key = SecureString::from(config.api_key.clone());
+            crate::security::lock_string_memory(self.api_key.as_str());
+        }
+        self.upstream_url = config.upstream_url.clone();
+        self.rate_limit_key = config.rate_limit_key;
+        self.path_trailing_slash = config.path_trailing_slash;
+        self.options_asterisk = config.options_asterisk;
+        self.error_response_format = config.error_response_format;
+        self.forward_delegation_chain = config.forward_delegation_chain;
+        self.expose_delegation_depth = config.expose_delegation_depth;
+        self.policy_eval_timeout = config.policy_eval_timeout_ms.map(Duration::from_millis);
+        self.soft_deny = config.soft_deny;
+        self.require_correlation_id = config.require_correlation_id;
+        self.max_token_bytes = config.max_token_bytes;
+        self.coalesce_idempotent = config.coalesce_idempotent;
+        self.strict_token_shape = config.strict_token_shape;
+        self.max_revocation_list_size = config.max_revocation_list_size;
+        self.lockdown_recovery_successes = config.lockdown_recovery_successes;
+        self.upstream_allowed_statuses = config.upstream_allowed_statuses.clone();
+        self.upstream_host_allowlist = config.upstream_host_allowlist.clone();
+        self.mint_receipts_for_methods = config.mint_receipts_for_methods.clone();
+        self.step_limiter.set_max_steps(config.max_steps_per_correlation);
+        self.replay_key_includes_operation = config.replay_key_includes_operation;
+        self.replay_key_request_hash = config.replay_key_request_hash;
+        self.adapter_concurrency
+            .set_max_concurrent(config.max_concurrent_adapters_per_correlation);
+        self.body_budget.set_max_total_bytes(config.max_total_body_bytes);
+        self.canonicalize_json_body = config.canonicalize_json_body;
+        self.forward_canonical_json_body = config.forward_canonical_json_body;
+        self.bind_correlation_to_token = config.bind_correlation_to_token;
+        self.accept_peer_receipts = config.accept_peer_receipts;
+        self.trusted_proxies = config.trusted_proxies.clone();
+        self.accept_compact_receipts = config.accept_compact_receipts;
+        self.receipt_expiry_secs = config.receipt_expiry_secs;
+        self.clock_skew_grace_secs = config.clock_skew_grace_secs;
+        self.adapter_reserved_facts = config.adapter_reserved_facts;
+        self.require_adapter_facts = config.require_adapter_facts;
+        self.adapter_context_headers = config.adapter_context_headers.clone();
+        self.flow_graph = config.flow_graph.clone().map(Arc::new);
+        self.batch_receipts = config.batch_receipts.clone();
+        if let Some(client) = control_plane_client {
+            self.control_plane_client = client;
+        }
+        self.policy = policy;
+        if let Ok(mut filter) = self.revocation_filter.write() {
+            if filter.bloom_settings() != config.revocation_bloom {
+                // A heartbeat may have revoked a token since the filter was built.
+                match revocation_filter {
+                    Some(new_filter) if filter.revoked_count() == 0 => *filter = new_filter,
+                    _ => tracing::warn!("[revocation] settings changed; they take effect on restart"),
+                }
+            }
+            filter.set_audit_log(config.revocation_audit_log.clone());
+        }
+        self.adapter_registry
+            .set_slow_threshold(std::time::Duration::from_millis(config.adapter_slow_threshold_ms));
+        self.adapter_registry.set_max_memory(config.adapter_max_memory_bytes);
+        // Keep the webhook (and its counters) unless the URL changed.
+        if self.receipt_webhook.as_ref().map(|w| w.url()) != config.receipt_webhook_url.as_deref() {
+            self.receipt_webhook = config
+                .receipt_webhook_url
+                .clone()
+                .map(|url| Arc::new(ReceiptWebhook::new(url)));
+        }
+        Ok(())
+    }
+    
+    /// Rebuild the upstream client if `settings` differ from the current client's.
+    ///
+    /// Returns whether the client was rebuilt (its connection pool starts cold).
+    pub fn set_upstream_client_settings(&mut self, settings: UpstreamClientSettings) -> bool {
+        if *self.proxy.settings() == settings {
+            return false;
+        }
+        self.proxy = Arc::new(AxumProxy::with_settings(settings));
+        true
+    }
+    
     /// Rotate session key (invalidates all existing receipts)
     pub fn rotate_session_key(&mut self) {
         self.session_key = KeyPair::new();
@@ -112,8 +423,129 @@ impl SidecarState {
         self.lockdown_mode = true;
     }
     
+    /// Leave lockdown mode (accept non-read-only requests again)
+    pub fn exit_lockdown(&mut self) {
+        self.lockdown_mode = false;
+    }
+    
     /// Check if request should be allowed in lockdown mode
     pub fn is_read_only(&self, method: &str) -> bool {
         matches!(method, "GET" | "HEAD" | "OPTIONS")
     }
+    
+    /// Check if a successful response to this method should carry a receipt
+    pub fn mints_receipt_for(&self, method: &str) -> bool {
+        match &self.mint_receipts_for_methods {
+            None => true,
+            Some(methods) => methods.iter().any(|m| m.eq_ignore_ascii_case(method)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> SidecarState {
+        SidecarState::new(KeyPair::new().public(), "k".to_string(), "http://upstream".to_string(), 100, 60, false, 60)
+    }
+
+    #[test]
+    fn mints_receipt_for_all_methods_by_default() {
+        let s = state();
+        assert!(s.mints_receipt_for("GET"));
+        assert!(s.mints_receipt_for("POST"));
+    }
+
+    #[test]
+    fn mints_receipt_only_for_configured_methods() {
+        let mut s = state();
+        s.mint_receipts_for_methods = Some(vec!["POST".to_string(), "PUT".to_string()]);
+        assert!(!s.mints_receipt_for("GET"));
+        assert!(!s.mints_receipt_for("HEAD"));
+        assert!(s.mints_receipt_for("POST"));
+        assert!(s.mints_receipt_for("put"));
+    }
+
+    #[test]
+    fn reload_keeps_upstream_client() {
+        let mut s = state();
+        let proxy = s.proxy.clone();
+        let session_key = s.session_key.public();
+
+        let new_root = KeyPair::new().public();
+        let cli_args = crate::config::CliArgs {
+            root_public_key: Some(hex::encode(new_root.to_bytes())),
+            api_key: Some("rotated-key".to_string()),
+            soft_deny: Some(true),
+            strict_token_shape: Some(true),
+            ..Default::default()
+        };
+        s.apply_config(&Config::load(&cli_args).unwrap()).unwrap();
+
+        assert_eq!(s.user_root_public_key, new_root);
+        assert_eq!(s.api_key(), "rotated-key");
+        assert!(s.soft_deny && s.strict_token_shape);
+        assert!(Arc::ptr_eq(&s.proxy, &proxy));
+        assert_eq!(s.session_key.public(), session_key);
+
+        // Only a client-affecting change rebuilds the client.
+        assert!(!s.set_upstream_client_settings(UpstreamClientSettings::default()));
+        assert!(Arc::ptr_eq(&s.proxy, &proxy));
+        let settings = UpstreamClientSettings {
+            pool_max_idle_per_host: Some(4),
+            ..Default::default()
+        };
+        assert!(s.set_upstream_client_settings(settings.clone()));
+        assert!(!Arc::ptr_eq(&s.proxy, &proxy));
+        assert_eq!(*s.proxy.settings(), settings);
+    }
+
+    #[test]
+    fn failed_reload_leaves_every_setting_in_place() {
+        let cli_args = crate::config::CliArgs {
+            root_public_key: Some(hex::encode(KeyPair::new().public().to_bytes())),
+            api_key: Some("new-key".to_string()),
+            upstream_url: Some("http://new-upstream".to_string()),
+            soft_deny: Some(true),
+            ..Default::default()
+        };
+        let mut config = Config::load(&cli_args).unwrap();
+        // Passed config validation but is not a usable key.
+        config.root_public_keys = vec![vec![7u8; 31]];
+
+        let mut s = state();
+        assert!(s.apply_config(&config).is_err());
+        assert_eq!(s.upstream_url, "http://upstream");
+        assert_eq!(s.api_key(), "k");
+        assert!(!s.soft_deny);
+    }
+
+    #[test]
+    fn pinned_policy_survives_mismatched_reload() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let policy_path = temp_dir.path().join("policy.dl");
+        let good = "allow if operation(\"GET\", \"/search\");";
+        std::fs::write(&policy_path, good).unwrap();
+        let cli_args = crate::config::CliArgs {
+            root_public_key: Some(hex::encode(KeyPair::new().public().to_bytes())),
+            api_key: Some("k".to_string()),
+            policy_file: Some(policy_path.clone()),
+            policy_pin_hash: Some(crate::policy_pin::policy_hash(good.as_bytes())),
+            ..Default::default()
+        };
+        let mut s = state();
+        s.apply_config(&Config::load(&cli_args).unwrap()).unwrap();
+        assert_eq!(s.policy.as_ref().unwrap().source(), good);
+
+        // Swapped on disk for a permissive policy: the reload is refused as a whole.
+        std::fs::write(&policy_path, "allow if true;").unwrap();
+        let swapped = crate::config::CliArgs {
+            soft_deny: Some(true),
+            ..cli_args
+        };
+        assert!(s.apply_config(&Config::load(&swapped).unwrap()).is_err());
+        assert_eq!(s.policy.as_ref().unwrap().source(), good);
+        assert!(!s.soft_deny);
+    }
 }
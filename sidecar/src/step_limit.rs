@@ -0,0 +1,165 @@
+//! Per-correlation-ID step limit (`max_steps_per_correlation`)
+//!
+//! Counts the receipts minted under each correlation ID and rejects further steps once
+//! the cap is reached, bounding how long a single flow's receipt chain can grow. A step
+//! is reserved when the request enters the guard and only counted if a receipt is
+//! minted for it, so denied or failed requests do not use up the budget and concurrent
+//! requests cannot overshoot the cap.
+
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::VacError;
+
+/// How long a correlation ID's step count is kept after its first step (1 hour).
+pub const DEFAULT_STEP_COUNT_TTL: Duration = Duration::from_secs(3600);
+
+struct StepEntry {
+    /// Reserved and committed steps
+    steps: u32,
+    first_seen: Instant,
+}
+
+/// Step counts per correlation ID.
+#[derive(Clone)]
+pub struct StepLimiter {
+    counts: Arc<DashMap<String, StepEntry>>,
+    /// `None` disables the limit
+    max_steps: Option<u32>,
+    ttl: Duration,
+}
+
+impl StepLimiter {
+    pub fn new(max_steps: Option<u32>, ttl: Duration) -> Self {
+        Self {
+            counts: Arc::new(DashMap::new()),
+            max_steps,
+            ttl,
+        }
+    }
+
+    /// Change the cap; existing counts are kept.
+    pub fn set_max_steps(&mut self, max_steps: Option<u32>) {
+        self.max_steps = max_steps;
+    }
+
+    /// Reserve a step for `correlation_id`.
+    ///
+    /// Returns `Ok(None)` when the limit is disabled and `Err(StepLimitExceeded)` once the
+    /// cap is reached. The reservation is released when dropped unless
+    /// [`StepReservation::commit`] is called.
+    pub fn try_reserve(&self, correlation_id: &str) -> Result<Option<StepReservation>, VacError> {
+        let Some(max_steps) = self.max_steps else {
+            return Ok(None);
+        };
+        let now = Instant::now();
+        let mut entry = self
+            .counts
+            .entry(correlation_id.to_string())
+            .or_insert(StepEntry { steps: 0, first_seen: now });
+        if now.duration_since(entry.first_seen) >= self.ttl {
+            *entry = StepEntry { steps: 0, first_seen: now };
+        }
+        if entry.steps >= max_steps {
+            return Err(VacError::StepLimitExceeded);
+        }
+        entry.steps += 1;
+        Ok(Some(StepReservation {
+            limiter: self.clone(),
+            correlation_id: correlation_id.to_string(),
+            committed: false,
+        }))
+    }
+
+    /// Steps counted (or reserved) for `correlation_id`.
+    pub fn steps(&self, correlation_id: &str) -> u32 {
+        self.counts.get(correlation_id).map(|e| e.steps).unwrap_or(0)
+    }
+
+    /// Drop counts older than the TTL.
+    pub fn cleanup_expired(&self) {
+        let now = Instant::now();
+        self.counts.retain(|_, entry| now.duration_since(entry.first_seen) < self.ttl);
+    }
+
+    /// Number of tracked correlation IDs (for monitoring)
+    pub fn size(&self) -> usize {
+        self.counts.len()
+    }
+
+    fn release(&self, correlation_id: &str) {
+        if let Some(mut entry) = self.counts.get_mut(correlation_id) {
+            entry.steps = entry.steps.saturating_sub(1);
+        }
+    }
+}
+
+/// A reserved step; released on drop unless committed.
+pub struct StepReservation {
+    limiter: StepLimiter,
+    correlation_id: String,
+    committed: bool,
+}
+
+impl StepReservation {
+    /// Count the step (a receipt was minted for it).
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for StepReservation {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.limiter.release(&self.correlation_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_over_cap_is_rejected() {
+        let limiter = StepLimiter::new(Some(3), DEFAULT_STEP_COUNT_TTL);
+        for _ in 0..3 {
+            limiter.try_reserve("flow-1").unwrap().unwrap().commit();
+        }
+        assert_eq!(limiter.steps("flow-1"), 3);
+        assert!(matches!(limiter.try_reserve("flow-1"), Err(VacError::StepLimitExceeded)));
+
+        // Other flows have their own budget.
+        assert!(limiter.try_reserve("flow-2").unwrap().is_some());
+    }
+
+    #[test]
+    fn uncommitted_steps_are_released() {
+        let limiter = StepLimiter::new(Some(1), DEFAULT_STEP_COUNT_TTL);
+        let step = limiter.try_reserve("flow-1").unwrap().unwrap();
+        // A concurrent step cannot overshoot the cap while the first is in flight.
+        assert!(limiter.try_reserve("flow-1").is_err());
+        drop(step);
+        assert_eq!(limiter.steps("flow-1"), 0);
+        assert!(limiter.try_reserve("flow-1").unwrap().is_some());
+    }
+
+    #[test]
+    fn counts_expire_after_ttl() {
+        let limiter = StepLimiter::new(Some(1), Duration::from_millis(20));
+        limiter.try_reserve("flow-1").unwrap().unwrap().commit();
+        assert!(limiter.try_reserve("flow-1").is_err());
+        std::thread::sleep(Duration::from_millis(30));
+        limiter.try_reserve("flow-1").unwrap().unwrap().commit();
+        limiter.cleanup_expired();
+        assert_eq!(limiter.size(), 1);
+    }
+
+    #[test]
+    fn disabled_limit_reserves_nothing() {
+        let limiter = StepLimiter::new(None, DEFAULT_STEP_COUNT_TTL);
+        assert!(limiter.try_reserve("flow-1").unwrap().is_none());
+        assert_eq!(limiter.size(), 0);
+    }
+}
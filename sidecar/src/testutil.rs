@@ -0,0 +1,85 @@
+//! Test helpers for building root tokens, receipts and delegation chains
+//! (`test-util` feature)
+//!
+//! One implementation for the sidecar's own integration tests and for downstream users
+//! testing their policies against real tokens. Every helper goes through the same code
+//! path as production issuance ([`build_root_biscuit`], [`delegate`], the receipt
+//! minting in [`crate::receipt`]), so a token built here is exactly what the sidecar
+//! accepts. Helpers panic instead of returning errors: they are meant for tests.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use biscuit_auth::{Biscuit, KeyPair};
+
+use crate::delegation::{delegate, DelegationClaims};
+use crate::issuer::{build_root_biscuit, RootClaims};
+use crate::receipt::NewReceipt;
+
+/// A root token signed by `kp`, with no facts.
+pub fn root_token(kp: &KeyPair) -> Biscuit {
+    build_root_biscuit(kp, RootClaims::default()).expect("build root token")
+}
+
+/// A root token signed by `kp` declaring `depth(d)`; the start of a delegation chain when
+/// `d` is 0.
+pub fn root_token_with_depth(kp: &KeyPair, d: i64) -> Biscuit {
+    build_root_biscuit(
+        kp,
+        RootClaims {
+            depth: Some(d),
+            ..Default::default()
+        },
+    )
+    .expect("build root token with depth")
+}
+
+/// A receipt for `op` (`"METHOD /path"`) under correlation ID `cid`, signed with
+/// `session_kp` as the sidecar would after a successful request at Unix time `ts`.
+///
+/// The guard accepts it when `session_kp` is the sidecar's session key (or a published
+/// peer key) and `ts` is within `receipt_expiry_secs`; see [`now`].
+pub fn mint_receipt(session_kp: &KeyPair, op: &str, cid: &str, ts: i64) -> Biscuit {
+    crate::receipt::mint_receipt(
+        session_kp,
+        &NewReceipt {
+            operation: op,
+            correlation_id: cid,
+            timestamp: ts,
+            delegation_chain: &[],
+            depth: None,
+            sidecar_id: "testutil",
+        },
+    )
+    .expect("mint receipt")
+}
+
+/// A delegation chain rooted in a `depth(0)` token signed by `kp`, delegated `depth`
+/// times: `depth + 1` tokens, root first. Present the last one as the bearer token and
+/// every token in one `X-VAC-Delegation` header each.
+pub fn build_chain(kp: &KeyPair, depth: i64) -> Vec<Biscuit> {
+    let mut chain = vec![root_token_with_depth(kp, 0)];
+    for _ in 0..depth {
+        let parent = chain.last().expect("chain starts with the root");
+        chain.push(delegate(parent, DelegationClaims::default()).expect("delegate token"));
+    }
+    chain
+}
+
+/// Base64 form of `token`, as sent in `Authorization`, `X-VAC-Receipt` and
+/// `X-VAC-Delegation` headers.
+pub fn b64(token: &Biscuit) -> String {
+    token.to_base64().expect("encode token")
+}
+
+/// `Bearer <token>`, the `Authorization` header value for `token`.
+pub fn bearer(token: &Biscuit) -> String {
+    format!("Bearer {}", b64(token))
+}
+
+/// Current Unix time in seconds, the timestamp of a fresh receipt.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
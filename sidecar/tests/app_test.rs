@@ -0,0 +1,150 @@
+//! Integration test for the production router from `app::build_router`: the real guard,
+//! operator policy and upstream proxy, wired exactly as the binary wires them.
+
+mod common;
+
+use std::io::Write;
+
+use biscuit_auth::KeyPair;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use tower::{Layer, ServiceExt};
+use vac_sidecar::{build_router, build_state, send_heartbeat, CliArgs, Config, VacGuardLayer, HEALTHZ_PATH, METRICS_PATH};
+
+#[tokio::test]
+async fn built_router_forwards_authorized_requests_only() {
+    let upstream = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/data"))
+        .and(header("authorization", "Bearer upstream-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("data"))
+        .expect(1)
+        .mount(&upstream)
+        .await;
+
+    let mut policy_file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(policy_file, r#"allow if operation("GET", "/data");"#).unwrap();
+    let root_kp = KeyPair::new();
+    let config = Config::load(&CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("upstream-key".to_string()),
+        upstream_url: Some(upstream.uri()),
+        policy_file: Some(policy_file.path().to_path_buf()),
+        ..Default::default()
+    })
+    .unwrap();
+    let app = build_router(build_state(&config).unwrap(), &config);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service()).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = |route: &str| {
+        client
+            .get(format!("{}{}", base, route))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+    };
+
+    // Allowed by the operator policy: forwarded with the upstream API key.
+    let resp = send("/data").await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.text().await.unwrap(), "data");
+
+    // Not allowed, or no token at all: stopped by the guard before the upstream.
+    assert_eq!(send("/other").await.unwrap().status().as_u16(), 403);
+    let resp = client.get(format!("{}/data", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+
+    // Probes and metrics are served next to the guarded routes.
+    assert_eq!(client.get(format!("{}{}", base, HEALTHZ_PATH)).send().await.unwrap().status().as_u16(), 200);
+    let resp = client.get(format!("{}{}", base, METRICS_PATH)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert!(resp.text().await.unwrap().contains("vac_requests_total{decision=\"allow\"} 1\n"));
+    // An upstream `/metrics` is not shadowed: without a token the guard stops it.
+    assert_eq!(client.get(format!("{}/metrics", base)).send().await.unwrap().status().as_u16(), 401);
+
+    upstream.verify().await;
+}
+
+#[tokio::test]
+async fn configured_sidecar_id_is_used_for_heartbeats_and_rate_limits() {
+    let control_plane = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/heartbeat"))
+        .and(body_partial_json(serde_json::json!({ "sidecar_id": "vac-pod-7" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "healthy": true })))
+        .expect(1)
+        .mount(&control_plane)
+        .await;
+
+    let config = Config::load(&CliArgs {
+        root_public_key: Some(hex::encode(KeyPair::new().public().to_bytes())),
+        api_key: Some("upstream-key".to_string()),
+        sidecar_id: Some("vac-pod-7".to_string()),
+        rate_limit_max_requests: Some(1),
+        ..Default::default()
+    })
+    .unwrap();
+    let state = build_state(&config).unwrap();
+    assert_eq!(state.read().await.sidecar_id, "vac-pod-7");
+
+    assert!(send_heartbeat(&state, &control_plane.uri(), 300).await.unwrap());
+    control_plane.verify().await;
+
+    // The one request in the window is spent under the configured ID's bucket.
+    let guarded = VacGuardLayer::new(state.clone())
+        .layer(axum::Router::new().route("/hello", axum::routing::get(|| async { "hello" })));
+    let request = || axum::http::Request::builder().uri("/hello").body(axum::body::Body::empty()).unwrap();
+    assert_eq!(guarded.clone().oneshot(request()).await.unwrap().status().as_u16(), 401);
+    let limited = guarded.oneshot(request()).await.unwrap();
+    assert_eq!(limited.status().as_u16(), 429);
+    // One request per 60s window: the next token is a whole window away.
+    assert_eq!(limited.headers()["retry-after"], "60");
+    let s = state.read().await;
+    assert!(!s.rate_limiter.check("vac-pod-7"));
+    assert!(s.rate_limiter.check("some-other-sidecar"));
+}
+
+#[tokio::test]
+async fn rate_limit_key_token_gives_each_token_its_own_bucket() {
+    let root_kp = KeyPair::new();
+    let config = Config::load(&CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("upstream-key".to_string()),
+        rate_limit_max_requests: Some(1),
+        rate_limit_key: Some("token".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    let guarded = VacGuardLayer::new(build_state(&config).unwrap())
+        .layer(axum::Router::new().route("/hello", axum::routing::get(|| async { "hello" })));
+    let request = |token: Option<&str>| {
+        let mut builder = axum::http::Request::builder().uri("/hello");
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+        builder.body(axum::body::Body::empty()).unwrap()
+    };
+    let status = |token: Option<&str>| {
+        let guarded = guarded.clone();
+        let request = request(token);
+        async move { guarded.oneshot(request).await.unwrap().status().as_u16() }
+    };
+    let first = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let second = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+
+    // No policy allows /hello, so a request that gets through the limiter is a 403.
+    assert_eq!(status(Some(&first)).await, 403);
+    assert_eq!(status(Some(&first)).await, 429);
+    // The second token's bucket is untouched by the first one's requests.
+    assert_eq!(status(Some(&second)).await, 403);
+    assert_eq!(status(Some(&second)).await, 429);
+    // Requests without a token share the sidecar's bucket.
+    assert_eq!(status(None).await, 401);
+    assert_eq!(status(None).await, 429);
+}
@@ -0,0 +1,79 @@
+//! Integration tests for `coalesce_idempotent`: identical concurrent GETs share one upstream call.
+
+mod common;
+
+use std::time::Duration;
+
+use biscuit_auth::KeyPair;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use vac_sidecar::SharedState;
+
+const CONCURRENT_REQUESTS: usize = 20;
+
+/// `method uri` from the agent's token, so identical requests share a coalescing key.
+async fn forward(state: SharedState, method: &'static str, uri: &'static str) -> axum::response::Response {
+    let mut req = common::request(method, uri, "");
+    req.headers_mut().insert("Authorization", "Bearer agent-token".parse().unwrap());
+    common::forward(state, req).await
+}
+
+async fn slow_upstream() -> MockServer {
+    let mock_server = MockServer::start().await;
+    Mock::given(path("/resource"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("resource")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+async fn burst(state: &SharedState, method: &'static str) -> Vec<axum::response::Response> {
+    let handles: Vec<_> = (0..CONCURRENT_REQUESTS)
+        .map(|_| tokio::spawn(forward(state.clone(), method, "/resource?page=1")))
+        .collect();
+    let mut responses = Vec::new();
+    for handle in handles {
+        responses.push(handle.await.unwrap());
+    }
+    responses
+}
+
+#[tokio::test]
+async fn test_concurrent_identical_gets_are_coalesced() {
+    let mock_server = slow_upstream().await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    state.write().await.coalesce_idempotent = true;
+
+    for resp in burst(&state, "GET").await {
+        assert_eq!(resp.status().as_u16(), 200);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"resource");
+    }
+
+    let hits = mock_server.received_requests().await.unwrap().len();
+    assert!(hits < CONCURRENT_REQUESTS / 4, "upstream hit {} times", hits);
+}
+
+#[tokio::test]
+async fn test_non_idempotent_requests_are_not_coalesced() {
+    let mock_server = slow_upstream().await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    state.write().await.coalesce_idempotent = true;
+
+    burst(&state, "POST").await;
+    assert_eq!(mock_server.received_requests().await.unwrap().len(), CONCURRENT_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_coalescing_off_by_default() {
+    let mock_server = slow_upstream().await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+
+    burst(&state, "GET").await;
+    assert_eq!(mock_server.received_requests().await.unwrap().len(), CONCURRENT_REQUESTS);
+}
@@ -1,29 +1,25 @@
 // Common test utilities
 
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::Response;
 use biscuit_auth::{KeyPair, Biscuit, PublicKey};
 use std::sync::Arc;
 use vac_sidecar::{SidecarState, SharedState};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
-/// Generate a test Root Biscuit signed with the given keypair
+/// Generate a test Root Biscuit signed with the given keypair (no facts; policies are
+/// added to the authorizer at evaluation time)
 #[allow(dead_code)]
 pub fn generate_test_root_biscuit(
     root_keypair: &KeyPair,
 ) -> Result<Biscuit, Box<dyn std::error::Error>> {
-    let builder = Biscuit::builder();
-    
-    // Add a permissive allow policy for Phase 1 testing
-    // This allows all operations - in production, policies would be more restrictive
-    // Note: In Biscuit, we add policies via the authorizer, not the token itself
-    // But for testing, we can add an allow rule that will be evaluated
-    // Actually, policies are added to the authorizer at evaluation time
-    // So we don't need to add policies to the biscuit builder
-    // The biscuit can be empty - policies are added via authorizer.add_code()
-    
-    let biscuit = builder.build(root_keypair)?;
-    Ok(biscuit)
+    Ok(vac_sidecar::testutil::root_token(root_keypair))
 }
 
 /// Create SharedState for tests with default rate-limit/replay settings.
+#[allow(dead_code)]
 pub fn default_test_state(
     public_key: PublicKey,
     api_key: impl Into<String>,
@@ -40,6 +36,39 @@ pub fn default_test_state(
     )))
 }
 
+/// `method uri` request carrying `body`, for [`forward`].
+#[allow(dead_code)]
+pub fn request(method: &str, uri: &str, body: impl Into<Body>) -> Request<Body> {
+    Request::builder().method(method).uri(uri).body(body.into()).unwrap()
+}
+
+/// Call the upstream handler directly, as the guard would after policy passes.
+#[allow(dead_code)]
+pub async fn forward(state: SharedState, req: Request<Body>) -> Response {
+    vac_sidecar::upstream_handler(axum::extract::State(state), req).await
+}
+
+/// Mock upstream answering `route_method route_path` with `response`; mount more routes
+/// on the returned server as needed.
+#[allow(dead_code)]
+pub async fn mock_upstream(route_method: &str, route_path: &str, response: ResponseTemplate) -> MockServer {
+    let mock_server = MockServer::start().await;
+    Mock::given(method(route_method))
+        .and(path(route_path))
+        .respond_with(response)
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+/// Value of `series` (name plus labels, e.g. `vac_queue_duration_seconds_count`) in
+/// rendered Prometheus text, if present.
+#[allow(dead_code)]
+pub fn metric_value(text: &str, series: &str) -> Option<f64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.parse().ok())
+}
+
 /// Cleanup test environment variables
 #[allow(dead_code)]
 pub fn cleanup_test_env() {
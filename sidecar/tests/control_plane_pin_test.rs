@@ -0,0 +1,82 @@
+//! Integration tests for `control_plane_cert_fingerprint`: heartbeats only trust a control
+//! plane presenting the pinned TLS certificate.
+
+mod common;
+
+use axum::{routing::post, Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use sha2::{Digest, Sha256};
+use tokio_native_tls::native_tls;
+
+use vac_sidecar::{send_heartbeat, ControlPlaneClient, SharedState};
+
+const CERT: &str = include_str!("fixtures/control_plane.cert.pem");
+const KEY: &str = include_str!("fixtures/control_plane.key.pem");
+const OTHER_CERT: &str = include_str!("fixtures/other_control_plane.cert.pem");
+
+/// SHA-256 of the DER certificate inside `pem`.
+fn fingerprint(pem: &str) -> [u8; 32] {
+    let b64: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+    Sha256::digest(general_purpose::STANDARD.decode(b64).unwrap()).into()
+}
+
+/// HTTPS control plane answering heartbeats as healthy; returns its base URL.
+async fn tls_control_plane() -> String {
+    let identity = native_tls::Identity::from_pkcs8(CERT.as_bytes(), KEY.as_bytes()).unwrap();
+    let acceptor = tokio_native_tls::TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+    let app = Router::new().route(
+        "/heartbeat",
+        post(|| async { Json(serde_json::json!({ "healthy": true, "revoked_token_ids": null })) }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else { return };
+            let (acceptor, app) = (acceptor.clone(), app.clone());
+            tokio::spawn(async move {
+                // A client that rejects the certificate ends the handshake; nothing to serve.
+                let Ok(tls) = acceptor.accept(stream).await else { return };
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(tls), TowerToHyperService::new(app))
+                    .await;
+            });
+        }
+    });
+    format!("https://localhost:{}", addr.port())
+}
+
+fn state_pinned_to(pin: [u8; 32]) -> SharedState {
+    let state = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.try_write().unwrap().control_plane_client = ControlPlaneClient::new(Some(pin)).unwrap();
+    state
+}
+
+#[tokio::test]
+async fn heartbeat_succeeds_with_matching_certificate_fingerprint() {
+    let control_plane = tls_control_plane().await;
+    let state = state_pinned_to(fingerprint(CERT));
+
+    assert!(send_heartbeat(&state, &control_plane, 300).await.unwrap());
+    let s = state.read().await;
+    assert!(s.heartbeat_healthy);
+    assert_eq!(s.heartbeat_failure_count, 0);
+}
+
+#[tokio::test]
+async fn heartbeat_rejects_a_different_certificate() {
+    let control_plane = tls_control_plane().await;
+    let state = state_pinned_to(fingerprint(OTHER_CERT));
+
+    let err = send_heartbeat(&state, &control_plane, 300).await.unwrap_err();
+    assert!(err.to_string().contains("does not match control_plane_cert_fingerprint"), "{}", err);
+    let s = state.read().await;
+    assert!(!s.heartbeat_healthy);
+    assert_eq!(s.heartbeat_failure_count, 1);
+}
@@ -156,3 +156,139 @@ async fn test_invalid_delegation_chain_denies() {
     assert_eq!(resp.status().as_u16(), 403);
 }
 
+#[tokio::test]
+async fn test_delegation_chain_with_unrelated_bearer_is_bad_request() {
+    let root_keypair = KeyPair::new();
+    let state = common::default_test_state(root_keypair.public(), "k", "http://upstream.invalid");
+    let app = create_app(state).await;
+
+    let t0 = root_biscuit_with_depth(&root_keypair, 0);
+    let t1 = root_biscuit_with_depth(&root_keypair, 1);
+    // Valid root token under the same key, but not the chain's terminal token.
+    let unrelated = root_biscuit_with_depth(&root_keypair, 1);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service()).await.unwrap();
+    });
+
+    let resp = reqwest::Client::new()
+        .get(format!("http://{}/test", addr))
+        .header("Authorization", format!("Bearer {}", unrelated.to_base64().unwrap()))
+        .header("X-Correlation-ID", Uuid::new_v4().to_string())
+        .header(DELEGATION_HEADER, t0.to_base64().unwrap())
+        .header(DELEGATION_HEADER, t1.to_base64().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 400);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "delegation_authorization_mismatch");
+}
+
+
+/// Call the upstream handler directly, as the guard would after policy passes.
+async fn forward_with_context(state: SharedState, context: vac_sidecar::VacContext) -> u16 {
+    let mut req = axum::http::Request::builder()
+        .uri("/api/resource")
+        .header("X-VAC-Delegation-Depth", "99") // client-supplied; must never reach upstream
+        .body(axum::body::Body::empty())
+        .unwrap();
+    req.extensions_mut().insert(context);
+    let resp = vac_sidecar::upstream_handler(axum::extract::State(state), req).await;
+    resp.status().as_u16()
+}
+
+fn delegated_context() -> vac_sidecar::VacContext {
+    vac_sidecar::VacContext {
+        correlation_id: Uuid::new_v4().to_string(),
+        delegation_chain: vec!["aa01".to_string(), "bb02".to_string()],
+        depth: Some(1),
+        delegation_depth: 1,
+    }
+}
+
+#[tokio::test]
+async fn test_forward_delegation_chain_enabled_sends_summary() {
+    let mock = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/resource"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock)
+        .await;
+
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock.uri());
+    state.write().await.forward_delegation_chain = true;
+
+    assert_eq!(forward_with_context(state, delegated_context()).await, 200);
+
+    let received = mock.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    let headers = &received[0].headers;
+    assert_eq!(headers.get("x-vac-delegation-depth").unwrap(), "1");
+    assert_eq!(headers.get("x-vac-delegation-chain").unwrap(), "aa01,bb02");
+}
+
+#[tokio::test]
+async fn test_forward_delegation_chain_disabled_strips_headers() {
+    let mock = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/resource"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock)
+        .await;
+
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock.uri());
+
+    assert_eq!(forward_with_context(state, delegated_context()).await, 200);
+
+    let received = mock.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    let headers = &received[0].headers;
+    assert!(headers.get("x-vac-delegation-depth").is_none());
+    assert!(headers.get("x-vac-delegation-chain").is_none());
+}
+
+#[tokio::test]
+async fn test_expose_delegation_depth_sets_header_both_ways() {
+    let mock = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/resource"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock)
+        .await;
+
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock.uri());
+    state.write().await.expose_delegation_depth = true;
+    let context = vac_sidecar::VacContext {
+        delegation_chain: vec!["aa01".to_string(), "bb02".to_string(), "cc03".to_string()],
+        delegation_depth: 2,
+        ..delegated_context()
+    };
+
+    let mut req = axum::http::Request::builder()
+        .uri("/api/resource")
+        .header("X-VAC-Depth", "0") // client-supplied; replaced by the verified depth
+        .body(axum::body::Body::empty())
+        .unwrap();
+    req.extensions_mut().insert(context);
+    let resp = vac_sidecar::upstream_handler(axum::extract::State(state.clone()), req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get("x-vac-depth").unwrap(), "2");
+
+    let received = mock.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].headers.get("x-vac-depth").unwrap(), "2");
+
+    // Disabled (the default): neither side sees it.
+    state.write().await.expose_delegation_depth = false;
+    let mut req = axum::http::Request::builder()
+        .uri("/api/resource")
+        .body(axum::body::Body::empty())
+        .unwrap();
+    req.extensions_mut().insert(delegated_context());
+    let resp = vac_sidecar::upstream_handler(axum::extract::State(state), req).await;
+    assert!(resp.headers().get("x-vac-depth").is_none());
+    assert!(mock.received_requests().await.unwrap()[1].headers.get("x-vac-depth").is_none());
+}
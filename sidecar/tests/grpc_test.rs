@@ -0,0 +1,182 @@
+//! Integration test for `protocol = grpc`: calls are forwarded to an h2c upstream and the
+//! receipt is delivered in the trailers, only for calls ending with `grpc-status: 0`.
+
+mod common;
+
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use biscuit_auth::KeyPair;
+use hyper::body::Frame;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+
+use vac_sidecar::{build_router, build_state, extract_receipt_info, verify_receipt_biscuit, CancellationToken, CliArgs, Config};
+
+/// A unary gRPC response: one message, then `grpc-status` in the trailers.
+struct UnaryResponse {
+    message: Option<Bytes>,
+    status: Option<&'static str>,
+}
+
+impl HttpBody for UnaryResponse {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if let Some(message) = self.message.take() {
+            return Poll::Ready(Some(Ok(Frame::data(message))));
+        }
+        Poll::Ready(self.status.take().map(|status| {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", status.parse().unwrap());
+            Ok(Frame::trailers(trailers))
+        }))
+    }
+}
+
+/// Length-prefixed gRPC message framing (uncompressed).
+fn grpc_message(payload: &[u8]) -> Bytes {
+    let mut framed = vec![0u8];
+    framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    framed.extend_from_slice(payload);
+    framed.into()
+}
+
+/// Minimal gRPC upstream: `Charge` succeeds, `Refund` fails with FAILED_PRECONDITION, and
+/// any call without the injected API key is UNAUTHENTICATED.
+async fn grpc_upstream() -> String {
+    fn reply(headers: &HeaderMap, status: &'static str) -> axum::response::Response {
+        let status = match headers.get("authorization") {
+            Some(auth) if auth == "Bearer upstream-key" => status,
+            _ => "16",
+        };
+        axum::http::Response::builder()
+            .header("content-type", "application/grpc")
+            .body(Body::new(UnaryResponse {
+                message: Some(grpc_message(b"\x08\x01")),
+                status: Some(status),
+            }))
+            .unwrap()
+    }
+    let app = Router::new()
+        .route("/demo.Payments/Charge", post(|headers: HeaderMap| async move { reply(&headers, "0") }))
+        .route("/demo.Payments/Refund", post(|headers: HeaderMap| async move { reply(&headers, "9") }));
+    serve(app).await
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(vac_sidecar::server::serve(listener, app, true, CancellationToken::new()));
+    addr.to_string()
+}
+
+/// Make one call over h2c; returns the HTTP status and the trailers.
+async fn call(addr: &str, path: &str, content_type: &str, token: &str) -> (StatusCode, Option<HeaderMap>) {
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+    let request = Request::post(format!("http://{}{}", addr, path))
+        .header("content-type", content_type)
+        .header("te", "trailers")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::from(grpc_message(b"\x08\x2a")))
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    let status = response.status();
+    let mut body = response.into_body();
+    let mut trailers = None;
+    while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        if let Ok(t) = frame.unwrap().into_trailers() {
+            trailers = Some(t);
+        }
+    }
+    (status, trailers)
+}
+
+#[tokio::test]
+async fn receipt_is_delivered_in_trailers_for_successful_calls_only() {
+    let upstream = grpc_upstream().await;
+    let mut policy_file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(policy_file, r#"allow if operation("POST", $rpc), $rpc.starts_with("/demo.Payments/");"#).unwrap();
+    let root_kp = KeyPair::new();
+    let config = Config::load(&CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("upstream-key".to_string()),
+        upstream_url: Some(format!("http://{}", upstream)),
+        policy_file: Some(policy_file.path().to_path_buf()),
+        protocol: Some("grpc".to_string()),
+        server_http2_enabled: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    let state = build_state(&config).unwrap();
+    let sidecar = serve(build_router(state.clone(), &config)).await;
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+
+    // grpc-status 0: the receipt arrives with it and records the RPC method.
+    let (status, trailers) = call(&sidecar, "/demo.Payments/Charge", "application/grpc", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let trailers = trailers.expect("trailers");
+    assert_eq!(trailers["grpc-status"], "0");
+    let receipt = trailers["x-vac-receipt"].to_str().unwrap();
+    let session_key = state.read().await.session_key.public();
+    let info = extract_receipt_info(&verify_receipt_biscuit(receipt, &session_key).unwrap()).unwrap();
+    assert_eq!(info.operation, "POST /demo.Payments/Charge");
+
+    // A failed call is still HTTP 200, but gets no receipt.
+    let (status, trailers) = call(&sidecar, "/demo.Payments/Refund", "application/grpc", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let trailers = trailers.expect("trailers");
+    assert_eq!(trailers["grpc-status"], "9");
+    assert!(!trailers.contains_key("x-vac-receipt"));
+
+    // Anything but a gRPC call is turned away.
+    let (status, _) = call(&sidecar, "/demo.Payments/Charge", "application/json", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unresponsive_grpc_upstream_times_out() {
+    // Accepts connections but never speaks HTTP/2.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+    let (parts, _) = Request::post("/demo.Payments/Charge")
+        .header("content-type", "application/grpc")
+        .body(())
+        .unwrap()
+        .into_parts();
+    let proxy = vac_sidecar::GrpcProxy::new();
+    let upstream_url = format!("http://{}", addr);
+    let extra_headers = HeaderMap::new();
+    let forward = || {
+        proxy.forward(
+            &parts,
+            grpc_message(b"\x08\x2a"),
+            "upstream-key",
+            &upstream_url,
+            &extra_headers,
+            Some(std::time::Duration::from_millis(200)),
+        )
+    };
+
+    let started = std::time::Instant::now();
+    let err = forward().await.unwrap_err();
+    assert!(matches!(err, vac_sidecar::VacError::UpstreamTimeout(_)), "{:?}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    // The lock is not left held: the next call times out the same way.
+    assert!(matches!(forward().await, Err(vac_sidecar::VacError::UpstreamTimeout(_))));
+}
@@ -0,0 +1,1145 @@
+//! Integration tests for `VacGuardLayer` mounted in front of a plain Axum handler (no proxy).
+
+mod common;
+
+use axum::{routing::get, Router};
+use biscuit_auth::KeyPair;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use vac_sidecar::{SharedState, VacGuardLayer};
+
+/// Router with a trivial handler behind the guard; `hits` counts how often the handler ran.
+fn app(state: SharedState, hits: Arc<AtomicUsize>) -> Router {
+    Router::new()
+        .route(
+            "/hello",
+            get(move || {
+                let hits = hits.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    "hello"
+                }
+            }),
+        )
+        .layer(VacGuardLayer::new(state))
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service()).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn guard_layer_enforces_auth_in_front_of_handler() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state, hits.clone())).await;
+    let client = reqwest::Client::new();
+
+    // No token
+    let resp = client.get(format!("{}/hello", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+
+    // Token signed by the wrong root key
+    let forged = common::generate_test_root_biscuit(&KeyPair::new()).unwrap().to_base64().unwrap();
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", forged))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // Valid token, but no allow policy matches: still fail-closed.
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "policy_violation");
+
+    assert_eq!(hits.load(Ordering::SeqCst), 0, "handler must not run for denied requests");
+}
+
+#[tokio::test]
+async fn queue_duration_measured_under_state_contention() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state.clone(), hits)).await;
+    let client = reqwest::Client::new();
+
+    // Hold the state write lock (as a reload would) while requests arrive.
+    let write_guard = state.write().await;
+    let pending: Vec<_> = (0..3)
+        .map(|_| tokio::spawn(client.get(format!("{}/hello", base)).send()))
+        .collect();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    drop(write_guard);
+
+    for p in pending {
+        assert_eq!(p.await.unwrap().unwrap().status().as_u16(), 401);
+    }
+
+    let text = state.read().await.metrics.render();
+    assert_eq!(common::metric_value(&text, "vac_queue_duration_seconds_count"), Some(3.0), "{}", text);
+    let sum = common::metric_value(&text, "vac_queue_duration_seconds_sum").unwrap();
+    assert!(sum >= 3.0 * 0.05, "queued requests should have waited for the lock, sum = {}", sum);
+}
+
+#[tokio::test]
+async fn soft_deny_softens_policy_denials_only() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.soft_deny = true;
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state, hits.clone())).await;
+    let client = reqwest::Client::new();
+
+    // Policy denial: 200 with a deny marker, handler not called.
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["vac_decision"], "deny");
+    assert_eq!(body["reason"], "policy_violation");
+
+    // Signature failure: still a hard 403.
+    let forged = common::generate_test_root_biscuit(&KeyPair::new()).unwrap().to_base64().unwrap();
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", forged))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // Missing token: still a hard 401.
+    let resp = client.get(format!("{}/hello", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn soft_deny_does_not_soften_lockdown() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.soft_deny = true;
+        s.enter_lockdown();
+    }
+    let hits = Arc::new(AtomicUsize::new(0));
+    let handler_hits = hits.clone();
+    let router = Router::new()
+        .route(
+            "/charge",
+            axum::routing::post(move || {
+                let hits = handler_hits.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    "charged"
+                }
+            }),
+        )
+        .layer(VacGuardLayer::new(state));
+    let base = serve(router).await;
+
+    // Lockdown rejects writes before any token is checked: a hard 403, not a 200 marker.
+    let resp = reqwest::Client::new().post(format!("{}/charge", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert!(!resp.text().await.unwrap().contains("vac_decision"));
+
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn fixed_correlation_id_generator_makes_replay_deterministic() {
+    let root_kp = KeyPair::new();
+    // Replay cache enabled.
+    let state: SharedState = Arc::new(tokio::sync::RwLock::new(vac_sidecar::SidecarState::new(
+        root_kp.public(),
+        "k".to_string(),
+        "http://upstream.invalid".to_string(),
+        100,
+        60,
+        true,
+        60,
+    )));
+    {
+        let mut s = state.write().await;
+        s.correlation_id_generator = Arc::new(|| "fixed-cid-1".to_string());
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state, hits)).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+
+    // Neither request sends X-Correlation-ID, so both get the generated "fixed-cid-1".
+    let send = || {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+    };
+    let first: serde_json::Value = send().await.unwrap().json().await.unwrap();
+    assert_eq!(first["correlation_id"], "fixed-cid-1");
+    assert_eq!(first["error"], "policy_violation");
+
+    let second = send().await.unwrap();
+    assert_eq!(second.status().as_u16(), 403);
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(second["correlation_id"], "fixed-cid-1");
+    assert_eq!(second["error"], "replay");
+}
+
+#[tokio::test]
+async fn require_correlation_id_rejects_requests_without_one() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+
+    // Default: a correlation ID is generated and the request continues (here: to the token check).
+    let resp = client.get(format!("{}/hello", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["correlation_id"].as_str().is_some_and(|c| !c.is_empty()));
+
+    state.write().await.require_correlation_id = true;
+
+    let resp = client.get(format!("{}/hello", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "bad_request");
+    assert!(body["correlation_id"].is_null());
+
+    // Invalid IDs are not replaced in require mode either.
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("X-Correlation-ID", "not valid!")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("X-Correlation-ID", "5f0c6d0e-8a43-4d55-9a3b-2f6f1c9e7b21")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn oversized_token_rejected_before_parsing() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let junk = "A".repeat(200);
+    let send = |token: String| {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+    };
+
+    // Within the limit, the junk is parsed and fails signature verification.
+    assert_eq!(send(junk.clone()).await.unwrap().status().as_u16(), 403);
+
+    state.write().await.max_token_bytes = 64;
+    let resp = send(junk).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "invalid_token_format");
+}
+
+#[tokio::test]
+async fn strict_token_shape_rejects_unknown_fact_predicate() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+
+    let mut builder = biscuit_auth::Biscuit::builder();
+    builder.add_code("depth(0); role(\"admin\");").unwrap();
+    let token = builder.build(&root_kp).unwrap().to_base64().unwrap();
+    let send = || {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+    };
+
+    // Off by default: the extra fact is accepted and the request reaches policy evaluation.
+    assert_eq!(send().await.unwrap().status().as_u16(), 403);
+
+    state.write().await.strict_token_shape = true;
+    let resp = send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "invalid_token_format");
+}
+
+#[tokio::test]
+async fn operator_policy_allows_matching_requests_only() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state.clone(), hits.clone())).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = || {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+    };
+    let set_policy = |source: &str| {
+        let policy = vac_sidecar::PinnedPolicy::load(source.to_string(), None).unwrap();
+        let state = state.clone();
+        async move { state.write().await.policy = Some(Arc::new(policy)) }
+    };
+
+    // No policy: nothing allows the request.
+    assert_eq!(send().await.unwrap().status().as_u16(), 403);
+
+    set_policy(r#"allow if operation("GET", "/hello");"#).await;
+    let resp = send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.text().await.unwrap(), "hello");
+
+    set_policy(r#"allow if operation("POST", "/hello");"#).await;
+    assert_eq!(send().await.unwrap().status().as_u16(), 403);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn charge_without_idempotency_key_is_denied_by_policy() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    // Datalog has no negation: the fact is always present, false without a valid key.
+    let policy = vac_sidecar::PinnedPolicy::load(
+        r#"deny if operation("POST", "/charge"), idempotency_key_present(false);
+            allow if operation("POST", "/charge");"#
+            .to_string(),
+        None,
+    )
+    .unwrap();
+    state.write().await.policy = Some(Arc::new(policy));
+    let charges = Arc::new(AtomicUsize::new(0));
+    let counter = charges.clone();
+    let app = Router::new()
+        .route(
+            "/charge",
+            axum::routing::post(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    "charged"
+                }
+            }),
+        )
+        .layer(VacGuardLayer::new(state));
+    let base = serve(app).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let charge = || {
+        client
+            .post(format!("{}/charge", base))
+            .header("Authorization", format!("Bearer {}", token))
+    };
+
+    assert_eq!(charge().send().await.unwrap().status().as_u16(), 403);
+    assert_eq!(charge().header("Idempotency-Key", "").send().await.unwrap().status().as_u16(), 403);
+    assert_eq!(charges.load(Ordering::SeqCst), 0);
+
+    let resp = charge().header("Idempotency-Key", "charge-7f3a").send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(charges.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn root_token_before_not_before_is_rejected() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+
+    let send = |not_before: std::time::SystemTime| {
+        let token = vac_sidecar::issuer::build_root_biscuit(
+            &root_kp,
+            vac_sidecar::issuer::RootClaims {
+                not_before: Some(not_before),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .to_base64()
+        .unwrap();
+        let request = client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token));
+        async move {
+            let resp = request.send().await.unwrap();
+            let status = resp.status().as_u16();
+            let body: serde_json::Value = resp.json().await.unwrap();
+            (status, body["error"].as_str().unwrap().to_string())
+        }
+    };
+    let hour = std::time::Duration::from_secs(3600);
+
+    let future = send(std::time::SystemTime::now() + hour).await;
+    assert_eq!(future, (403, "token_not_yet_valid".to_string()));
+    // Already active: the token passes and the request reaches the fail-closed policy.
+    let past = send(std::time::SystemTime::now() - hour).await;
+    assert_eq!(past, (403, "policy_violation".to_string()));
+}
+
+#[tokio::test]
+async fn receipts_in_one_comma_joined_header_are_each_verified() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let cid = "0b8e4f4e-6a7c-4f1e-9d2b-3c5a7e9f1d20";
+
+    let mint = |key: &KeyPair, operation: &str| {
+        vac_sidecar::mint_receipt(
+            key,
+            &vac_sidecar::NewReceipt {
+                operation,
+                correlation_id: cid,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64,
+                delegation_chain: &[],
+                depth: None,
+                sidecar_id: "sidecar-1",
+            },
+        )
+        .unwrap()
+        .to_base64()
+        .unwrap()
+    };
+    let (first, second) = {
+        let s = state.read().await;
+        (mint(&s.session_key, "GET /search"), mint(&s.session_key, "GET /details"))
+    };
+    let send = |receipts: String| {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", cid)
+            .header("X-VAC-Receipt", receipts)
+            .send()
+    };
+
+    // Both receipts verify, so the request reaches policy evaluation (no allow policy: 403).
+    let resp = send(format!("{}, {}", first, second)).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "policy_violation");
+
+    // A foreign receipt after the comma is verified too, and rejected.
+    let foreign = mint(&KeyPair::new(), "GET /details");
+    let resp = send(format!("{},{}", first, foreign)).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert_eq!(resp.json::<serde_json::Value>().await.unwrap()["error"], "invalid_signature");
+}
+
+#[tokio::test]
+async fn steps_over_max_steps_per_correlation_are_rejected() {
+    const FLOW_1: &str = "0b7f4c2e-1d3a-4e5f-8a6b-9c0d1e2f3a4b";
+    const FLOW_2: &str = "6a1e2b3c-4d5e-4f60-8172-93a4b5c6d7e8";
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+        s.step_limiter.set_max_steps(Some(2));
+        // Two steps of this flow already minted receipts.
+        for _ in 0..2 {
+            s.step_limiter.try_reserve(FLOW_1).unwrap().unwrap().commit();
+        }
+    }
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state.clone(), hits.clone())).await;
+    let client = reqwest::Client::new();
+    let send = |cid: &'static str| {
+        client
+            .get(format!("{}/hello", base))
+            .header("X-Correlation-ID", cid)
+            .send()
+    };
+
+    let resp = send(FLOW_1).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "step_limit");
+    assert_eq!(body["correlation_id"], FLOW_1);
+
+    // Another flow still has its budget (and fails later, at the token check); its
+    // denied request does not use up a step.
+    assert_eq!(send(FLOW_2).await.unwrap().status().as_u16(), 401);
+    assert_eq!(state.read().await.step_limiter.steps(FLOW_2), 0);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn operation_scoped_replay_key_allows_same_id_on_other_paths() {
+    const CID: &str = "3c9d8e7f-6a5b-4c3d-9e2f-1a0b9c8d7e6f";
+    // Replay cache enabled.
+    let state: SharedState = Arc::new(tokio::sync::RwLock::new(vac_sidecar::SidecarState::new(
+        KeyPair::new().public(),
+        "k".to_string(),
+        "http://upstream.invalid".to_string(),
+        100,
+        60,
+        true,
+        60,
+    )));
+    {
+        let mut s = state.write().await;
+        s.replay_key_includes_operation = true;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let send = |path: &'static str| {
+        client
+            .get(format!("{}{}", base, path))
+            .header("X-Correlation-ID", CID)
+            .send()
+    };
+    let error = |resp: reqwest::Response| async move {
+        let body: serde_json::Value = resp.json().await.unwrap();
+        body["error"].as_str().unwrap().to_string()
+    };
+
+    // First use on each path passes the replay check (and stops at the token check).
+    assert_eq!(error(send("/hello").await.unwrap()).await, "missing_token");
+    assert_eq!(error(send("/other").await.unwrap()).await, "missing_token");
+
+    // The same path with the same ID is still a replay.
+    let resp = send("/hello").await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert_eq!(error(resp).await, "replay");
+}
+
+#[tokio::test]
+async fn request_hash_replay_key_blocks_identical_requests_only() {
+    const CID: &str = "5f4e3d2c-1b0a-4988-8776-655443322110";
+    let root_kp = KeyPair::new();
+    // Replay cache enabled.
+    let state: SharedState = Arc::new(tokio::sync::RwLock::new(vac_sidecar::SidecarState::new(
+        root_kp.public(),
+        "k".to_string(),
+        "http://upstream.invalid".to_string(),
+        100,
+        60,
+        true,
+        60,
+    )));
+    {
+        let mut s = state.write().await;
+        s.replay_key_request_hash = true;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = |correlation_id: &'static str, body: &'static str| {
+        let req = client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", correlation_id)
+            .body(body)
+            .send();
+        async move {
+            let body: serde_json::Value = req.await.unwrap().json().await.unwrap();
+            body["error"].as_str().unwrap().to_string()
+        }
+    };
+
+    // First use passes the replay check (and stops at the policy, which allows nothing).
+    assert_eq!(send(CID, r#"{"amount":1}"#).await, "policy_violation");
+    // The identical request is a replay, under the same or a fresh correlation ID.
+    assert_eq!(send(CID, r#"{"amount":1}"#).await, "replay");
+    assert_eq!(send("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", r#"{"amount":1}"#).await, "replay");
+    // A different body may reuse the correlation ID.
+    assert_eq!(send(CID, r#"{"amount":2}"#).await, "policy_violation");
+}
+
+/// Adapter whose `extract_facts` sleeps for `SLOW_ADAPTER_MS` (WASI `poll_oneoff` on a
+/// monotonic clock) and then returns no facts.
+const SLOW_ADAPTER_MS: u64 = 1000;
+const SLOW_ADAPTER_WAT: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "[]\00")
+  (func (export "extract_facts") (param i32 i32) (result i32)
+    ;; One clock subscription at 64: clock id (monotonic) at 80, timeout (ns) at 88.
+    (i32.store (i32.const 80) (i32.const 1))
+    (i64.store (i32.const 88) (i64.const 1000000000))
+    (drop (call $poll_oneoff (i32.const 64) (i32.const 128) (i32.const 1) (i32.const 192)))
+    (i32.const 0))
+)
+"#;
+
+#[tokio::test]
+async fn concurrent_adapter_runs_per_correlation_id_are_shed() {
+    use sha2::{Digest, Sha256};
+
+    const CID: &str = "9e8d7c6b-5a49-4837-a625-14f3e2d1c0b9";
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let wasm = wat::parse_str(SLOW_ADAPTER_WAT).unwrap();
+    let hash = hex::encode(Sha256::digest(&wasm));
+    {
+        let mut s = state.write().await;
+        s.adapter_registry.load_adapter(&wasm, &hash).unwrap();
+        s.adapter_concurrency.set_max_concurrent(Some(1));
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let token = vac_sidecar::build_root_biscuit(
+        &root_kp,
+        vac_sidecar::RootClaims {
+            adapter_hash: Some(hash),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_base64()
+    .unwrap();
+
+    let send = {
+        let client = reqwest::Client::new();
+        move || {
+            let request = client
+                .post(format!("{}/hello", base))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("X-Correlation-ID", CID);
+            async move {
+                let resp = request.send().await.unwrap();
+                let status = resp.status().as_u16();
+                let body: serde_json::Value = resp.json().await.unwrap();
+                (status, body["error"].as_str().unwrap_or_default().to_string())
+            }
+        }
+    };
+
+    let started = std::time::Instant::now();
+    let tasks: Vec<_> = (0..4).map(|_| tokio::spawn(send())).collect();
+    let mut results = Vec::new();
+    for task in tasks {
+        results.push(task.await.unwrap());
+    }
+    assert!(started.elapsed() >= std::time::Duration::from_millis(SLOW_ADAPTER_MS / 2));
+
+    // One request ran the adapter (and then hit the fail-closed policy); the rest were shed.
+    let shed = results.iter().filter(|r| *r == &(429, "adapter_busy".to_string())).count();
+    let ran = results.iter().filter(|r| r.1 == "policy_violation").count();
+    assert_eq!((ran, shed), (1, 3), "{:?}", results);
+
+    // The permit is released once the run finishes.
+    assert_eq!(state.read().await.adapter_concurrency.running(CID), 0);
+    assert_eq!(send().await.1, "policy_violation");
+}
+
+#[tokio::test]
+async fn adapter_yielding_no_facts_rejected_when_required() {
+    use sha2::{Digest, Sha256};
+
+    const CID: &str = "0b1c2d3e-4f5a-4b6c-8d7e-9f0a1b2c3d4e";
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let wasm = wat::parse_str(
+        r#"(module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[]\00")
+          (func (export "extract_facts") (param i32 i32) (result i32) (i32.const 0)))"#,
+    )
+    .unwrap();
+    let hash = hex::encode(Sha256::digest(&wasm));
+    {
+        let mut s = state.write().await;
+        s.adapter_registry.load_adapter(&wasm, &hash).unwrap();
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let token = vac_sidecar::build_root_biscuit(
+        &root_kp,
+        vac_sidecar::RootClaims {
+            adapter_hash: Some(hash),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_base64()
+    .unwrap();
+    let send = || async {
+        let resp = reqwest::Client::new()
+            .post(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", CID)
+            .body(r#"{"unexpected":"shape"}"#)
+            .send()
+            .await
+            .unwrap();
+        let status = resp.status().as_u16();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        (status, body["error"].as_str().unwrap_or_default().to_string())
+    };
+
+    // Default: the policy is evaluated without adapter facts (and fails closed here).
+    assert_eq!(send().await, (403, "policy_violation".to_string()));
+
+    state.write().await.require_adapter_facts = true;
+    assert_eq!(send().await, (422, "adapter_no_facts".to_string()));
+}
+
+#[tokio::test]
+async fn facts_from_every_pinned_adapter_reach_the_policy() {
+    use sha2::{Digest, Sha256};
+
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let adapter = |facts: &str| {
+        wat::parse_str(format!(
+            r#"(module
+              (memory (export "memory") 1)
+              (data (i32.const 0) "{}\00")
+              (func (export "extract_facts") (param i32 i32) (result i32) (i32.const 0)))"#,
+            facts.replace('"', "\\\""),
+        ))
+        .unwrap()
+    };
+    let payments = adapter(r#"[{"fact":"amount","args":["350"]}]"#);
+    let pii = adapter(r#"[{"fact":"pii_redacted","args":["yes"]}]"#);
+    let (payments_hash, pii_hash) = (hex::encode(Sha256::digest(&payments)), hex::encode(Sha256::digest(&pii)));
+    {
+        let mut s = state.write().await;
+        s.adapter_registry.load_adapter(&payments, &payments_hash).unwrap();
+        s.adapter_registry.load_adapter(&pii, &pii_hash).unwrap();
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+        s.policy = Some(Arc::new(
+            vac_sidecar::PinnedPolicy::load(
+                r#"allow if amount("350"), pii_redacted("yes");"#.to_string(),
+                None,
+            )
+            .unwrap(),
+        ));
+    }
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let token = |hashes: &[&str]| {
+        let mut builder = biscuit_auth::Biscuit::builder();
+        for hash in hashes {
+            builder.add_code(format!(r#"adapter_hash("{}");"#, hash)).unwrap();
+        }
+        builder.build(&root_kp).unwrap().to_base64().unwrap()
+    };
+    let send = |token: String| {
+        let base = base.clone();
+        async move {
+            let resp = reqwest::Client::new()
+                .post(format!("{}/hello", base))
+                .header("Authorization", format!("Bearer {}", token))
+                .send()
+                .await
+                .unwrap();
+            let status = resp.status().as_u16();
+            let body: serde_json::Value = resp.json().await.unwrap_or_default();
+            (status, body["error"].as_str().unwrap_or_default().to_string())
+        }
+    };
+
+    // Both adapters ran: the policy needs a fact from each.
+    assert_eq!(send(token(&[&payments_hash, &pii_hash])).await.0, 200);
+    assert_eq!(send(token(&[&payments_hash])).await, (403, "policy_violation".to_string()));
+
+    // One pinned adapter not loaded: the request fails rather than skipping it.
+    let unknown = "0".repeat(64);
+    assert_eq!(send(token(&[&payments_hash, &pii_hash, &unknown])).await, (500, "config_error".to_string()));
+}
+
+#[tokio::test]
+async fn correlation_id_bound_to_first_token() {
+    const CID: &str = "1f2e3d4c-5b6a-4798-8a9b-0c1d2e3f4a5b";
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.bind_correlation_to_token = true;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let token_a = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let token_b = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    assert_ne!(token_a, token_b);
+    let send = |token: &str| {
+        client
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", CID)
+            .send()
+    };
+    let error = |resp: reqwest::Response| async move {
+        let body: serde_json::Value = resp.json().await.unwrap();
+        body["error"].as_str().unwrap().to_string()
+    };
+
+    // Token A starts the flow (and reaches the fail-closed policy) and may continue it.
+    assert_eq!(error(send(&token_a).await.unwrap()).await, "policy_violation");
+    assert_eq!(error(send(&token_a).await.unwrap()).await, "policy_violation");
+
+    // Token B, equally valid, cannot continue A's flow.
+    let resp = send(&token_b).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 409);
+    assert_eq!(error(resp).await, "correlation_token_mismatch");
+}
+
+#[tokio::test]
+async fn body_not_matching_content_length_is_rejected() {
+    use tower::ServiceExt;
+
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let hits = Arc::new(AtomicUsize::new(0));
+    let router = app(state, hits.clone());
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    // HTTP clients frame bodies by their Content-Length, so drive the router directly.
+    let send = |content_length: &'static str, body: &'static str| {
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri("/hello")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Length", content_length)
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        let router = router.clone();
+        async move {
+            let resp = router.oneshot(req).await.unwrap();
+            let status = resp.status().as_u16();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            (status, body["error"].as_str().unwrap().to_string())
+        }
+    };
+
+    // Shorter and longer than declared.
+    assert_eq!(send("10", "{}").await, (400, "bad_request".to_string()));
+    assert_eq!(send("1", "{}").await, (400, "bad_request".to_string()));
+    // Matching length gets through to the (fail-closed) policy.
+    assert_eq!(send("2", "{}").await, (403, "policy_violation".to_string()));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn options_asterisk_and_absolute_form_targets() {
+    use tower::{Layer, ServiceExt};
+
+    const CID: &str = "7d6c5b4a-3f2e-4d1c-8b0a-9f8e7d6c5b4a";
+    // Replay cache enabled and scoped to the operation, so the replay key shows the path
+    // the guard authorized.
+    let state: SharedState = Arc::new(tokio::sync::RwLock::new(vac_sidecar::SidecarState::new(
+        KeyPair::new().public(),
+        "k".to_string(),
+        "http://upstream.invalid".to_string(),
+        100,
+        60,
+        true,
+        60,
+    )));
+    {
+        let mut s = state.write().await;
+        s.replay_key_includes_operation = true;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let hits = Arc::new(AtomicUsize::new(0));
+    // Guard the whole router, so targets that match no route still reach the guard.
+    let inner = Router::new().route(
+        "/hello",
+        get({
+            let hits = hits.clone();
+            move || async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                "hello"
+            }
+        }),
+    );
+    let guarded = VacGuardLayer::new(state.clone()).layer(inner);
+    let send = |method: &'static str, uri: &'static str| {
+        let req = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("X-Correlation-ID", CID)
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let guarded = guarded.clone();
+        async move {
+            let resp = guarded.oneshot(req).await.unwrap();
+            let status = resp.status().as_u16();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let error = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|b| b["error"].as_str().map(str::to_string));
+            (status, error)
+        }
+    };
+
+    // `OPTIONS *` is rejected by default, or answered locally.
+    assert_eq!(send("OPTIONS", "*").await, (400, Some("bad_request".to_string())));
+    state.write().await.options_asterisk = vac_sidecar::OptionsAsterisk::Respond;
+    assert_eq!(send("OPTIONS", "*").await, (204, None));
+
+    // An absolute-form target is authorized as its path: the origin-form request for the
+    // same operation afterwards is a replay of it.
+    assert_eq!(
+        send("GET", "http://other.example/hello").await,
+        (401, Some("missing_token".to_string()))
+    );
+    assert_eq!(send("GET", "/hello").await, (403, Some("replay".to_string())));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn bearer_scheme_matched_case_insensitively() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = |authorization: String| {
+        let req = client
+            .get(format!("{}/hello", base))
+            .header("Authorization", authorization)
+            .send();
+        async move {
+            let resp = req.await.unwrap();
+            let status = resp.status().as_u16();
+            let body: serde_json::Value = resp.json().await.unwrap();
+            (status, body["error"].as_str().unwrap().to_string())
+        }
+    };
+    let policy_violation = (403, "policy_violation".to_string());
+
+    // Any scheme case and extra whitespace: the token is verified (and reaches the policy).
+    assert_eq!(send(format!("bearer {}", token)).await, policy_violation);
+    assert_eq!(send(format!("BEARER   {}", token)).await, policy_violation);
+    // Another scheme carries no bearer token.
+    assert_eq!(send("Basic dXNlcjpwdw==".to_string()).await, (401, "missing_token".to_string()));
+    // Bearer without a single token is malformed.
+    assert_eq!(send("Bearer".to_string()).await, (400, "invalid_token_format".to_string()));
+    assert_eq!(
+        send(format!("Bearer {} extra", token)).await,
+        (400, "invalid_token_format".to_string())
+    );
+}
+
+/// External root verifier that counts calls and either vouches for `key` or denies.
+struct MockRootVerifier {
+    key: biscuit_auth::PublicKey,
+    allow: bool,
+    calls: AtomicUsize,
+}
+
+impl vac_sidecar::RootTokenVerifier for MockRootVerifier {
+    fn verify<'a>(&'a self, _token: &'a str) -> vac_sidecar::RootVerifyFuture<'a> {
+        Box::pin(async move {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.allow {
+                Ok(self.key)
+            } else {
+                Err(vac_sidecar::VacError::InvalidSignature)
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn external_root_verifier_is_invoked_and_honored() {
+    let root_kp = KeyPair::new();
+    // The in-process key is wrong: only the external verifier can vouch for the token.
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let base = serve(app(state.clone(), Arc::new(AtomicUsize::new(0)))).await;
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = || {
+        let req = reqwest::Client::new()
+            .get(format!("{}/hello", base))
+            .header("Authorization", format!("Bearer {}", token))
+            .send();
+        async move {
+            let resp = req.await.unwrap();
+            let status = resp.status().as_u16();
+            let body: serde_json::Value = resp.json().await.unwrap();
+            (status, body["error"].as_str().unwrap().to_string())
+        }
+    };
+
+    for (allow, expected) in [(false, "invalid_signature"), (true, "policy_violation")] {
+        let verifier = Arc::new(MockRootVerifier {
+            key: root_kp.public(),
+            allow,
+            calls: AtomicUsize::new(0),
+        });
+        state.write().await.root_token_verifier = Some(verifier.clone());
+        assert_eq!(send().await, (403, expected.to_string()));
+        assert_eq!(verifier.calls.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[tokio::test]
+async fn duplicate_authorization_headers_rejected_before_verification() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    let verifier = Arc::new(MockRootVerifier {
+        key: root_kp.public(),
+        allow: true,
+        calls: AtomicUsize::new(0),
+    });
+    state.write().await.root_token_verifier = Some(verifier.clone());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let base = serve(app(state, hits.clone())).await;
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+
+    // One valid token and one junk token: neither is picked, the request is malformed.
+    let resp = reqwest::Client::new()
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Authorization", "Bearer junk")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "bad_request");
+    assert_eq!(verifier.calls.load(Ordering::SeqCst), 0);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn correlation_id_is_echoed_on_allowed_and_denied_responses() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.correlation_id_generator = Arc::new(|| "generated-cid".to_string());
+        let policy = vac_sidecar::PinnedPolicy::load(r#"allow if operation("GET", "/hello");"#.to_string(), None);
+        s.policy = Some(Arc::new(policy.unwrap()));
+    }
+    let base = serve(app(state, Arc::new(AtomicUsize::new(0)))).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let correlation_id = |resp: &reqwest::Response| {
+        resp.headers()
+            .get(vac_sidecar::CORRELATION_ID_HEADER)
+            .map(|v| v.to_str().unwrap().to_string())
+    };
+
+    // Denied without a token, under a generated ID.
+    let resp = client.get(format!("{}/hello", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+    assert_eq!(correlation_id(&resp).as_deref(), Some("generated-cid"));
+
+    // The caller's own ID comes back on a denial and on an allowed request.
+    let cid = "5e4d3c2b-1a09-4f8e-9d7c-6b5a4f3e2d1c";
+    let resp = client
+        .post(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("X-Correlation-ID", cid)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    assert_eq!(correlation_id(&resp).as_deref(), Some(cid));
+    let resp = client
+        .get(format!("{}/hello", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("X-Correlation-ID", cid)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(correlation_id(&resp).as_deref(), Some(cid));
+}
+
+#[tokio::test]
+async fn completion_receipt_covers_every_step_of_the_flow() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let policy = vac_sidecar::PinnedPolicy::load(
+        r#"allow if operation("GET", "/search");
+allow if operation("POST", "/select");
+allow if operation("POST", "/charge");"#
+            .to_string(),
+        None,
+    );
+    state.write().await.policy = Some(Arc::new(policy.unwrap()));
+    let router = Router::new()
+        .route("/search", get(|| async { "results" }))
+        .route("/select", axum::routing::post(|| async { "selected" }))
+        .route("/charge", axum::routing::post(|| async { "charged" }))
+        .layer(VacGuardLayer::new(state.clone()));
+    let base = serve(router).await;
+    let client = reqwest::Client::new();
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let cid = "0f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9";
+    let step = |method: reqwest::Method, route: &str, receipts: &[String]| {
+        let mut req = client
+            .request(method, format!("{}{}", base, route))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", cid);
+        for receipt in receipts {
+            req = req.header("X-VAC-Receipt", receipt.as_str());
+        }
+        req
+    };
+    let receipt_of = |resp: &reqwest::Response| {
+        assert_eq!(resp.status().as_u16(), 200);
+        resp.headers()["x-vac-receipt"].to_str().unwrap().to_string()
+    };
+
+    let search = receipt_of(&step(reqwest::Method::GET, "/search", &[]).send().await.unwrap());
+    let select = receipt_of(&step(reqwest::Method::POST, "/select", std::slice::from_ref(&search)).send().await.unwrap());
+    // Without the header, no completion receipt.
+    let resp = step(reqwest::Method::POST, "/charge", &[search.clone(), select.clone()]).send().await.unwrap();
+    assert!(resp.headers().get(vac_sidecar::COMPLETION_RECEIPT_HEADER).is_none());
+    let resp = step(reqwest::Method::POST, "/charge", &[search, select])
+        .header(vac_sidecar::COMPLETE_FLOW_HEADER, "true")
+        .send()
+        .await
+        .unwrap();
+    receipt_of(&resp);
+    let completion = resp.headers()[vac_sidecar::COMPLETION_RECEIPT_HEADER].to_str().unwrap().to_string();
+
+    // Signed by this sidecar's session key, listing all three operations in order.
+    let session_key = state.read().await.session_key.public();
+    let completion = vac_sidecar::verify_receipt_biscuit(&completion, &session_key).unwrap();
+    let info = vac_sidecar::extract_completion_info(&completion).unwrap();
+    assert_eq!(info.correlation_id, cid);
+    let operations: Vec<&str> = info.steps.iter().map(|(op, _)| op.as_str()).collect();
+    assert_eq!(operations, ["GET /search", "POST /select", "POST /charge"]);
+}
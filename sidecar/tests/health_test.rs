@@ -0,0 +1,67 @@
+//! Integration tests for the `/_vac/healthz` and `/_vac/readyz` probes: served without a
+//! token, while other paths (including an upstream `/healthz`) still go through the guard.
+
+mod common;
+
+use axum::{
+    routing::{any, get},
+    Router,
+};
+use biscuit_auth::KeyPair;
+use tower::ServiceExt;
+
+use vac_sidecar::{
+    healthz_handler, readyz_handler, SharedState, VacGuardLayer, HEALTHZ_PATH, READYZ_PATH,
+};
+
+/// Router shaped like the sidecar binary's: probes ahead of the guarded catch-all.
+fn app(state: SharedState) -> Router {
+    Router::new()
+        .route(HEALTHZ_PATH, get(healthz_handler))
+        .route(READYZ_PATH, get(readyz_handler))
+        .route(
+            "/*path",
+            any(|| async { "upstream" }).layer(VacGuardLayer::new(state.clone())),
+        )
+        .with_state(state)
+}
+
+async fn status(app: &Router, path: &str) -> u16 {
+    let req = axum::http::Request::builder()
+        .uri(path)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    app.clone().oneshot(req).await.unwrap().status().as_u16()
+}
+
+#[tokio::test]
+async fn probes_bypass_the_guard() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let app = app(state.clone());
+
+    assert_eq!(status(&app, HEALTHZ_PATH).await, 200);
+    // Not ready until the first heartbeat succeeds.
+    assert_eq!(status(&app, READYZ_PATH).await, 503);
+    state.write().await.heartbeat_healthy = true;
+    assert_eq!(status(&app, READYZ_PATH).await, 200);
+    // An upstream path named `/healthz` is not shadowed: it still needs a token.
+    assert_eq!(status(&app, "/healthz").await, 401);
+}
+
+#[tokio::test]
+async fn readyz_reports_unhealthy_heartbeat_and_lockdown() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let app = app(state.clone());
+
+    state.write().await.heartbeat_healthy = false;
+    assert_eq!(status(&app, READYZ_PATH).await, 503);
+    assert_eq!(status(&app, HEALTHZ_PATH).await, 200);
+
+    {
+        let mut s = state.write().await;
+        s.heartbeat_healthy = true;
+        s.enter_lockdown();
+    }
+    assert_eq!(status(&app, READYZ_PATH).await, 503);
+    assert_eq!(status(&app, HEALTHZ_PATH).await, 200);
+}
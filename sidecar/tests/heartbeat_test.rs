@@ -5,7 +5,9 @@ mod common;
 use wiremock::{Mock, MockServer, ResponseTemplate};
 use wiremock::matchers::{method, path};
 
-use vac_sidecar::{send_heartbeat, SharedState};
+use std::time::Duration;
+
+use vac_sidecar::{heartbeat_retry_delay, send_heartbeat, send_heartbeat_to_any, SharedState, HEARTBEAT_MAX_BACKOFF};
 
 #[tokio::test]
 async fn heartbeat_success_updates_state() {
@@ -55,3 +57,318 @@ async fn heartbeat_failure_increments_count() {
     assert!(!s.heartbeat_healthy);
     assert_eq!(s.heartbeat_failure_count, 1);
 }
+
+#[tokio::test]
+async fn heartbeat_delay_backs_off_on_consecutive_failures() {
+    let mock = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(4)
+        .mount(&mock)
+        .await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "healthy": true })))
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    let interval = Duration::from_secs(10);
+    let next_delay = || async { heartbeat_retry_delay(interval, state.read().await.heartbeat_failure_count) };
+
+    // Each failure doubles the delay (less up to a quarter of jitter): ~10s, ~20s, ~40s, ~80s.
+    let mut previous = Duration::ZERO;
+    for failures in 1..=4u32 {
+        assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.is_err());
+        let delay = next_delay().await;
+        let backoff = interval * (1 << (failures - 1));
+        assert!(delay > previous, "failure {}: {:?} after {:?}", failures, delay, previous);
+        assert!(delay <= backoff && delay >= backoff.mul_f64(0.75), "failure {}: {:?}", failures, delay);
+        previous = delay;
+    }
+    // Lockdown still counts the consecutive failures.
+    assert!(state.read().await.lockdown_mode);
+
+    // Capped however long the outage lasts.
+    let capped = heartbeat_retry_delay(interval, 30);
+    assert!(capped <= HEARTBEAT_MAX_BACKOFF && capped >= HEARTBEAT_MAX_BACKOFF.mul_f64(0.75));
+
+    // The first success goes back to the plain interval.
+    assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.is_ok());
+    assert_eq!(next_delay().await, interval);
+}
+
+#[tokio::test]
+async fn lockdown_is_lifted_after_consecutive_successful_heartbeats() {
+    let mock = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(3)
+        .mount(&mock)
+        .await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "healthy": true })))
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    for _ in 0..3 {
+        assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.is_err());
+    }
+    assert!(state.read().await.lockdown_mode);
+
+    // The control plane is back: the task lifts lockdown after three successes in a row.
+    let shutdown = vac_sidecar::CancellationToken::new();
+    let task = tokio::spawn(vac_sidecar::start_heartbeat_task(
+        state.clone(),
+        vec![mock.uri()],
+        0,
+        300,
+        shutdown.clone(),
+    ));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state.read().await.lockdown_mode {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("lockdown was not lifted");
+    shutdown.cancel();
+    task.await.unwrap();
+
+    let heartbeats = mock.received_requests().await.unwrap().iter().filter(|r| r.url.path() == "/heartbeat").count();
+    assert!(heartbeats >= 3 + 3, "lifted after {} heartbeats", heartbeats);
+}
+
+#[tokio::test]
+async fn unrevoked_token_ids_are_removed_from_the_filter() {
+    let mock = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "healthy": true,
+                "revoked_token_ids": [vec![1u8; 32], vec![2u8; 32]],
+                "unrevoked_token_ids": [vec![2u8; 32]]
+            })),
+        )
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+
+    assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.unwrap());
+    let s = state.read().await;
+    let filter = s.revocation_filter.read().unwrap();
+    assert!(filter.is_revoked(&[1u8; 32]));
+    assert!(!filter.is_revoked(&[2u8; 32]));
+    assert_eq!(filter.revoked_count(), 1);
+}
+
+#[tokio::test]
+async fn over_limit_revocation_list_not_applied() {
+    let mock = MockServer::start().await;
+    let ids: Vec<Vec<u8>> = (1..=3u8).map(|i| vec![i; 32]).collect();
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "healthy": true,
+                "revoked_token_ids": ids
+            })),
+        )
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.write().await.max_revocation_list_size = 2;
+
+    // The rest of the response still counts: the heartbeat succeeds, the list is dropped.
+    let res = send_heartbeat(&state, mock.uri().as_str(), 300).await;
+    assert!(res.unwrap());
+    let s = state.read().await;
+    assert!(s.heartbeat_healthy);
+    assert_eq!(s.revocation_filter.read().unwrap().revoked_count(), 0);
+    drop(s);
+
+    state.write().await.max_revocation_list_size = 3;
+    send_heartbeat(&state, mock.uri().as_str(), 300).await.unwrap();
+    assert_eq!(state.read().await.revocation_filter.read().unwrap().revoked_count(), 3);
+}
+
+#[tokio::test]
+async fn oversized_heartbeat_body_rejected_before_parsing() {
+    let mock = MockServer::start().await;
+    // Far beyond the body budget for a 2-entry list; never buffered in full.
+    let huge_list = format!(
+        "{{\"healthy\":true,\"revoked_token_ids\":[{}]}}",
+        vec![format!("[{}]", vec!["255"; 32].join(",")); 20_000].join(",")
+    );
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(huge_list))
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.write().await.max_revocation_list_size = 2;
+
+    let res = send_heartbeat(&state, mock.uri().as_str(), 300).await;
+    assert!(res.is_err());
+    let s = state.read().await;
+    assert_eq!(s.heartbeat_failure_count, 1);
+    assert_eq!(s.revocation_filter.read().unwrap().revoked_count(), 0);
+}
+
+#[tokio::test]
+async fn heartbeat_body_budget_fits_full_lists_of_the_longest_ids() {
+    let longest_id = format!("[{}]", vec!["255"; 32].join(","));
+    let body = |entries: usize| {
+        let list = vec![longest_id.as_str(); entries].join(",");
+        format!("{{\"healthy\":true,\"revoked_token_ids\":[{list}],\"unrevoked_token_ids\":[{list}]}}")
+    };
+    let mock = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body(1_000)))
+        .up_to_n_times(1)
+        .mount(&mock)
+        .await;
+    // Three times the allowed list size is refused before parsing, not parsed and dropped.
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body(3_000)))
+        .mount(&mock)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.write().await.max_revocation_list_size = 1_000;
+
+    assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.unwrap());
+    assert!(send_heartbeat(&state, mock.uri().as_str(), 300).await.is_err());
+    assert_eq!(state.read().await.heartbeat_failure_count, 1);
+}
+
+#[tokio::test]
+async fn heartbeat_task_panic_trips_lockdown() {
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.write().await.heartbeat_healthy = true;
+
+    let supervisor = vac_sidecar::supervise_heartbeat(
+        state.clone(),
+        vac_sidecar::HeartbeatExitAction::Lockdown,
+        std::time::Duration::from_millis(10),
+        vac_sidecar::CancellationToken::new(),
+        || async { panic!("heartbeat loop crashed") },
+    );
+    // Returns once lockdown is tripped; the task is not restarted.
+    tokio::time::timeout(std::time::Duration::from_secs(5), supervisor).await.unwrap();
+
+    let s = state.read().await;
+    assert!(!s.heartbeat_healthy);
+    assert!(s.lockdown_mode);
+}
+
+#[tokio::test]
+async fn exited_heartbeat_task_is_restarted() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    state.write().await.heartbeat_healthy = true;
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let supervisor = tokio::spawn(vac_sidecar::supervise_heartbeat(
+        state.clone(),
+        vac_sidecar::HeartbeatExitAction::Restart,
+        std::time::Duration::from_millis(10),
+        vac_sidecar::CancellationToken::new(),
+        {
+            let runs = runs.clone();
+            move || {
+                let runs = runs.clone();
+                // Exits immediately, as the loop does when the control plane reports unhealthy.
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        },
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    supervisor.abort();
+
+    assert!(runs.load(Ordering::SeqCst) >= 2, "task was not restarted");
+    let s = state.read().await;
+    assert!(!s.heartbeat_healthy);
+    assert!(!s.lockdown_mode);
+}
+
+#[tokio::test]
+async fn heartbeat_fails_over_to_secondary_control_plane() {
+    // Primary: nothing listening on the port any more.
+    let dead_primary = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+    let secondary = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/heartbeat"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "healthy": true,
+                "revoked_token_ids": [vec![7u8; 32]]
+            })),
+        )
+        .expect(1)
+        .mount(&secondary)
+        .await;
+
+    let state: SharedState = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    let control_plane_urls = vec![dead_primary.clone(), secondary.uri()];
+
+    let (healthy, answered_by) = send_heartbeat_to_any(&state, &control_plane_urls, 300).await.unwrap();
+    assert!(healthy);
+    assert_eq!(answered_by, secondary.uri());
+    {
+        let s = state.read().await;
+        assert!(s.heartbeat_healthy);
+        assert_eq!(s.heartbeat_failure_count, 0);
+        assert!(s.revocation_filter.read().unwrap().is_revoked(&[7u8; 32]));
+    }
+    secondary.verify().await;
+
+    // Every control plane down: one failure, not one per URL.
+    assert!(send_heartbeat_to_any(&state, &[dead_primary.as_str()], 300).await.is_err());
+    let s = state.read().await;
+    assert!(!s.heartbeat_healthy);
+    assert_eq!(s.heartbeat_failure_count, 1);
+}
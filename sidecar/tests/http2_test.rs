@@ -0,0 +1,88 @@
+//! Integration tests for inbound HTTP/2 (`server_http2_enabled`, h2c prior knowledge).
+
+mod common;
+
+use axum::{routing::any, Router};
+use biscuit_auth::KeyPair;
+
+use vac_sidecar::SharedState;
+
+/// Verify the bearer token against the root key and answer 200, like an authorized request.
+fn app(state: SharedState) -> Router {
+    async fn handler(
+        axum::extract::State(state): axum::extract::State<SharedState>,
+        req: axum::extract::Request,
+    ) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        use vac_sidecar::{verify_root_biscuit, VacError};
+
+        let token = match req
+            .headers()
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        {
+            Some(t) => t.to_string(),
+            None => return VacError::MissingToken.into_response(),
+        };
+        let root_key = state.read().await.user_root_public_key;
+        match verify_root_biscuit(&token, &root_key, None) {
+            Ok(_) => "OK".into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    Router::new().route("/*path", any(handler)).with_state(state)
+}
+
+async fn serve(app: Router, http2_enabled: bool) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(vac_sidecar::server::serve(
+        listener,
+        app,
+        http2_enabled,
+        vac_sidecar::CancellationToken::new(),
+    ));
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_http2_prior_knowledge_request_authorized() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let base = serve(app(state), true).await;
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+
+    let client = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+    let resp = client
+        .get(format!("{}/test", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+    assert_eq!(resp.status().as_u16(), 200);
+
+    // HTTP/1.1 clients keep working on the same listener.
+    let resp = reqwest::Client::new()
+        .get(format!("{}/test", base))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_http2_rejected_when_disabled() {
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let base = serve(app(state), false).await;
+
+    let client = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+    assert!(client.get(format!("{}/test", base)).send().await.is_err());
+
+    let resp = reqwest::Client::new().get(format!("{}/test", base)).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+}
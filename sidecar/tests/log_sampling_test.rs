@@ -0,0 +1,86 @@
+//! Integration test for `log_sampling`: a flood of identical denials is logged as a few
+//! lines plus one summary instead of one line per request.
+
+mod common;
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{routing::get, Router};
+use biscuit_auth::KeyPair;
+use tower::{Layer, ServiceExt};
+use tracing_subscriber::prelude::*;
+
+use vac_sidecar::{log_suppressed, LogSampler, LogSampling, VacGuardLayer};
+
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    fn lines_containing(&self, needle: &str) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains(needle))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn invalid_signature_flood_is_summarized() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let sampler = LogSampler::new(LogSampling {
+        max_per_interval: 5,
+        interval: Duration::from_secs(10),
+    });
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .with_filter(Some(sampler.clone())),
+    );
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let guarded = VacGuardLayer::new(state).layer(Router::new().route("/hello", get(|| async { "hello" })));
+    let forged = common::generate_test_root_biscuit(&KeyPair::new()).unwrap().to_base64().unwrap();
+    for _ in 0..200 {
+        let req = axum::http::Request::builder()
+            .uri("/hello")
+            .header("Authorization", format!("Bearer {}", forged))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = guarded.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+    }
+
+    // Only the first five denials are logged individually...
+    assert_eq!(captured.lines_containing("Root Biscuit verification failed").len(), 5);
+
+    // ...and the interval summary accounts for all of them.
+    log_suppressed(&sampler);
+    let summary = captured.lines_containing("denials in last 10s");
+    assert_eq!(summary.len(), 1, "{:?}", summary);
+    assert!(
+        summary[0].contains("200 invalid_biscuit_signature denials in last 10s (195 not logged individually)"),
+        "{}",
+        summary[0]
+    );
+
+    // A new interval starts from zero.
+    log_suppressed(&sampler);
+    assert_eq!(captured.lines_containing("denials in last 10s").len(), 1);
+}
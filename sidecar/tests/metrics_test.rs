@@ -0,0 +1,134 @@
+//! Integration tests for `/_vac/metrics`: distinct deny reasons show up as separately labeled counters.
+
+use axum::{
+    routing::{any, get},
+    Router,
+};
+use biscuit_auth::KeyPair;
+use std::sync::Arc;
+
+use vac_sidecar::metrics::metrics_handler;
+use vac_sidecar::{extract_token_id, verify_root_biscuit, SharedState, SidecarState, VacError, METRICS_PATH};
+
+/// Guard front half (replay -> rate limit -> token -> root verification), recording the
+/// decision the same way the sidecar binary does.
+async fn deny_path_handler(
+    axum::extract::State(state): axum::extract::State<SharedState>,
+    req: axum::extract::Request,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+
+    async fn guard(state: &SharedState, headers: &axum::http::HeaderMap) -> Result<(), VacError> {
+        let s = state.read().await;
+        if let Some(cid) = headers.get("X-Correlation-ID").and_then(|h| h.to_str().ok()) {
+            if s.replay_cache.check_and_insert(cid) == Ok(false) {
+                return Err(VacError::Replay);
+            }
+        }
+        if !s.rate_limiter.check(&s.sidecar_id) {
+            return Err(VacError::RateLimited { retry_after_secs: 1 });
+        }
+        let token = headers
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(VacError::MissingToken)?;
+        verify_root_biscuit(token, &s.user_root_public_key, Some(&s.revocation_filter))?;
+        Ok(())
+    }
+
+    let metrics = state.read().await.metrics.clone();
+    match guard(&state, req.headers()).await {
+        Ok(()) => {
+            metrics.record_allow();
+            "OK".into_response()
+        }
+        Err(e) => {
+            metrics.record_error(&e);
+            e.into_response()
+        }
+    }
+}
+
+fn app(state: SharedState) -> Router {
+    Router::new()
+        .route(METRICS_PATH, get(metrics_handler))
+        .route("/*path", any(deny_path_handler))
+        .with_state(state)
+}
+
+#[tokio::test]
+async fn deny_reasons_are_counted_separately() {
+    let root_kp = KeyPair::new();
+    // Replay cache on, and a rate limit of 5 requests so the last request trips it.
+    let state: SharedState = Arc::new(tokio::sync::RwLock::new(SidecarState::new(
+        root_kp.public(),
+        "k".to_string(),
+        "http://upstream.example".to_string(),
+        5,
+        60,
+        true,
+        60,
+    )));
+
+    let good = biscuit_auth::Biscuit::builder().build(&root_kp).unwrap().to_base64().unwrap();
+    let revoked = {
+        let mut b = biscuit_auth::Biscuit::builder();
+        b.add_fact(biscuit_auth::builder::Fact::new(
+            "user".to_string(),
+            vec![biscuit_auth::builder::string("revoked")],
+        ))
+        .unwrap();
+        b.build(&root_kp).unwrap().to_base64().unwrap()
+    };
+    let forged = biscuit_auth::Biscuit::builder().build(&KeyPair::new()).unwrap().to_base64().unwrap();
+    {
+        let s = state.read().await;
+        let token_id = extract_token_id(&revoked).unwrap();
+        s.revocation_filter.write().unwrap().revoke(&token_id, vac_sidecar::RevocationSource::Admin).unwrap();
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app(state).into_make_service()).await.unwrap();
+    });
+    let base = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+
+    let send = |auth: Option<&str>, cid: &str| {
+        let mut rb = client.get(format!("{}/test", base)).header("X-Correlation-ID", cid);
+        if let Some(token) = auth {
+            rb = rb.header("Authorization", format!("Bearer {}", token));
+        }
+        rb.send()
+    };
+
+    assert_eq!(send(Some(&good), "cid-1").await.unwrap().status(), 200);
+    assert_eq!(send(None, "cid-2").await.unwrap().status(), 401);
+    assert_eq!(send(Some(&forged), "cid-3").await.unwrap().status(), 403);
+    assert_eq!(send(Some(&revoked), "cid-4").await.unwrap().status(), 403);
+    assert_eq!(send(Some(&good), "cid-1").await.unwrap().status(), 403); // replay
+    assert_eq!(send(Some(&good), "cid-5").await.unwrap().status(), 200);
+    assert_eq!(send(Some(&good), "cid-6").await.unwrap().status(), 429); // 6th request: rate limited
+
+    let text = client
+        .get(format!("{}{}", base, METRICS_PATH))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    for line in [
+        "vac_requests_total{decision=\"allow\"} 2",
+        "vac_requests_total{decision=\"deny\",reason=\"missing_token\"} 1",
+        "vac_requests_total{decision=\"deny\",reason=\"invalid_signature\"} 1",
+        "vac_requests_total{decision=\"deny\",reason=\"revoked\"} 1",
+        "vac_requests_total{decision=\"deny\",reason=\"replay\"} 1",
+        "vac_requests_total{decision=\"deny\",reason=\"rate_limit\"} 1",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing `{}` in:\n{}", line, text);
+    }
+}
@@ -0,0 +1,234 @@
+//! Integration tests for the upstream proxy behind the guard: allowed statuses, the host
+//! allowlist, forwarded headers, the upstream timeout and truncated upstream responses.
+
+mod common;
+
+use std::time::Duration;
+
+use biscuit_auth::KeyPair;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use common::{forward, metric_value, mock_upstream, request};
+use vac_sidecar::{UpstreamClientSettings, VacError};
+
+async fn json_body(resp: axum::response::Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+// --- upstream_allowed_statuses: disallowed upstream statuses become 502 ---
+
+/// Upstream whose `/redirect` points at `target`.
+async fn status_upstream(target: &MockServer) -> MockServer {
+    let redirect = ResponseTemplate::new(302).insert_header("Location", format!("{}/landing", target.uri()));
+    let mock_server = mock_upstream("GET", "/redirect", redirect).await;
+    Mock::given(method("GET"))
+        .and(path("/ok"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("OK"))
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+/// Stand-in for the site a redirect points at; it must never be called.
+async fn redirect_target() -> MockServer {
+    mock_upstream("GET", "/landing", ResponseTemplate::new(200).set_body_string("elsewhere")).await
+}
+
+#[tokio::test]
+async fn test_disallowed_upstream_status_becomes_502() {
+    let target = redirect_target().await;
+    let mock_server = status_upstream(&target).await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    state.write().await.upstream_allowed_statuses = Some(vec![200, 201, 204, 400, 404]);
+
+    // The redirect is not followed: its 302 is refused, not replaced by the target's 200.
+    let resp = forward(state.clone(), request("GET", "/redirect", "")).await;
+    assert_eq!(resp.status().as_u16(), 502);
+    assert!(target.received_requests().await.unwrap().is_empty());
+
+    let resp = forward(state, request("GET", "/ok", "")).await;
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_all_upstream_statuses_pass_by_default() {
+    let target = redirect_target().await;
+    let mock_server = status_upstream(&target).await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+
+    let resp = forward(state, request("GET", "/redirect", "")).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(
+        resp.headers().get("location").unwrap().to_str().unwrap(),
+        format!("{}/landing", target.uri())
+    );
+    assert!(target.received_requests().await.unwrap().is_empty());
+}
+
+// --- upstream_host_allowlist: requests resolving to other hosts are refused ---
+
+#[tokio::test]
+async fn test_disallowed_upstream_host_is_refused() {
+    let mock_server = mock_upstream("GET", "/ok", ResponseTemplate::new(200).set_body_string("OK")).await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+
+    // The upstream resolves to 127.0.0.1, which is not allowed: nothing is sent.
+    state.write().await.upstream_host_allowlist = Some(vec!["api.internal".to_string()]);
+    let resp = forward(state.clone(), request("GET", "/ok", "")).await;
+    assert_eq!(resp.status().as_u16(), 500);
+    assert!(mock_server.received_requests().await.unwrap().is_empty());
+
+    state.write().await.upstream_host_allowlist = Some(vec!["api.internal".to_string(), "127.0.0.1".to_string()]);
+    let resp = forward(state, request("GET", "/ok", "")).await;
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_allowed_host_cannot_redirect_to_unlisted_host() {
+    // The target listens on 127.0.0.1 too, but the redirect names it as `localhost`,
+    // which is not on the list.
+    let target = redirect_target().await;
+    let unlisted = format!("http://localhost:{}/landing", target.address().port());
+    let mock_server = mock_upstream(
+        "GET",
+        "/redirect",
+        ResponseTemplate::new(302).insert_header("Location", unlisted.as_str()),
+    )
+    .await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    state.write().await.upstream_host_allowlist = Some(vec!["127.0.0.1".to_string()]);
+
+    // The 302 comes back to the client; the sidecar never calls the unlisted host.
+    let resp = forward(state, request("GET", "/redirect", "")).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("location").unwrap().to_str().unwrap(), unlisted);
+    assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+    assert!(target.received_requests().await.unwrap().is_empty());
+}
+
+// --- Forwarded request: upstream API key in, client `X-VAC-*` headers out ---
+
+#[tokio::test]
+async fn upstream_sees_api_key_and_no_vac_headers() {
+    let mock_server = mock_upstream("GET", "/orders", ResponseTemplate::new(200)).await;
+    let state = common::default_test_state(KeyPair::new().public(), "upstream-key", mock_server.uri());
+
+    let req = axum::http::Request::builder()
+        .uri("/orders?limit=5")
+        .header("Authorization", "Bearer agent-root-biscuit")
+        .header("X-VAC-Receipt", "receipt-token")
+        .header("X-VAC-Delegation", "delegation-token")
+        .header("X-VAC-Delegation-Depth", "0")
+        .header("X-Request-Source", "agent")
+        .body(axum::body::Body::empty())
+        .unwrap();
+    assert_eq!(forward(state, req).await.status().as_u16(), 200);
+
+    let received = mock_server.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].url.query(), Some("limit=5"));
+    let headers = &received[0].headers;
+    assert_eq!(headers.get("authorization").unwrap(), "Bearer upstream-key");
+    assert_eq!(headers.get("x-request-source").unwrap(), "agent");
+    let vac_headers: Vec<_> = headers.keys().filter(|k| k.as_str().starts_with("x-vac-")).collect();
+    assert!(vac_headers.is_empty(), "x-vac-* headers forwarded: {:?}", vac_headers);
+}
+
+// --- upstream_timeout_secs: a hung upstream is answered with 504 ---
+
+async fn slow_upstream(delay: Duration) -> MockServer {
+    mock_upstream("POST", "/charge", ResponseTemplate::new(200).set_body_string("charged").set_delay(delay)).await
+}
+
+#[tokio::test]
+async fn slow_upstream_times_out_with_504() {
+    let mock_server = slow_upstream(Duration::from_secs(5)).await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    {
+        let mut s = state.write().await;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+        s.set_upstream_client_settings(UpstreamClientSettings {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        });
+    }
+
+    let started = std::time::Instant::now();
+    let resp = forward(state.clone(), request("POST", "/charge", "{}")).await;
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert_eq!(resp.status().as_u16(), 504);
+    assert_eq!(json_body(resp).await["error"], "upstream_timeout");
+
+    // The failed call shows up in the upstream metrics.
+    let text = state.read().await.metrics.render();
+    assert_eq!(metric_value(&text, "vac_upstream_errors_total{reason=\"upstream_timeout\"}"), Some(1.0), "{}", text);
+    assert_eq!(metric_value(&text, "vac_upstream_latency_seconds_count"), Some(1.0), "{}", text);
+}
+
+#[tokio::test]
+async fn upstream_within_timeout_succeeds() {
+    let mock_server = slow_upstream(Duration::from_millis(50)).await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", mock_server.uri());
+    state.write().await.set_upstream_client_settings(UpstreamClientSettings {
+        timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    });
+
+    let resp = forward(state, request("POST", "/charge", "{}")).await;
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+// --- Upstreams that close the connection mid-response (`UpstreamTruncated`) ---
+
+/// Upstream that sends a 200 status line and headers announcing 100 body bytes, writes
+/// only a few of them and then closes the connection.
+async fn truncating_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 100\r\n\r\n{\"status\":")
+                .await;
+            let _ = stream.shutdown().await;
+        }
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn truncated_upstream_body_is_a_distinct_502() {
+    let upstream = truncating_upstream().await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", upstream);
+    state.write().await.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+
+    let resp = forward(state, request("POST", "/charge", "{}")).await;
+    // Not a 2xx, so the guard does not mint a receipt for it.
+    assert_eq!(resp.status().as_u16(), 502);
+    assert!(resp.headers().get("X-VAC-Receipt").is_none());
+    let body = json_body(resp).await;
+    assert_eq!(body["error"], "upstream_truncated");
+    assert!(
+        body["message"].as_str().unwrap().contains("closed the connection"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn proxy_reports_truncation_as_upstream_truncated() {
+    let upstream = truncating_upstream().await;
+    let (parts, _) = request("GET", "/charge", "").into_parts();
+
+    let err = vac_sidecar::AxumProxy::new()
+        .forward_with_headers(&parts, Default::default(), "k", &upstream, &Default::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, VacError::UpstreamTruncated(_)), "{:?}", err);
+}
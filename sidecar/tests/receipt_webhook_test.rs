@@ -0,0 +1,87 @@
+//! Integration tests for receipt delivery to `receipt_webhook_url`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use biscuit_auth::KeyPair;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use vac_sidecar::{mint_receipt, NewReceipt, ReceiptEvent, ReceiptWebhook, RECEIPT_WEBHOOK_MAX_ATTEMPTS};
+
+fn minted_event() -> ReceiptEvent {
+    let chain = vec!["ab".repeat(32)];
+    let receipt = mint_receipt(
+        &KeyPair::new(),
+        &NewReceipt {
+            operation: "POST /charge",
+            correlation_id: "cid-1",
+            timestamp: 1_700_000_000,
+            delegation_chain: &chain,
+            depth: Some(0),
+            sidecar_id: "sidecar-1",
+        },
+    )
+    .unwrap();
+    ReceiptEvent {
+        receipt: receipt.to_base64().unwrap(),
+        operation: "POST /charge".to_string(),
+        correlation_id: "cid-1".to_string(),
+        timestamp: 1_700_000_000,
+        sidecar_id: "sidecar-1".to_string(),
+        depth: Some(0),
+        delegation_chain: chain,
+    }
+}
+
+async fn wait_for(mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for webhook delivery");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn webhook_receives_minted_receipt() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/receipts"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let webhook = Arc::new(ReceiptWebhook::new(format!("{}/receipts", server.uri())));
+    let event = minted_event();
+    webhook.emit(event.clone());
+    wait_for(|| webhook.delivered_count() == 1).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["receipt"], event.receipt.as_str());
+    assert_eq!(body["operation"], "POST /charge");
+    assert_eq!(body["correlation_id"], "cid-1");
+    assert_eq!(body["sidecar_id"], "sidecar-1");
+    assert_eq!(body["delegation_chain"][0], event.delegation_chain[0].as_str());
+    assert_eq!(webhook.dropped_count(), 0);
+}
+
+#[tokio::test]
+async fn failing_webhook_does_not_block_and_is_dropped_after_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500).set_delay(Duration::from_millis(200)))
+        .expect(RECEIPT_WEBHOOK_MAX_ATTEMPTS as u64)
+        .mount(&server)
+        .await;
+
+    let webhook = Arc::new(ReceiptWebhook::new(server.uri()));
+    let started = Instant::now();
+    webhook.emit(minted_event());
+    // Emitting only queues the delivery; the client response is not held up by it.
+    assert!(started.elapsed() < Duration::from_millis(100));
+
+    wait_for(|| webhook.dropped_count() == 1).await;
+    assert_eq!(webhook.delivered_count(), 0);
+}
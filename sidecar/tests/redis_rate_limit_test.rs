@@ -0,0 +1,51 @@
+//! Integration tests for `rate_limit_backend = "redis"` (`cargo test --features redis`).
+//!
+//! The shared-bucket test needs a running Redis at `VAC_TEST_REDIS_URL` (e.g.
+//! `redis://127.0.0.1:6379`) and is skipped without one.
+
+#![cfg(feature = "redis")]
+
+use std::time::Duration;
+
+use vac_sidecar::{RateLimitBackend, RateLimiter, RedisRateLimiter};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+fn replica(redis_url: &str) -> RedisRateLimiter {
+    RedisRateLimiter::new(redis_url, 3, WINDOW, Duration::from_secs(600), RateLimiter::new(3, WINDOW)).unwrap()
+}
+
+#[tokio::test]
+async fn replicas_share_buckets_through_redis() {
+    let Ok(redis_url) = std::env::var("VAC_TEST_REDIS_URL") else {
+        eprintln!("VAC_TEST_REDIS_URL not set; skipping");
+        return;
+    };
+    let (a, b) = (replica(&redis_url), replica(&redis_url));
+    let key = format!("test-{}", uuid::Uuid::new_v4());
+
+    // Three requests per window in total, wherever they land.
+    assert!(a.check(&key).await);
+    assert!(b.check(&key).await);
+    assert!(a.check(&key).await);
+    assert!(!b.check(&key).await);
+    assert!(!a.check(&key).await);
+
+    // One token every 20s, and the wait is the same from either replica.
+    let wait = b.time_until_token(&key).await;
+    assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20), "{:?}", wait);
+
+    // Other keys have their own buckets.
+    assert!(b.check(&format!("{}-other", key)).await);
+}
+
+#[tokio::test]
+async fn unreachable_redis_falls_back_to_the_local_limiter() {
+    // Nothing listens on port 1: every request is counted locally instead.
+    let limiter = replica("redis://127.0.0.1:1");
+    for _ in 0..3 {
+        assert!(limiter.check("sidecar1").await);
+    }
+    assert!(!limiter.check("sidecar1").await);
+    assert!(limiter.time_until_token("sidecar1").await > Duration::ZERO);
+}
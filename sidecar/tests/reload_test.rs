@@ -0,0 +1,119 @@
+//! Integration test for runtime reload: a new `upstream_url` and `api_key` take effect
+//! for the next request without rebuilding the upstream client.
+
+mod common;
+
+use std::sync::Arc;
+
+use biscuit_auth::KeyPair;
+use wiremock::{MockServer, ResponseTemplate};
+
+use vac_sidecar::{policy_hash, reload_config, CliArgs, PinnedPolicy, SharedState};
+
+async fn upstream(body: &'static str) -> MockServer {
+    common::mock_upstream("GET", "/data", ResponseTemplate::new(200).set_body_string(body)).await
+}
+
+/// Body of `GET /data` through the upstream handler.
+async fn forward(state: &SharedState) -> String {
+    let resp = common::forward(state.clone(), common::request("GET", "/data", "")).await;
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn reloaded_upstream_url_and_api_key_apply_to_next_request() {
+    let old_upstream = upstream("old").await;
+    let new_upstream = upstream("new").await;
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "old-key", old_upstream.uri());
+    let proxy = state.read().await.proxy.clone();
+
+    assert_eq!(forward(&state).await, "old");
+
+    let cli_args = CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("new-key".to_string()),
+        upstream_url: Some(new_upstream.uri()),
+        ..Default::default()
+    };
+    reload_config(&state, &cli_args).await.unwrap();
+
+    assert_eq!(forward(&state).await, "new");
+    assert_eq!(old_upstream.received_requests().await.unwrap().len(), 1);
+    let received = new_upstream.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].headers.get("authorization").unwrap(), "Bearer new-key");
+    // The warm connection pool survives the reload.
+    assert!(Arc::ptr_eq(&state.read().await.proxy, &proxy));
+}
+
+#[tokio::test]
+async fn invalid_reload_keeps_current_settings() {
+    let current = upstream("current").await;
+    let state = common::default_test_state(KeyPair::new().public(), "k", current.uri());
+    let cli_args = CliArgs {
+        root_public_key: Some("not hex".to_string()),
+        api_key: Some("k2".to_string()),
+        upstream_url: Some("http://elsewhere.invalid".to_string()),
+        ..Default::default()
+    };
+    assert!(reload_config(&state, &cli_args).await.is_err());
+    assert_eq!(forward(&state).await, "current");
+}
+
+#[tokio::test]
+async fn reload_cannot_remove_a_pinned_policy() {
+    let current = upstream("current").await;
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", current.uri());
+    let policy = r#"allow if operation("GET", "/data");"#.to_string();
+    let pin = policy_hash(policy.as_bytes());
+    state.write().await.policy = Some(Arc::new(PinnedPolicy::load(policy.clone(), Some(&pin)).unwrap()));
+
+    // No policy_file any more: refused, and nothing else from this config applies either.
+    let cli_args = CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("k2".to_string()),
+        upstream_url: Some("http://elsewhere.invalid".to_string()),
+        ..Default::default()
+    };
+    assert!(reload_config(&state, &cli_args).await.is_err());
+    let s = state.read().await;
+    assert_eq!(s.policy.as_ref().unwrap().source(), policy);
+    assert_eq!(s.upstream_url, current.uri());
+}
+
+#[tokio::test]
+async fn pinned_policy_mismatch_on_reload_keeps_prior_policy() {
+    let current = upstream("current").await;
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", current.uri());
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let policy_path = temp_dir.path().join("policy.dl");
+    let good = r#"allow if operation("GET", "/data");"#;
+    std::fs::write(&policy_path, good).unwrap();
+    let pinned = |pin: String| CliArgs {
+        root_public_key: Some(hex::encode(root_kp.public().to_bytes())),
+        api_key: Some("k".to_string()),
+        upstream_url: Some(current.uri()),
+        policy_file: Some(policy_path.clone()),
+        policy_pin_hash: Some(pin),
+        ..Default::default()
+    };
+    let cli_args = pinned(policy_hash(good.as_bytes()));
+
+    // A first load that does not match the pin is refused, as at startup.
+    assert!(reload_config(&state, &pinned(policy_hash(b"allow if true;"))).await.is_err());
+    assert!(state.read().await.policy.is_none());
+
+    reload_config(&state, &cli_args).await.unwrap();
+    let pinned_hash = state.read().await.policy.as_ref().unwrap().hash().to_string();
+
+    // Swapped on disk for a permissive policy: the reload is rejected, the prior policy stays.
+    std::fs::write(&policy_path, "allow if true;").unwrap();
+    assert!(reload_config(&state, &cli_args).await.is_err());
+    let s = state.read().await;
+    assert_eq!(s.policy.as_ref().unwrap().source(), good);
+    assert_eq!(s.policy.as_ref().unwrap().hash(), pinned_hash);
+}
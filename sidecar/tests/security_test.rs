@@ -7,6 +7,7 @@ use vac_sidecar::security::{
 use vac_sidecar::rate_limit::{RateLimiter, DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION};
 use vac_sidecar::replay_cache::{ReplayCache, DEFAULT_REPLAY_CACHE_TTL};
 use std::time::Duration;
+use vac_sidecar::{CliArgs, Config};
 
 #[test]
 fn test_secure_string_zeroization() {
@@ -208,6 +209,33 @@ fn test_rate_limiter_cleanup() {
     assert!(limiter.check("sidecar1"));
 }
 
+#[test]
+fn test_rate_limiter_cleanup_uses_configured_bucket_max_age() {
+    let load = |max_age: Option<u64>| {
+        Config::load(&CliArgs {
+            root_public_key: Some("ab".repeat(32)),
+            api_key: Some("upstream-key".to_string()),
+            rate_limit_window_secs: Some(1),
+            rate_limit_bucket_max_age_secs: max_age,
+            ..Default::default()
+        })
+    };
+    // Ten windows by default; less than one window is refused.
+    assert_eq!(load(None).unwrap().rate_limit_bucket_max_age_secs, 10);
+    assert!(load(Some(0)).is_err());
+    let max_age = Duration::from_secs(load(Some(2)).unwrap().rate_limit_bucket_max_age_secs);
+
+    let limiter = RateLimiter::new(1, Duration::from_secs(1));
+    assert!(limiter.check("idle-sidecar"));
+    std::thread::sleep(Duration::from_millis(2100));
+    assert!(limiter.check("busy-sidecar"));
+
+    limiter.cleanup_old_buckets(max_age);
+    assert_eq!(limiter.bucket_count(), 1);
+    // The young bucket is kept, tokens spent and all.
+    assert!(!limiter.check("busy-sidecar"));
+}
+
 #[test]
 fn test_rate_limiter_defaults() {
     let limiter = RateLimiter::new(DEFAULT_MAX_REQUESTS, DEFAULT_WINDOW_DURATION);
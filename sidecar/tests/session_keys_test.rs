@@ -0,0 +1,170 @@
+//! Integration tests for cross-instance receipt verification via the control plane's
+//! published session key set (`GET /session-keys`, `accept_peer_receipts`).
+
+mod common;
+
+use axum::{routing::get, Router};
+use base64::{engine::general_purpose, Engine as _};
+use biscuit_auth::KeyPair;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::{Layer, ServiceExt};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use vac_sidecar::{
+    mint_receipt, refresh_session_keys, verify_receipt_biscuit_with_keys, NewReceipt, SharedState,
+    VacGuardLayer,
+};
+
+const CID: &str = "5e4d3c2b-1a09-4f8e-9d7c-6b5a4f3e2d1c";
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Receipt for `GET /search` minted by `state`'s sidecar.
+async fn mint_on(state: &SharedState) -> String {
+    let s = state.read().await;
+    mint_receipt(
+        &s.session_key,
+        &NewReceipt {
+            operation: "GET /search",
+            correlation_id: CID,
+            timestamp: now() as i64,
+            delegation_chain: &[],
+            depth: None,
+            sidecar_id: &s.sidecar_id,
+        },
+    )
+    .unwrap()
+    .to_base64()
+    .unwrap()
+}
+
+/// Control plane publishing `state`'s session key until `expires_at`.
+async fn control_plane_publishing(state: &SharedState, expires_at: u64) -> MockServer {
+    let (sidecar_id, session_key_pub) = {
+        let s = state.read().await;
+        (
+            s.sidecar_id.clone(),
+            general_purpose::STANDARD.encode(s.session_key.public().to_bytes()),
+        )
+    };
+    let mock = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/session-keys"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "keys": [{
+                "sidecar_id": sidecar_id,
+                "session_key_pub": session_key_pub,
+                "expires_at": expires_at,
+            }]
+        })))
+        .mount(&mock)
+        .await;
+    mock
+}
+
+async fn verify_on(state: &SharedState, receipt: &str) -> bool {
+    let s = state.read().await;
+    verify_receipt_biscuit_with_keys(receipt, &s.session_key.public(), &s.session_key_set).is_ok()
+}
+
+#[tokio::test]
+async fn receipt_from_instance_a_verifies_on_b_after_key_set_fetch() {
+    let root_kp = KeyPair::new();
+    let instance_a = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let instance_b = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    let receipt = mint_on(&instance_a).await;
+
+    // B does not know A's session key yet.
+    assert!(!verify_on(&instance_b, &receipt).await);
+
+    let control_plane = control_plane_publishing(&instance_a, now() + 300).await;
+    assert_eq!(refresh_session_keys(&instance_b, &control_plane.uri()).await.unwrap(), 1);
+    assert!(verify_on(&instance_b, &receipt).await);
+
+    // Through B's guard: the receipt is accepted (the request goes on to the fail-closed
+    // policy) only with `accept_peer_receipts`.
+    {
+        let mut s = instance_b.write().await;
+        s.error_response_format = vac_sidecar::ErrorResponseFormat::Json;
+    }
+    let guarded = VacGuardLayer::new(instance_b.clone())
+        .layer(Router::new().route("/details", get(|| async { "details" })));
+    let token = common::generate_test_root_biscuit(&root_kp).unwrap().to_base64().unwrap();
+    let send = || {
+        let req = axum::http::Request::builder()
+            .uri("/details")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Correlation-ID", CID)
+            .header("X-VAC-Receipt", receipt.as_str())
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let guarded = guarded.clone();
+        async move {
+            let resp = guarded.oneshot(req).await.unwrap();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["error"].as_str().unwrap().to_string()
+        }
+    };
+    assert_eq!(send().await, "invalid_signature");
+    instance_b.write().await.accept_peer_receipts = true;
+    assert_eq!(send().await, "policy_violation");
+}
+
+#[tokio::test]
+async fn expired_or_removed_peer_key_is_rejected() {
+    let instance_a = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let instance_b = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let receipt = mint_on(&instance_a).await;
+
+    // Published, but already expired (A stopped heartbeating a rotation period ago).
+    let control_plane = control_plane_publishing(&instance_a, now() - 1).await;
+    refresh_session_keys(&instance_b, &control_plane.uri()).await.unwrap();
+    assert!(!verify_on(&instance_b, &receipt).await);
+
+    // Live, then removed from the published set.
+    let control_plane = control_plane_publishing(&instance_a, now() + 300).await;
+    refresh_session_keys(&instance_b, &control_plane.uri()).await.unwrap();
+    assert!(verify_on(&instance_b, &receipt).await);
+    let empty = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/session-keys"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "keys": [] })))
+        .mount(&empty)
+        .await;
+    assert_eq!(refresh_session_keys(&instance_b, &empty.uri()).await.unwrap(), 0);
+    assert!(!verify_on(&instance_b, &receipt).await);
+}
+
+#[tokio::test]
+async fn chunked_session_key_body_is_capped_without_content_length() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // A control plane that streams JSON whitespace in chunks without a Content-Length and
+    // never finishes: only the body cap stops the read.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = [0u8; 4096];
+        let _ = socket.read(&mut request).await;
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ntransfer-encoding: chunked\r\n\r\n";
+        if socket.write_all(head.as_bytes()).await.is_err() {
+            return;
+        }
+        let chunk = vec![b' '; 64 * 1024];
+        loop {
+            let framed = [format!("{:x}\r\n", chunk.len()).into_bytes(), chunk.clone(), b"\r\n".to_vec()].concat();
+            if socket.write_all(&framed).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let state = common::default_test_state(KeyPair::new().public(), "k", "http://upstream.invalid");
+    let err = refresh_session_keys(&state, &format!("http://{}", addr)).await.unwrap_err();
+    assert!(err.to_string().contains("exceeds"), "{}", err);
+}
@@ -0,0 +1,135 @@
+//! Integration test for coordinated shutdown: one cancelled token stops the server,
+//! the supervised heartbeat task (after a going-away notice) and the cleanup tasks.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{routing::get, Router};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use vac_sidecar::{
+    start_cache_size_log_task, start_replay_cleanup_task, supervise_heartbeat_task,
+    CancellationToken, HeartbeatExitAction,
+};
+
+#[tokio::test]
+async fn cancelling_the_shutdown_token_stops_all_tasks() {
+    let control_plane = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "healthy": true,
+            "revoked_token_ids": null
+        })))
+        .mount(&control_plane)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/going-away"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&control_plane)
+        .await;
+
+    let state = common::default_test_state(
+        biscuit_auth::KeyPair::new().public(),
+        "api-key",
+        "http://upstream.example",
+    );
+    let shutdown = CancellationToken::new();
+
+    let heartbeat = tokio::spawn(supervise_heartbeat_task(
+        state.clone(),
+        vec![control_plane.uri()],
+        1,
+        300,
+        HeartbeatExitAction::Restart,
+        shutdown.clone(),
+    ));
+    let replay_cleanup = tokio::spawn(start_replay_cleanup_task(
+        state.read().await.replay_cache.clone(),
+        Duration::from_millis(10),
+        shutdown.clone(),
+    ));
+    let cache_log = tokio::spawn(start_cache_size_log_task(state.clone(), 1, shutdown.clone()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/ping", get(|| async { "pong" }));
+    let server = tokio::spawn(vac_sidecar::server::serve(listener, app, false, shutdown.clone()));
+
+    // Everything is running: the first heartbeat went out and the server answers.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(state.read().await.heartbeat_healthy);
+    let body = reqwest::get(format!("http://{}/ping", addr)).await.unwrap().text().await.unwrap();
+    assert_eq!(body, "pong");
+
+    shutdown.cancel();
+    let all = async {
+        server.await.unwrap().unwrap();
+        heartbeat.await.unwrap();
+        replay_cleanup.await.unwrap();
+        cache_log.await.unwrap();
+    };
+    tokio::time::timeout(Duration::from_secs(5), all)
+        .await
+        .expect("a task did not stop after shutdown");
+
+    // The listener is closed, and the heartbeat said goodbye exactly once.
+    assert!(reqwest::get(format!("http://{}/ping", addr)).await.is_err());
+    control_plane.verify().await;
+}
+
+#[tokio::test]
+async fn shutdown_drains_in_flight_requests_within_the_grace_period() {
+    // Each handler reports when it starts, so shutdown begins with both requests in flight.
+    let (started_tx, mut started_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let (quick_tx, stuck_tx) = (started_tx.clone(), started_tx);
+    let app = Router::new()
+        .route(
+            "/quick",
+            get(move || async move {
+                let _ = quick_tx.send(());
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                "done"
+            }),
+        )
+        .route(
+            "/stuck",
+            get(move || async move {
+                let _ = stuck_tx.send(());
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                "too late"
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    let server = tokio::spawn(vac_sidecar::server::serve_with_grace(
+        listener,
+        app,
+        false,
+        shutdown.clone(),
+        Duration::from_secs(1),
+    ));
+
+    let client = reqwest::Client::new();
+    let quick = tokio::spawn(client.get(format!("http://{}/quick", addr)).send());
+    let _stuck = tokio::spawn(client.get(format!("http://{}/stuck", addr)).send());
+    started_rx.recv().await.unwrap();
+    started_rx.recv().await.unwrap();
+
+    // The quick request still finishes; the stuck one is cut off when the grace period
+    // runs out instead of holding shutdown for its full 30 seconds.
+    let cancelled_at = std::time::Instant::now();
+    shutdown.cancel();
+    let body = quick.await.unwrap().unwrap().text().await.unwrap();
+    assert_eq!(body, "done");
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("serve_with_grace outlived its grace period")
+        .unwrap()
+        .unwrap();
+    assert!(cancelled_at.elapsed() >= Duration::from_millis(900));
+}
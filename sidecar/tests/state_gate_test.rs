@@ -0,0 +1,161 @@
+//! State-gate flow through `VacGuardLayer`, with tokens and receipts from
+//! `vac_sidecar::testutil`.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::routing::{get, post};
+use axum::Router;
+use biscuit_auth::KeyPair;
+use tower::{Layer, ServiceExt};
+
+use vac_sidecar::testutil;
+use vac_sidecar::{PinnedPolicy, VacGuardLayer};
+
+#[tokio::test]
+async fn charge_requires_a_search_receipt_for_the_same_correlation_id() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    state.write().await.policy = Some(Arc::new(
+        PinnedPolicy::load(
+            r#"allow if operation("GET", "/search");
+allow if operation("POST", "/charge"), prior_event($op, $cid, $ts), $op.starts_with("GET /search");"#
+                .to_string(),
+            None,
+        )
+        .unwrap(),
+    ));
+    let guarded = VacGuardLayer::new(state.clone()).layer(
+        Router::new()
+            .route("/search", get(|| async { "results" }))
+            .route("/charge", post(|| async { "charged" })),
+    );
+    let token = testutil::root_token(&root_kp);
+    let cid = "7d3c1a52-90f4-4b8e-a6d1-2f5e8c9b0a13";
+    let charge = |receipt: Option<String>| {
+        let mut req = axum::http::Request::post("/charge")
+            .header("Authorization", testutil::bearer(&token))
+            .header("X-Correlation-ID", cid);
+        if let Some(receipt) = receipt {
+            req = req.header("X-VAC-Receipt", receipt);
+        }
+        req.body(axum::body::Body::empty()).unwrap()
+    };
+
+    // No prior search: denied by policy.
+    let resp = guarded.clone().oneshot(charge(None)).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // A search receipt from another correlation ID does not count.
+    let (other, search) = {
+        let s = state.read().await;
+        (
+            testutil::mint_receipt(&s.session_key, "GET /search", "another-correlation", testutil::now()),
+            testutil::mint_receipt(&s.session_key, "GET /search", cid, testutil::now()),
+        )
+    };
+    let resp = guarded.clone().oneshot(charge(Some(testutil::b64(&other)))).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // The search receipt for this flow unlocks the charge.
+    let resp = guarded.oneshot(charge(Some(testutil::b64(&search)))).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert!(resp.headers().contains_key("x-vac-receipt"));
+}
+
+#[tokio::test]
+async fn flow_graph_enforces_search_select_charge_order() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.policy = Some(Arc::new(PinnedPolicy::load("allow if true;".to_string(), None).unwrap()));
+        let steps = [("search", "GET /search"), ("select", "POST /select"), ("charge", "POST /charge")]
+            .into_iter()
+            .map(|(name, op)| (name.to_string(), op.to_string()))
+            .collect();
+        let transitions = ["search -> select".to_string(), "select -> charge".to_string()];
+        s.flow_graph = Some(Arc::new(vac_sidecar::flow_graph::FlowGraph::new(&steps, &transitions, None).unwrap()));
+    }
+    let guarded = VacGuardLayer::new(state.clone()).layer(
+        Router::new()
+            .route("/search", get(|| async { "results" }))
+            .route("/select", post(|| async { "selected" }))
+            .route("/charge", post(|| async { "charged" })),
+    );
+    let token = testutil::root_token(&root_kp);
+    let cid = "3a9f6c21-7e4b-4d0a-9c85-b61e2f7d4a08";
+    let request = |method: &str, path: &str, receipts: &[&biscuit_auth::Biscuit]| {
+        let mut req = axum::http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header("Authorization", testutil::bearer(&token))
+            .header("X-Correlation-ID", cid);
+        for receipt in receipts {
+            req = req.header("X-VAC-Receipt", testutil::b64(receipt));
+        }
+        req.body(axum::body::Body::empty()).unwrap()
+    };
+    let (search, select) = {
+        let s = state.read().await;
+        (
+            testutil::mint_receipt(&s.session_key, "GET /search", cid, testutil::now() - 1),
+            testutil::mint_receipt(&s.session_key, "POST /select", cid, testutil::now()),
+        )
+    };
+
+    // Charge straight after search skips select.
+    let resp = guarded.clone().oneshot(request("POST", "/charge", &[&search])).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+    // Charge cannot start a flow either.
+    let resp = guarded.clone().oneshot(request("POST", "/charge", &[])).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // search -> select -> charge passes.
+    let resp = guarded.clone().oneshot(request("GET", "/search", &[])).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let resp = guarded.clone().oneshot(request("POST", "/select", &[&search])).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let resp = guarded.oneshot(request("POST", "/charge", &[&search, &select])).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn batch_endpoint_mints_a_receipt_per_sub_operation() {
+    let root_kp = KeyPair::new();
+    let state = common::default_test_state(root_kp.public(), "k", "http://upstream.invalid");
+    {
+        let mut s = state.write().await;
+        s.policy = Some(Arc::new(PinnedPolicy::load("allow if true;".to_string(), None).unwrap()));
+        s.batch_receipts.insert(
+            "POST /batch".to_string(),
+            vec!["GET /search".to_string(), "POST /charge".to_string()],
+        );
+    }
+    let guarded = VacGuardLayer::new(state.clone()).layer(Router::new().route("/batch", post(|| async { "done" })));
+    let cid = "c41e8b07-2d9a-4f63-8e15-a0b7d3f96c24";
+    let req = axum::http::Request::post("/batch")
+        .header("Authorization", testutil::bearer(&testutil::root_token(&root_kp)))
+        .header("X-Correlation-ID", cid)
+        .body(axum::body::Body::empty())
+        .unwrap();
+
+    let resp = guarded.oneshot(req).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+
+    // One receipt per sub-operation, in order, each signed by the session key.
+    let session_key = state.read().await.session_key.public();
+    let operations: Vec<String> = resp
+        .headers()
+        .get_all("x-vac-receipt")
+        .iter()
+        .map(|receipt| {
+            let receipt = vac_sidecar::verify_receipt_biscuit(receipt.to_str().unwrap(), &session_key).unwrap();
+            let info = vac_sidecar::extract_receipt_info(&receipt).unwrap();
+            assert_eq!(info.correlation_id, cid);
+            info.operation
+        })
+        .collect();
+    assert_eq!(operations, ["GET /search", "POST /charge"]);
+}
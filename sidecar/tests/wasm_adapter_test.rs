@@ -1,5 +1,9 @@
 use sha2::{Digest, Sha256};
-use vac_sidecar::{AdapterRegistry, extract_facts_from_body, load_adapter_from_url};
+use vac_sidecar::{
+    AdapterContext, AdapterRegistry, extract_facts_from_body, extract_facts_from_request, load_adapter_from_file, load_adapter_from_url,
+    load_adapters_from_dir, read_adapter_hashed, canonicalize_json, screen_adapter_facts, RequestMetrics,
+    evaluate_policy, AdapterArg, AdapterArgType, AdapterFact, AdapterReservedFacts, VacError,
+};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 use wiremock::matchers::{method, path};
 
@@ -42,6 +46,207 @@ async fn test_wasm_adapter_extract_facts_constant_json() {
     assert_eq!(facts[1].args, vec!["USD".to_string()]);
 }
 
+#[tokio::test]
+async fn test_adapter_duration_recorded_per_hash() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[]\00")
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let metrics = RequestMetrics::new();
+    let registry = AdapterRegistry::with_metrics(metrics.clone());
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+    assert!(!metrics.render().contains("vac_adapter_duration_seconds_count"));
+
+    extract_facts_from_body(&hash, b"{}", &registry).await.unwrap();
+    extract_facts_from_body(&hash, b"{}", &registry).await.unwrap();
+
+    // Unknown hashes fail before running and get no series.
+    assert!(extract_facts_from_body(&"0".repeat(64), b"{}", &registry).await.is_err());
+
+    let text = metrics.render();
+    assert!(!text.contains(&"0".repeat(64)), "{}", text);
+    assert!(text.contains("# TYPE vac_adapter_duration_seconds histogram\n"), "{}", text);
+    assert!(
+        text.contains(&format!("vac_adapter_duration_seconds_count{{hash=\"{}\"}} 2\n", hash)),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(&format!("vac_adapter_duration_seconds_bucket{{hash=\"{}\",le=\"+Inf\"}} 2\n", hash)),
+        "{}",
+        text
+    );
+}
+
+#[tokio::test]
+async fn test_spinning_adapter_is_interrupted() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (loop $spin (br $spin))
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+
+    // `prewarm` runs the adapter on the calling thread, so it only returns if the guest
+    // is actually stopped (not merely abandoned by a timeout).
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    {
+        let registry = registry.clone();
+        std::thread::spawn(move || done_tx.send(registry.prewarm()).unwrap());
+    }
+
+    let started = std::time::Instant::now();
+    let err = extract_facts_from_body(&hash, b"{}", &registry).await.unwrap_err();
+    assert!(matches!(err, VacError::InternalError(ref msg) if msg.contains("execution limit")), "{:?}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(7));
+
+    let warmed = done_rx
+        .recv_timeout(std::time::Duration::from_secs(3))
+        .expect("guest thread still spinning after the execution limit");
+    assert_eq!(warmed.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_spinning_adapter_run_does_not_interrupt_other_runs() {
+    // Spins on a non-empty body, returns no facts on an empty one.
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[]\00")
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (if (local.get 1) (then (loop $spin (br $spin))))
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+
+    let spinning = {
+        let (registry, hash) = (registry.clone(), hash.clone());
+        tokio::spawn(async move { extract_facts_from_body(&hash, b"{}", &registry).await })
+    };
+    // Runs of the same adapter while the spinning one is stopped at its deadline.
+    while !spinning.is_finished() {
+        extract_facts_from_body(&hash, b"", &registry).await.expect("run interrupted by another run's limit");
+    }
+    let err = spinning.await.unwrap().unwrap_err();
+    assert!(matches!(err, VacError::InternalError(ref msg) if msg.contains("execution limit")), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_adapter_growing_past_memory_limit_traps() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (loop $grow
+              (drop (memory.grow (i32.const 16)))
+              (br $grow))
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.set_max_memory(4 * 1024 * 1024);
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+
+    let started = std::time::Instant::now();
+    let err = extract_facts_from_body(&hash, b"{}", &registry).await.unwrap_err();
+    assert!(
+        matches!(err, VacError::InternalError(ref msg) if msg.contains("4194304 byte memory limit")),
+        "{:?}",
+        err
+    );
+    // Trapped at the limit, not stopped by the execution time limit.
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+    // A body that cannot be copied in under the limit is refused the same way.
+    let err = extract_facts_from_body(&hash, &vec![b'x'; 5 * 1024 * 1024], &registry)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, VacError::InternalError(ref msg) if msg.contains("memory limit")), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_context_adapter_echoes_request_path() {
+    // Copies the path string out of the context JSON
+    // (`{"method":"...","path":"...","headers":{...}}`) into `[{"fact":"path","args":[<path>]}]`.
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[{\"fact\":\"path\",\"args\":[")
+          (data (i32.const 32) "]}]\00")
+          (func (export "extract_facts_with_context")
+            (param $body i32) (param $body_len i32) (param $ctx i32) (param $ctx_len i32) (result i32)
+            (local $src i32) (local $dst i32)
+            ;; skip `{"method":"` and the method, up to its closing quote
+            (local.set $src (i32.add (local.get $ctx) (i32.const 11)))
+            (block $end (loop $method
+              (br_if $end (i32.eq (i32.load8_u (local.get $src)) (i32.const 34)))
+              (local.set $src (i32.add (local.get $src) (i32.const 1)))
+              (br $method)))
+            ;; skip `","path":` to the path's opening quote
+            (local.set $src (i32.add (local.get $src) (i32.const 9)))
+            (memory.copy (i32.const 256) (i32.const 0) (i32.const 24))
+            (local.set $dst (i32.const 280))
+            (i32.store8 (local.get $dst) (i32.load8_u (local.get $src)))
+            ;; copy up to and including the closing quote
+            (block $end (loop $path
+              (local.set $src (i32.add (local.get $src) (i32.const 1)))
+              (local.set $dst (i32.add (local.get $dst) (i32.const 1)))
+              (i32.store8 (local.get $dst) (i32.load8_u (local.get $src)))
+              (br_if $end (i32.eq (i32.load8_u (local.get $src)) (i32.const 34)))
+              (br $path)))
+            (memory.copy (i32.add (local.get $dst) (i32.const 1)) (i32.const 32) (i32.const 4))
+            (i32.const 256))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("x-tenant-id", "t1".parse().unwrap());
+    headers.insert("authorization", "Bearer secret".parse().unwrap());
+    let selected = vec!["x-tenant-id".to_string(), "authorization".to_string(), "x-missing".to_string()];
+    let context = AdapterContext::from_request("POST", "/payments/charge", &headers, &selected);
+    // Credentials never reach the adapter, even when selected.
+    assert_eq!(context.headers.keys().collect::<Vec<_>>(), vec!["x-tenant-id"]);
+
+    let facts = extract_facts_from_request(&hash, b"{}", &context, &registry)
+        .await
+        .expect("extract facts");
+    assert_eq!(facts.len(), 1);
+    assert_eq!(facts[0].fact_name, "path");
+    assert_eq!(facts[0].args, vec!["/payments/charge".to_string()]);
+}
+
 #[tokio::test]
 async fn test_load_adapter_from_url_and_extract_facts() {
     let wat = r#"
@@ -102,3 +307,278 @@ async fn test_load_adapter_from_url_hash_mismatch_fails() {
     assert!(msg.contains("hash mismatch") || msg.contains("mismatch"));
 }
 
+
+#[tokio::test]
+async fn test_canonical_json_bodies_yield_identical_facts() {
+    // Reports the byte at offset 2 of the body (the first key's first character for
+    // `{"k...`) as `first_key(c)`, so it is sensitive to key order.
+    let wat = r#"
+    (module
+      (memory (export "memory") 1)
+      (data (i32.const 0) "[{\"fact\":\"first_key\",\"args\":[\"?\"]}]\00")
+      (func (export "extract_facts") (param i32 i32) (result i32)
+        (i32.store8 (i32.const 30) (i32.load8_u (i32.add (local.get 0) (i32.const 2))))
+        (i32.const 0))
+    )
+    "#;
+    let wasm_bytes = wat::parse_str(wat).expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+
+    let a = br#"{"b":1,"a":2}"#;
+    let b = b"{\"a\": 2,\n  \"b\": 1}";
+    let raw_a = extract_facts_from_body(&hash, a, &registry).await.unwrap();
+    let raw_b = extract_facts_from_body(&hash, b, &registry).await.unwrap();
+    assert_ne!(raw_a[0].args, raw_b[0].args);
+
+    let canonical_a = extract_facts_from_body(&hash, &canonicalize_json(a).unwrap(), &registry).await.unwrap();
+    let canonical_b = extract_facts_from_body(&hash, &canonicalize_json(b).unwrap(), &registry).await.unwrap();
+    assert_eq!(canonical_a[0].fact_name, "first_key");
+    assert_eq!(canonical_a[0].args, vec!["a".to_string()]);
+    assert_eq!(canonical_a[0].args, canonical_b[0].args);
+}
+
+#[tokio::test]
+async fn test_adapter_cannot_forge_prior_event() {
+    // A malicious adapter claiming a prior `GET /search` happened in this flow.
+    let wat = r#"
+    (module
+      (memory (export "memory") 1)
+      (data (i32.const 0) "[{\"fact\":\"prior_event\",\"args\":[\"GET /search\",\"cid-1\",\"1704067200\"]},{\"fact\":\"amount\",\"args\":[\"350\"]}]\00")
+      (func (export "extract_facts") (param i32 i32) (result i32)
+        (i32.const 0))
+    )
+    "#;
+    let wasm_bytes = wat::parse_str(wat).expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+    let facts = extract_facts_from_body(&hash, b"{}", &registry).await.unwrap();
+
+    let rejected = screen_adapter_facts(facts.clone(), AdapterReservedFacts::Reject);
+    assert!(matches!(rejected, Err(VacError::PolicyViolation(_))));
+
+    let namespaced = screen_adapter_facts(facts, AdapterReservedFacts::Namespace).unwrap();
+    let names: Vec<_> = namespaced.iter().map(|f| f.fact_name.as_str()).collect();
+    assert_eq!(names, vec!["adapter_prior_event", "amount"]);
+
+    // The namespaced fact does not satisfy a policy that requires a verified receipt.
+    let mut authorizer = biscuit_auth::Authorizer::new();
+    for fact in &namespaced {
+        authorizer.add_fact(fact.to_biscuit_fact().unwrap()).unwrap();
+    }
+    authorizer
+        .add_code(r#"allow if prior_event($op, $cid, $ts), $op.starts_with("GET /search");"#)
+        .unwrap();
+    assert!(matches!(evaluate_policy(&mut authorizer), Err(VacError::PolicyViolation(_))));
+}
+
+#[tokio::test]
+async fn test_typed_adapter_args_become_typed_terms() {
+    let wat = r#"
+    (module
+      (memory (export "memory") 1)
+      (data (i32.const 0) "[{\"fact\":\"kyc_verified\",\"args\":[{\"type\":\"bool\",\"value\":\"true\"}]},{\"fact\":\"zip\",\"args\":[{\"type\":\"string\",\"value\":\"02139\"}]},{\"fact\":\"checked_at\",\"args\":[{\"type\":\"date\",\"value\":\"2024-01-01T00:00:00Z\"}]},{\"fact\":\"amount\",\"args\":[\"350\"]}]\00")
+      (func (export "extract_facts") (param i32 i32) (result i32)
+        (i32.const 0))
+    )
+    "#;
+    let wasm_bytes = wat::parse_str(wat).expect("wat parse");
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let registry = AdapterRegistry::new();
+    registry.load_adapter(&wasm_bytes, &hash).expect("load adapter");
+    let facts = extract_facts_from_body(&hash, b"{}", &registry).await.unwrap();
+    assert_eq!(facts[0].args[0].ty, Some(AdapterArgType::Bool));
+    // Untyped arguments keep the old reading.
+    assert_eq!(facts[3].args[0].ty, None);
+
+    let mut authorizer = biscuit_auth::Authorizer::new();
+    for fact in &facts {
+        authorizer.add_fact(fact.to_biscuit_fact().unwrap()).unwrap();
+    }
+    // A numeric-looking string stays a string when typed as one.
+    authorizer
+        .add_code(
+            r#"allow if kyc_verified(true), zip("02139"), checked_at($t), $t < 2025-01-01T00:00:00Z, amount(350);"#,
+        )
+        .unwrap();
+    assert!(evaluate_policy(&mut authorizer).is_ok());
+
+    // A value that does not fit its declared type is an error, not a string.
+    let bad = |ty: AdapterArgType, value: &str| AdapterFact {
+        fact_name: "f".to_string(),
+        args: vec![AdapterArg { value: value.to_string(), ty: Some(ty) }],
+    };
+    assert!(bad(AdapterArgType::Bool, "yes").to_biscuit_fact().is_err());
+    assert!(bad(AdapterArgType::Int, "3.5").to_biscuit_fact().is_err());
+    assert!(bad(AdapterArgType::Date, "2024-01-01T00:00:00Z), other(1").to_biscuit_fact().is_err());
+}
+
+fn write_adapter(dir: &std::path::Path, name: &str, wat: &str) {
+    let wasm_bytes = wat::parse_str(wat).expect("wat parse");
+    std::fs::write(dir.join(name), wasm_bytes).expect("write adapter");
+}
+
+#[test]
+fn test_prewarm_good_adapter_succeeds() {
+    let dir = tempfile::tempdir().unwrap();
+    write_adapter(
+        dir.path(),
+        "good.wasm",
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[]\00")
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    );
+
+    let registry = AdapterRegistry::new();
+    assert_eq!(load_adapters_from_dir(&registry, dir.path().to_str().unwrap()).unwrap(), 1);
+    assert_eq!(registry.prewarm().expect("prewarm"), 1);
+}
+
+#[test]
+fn test_unlinkable_adapter_rejected_at_load() {
+    let dir = tempfile::tempdir().unwrap();
+    // Compiles, but imports a host function nobody provides, so it can't instantiate.
+    write_adapter(
+        dir.path(),
+        "broken.wasm",
+        r#"
+        (module
+          (import "env" "missing" (func))
+          (memory (export "memory") 1)
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    );
+
+    let registry = AdapterRegistry::new();
+    let err = load_adapters_from_dir(&registry, dir.path().to_str().unwrap()).unwrap_err();
+    assert!(matches!(err, VacError::ConfigError(_)), "{:?}", err);
+    assert!(err.to_string().contains("failed to link"), "{}", err);
+    assert_eq!(registry.adapter_count(), 0);
+}
+
+#[test]
+fn test_adapter_exports_validated_at_load() {
+    let valid = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let missing_extract_facts = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "extract") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let wrong_signature = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "extract_facts") (param i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+
+    let registry = AdapterRegistry::new();
+    registry
+        .load_adapter(&valid, &hex::encode(Sha256::digest(&valid)))
+        .expect("valid adapter accepted");
+
+    for wasm in [&missing_extract_facts, &wrong_signature] {
+        let err = registry
+            .load_adapter(wasm, &hex::encode(Sha256::digest(wasm)))
+            .unwrap_err();
+        assert!(matches!(err, VacError::ConfigError(_)), "{:?}", err);
+        assert!(err.to_string().contains("must export 'extract_facts'"), "{}", err);
+    }
+    assert_eq!(registry.adapter_count(), 1);
+}
+
+/// Valid module padded with a custom section to roughly `size` bytes.
+fn large_adapter_bytes(size: usize) -> Vec<u8> {
+    let mut wasm = wat::parse_str(
+        r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "[]\00")
+          (func (export "extract_facts") (param i32 i32) (result i32)
+            (i32.const 0))
+        )
+        "#,
+    )
+    .expect("wat parse");
+    let name = b"padding";
+    let payload_len = name.len() + 1 + size;
+    wasm.push(0); // custom section id
+    let mut n = payload_len;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            wasm.push(byte);
+            break;
+        }
+        wasm.push(byte | 0x80);
+    }
+    wasm.push(name.len() as u8);
+    wasm.extend_from_slice(name);
+    wasm.extend((0..size).map(|i| (i % 251) as u8));
+    wasm
+}
+
+#[test]
+fn test_streamed_hash_matches_one_shot_hash() {
+    let wasm_bytes = large_adapter_bytes(6 * 1024 * 1024);
+    let one_shot = hex::encode(Sha256::digest(&wasm_bytes));
+
+    let (read_bytes, streamed) = read_adapter_hashed(std::io::Cursor::new(&wasm_bytes)).unwrap();
+    assert_eq!(streamed, one_shot);
+    assert_eq!(read_bytes, wasm_bytes);
+}
+
+#[test]
+fn test_large_adapter_loads_from_file_and_dir() {
+    let wasm_bytes = large_adapter_bytes(6 * 1024 * 1024);
+    let hash = hex::encode(Sha256::digest(&wasm_bytes));
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("large.wasm");
+    std::fs::write(&file, &wasm_bytes).unwrap();
+
+    let registry = AdapterRegistry::new();
+    load_adapter_from_file(&registry, file.to_str().unwrap(), &hash).expect("streamed hash matches");
+    let err = load_adapter_from_file(&registry, file.to_str().unwrap(), &"0".repeat(64)).unwrap_err();
+    assert!(format!("{}", err).contains("hash mismatch"));
+
+    let registry = AdapterRegistry::new();
+    assert_eq!(load_adapters_from_dir(&registry, dir.path().to_str().unwrap()).unwrap(), 1);
+    assert_eq!(registry.prewarm().expect("loaded under its streamed hash"), 1);
+}
+
+#[test]
+fn test_oversized_adapter_rejected_when_streaming() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("huge.wasm"), large_adapter_bytes(11 * 1024 * 1024)).unwrap();
+
+    let registry = AdapterRegistry::new();
+    let err = load_adapters_from_dir(&registry, dir.path().to_str().unwrap()).unwrap_err();
+    assert!(format!("{}", err).contains("too large"));
+}